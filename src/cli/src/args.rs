@@ -0,0 +1,300 @@
+//! Command-line flags.
+
+use std::path::PathBuf;
+
+use runtime::{NavigationOptions, SearchScope, TestFilePolicy};
+
+pub const USAGE: &str =
+    "usage: luna [stats [REPO] | files QUERY | languages | doctor | schema] [--min-file-bytes N] [--max-file-bytes N] [--max-file-bytes-for EXT=N]... [--max-files N] [--max-hits N] [--ignore-dir DIR]... [--ignore-file GLOB]... [--scope DIR | --scope-file PATH...] [--code-only] [--test-files include|exclude|only] [--qualify-names] [--cite]";
+
+/// What to run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Interactive session (default)
+    Tui,
+    /// `luna stats [REPO]`: print repository statistics and exit; without
+    /// REPO, the repository containing the working directory
+    Stats(Option<PathBuf>),
+    /// `luna files QUERY`: list files whose path fuzzy-matches QUERY and exit
+    Files(String),
+    /// `luna languages`: list the languages this build can parse and exit
+    Languages,
+    /// `luna doctor`: check the LLM setup, settings and repository, then exit
+    Doctor,
+    /// `luna schema`: print JSON Schemas of the config structs and exit
+    Schema,
+}
+
+/// Split an optional subcommand off the front of `args` (program name
+/// already skipped); the rest are flags for [`parse_navigation_args`].
+pub fn parse_command(args: impl IntoIterator<Item = String>) -> (Command, Vec<String>) {
+    let mut args = args.into_iter().peekable();
+    match args.peek().map(String::as_str) {
+        Some("stats") => {}
+        Some("files") => {
+            args.next();
+            let query = args.next_if(|a| !a.starts_with('-')).unwrap_or_default();
+            return (Command::Files(query), args.collect());
+        }
+        Some("languages") => {
+            args.next();
+            return (Command::Languages, args.collect());
+        }
+        Some("doctor") => {
+            args.next();
+            return (Command::Doctor, args.collect());
+        }
+        Some("schema") => {
+            args.next();
+            return (Command::Schema, args.collect());
+        }
+        _ => return (Command::Tui, args.collect()),
+    }
+    args.next();
+    let repo = args.next_if(|a| !a.starts_with('-')).map(PathBuf::from);
+    (Command::Stats(repo), args.collect())
+}
+
+/// Remove every `switch` from `args`, returning whether there was one.
+///
+/// For flags that configure the runtime rather than navigation, e.g. `--cite`.
+pub fn take_switch(args: &mut Vec<String>, switch: &str) -> bool {
+    let before = args.len();
+    args.retain(|a| a != switch);
+    args.len() != before
+}
+
+/// Parse search flags (program name already skipped).
+///
+/// - `--min-file-bytes N`: skip files smaller than N bytes
+/// - `--max-file-bytes N`: skip files larger than N bytes
+/// - `--max-file-bytes-for EXT=N`: size limit for `.EXT` files instead (repeatable)
+/// - `--max-files N`: scan at most N files per search
+/// - `--max-hits N`: list at most N references per symbol
+/// - `--ignore-dir DIR`: skip directories named DIR (repeatable)
+/// - `--ignore-file GLOB`: skip files matching GLOB (repeatable)
+/// - `--scope DIR`: search only under DIR (relative to the repo root)
+/// - `--scope-file PATH`: search only PATH (repeatable; replaces `--scope`)
+/// - `--code-only`: ignore matches in comments and string literals
+/// - `--test-files include|exclude|only`: skip test files, or search only them
+/// - `--qualify-names`: show definitions as `module::Type::name`
+pub fn parse_navigation_args(
+    args: impl IntoIterator<Item = String>,
+) -> Result<NavigationOptions, String> {
+    let mut opt = NavigationOptions::default();
+    let mut args = args.into_iter();
+
+    while let Some(flag) = args.next() {
+        let (flag, inline) = match flag.split_once('=') {
+            Some((f, v)) => (f.to_owned(), Some(v.to_owned())),
+            None => (flag, None),
+        };
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("missing value for {flag}\n{USAGE}"))
+        };
+        let number = |v: String| {
+            v.parse::<usize>()
+                .map_err(|_| format!("invalid number for {flag}: {v}\n{USAGE}"))
+        };
+
+        match flag.as_str() {
+            "--min-file-bytes" => opt.scan.min_file_size_bytes = number(value()?)?,
+            "--max-file-bytes" => opt.scan.max_file_size_bytes = number(value()?)?,
+            "--max-file-bytes-for" => {
+                let v = value()?;
+                let (ext, max) = v
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected EXT=N for {flag}: {v}\n{USAGE}"))?;
+                let max = number(max.to_owned())?;
+                opt.scan = opt
+                    .scan
+                    .with_max_file_size_for_extension(ext.trim_start_matches('.'), max);
+            }
+            "--max-files" => opt.scan.max_files = Some(number(value()?)?),
+            "--max-hits" => opt.max_references = number(value()?)?,
+            "--ignore-dir" => opt.scan = opt.scan.with_exclude_dir(value()?),
+            "--ignore-file" => opt.scan = opt.scan.with_exclude_file_glob(value()?),
+            "--scope" => opt.scan.scope = SearchScope::Subdir(PathBuf::from(value()?)),
+            "--scope-file" => {
+                let path = PathBuf::from(value()?);
+                match &mut opt.scan.scope {
+                    SearchScope::Files(files) => files.push(path),
+                    scope => *scope = SearchScope::Files(vec![path]),
+                }
+            }
+            "--code-only" => opt.scan.code_only = true,
+            "--test-files" => {
+                opt.scan.test_files = match value()?.as_str() {
+                    "include" => TestFilePolicy::Include,
+                    "exclude" => TestFilePolicy::Exclude,
+                    "only" => TestFilePolicy::Only,
+                    other => {
+                        return Err(format!("invalid value for {flag}: {other}\n{USAGE}"));
+                    }
+                }
+            }
+            "--qualify-names" => opt.qualify_names = true,
+            "-h" | "--help" => return Err(USAGE.to_owned()),
+            other => return Err(format!("unknown argument: {other}\n{USAGE}")),
+        }
+    }
+
+    if let Some(path) = opt.scan.scope.escaping_path() {
+        return Err(format!(
+            "scope must be relative to the repo root, without `..`: {}\n{USAGE}",
+            path.display()
+        ));
+    }
+
+    Ok(opt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| (*a).to_owned()).collect()
+    }
+
+    #[test]
+    fn parse_navigation_args_accepts_valid_flags() {
+        type Check = fn(&NavigationOptions) -> bool;
+        let cases: &[(&[&str], Check)] = &[
+            (&[], |o| {
+                o.scan.scope == SearchScope::WholeRepo
+                    && o.scan.test_files == TestFilePolicy::Include
+                    && !o.scan.code_only
+                    && !o.qualify_names
+            }),
+            (&["--min-file-bytes", "10"], |o| {
+                o.scan.min_file_size_bytes == 10
+            }),
+            (&["--max-file-bytes=2048"], |o| {
+                o.scan.max_file_size_bytes == 2048
+            }),
+            (&["--max-file-bytes-for", ".json=100"], |o| {
+                o.scan.max_file_size_by_extension.get("json") == Some(&100)
+            }),
+            (&["--max-files", "3"], |o| o.scan.max_files == Some(3)),
+            (&["--max-hits", "7"], |o| o.max_references == 7),
+            (&["--ignore-dir", "vendor"], |o| {
+                o.scan.exclude_dir_names.iter().any(|d| d == "vendor")
+            }),
+            (&["--ignore-file", "*.gen.rs"], |o| {
+                o.scan.exclude_file_globs.iter().any(|g| g == "*.gen.rs")
+            }),
+            (&["--scope", "src/app"], |o| {
+                o.scan.scope == SearchScope::Subdir(PathBuf::from("src/app"))
+            }),
+            (&["--scope-file", "a.rs", "--scope-file=b.rs"], |o| {
+                o.scan.scope
+                    == SearchScope::Files(vec![PathBuf::from("a.rs"), PathBuf::from("b.rs")])
+            }),
+            // The last of `--scope` / `--scope-file` wins
+            (&["--scope-file", "a.rs", "--scope", "src"], |o| {
+                o.scan.scope == SearchScope::Subdir(PathBuf::from("src"))
+            }),
+            (&["--code-only"], |o| o.scan.code_only),
+            (&["--test-files", "include"], |o| {
+                o.scan.test_files == TestFilePolicy::Include
+            }),
+            (&["--test-files", "exclude"], |o| {
+                o.scan.test_files == TestFilePolicy::Exclude
+            }),
+            (&["--test-files=only"], |o| {
+                o.scan.test_files == TestFilePolicy::Only
+            }),
+            (&["--qualify-names"], |o| o.qualify_names),
+        ];
+
+        for (args, check) in cases {
+            let opt = parse_navigation_args(strings(args))
+                .unwrap_or_else(|e| panic!("{args:?} rejected: {e}"));
+            assert!(check(&opt), "{args:?} parsed to {opt:?}");
+        }
+    }
+
+    #[test]
+    fn parse_navigation_args_rejects_missing_and_invalid_values() {
+        let cases: &[(&[&str], &str)] = &[
+            (&["--max-files"], "missing value for --max-files"),
+            (&["--scope"], "missing value for --scope"),
+            (&["--scope-file"], "missing value for --scope-file"),
+            (&["--test-files"], "missing value for --test-files"),
+            (
+                &["--max-hits", "many"],
+                "invalid number for --max-hits: many",
+            ),
+            (
+                &["--min-file-bytes=-1"],
+                "invalid number for --min-file-bytes: -1",
+            ),
+            (
+                &["--max-file-bytes-for", "json"],
+                "expected EXT=N for --max-file-bytes-for",
+            ),
+            (
+                &["--max-file-bytes-for", "json=big"],
+                "invalid number for --max-file-bytes-for",
+            ),
+            (
+                &["--test-files", "some"],
+                "invalid value for --test-files: some",
+            ),
+            (
+                &["--scope", "../other"],
+                "scope must be relative to the repo root",
+            ),
+            (
+                &["--scope-file", "/etc/passwd"],
+                "scope must be relative to the repo root",
+            ),
+            (&["--frobnicate"], "unknown argument: --frobnicate"),
+            // Runtime switches must be taken off before parsing
+            (&["--cite"], "unknown argument: --cite"),
+        ];
+
+        for (args, expected) in cases {
+            let err = parse_navigation_args(strings(args)).unwrap_err();
+            assert!(err.starts_with(expected), "{args:?}: {err}");
+            assert!(err.ends_with(USAGE), "{args:?}: {err}");
+        }
+    }
+
+    #[test]
+    fn cite_is_taken_off_before_navigation_flags_are_parsed() {
+        let mut args = strings(&["--cite", "--test-files", "exclude", "--scope", "src"]);
+        assert!(take_switch(&mut args, "--cite"));
+        let opt = parse_navigation_args(args).unwrap();
+        assert_eq!(opt.scan.test_files, TestFilePolicy::Exclude);
+        assert_eq!(opt.scan.scope, SearchScope::Subdir(PathBuf::from("src")));
+    }
+
+    #[test]
+    fn take_switch_removes_every_occurrence() {
+        let mut args = strings(&["--cite", "--max-hits", "5", "--cite"]);
+        assert!(take_switch(&mut args, "--cite"));
+        assert_eq!(args, strings(&["--max-hits", "5"]));
+
+        // Absent, or only as another flag's value prefix: left alone
+        let mut args = strings(&["--scope", "--cited"]);
+        assert!(!take_switch(&mut args, "--cite"));
+        assert_eq!(args, strings(&["--scope", "--cited"]));
+    }
+
+    #[test]
+    fn stats_repo_is_optional() {
+        let (command, rest) = parse_command(strings(&["stats", "../other", "--max-files", "3"]));
+        assert_eq!(command, Command::Stats(Some(PathBuf::from("../other"))));
+        assert_eq!(rest, strings(&["--max-files", "3"]));
+
+        // Left to the caller to resolve from the working directory
+        let (command, rest) = parse_command(strings(&["stats", "--code-only"]));
+        assert_eq!(command, Command::Stats(None));
+        assert_eq!(rest, strings(&["--code-only"]));
+    }
+}
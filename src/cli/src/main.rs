@@ -1,14 +1,106 @@
-use runtime::LunaRuntime;
+use runtime::{LunaRuntime, RuntimeConfig};
 use std::sync::Arc;
 
+mod args;
 mod tui;
 
+/// Matches printed by `luna files`.
+const MAX_LISTED_FILES: usize = 20;
+
 #[tokio::main]
 async fn main() {
-    let runtime = Arc::new(LunaRuntime::new());
+    let (command, mut flags) = args::parse_command(std::env::args().skip(1));
+    // `--cite`: list numbered sources after each answer
+    let cite = args::take_switch(&mut flags, "--cite");
+    let navigation = match args::parse_navigation_args(flags) {
+        Ok(v) => v,
+        Err(msg) => {
+            eprintln!("{msg}");
+            std::process::exit(2);
+        }
+    };
+
+    if command == args::Command::Languages {
+        for lang in runtime::supported_languages() {
+            println!("{:<12} .{}", lang.id, lang.extensions.join(" ."));
+        }
+        return;
+    }
+
+    if command == args::Command::Doctor {
+        let cwd = std::env::current_dir().unwrap_or_else(|_| ".".into());
+        let repo = runtime::router::find_repo_root(&cwd).unwrap_or(cwd);
+        let checks = runtime::doctor::run_checks(&repo, true);
+        for check in &checks {
+            println!("{check}");
+        }
+        if checks.iter().any(|c| !c.passed) {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if command == args::Command::Schema {
+        print_schemas();
+        return;
+    }
+
+    let mut config = RuntimeConfig::default().with_navigation(navigation);
+    if cite {
+        config = config.with_cite_sources(true);
+    }
+    let runtime = Arc::new(LunaRuntime::with_config(config));
+
+    if let args::Command::Files(query) = &command {
+        if query.is_empty() {
+            eprintln!("missing query for files\n{}", args::USAGE);
+            std::process::exit(2);
+        }
+        let cwd = std::env::current_dir().unwrap_or_else(|_| ".".into());
+        let repo = runtime::router::find_repo_root(&cwd).unwrap_or(cwd);
+        match runtime.find_files(&repo, query) {
+            Ok(hits) => {
+                for (path, score) in hits.iter().take(MAX_LISTED_FILES) {
+                    println!("{score:.2}  {}", path.display());
+                }
+            }
+            Err(err) => {
+                eprintln!("Error: {err}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let args::Command::Stats(repo) = command {
+        let repo = repo.unwrap_or_else(|| {
+            let cwd = std::env::current_dir().unwrap_or_else(|_| ".".into());
+            runtime::router::find_repo_root(&cwd).unwrap_or(cwd)
+        });
+        match runtime.repo_stats(&repo) {
+            Ok(stats) => print!("{}", stats.render()),
+            Err(err) => {
+                eprintln!("Error: {err}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     let cwd = std::env::current_dir().ok();
 
     if let Err(err) = tui::run(runtime, cwd).await {
         eprintln!("Error: {err}");
     }
 }
+
+#[cfg(feature = "schema")]
+fn print_schemas() {
+    println!("{:#}", runtime::schema::config_schemas());
+}
+
+#[cfg(not(feature = "schema"))]
+fn print_schemas() {
+    eprintln!("luna was built without the `schema` feature");
+    std::process::exit(2);
+}
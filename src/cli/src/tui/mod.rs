@@ -10,7 +10,7 @@ use futures_util::StreamExt;
 use ratatui::{backend::CrosstermBackend, Terminal};
 use tokio::sync::mpsc;
 
-use runtime::{LunaRuntime, RuntimeEvent};
+use runtime::{CancelToken, LunaRuntime, RuntimeEvent};
 
 mod runtime_bridge;
 mod state;
@@ -57,6 +57,19 @@ impl Drop for TerminalGuard {
 fn format_event_status(event: &RuntimeEvent) -> String {
     match event {
         RuntimeEvent::TparTaskClassified { task } => format!("[Task] {task}"),
+        RuntimeEvent::TparContextCollected { chunks, kinds } => {
+            if kinds.is_empty() {
+                format!("[Context] {chunks} chunk(s)")
+            } else {
+                format!("[Context] {chunks} chunk(s), matched: {kinds}")
+            }
+        }
+        RuntimeEvent::TparSourcesCollected { sources } => {
+            format!("[Sources] {} numbered", sources.len())
+        }
+        RuntimeEvent::TparContextRefreshed {
+            step_id, chunks, ..
+        } => format!("[Context] {chunks} chunk(s) refreshed after step {step_id}"),
         RuntimeEvent::TparPlanBuilt { plan } => format!("[Plan] {plan}"),
         RuntimeEvent::TparStepStarted { step_id, step } => {
             format!("[Step {step_id}] {step} ...")
@@ -67,6 +80,9 @@ fn format_event_status(event: &RuntimeEvent) -> String {
         RuntimeEvent::TparReviewed { ok } => {
             format!("[Review] {}", if *ok { "ok" } else { "needs revision" })
         }
+        RuntimeEvent::AnswerDelta { text } => {
+            format!("[Answer] {}", text.lines().next().unwrap_or_default())
+        }
         RuntimeEvent::ScopeGraphSearchStarted { repo_root } => {
             format!("[ScopeGraph] searching in {repo_root}")
         }
@@ -119,6 +135,13 @@ pub async fn run(runtime: Arc<LunaRuntime>, cwd: Option<std::path::PathBuf>) ->
                     _ => {}
                 }
             }
+            // Raw mode turns Ctrl+C into a key event; this catches SIGINT sent
+            // from elsewhere (e.g. `kill -INT`).
+            _ = tokio::signal::ctrl_c() => {
+                if interrupt(&mut app) {
+                    break;
+                }
+            }
             maybe_msg = rx.recv() => {
                 if let Some(msg) = maybe_msg {
                     handle_ui_msg(&mut app, msg);
@@ -143,6 +166,7 @@ fn handle_ui_msg(app: &mut state::AppState, msg: UiMsg) {
         } => {
             app.session_id = Some(session_id);
             app.busy = false;
+            app.cancel_token = None;
 
             // For slash commands, keep the result visible in status bar as well.
             if from_command {
@@ -178,37 +202,25 @@ fn handle_ui_msg(app: &mut state::AppState, msg: UiMsg) {
         }
         UiMsg::Err { err } => {
             app.busy = false;
+            app.cancel_token = None;
             app.status.clear();
             app.push_system(format!("❌ Error: {err}"));
         }
     }
 }
 
-/// Cancellation token
+/// Handles Ctrl+C / SIGINT; returns true to exit.
 ///
-/// Used to interrupt long-running tasks (reserved for future use)
-#[derive(Clone)]
-#[allow(dead_code)]
-pub struct CancelToken {
-    inner: Arc<tokio::sync::RwLock<bool>>,
-}
-
-impl CancelToken {
-    #[allow(dead_code)]
-    fn new() -> Self {
-        Self {
-            inner: Arc::new(tokio::sync::RwLock::new(false)),
+/// The first interrupt cancels a running turn, which then returns the output
+/// of the steps it finished; a second one (or one while idle) exits.
+fn interrupt(app: &mut state::AppState) -> bool {
+    match &app.cancel_token {
+        Some(cancel) if app.busy && !cancel.is_cancelled() => {
+            cancel.cancel();
+            app.status = "Cancelling after the current step... (Ctrl+C again to quit)".to_owned();
+            false
         }
-    }
-
-    #[allow(dead_code)]
-    async fn cancel(&self) {
-        *self.inner.write().await = true;
-    }
-
-    #[allow(dead_code)]
-    async fn is_cancelled(&self) -> bool {
-        *self.inner.read().await
+        _ => true,
     }
 }
 
@@ -217,9 +229,9 @@ async fn handle_key(
     key: KeyEvent,
     tx: mpsc::Sender<UiMsg>,
 ) -> error::Result<bool> {
-    // Exit
+    // Cancel the running turn, or exit
     if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
-        return Ok(true);
+        return Ok(interrupt(app));
     }
 
     match key.code {
@@ -1,8 +1,7 @@
 use std::path::PathBuf;
 use std::sync::Arc;
 
-use runtime::{LunaRuntime, RunRequest, SessionRef};
-use crate::tui::CancelToken;
+use runtime::{CancelToken, LunaRuntime, RunRequest, SessionRef};
 
 pub fn build_request(session_id: Option<&str>, cwd: Option<&PathBuf>, input: &str) -> RunRequest {
     let session = match session_id {
@@ -20,7 +19,9 @@ pub fn build_request(session_id: Option<&str>, cwd: Option<&PathBuf>, input: &st
 
 /// Run a single turn in a blocking thread, streaming RuntimeEvent to `event_tx`.
 ///
-/// Supports cancellation via `cancel` token (checked between events).
+/// Setting `cancel` stops the turn after its current plan step; the steps
+/// that ran are still returned. With `cite_sources` on, the numbered sources
+/// are listed after the answer.
 pub fn run_turn_blocking_with_events(
     handle: tokio::runtime::Handle,
     runtime: Arc<LunaRuntime>,
@@ -28,12 +29,63 @@ pub fn run_turn_blocking_with_events(
     cwd: Option<PathBuf>,
     input: String,
     event_tx: tokio::sync::mpsc::Sender<runtime::RuntimeEvent>,
-    _cancel: CancelToken,
+    cancel: CancelToken,
 ) -> error::Result<(String, String)> {
-    let req = build_request(session_id.as_deref(), cwd.as_ref(), &input);
+    let req = build_request(session_id.as_deref(), cwd.as_ref(), &input).with_cancel(cancel);
     let resp = handle.block_on(runtime.run_with_event_hook(req, |ev| {
         // Bounded channel: use try_send to avoid blocking
         let _ = event_tx.try_send(ev.clone());
     }))?;
-    Ok((resp.session_id, resp.output))
+    let mut output = resp.output.clone();
+    if runtime.config().cite_sources() && !resp.sources().is_empty() {
+        output.push_str("\n\nSources:");
+        for source in resp.sources() {
+            output.push_str(&format!("\n{source}"));
+        }
+    }
+    Ok((resp.session_id, output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answer `where is target_fn` over a one-file repo, with or without `--cite`.
+    fn answer(cite_sources: bool) -> String {
+        let repo = std::env::temp_dir().join(format!("luna_cli_cite_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&repo).unwrap();
+        std::fs::write(repo.join("lib.rs"), "pub fn target_fn() -> u32 {\n    1\n}\n").unwrap();
+
+        let config = runtime::RuntimeConfig::new()
+            .with_session_store(Arc::new(session::InMemorySessionStore::new()))
+            .with_trajectory(Arc::new(runtime::NoopTrajectoryRecorder))
+            .with_planner(Arc::new(runtime::planner::RuleBasedPlanner::new()))
+            .with_cite_sources(cite_sources);
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let (event_tx, _event_rx) = tokio::sync::mpsc::channel(64);
+        let (_, output) = run_turn_blocking_with_events(
+            rt.handle().clone(),
+            Arc::new(LunaRuntime::with_config(config)),
+            None,
+            Some(repo.clone()),
+            "where is target_fn defined?".to_owned(),
+            event_tx,
+            CancelToken::new(),
+        )
+        .unwrap();
+
+        let _ = std::fs::remove_dir_all(&repo);
+        output
+    }
+
+    #[test]
+    fn cite_lists_numbered_sources_after_the_answer() {
+        let cited = answer(true);
+        let (body, sources) = cited.split_once("\n\nSources:").expect(&cited);
+        assert!(body.contains("target_fn"), "{cited}");
+        assert!(sources.starts_with("\n[1] lib.rs:1"), "{cited}");
+
+        let plain = answer(false);
+        assert!(!plain.contains("Sources:"), "{plain}");
+    }
 }
@@ -1,8 +1,7 @@
 use std::path::PathBuf;
 use std::sync::Arc;
 
-use runtime::LunaRuntime;
-use crate::tui::CancelToken;
+use runtime::{CancelToken, LunaRuntime};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ChatRole {
@@ -42,7 +41,7 @@ impl AppState {
             session_id: None,
             messages: vec![ChatMessage {
                 role: ChatRole::System,
-                content: "🌙 Luna - AI Code Assistant\nCtrl+C Exit | Enter Send | PgUp/PgDn Scroll"
+                content: "🌙 Luna - AI Code Assistant\nCtrl+C Cancel/Exit | Enter Send | PgUp/PgDn Scroll"
                     .to_owned(),
             }],
             input: String::new(),
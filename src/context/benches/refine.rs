@@ -0,0 +1,132 @@
+//! Benchmarks for the refine phase on large hit sets.
+//!
+//! Run with `cargo bench -p context`.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use context::{
+    refill::{FileProvider, SymbolResolver},
+    IndexChunk, IndexChunkType, RefillPipeline, SourceLocation, SymbolId, TextRange, TokenBudget,
+};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+struct NoFiles;
+
+impl FileProvider for NoFiles {
+    fn list_files(&self, _repo_root: &Path) -> error::Result<Vec<PathBuf>> {
+        Ok(Vec::new())
+    }
+
+    fn read_file(&self, path: &Path) -> error::Result<String> {
+        Err(error::LunaError::not_found(format!("{}", path.display())))
+    }
+
+    fn modified_time(&self, _path: &Path) -> error::Result<u64> {
+        Ok(0)
+    }
+}
+
+struct NoSymbols;
+
+impl SymbolResolver for NoSymbols {
+    fn find_definition(
+        &self,
+        _repo_root: &Path,
+        _name: &str,
+    ) -> error::Result<Vec<SourceLocation>> {
+        Ok(Vec::new())
+    }
+
+    fn find_references(
+        &self,
+        _repo_root: &Path,
+        _name: &str,
+        _max: usize,
+    ) -> error::Result<Vec<SourceLocation>> {
+        Ok(Vec::new())
+    }
+
+    fn get_signature(
+        &self,
+        _repo_root: &Path,
+        _location: &SourceLocation,
+    ) -> error::Result<Option<String>> {
+        Ok(None)
+    }
+
+    fn get_snippet(
+        &self,
+        _repo_root: &Path,
+        _location: &SourceLocation,
+        _context_lines: usize,
+    ) -> error::Result<String> {
+        Ok(String::new())
+    }
+}
+
+/// `n` hits over a mix of chunk types, with every 10th body duplicated in
+/// another file and every 7th symbol repeated.
+fn hits(n: usize) -> Vec<IndexChunk> {
+    let types = [
+        IndexChunkType::SymbolDefinition,
+        IndexChunkType::SymbolReference,
+        IndexChunkType::CodeBlock,
+        IndexChunkType::Documentation,
+        IndexChunkType::FileSummary,
+    ];
+    (0..n)
+        .map(|i| {
+            let body = if i % 10 == 0 { 0 } else { i };
+            let content = format!(
+                "pub fn item_{body}(x: usize) -> usize {{\n    let y = x + {body};\n    y * 2\n}}"
+            );
+            let source = SourceLocation {
+                repo_root: PathBuf::from("/repo"),
+                rel_path: PathBuf::from(format!("src/m{}/f{i}.rs", i % 17)),
+                range: TextRange::new(1 + i % 50, 4 + i % 50),
+            };
+            let mut chunk = IndexChunk::new(content, source, types[i % types.len()]);
+            if i % 7 == 0 {
+                chunk
+                    .symbols
+                    .push(SymbolId::new(format!("item_{}", i % 49), "crate"));
+            }
+            chunk
+        })
+        .collect()
+}
+
+fn pipeline() -> RefillPipeline {
+    RefillPipeline::new(
+        PathBuf::from("/repo"),
+        Arc::new(NoFiles),
+        Arc::new(NoSymbols),
+        TokenBudget {
+            max_context_tokens: 4000,
+        },
+    )
+}
+
+fn bench_refine(c: &mut Criterion) {
+    let mut group = c.benchmark_group("refine");
+    for n in [100, 1_000, 10_000] {
+        let chunks = hits(n);
+        group.bench_with_input(BenchmarkId::from_parameter(n), &chunks, |b, chunks| {
+            // Fresh pipeline per iteration so the token cache doesn't carry over.
+            b.iter(|| pipeline().refine(black_box(chunks)).len());
+        });
+    }
+    group.finish();
+}
+
+fn bench_render(c: &mut Criterion) {
+    let pipeline = pipeline();
+    let refined = pipeline.refine(&hits(1_000));
+    c.bench_function("build_context_string", |b| {
+        b.iter(|| pipeline.build_context_string(black_box(&refined)).len());
+    });
+}
+
+criterion_group!(benches, bench_refine, bench_render);
+criterion_main!(benches);
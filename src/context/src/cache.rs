@@ -3,9 +3,42 @@
 //! Supports incremental Refill operations by caching retrieved chunks.
 
 use std::collections::HashMap;
-use std::sync::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    ChunkId, ContextChunk, ContextQuery, IndexChunk, SourceLocation, SymbolId, TokenCounter,
+};
+
+/// Token ids and byte offsets for one piece of text
+pub type TokenEncoding = Arc<(Vec<u32>, Vec<(usize, usize)>)>;
+
+/// Token encodings are spread over this many locks, so sessions sharing one
+/// cache across threads rarely wait on each other.
+const TOKEN_SHARDS: usize = 16;
+
+type EncodingShard = Mutex<HashMap<(u64, usize), TokenEncoding>>;
+
+/// One version of a file, identified by content rather than mtime
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FileCacheKey {
+    pub rel_path: PathBuf,
+    /// FNV-1a hash of the content
+    pub content_hash: u64,
+    pub len: usize,
+}
 
-use crate::{ChunkId, ContextChunk, ContextQuery, IndexChunk, SourceLocation, SymbolId};
+impl FileCacheKey {
+    #[must_use]
+    pub fn from_content(rel_path: impl Into<PathBuf>, content: &str) -> Self {
+        Self {
+            rel_path: rel_path.into(),
+            content_hash: crate::fnv1a64(content.as_bytes()),
+            len: content.len(),
+        }
+    }
+}
 
 /// Cache entry with metadata
 #[derive(Debug, Clone)]
@@ -45,6 +78,15 @@ pub struct ContextCache {
     file_index: Mutex<HashMap<SourceLocation, Vec<ChunkId>>>,
     /// Map from query hash to results
     query_cache: Mutex<HashMap<String, Vec<ChunkId>>>,
+    /// Map from (content hash, length) to its encoding
+    ///
+    /// Offsets are cached alongside ids: truncation needs them, and
+    /// recomputing them costs a full encode anyway.
+    tokens: Box<[EncodingShard]>,
+    /// Last content seen per file, see `sync_file_content`
+    file_versions: Mutex<HashMap<PathBuf, FileCacheKey>>,
+    token_hits: AtomicUsize,
+    token_misses: AtomicUsize,
     /// Maximum cache size
     max_entries: usize,
 }
@@ -58,6 +100,12 @@ impl ContextCache {
             symbol_index: Mutex::new(HashMap::new()),
             file_index: Mutex::new(HashMap::new()),
             query_cache: Mutex::new(HashMap::new()),
+            tokens: (0..TOKEN_SHARDS)
+                .map(|_| Mutex::new(HashMap::new()))
+                .collect(),
+            file_versions: Mutex::new(HashMap::new()),
+            token_hits: AtomicUsize::new(0),
+            token_misses: AtomicUsize::new(0),
             max_entries,
         }
     }
@@ -194,6 +242,24 @@ impl ContextCache {
         }
     }
 
+    /// Cached results for `query`, or the result of `retrieve`, which is then
+    /// stored and cached. Errors are passed through and not cached.
+    pub fn get_or_retrieve_with(
+        &self,
+        query: &ContextQuery,
+        retrieve: impl FnOnce() -> error::Result<Vec<IndexChunk>>,
+    ) -> error::Result<Vec<IndexChunk>> {
+        if let Some(hit) = self.get_cached_query(query) {
+            return Ok(hit);
+        }
+
+        let chunks = retrieve()?;
+        let ids = chunks.iter().map(|c| c.id).collect();
+        self.store_batch(chunks.clone());
+        self.cache_query_result(query, ids);
+        Ok(chunks)
+    }
+
     /// Check if a query is cached
     #[must_use]
     pub fn is_query_cached(&self, query: &ContextQuery) -> bool {
@@ -228,6 +294,37 @@ impl ContextCache {
         query_cache.clear();
     }
 
+    /// Record the current content of a file, invalidating its chunks if it
+    /// differs from the last recorded content. Returns `true` on a change.
+    ///
+    /// Compares content, so it catches edits that keep the mtime (checkouts,
+    /// tools preserving timestamps).
+    pub fn sync_file_content(&self, rel_path: &Path, content: &str) -> bool {
+        let key = FileCacheKey::from_content(rel_path, content);
+        let previous = self
+            .file_versions
+            .lock()
+            .unwrap()
+            .insert(rel_path.to_path_buf(), key.clone());
+        if previous.is_none_or(|prev| prev == key) {
+            return false;
+        }
+
+        let stale = self
+            .file_index
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|source| source.rel_path == rel_path)
+            .cloned()
+            .collect::<Vec<_>>();
+        for source in &stale {
+            self.invalidate_file(source);
+        }
+        self.query_cache.lock().unwrap().clear();
+        true
+    }
+
     /// Get cache statistics
     #[must_use]
     pub fn stats(&self) -> CacheStats {
@@ -241,6 +338,9 @@ impl ContextCache {
             total_symbols: symbol_index.len(),
             total_files: file_index.len(),
             cached_queries: query_cache.len(),
+            cached_encodings: self.tokens.iter().map(|s| s.lock().unwrap().len()).sum(),
+            token_hits: self.token_hits.load(Ordering::Relaxed),
+            token_misses: self.token_misses.load(Ordering::Relaxed),
         }
     }
 
@@ -250,11 +350,16 @@ impl ContextCache {
         let mut symbol_index = self.symbol_index.lock().unwrap();
         let mut file_index = self.file_index.lock().unwrap();
         let mut query_cache = self.query_cache.lock().unwrap();
+        let mut file_versions = self.file_versions.lock().unwrap();
 
         chunks.clear();
         symbol_index.clear();
         file_index.clear();
         query_cache.clear();
+        for shard in self.tokens.iter() {
+            shard.lock().unwrap().clear();
+        }
+        file_versions.clear();
     }
 
     /// Evict entries when cache is full
@@ -304,6 +409,61 @@ pub struct CacheStats {
     pub total_symbols: usize,
     pub total_files: usize,
     pub cached_queries: usize,
+    pub cached_encodings: usize,
+    pub token_hits: usize,
+    pub token_misses: usize,
+}
+
+/// Tokenization through the cache
+impl ContextCache {
+    /// Encode `text` with `counter`, reusing a previous encoding of identical text.
+    ///
+    /// Entries are keyed by content, so they stay valid until the text changes;
+    /// the cache must only be shared between callers using the same counter.
+    pub fn encode_cached(&self, text: &str, counter: &dyn TokenCounter) -> TokenEncoding {
+        let key = (crate::fnv1a64(text.as_bytes()), text.len());
+        let shard = &self.tokens[(key.0 as usize) % TOKEN_SHARDS];
+        if let Some(hit) = shard.lock().unwrap().get(&key) {
+            self.token_hits.fetch_add(1, Ordering::Relaxed);
+            return Arc::clone(hit);
+        }
+
+        self.token_misses.fetch_add(1, Ordering::Relaxed);
+        let encoding = Arc::new(counter.encode_with_offsets(text));
+
+        let mut tokens = shard.lock().unwrap();
+        // Simple eviction: start the shard over when full
+        if tokens.len() >= self.max_entries.div_ceil(TOKEN_SHARDS) {
+            tokens.clear();
+        }
+        tokens.insert(key, Arc::clone(&encoding));
+        encoding
+    }
+
+    /// Wrap `counter` so counts and encodings go through this cache.
+    #[must_use]
+    pub fn cached_counter<'a>(&'a self, counter: &'a dyn TokenCounter) -> CachedTokenCounter<'a> {
+        CachedTokenCounter {
+            cache: self,
+            inner: counter,
+        }
+    }
+}
+
+/// [`TokenCounter`] backed by a [`ContextCache`]; see [`ContextCache::cached_counter`].
+pub struct CachedTokenCounter<'a> {
+    cache: &'a ContextCache,
+    inner: &'a dyn TokenCounter,
+}
+
+impl TokenCounter for CachedTokenCounter<'_> {
+    fn count(&self, text: &str) -> usize {
+        self.cache.encode_cached(text, self.inner).0.len()
+    }
+
+    fn encode_with_offsets(&self, text: &str) -> (Vec<u32>, Vec<(usize, usize)>) {
+        self.cache.encode_cached(text, self.inner).as_ref().clone()
+    }
 }
 
 /// Conversion from IndexChunk to ContextChunk (for cached results)
@@ -369,6 +529,66 @@ mod tests {
         chunk
     }
 
+    #[test]
+    fn test_encode_cached_reuses_encoding() {
+        let cache = ContextCache::new(100);
+        let counter = crate::CharApproxCounter;
+        let cached = cache.cached_counter(&counter);
+
+        assert_eq!(cached.count("fn foo() {}"), 3);
+        assert_eq!(cached.count("fn foo() {}"), 3);
+        assert_eq!(cached.count("fn bar() {}"), 3);
+
+        let stats = cache.stats();
+        assert_eq!(stats.cached_encodings, 2);
+        assert_eq!(stats.token_hits, 1);
+        assert_eq!(stats.token_misses, 2);
+    }
+
+    #[test]
+    fn test_file_cache_key_tracks_content() {
+        let a = FileCacheKey::from_content("src/lib.rs", "fn foo() {}");
+        let b = FileCacheKey::from_content("src/lib.rs", "fn bar() {}");
+        assert_ne!(a, b);
+        assert_eq!(a, FileCacheKey::from_content("src/lib.rs", "fn foo() {}"));
+
+        let cache = ContextCache::new(100);
+        let chunk = test_chunk("foo");
+        let id = chunk.id;
+        cache.store(chunk);
+
+        let path = Path::new("src/lib.rs");
+        assert!(!cache.sync_file_content(path, "fn foo() {}"));
+        assert!(!cache.sync_file_content(path, "fn foo() {}"));
+        assert!(cache.get(id).is_some());
+
+        assert!(cache.sync_file_content(path, "fn bar() {}"));
+        assert!(cache.get(id).is_none());
+    }
+
+    #[test]
+    fn test_encode_cached_from_many_threads() {
+        let cache = ContextCache::new(1000);
+        let texts = (0..50)
+            .map(|i| format!("fn f{i}() {{}}"))
+            .collect::<Vec<_>>();
+
+        std::thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| {
+                    for text in &texts {
+                        let enc = cache.encode_cached(text, &crate::CharApproxCounter);
+                        assert_eq!(enc.1.last().map(|o| o.1), Some(text.len()));
+                    }
+                });
+            }
+        });
+
+        let stats = cache.stats();
+        assert_eq!(stats.cached_encodings, 50);
+        assert_eq!(stats.token_hits + stats.token_misses, 400);
+    }
+
     #[test]
     fn test_store_and_get() {
         let cache = ContextCache::new(100);
@@ -412,6 +632,29 @@ mod tests {
         assert_eq!(results.unwrap().len(), 1);
     }
 
+    #[test]
+    fn test_get_or_retrieve_with_runs_retrieve_once() {
+        let cache = ContextCache::new(100);
+        let query = ContextQuery::symbol("foo");
+        let calls = AtomicUsize::new(0);
+        let retrieve = || {
+            calls.fetch_add(1, Ordering::Relaxed);
+            Ok(vec![test_chunk("foo")])
+        };
+
+        let first = cache.get_or_retrieve_with(&query, retrieve).unwrap();
+        for _ in 0..3 {
+            let again = cache.get_or_retrieve_with(&query, retrieve).unwrap();
+            assert_eq!(again[0].id, first[0].id);
+        }
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        let failing = ContextQuery::symbol("bar");
+        let err = cache.get_or_retrieve_with(&failing, || Err(error::LunaError::not_found("bar")));
+        assert!(err.is_err());
+        assert!(!cache.is_query_cached(&failing));
+    }
+
     #[test]
     fn test_invalidate_file() {
         let cache = ContextCache::new(100);
@@ -2,9 +2,12 @@
 //!
 //! Optimized for LLM consumption - token-efficient, high relevance.
 
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
 use serde::{Deserialize, Serialize};
 
-use crate::{ChunkId, SourceLocation};
+use crate::{CharApproxCounter, ChunkId, SourceLocation, TokenCounter};
 
 /// Context type - what role this chunk plays in the prompt
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -22,6 +25,10 @@ pub enum ContextType {
     RelatedSymbol,
     /// Documentation
     Documentation,
+    /// Import/use block of a file that other selected chunks come from
+    Imports,
+    /// File the user pinned; included regardless of retrieval
+    Pinned,
 }
 
 /// ContextChunk: Refined content ready for LLM consumption
@@ -31,18 +38,40 @@ pub enum ContextType {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContextChunk {
     pub id: ChunkId,
+    /// Location-derived handle, stable across retrieval runs
+    #[serde(default)]
+    pub stable_id: String,
     /// Refined content (may be truncated/optimized)
     pub content: String,
     /// Source location
     pub source: SourceLocation,
     /// Combined relevance score (0.0 - 1.0)
     pub relevance_score: f32,
+    /// How precisely this chunk was located (0.0 - 1.0): scope-resolved
+    /// definitions are high, fixed line windows and file heads are low
+    #[serde(default = "default_confidence")]
+    pub confidence: f32,
     /// Actual token count
     pub token_count: usize,
     /// Symbol signatures injected into this chunk
     pub symbol_signatures: Vec<String>,
     /// Type of context
     pub context_type: ContextType,
+    /// Other paths containing byte-identical content (collapsed into this chunk)
+    #[serde(default)]
+    pub also_in: Vec<PathBuf>,
+    /// Near-identical chunks collapsed into this one (see
+    /// `RefillPipeline::with_near_duplicate_threshold`)
+    #[serde(default)]
+    pub near_duplicates: usize,
+    /// Scope-graph kind (`function`, `struct`, ...) when the chunk was resolved
+    /// to a definition; takes precedence over guessing from signatures
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope_kind: Option<String>,
+    /// 1-based line that matched; [`Self::truncate_to_lines`] keeps the lines
+    /// around it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hit_line: Option<usize>,
 }
 
 impl ContextChunk {
@@ -58,12 +87,18 @@ impl ContextChunk {
 
         Self {
             id: ChunkId::new(),
+            stable_id: source.stable_id(),
             content,
             source,
             relevance_score: 0.0,
+            confidence: default_confidence(),
             token_count,
             symbol_signatures: Vec::new(),
             context_type,
+            also_in: Vec::new(),
+            near_duplicates: 0,
+            scope_kind: None,
+            hit_line: None,
         }
     }
 
@@ -102,58 +137,365 @@ impl ContextChunk {
         self.relevance_score = score.clamp(0.0, 1.0);
     }
 
-    /// Format this chunk for inclusion in a prompt
+    /// Set retrieval confidence
+    pub fn set_confidence(&mut self, confidence: f32) {
+        self.confidence = confidence.clamp(0.0, 1.0);
+    }
+
+    /// Score used for ranking under a token budget: relevance weighted by confidence
+    #[must_use]
+    pub fn rank_score(&self) -> f32 {
+        self.relevance_score * self.confidence
+    }
+
+    /// Format this chunk for inclusion in a prompt (default template)
     #[must_use]
     pub fn format_for_prompt(&self) -> String {
-        let mut output = String::new();
-
-        // Header with location
-        output.push_str(&format!(
-            "// {}:{}-{}",
-            self.source.rel_path.display(),
-            self.source.range.start_line,
-            self.source.range.end_line
-        ));
-
-        // Add signatures if present
-        if !self.symbol_signatures.is_empty() {
-            output.push_str(" (");
-            for (i, sig) in self.symbol_signatures.iter().enumerate() {
-                if i > 0 {
-                    output.push_str(", ");
-                }
-                output.push_str(sig);
-            }
-            output.push(')');
+        crate::PromptTemplate::default().render_chunk(self)
+    }
+
+    /// Kind of the symbol this chunk is about (`fn`, `struct`, ...).
+    ///
+    /// Uses `scope_kind` when the chunk was resolved through the scope graph,
+    /// otherwise the first recognizable signature.
+    #[must_use]
+    pub fn symbol_kind(&self) -> Option<&'static str> {
+        if let Some(kind) = &self.scope_kind {
+            return scope_kind(kind);
         }
-        output.push('\n');
+        self.symbol_signatures.iter().find_map(|sig| signature_kind(sig))
+    }
 
-        // Content
-        output.push_str(&self.content);
-        output.push('\n');
+    /// Like [`Self::symbol_kind`], with `overrides` applied to each signature.
+    #[must_use]
+    pub fn symbol_kind_with<'a>(&self, overrides: &'a KindOverrides) -> Option<&'a str> {
+        if let Some(kind) = &self.scope_kind {
+            return scope_kind(kind);
+        }
+        self.symbol_signatures
+            .iter()
+            .find_map(|sig| signature_kind_with(sig, overrides))
+    }
 
-        output
+    /// Recompute `token_count` with a specific counter
+    pub fn recount_tokens(&mut self, counter: &dyn TokenCounter) {
+        self.token_count = counter.count(&self.content);
     }
 
     /// Truncate content to fit within token budget
     pub fn truncate_to_tokens(&mut self, max_tokens: usize) {
+        self.truncate_to_tokens_with(max_tokens, &CharApproxCounter);
+    }
+
+    /// Truncate content to fit within token budget, cutting at `counter`'s token boundaries
+    pub fn truncate_to_tokens_with(&mut self, max_tokens: usize, counter: &dyn TokenCounter) {
         if self.token_count <= max_tokens {
             return;
         }
 
-        // Approximate: 4 chars per token
-        let max_chars = max_tokens * 4;
-        if self.content.len() > max_chars {
-            let truncated = &self.content[..max_chars];
-            // Try to truncate at a line boundary
+        // Zero-width tokens (special tokens such as a `(0, 0)` `[SEP]`) cover no
+        // text: they neither use up the budget nor mark a boundary.
+        let (_, offsets) = counter.encode_with_offsets(&self.content);
+        let ends = offsets
+            .iter()
+            .filter(|(start, end)| start < end)
+            .map(|&(_, end)| end)
+            .collect::<Vec<_>>();
+        if ends.len() > max_tokens {
+            let cut = max_tokens.checked_sub(1).map_or(0, |last| ends[last]);
+            let truncated = &self.content[..cut];
+            // Try to truncate at a line boundary (dropping a CRLF's `\r` too)
             if let Some(last_newline) = truncated.rfind('\n') {
-                self.content = truncated[..last_newline].to_string();
+                self.content = truncated[..last_newline].trim_end_matches('\r').to_string();
             } else {
                 self.content = truncated.to_string();
             }
-            self.token_count = crate::TokenBudget::estimate_tokens(&self.content);
         }
+        self.recount_tokens(counter);
     }
+
+    /// Keep `max_lines` lines of content centred on `hit_line`, replacing the
+    /// lines cut above and below with `// ... (N lines omitted) ...` (`#` for
+    /// languages commented that way). Call [`Self::recount_tokens`] afterwards.
+    ///
+    /// The window is centred only when content is exactly the lines of
+    /// `source.range`; `source.range` then shrinks to the kept lines.
+    /// Otherwise (a signature prepended, a hit outside the range) the first
+    /// lines are kept.
+    pub fn truncate_to_lines(&mut self, max_lines: usize) {
+        let lines: Vec<&str> = self.content.lines().collect();
+        // Eliding a single line would save nothing over the marker itself.
+        if lines.len() <= max_lines + 1 {
+            return;
+        }
+        let range = self.source.range;
+        let aligned = range.end_line + 1 == range.start_line + lines.len();
+        let hit = self
+            .hit_line
+            .filter(|_| aligned)
+            .and_then(|line| line.checked_sub(range.start_line))
+            .filter(|&i| i < lines.len())
+            .unwrap_or(0);
+
+        let mut start = hit
+            .saturating_sub(max_lines.saturating_sub(1) / 2)
+            .min(lines.len() - max_lines);
+        let mut end = start + max_lines;
+        // Same as above: keep a lone cut line rather than mark it.
+        if start == 1 {
+            start = 0;
+        }
+        if lines.len() - end == 1 {
+            end = lines.len();
+        }
+
+        let comment = match self.source.rel_path.extension().and_then(|e| e.to_str()) {
+            Some("py" | "rb" | "r" | "R" | "sh" | "toml" | "yaml" | "yml") => "#",
+            _ => "//",
+        };
+        let marker = |omitted: usize, next_to: &str| {
+            let indent = next_to
+                .find(|c: char| !c.is_whitespace())
+                .map_or("", |n| &next_to[..n]);
+            format!("{indent}{comment} ... ({omitted} lines omitted) ...")
+        };
+
+        let mut out = Vec::with_capacity(end - start + 2);
+        if start > 0 {
+            out.push(marker(start, lines[start]));
+        }
+        out.extend(lines[start..end].iter().map(|l| (*l).to_string()));
+        if end < lines.len() {
+            out.push(marker(lines.len() - end, lines[end.saturating_sub(1)]));
+        }
+        self.content = out.join("\n");
+        if aligned {
+            self.source.range =
+                crate::TextRange::new(range.start_line + start, range.start_line + end - 1);
+        }
+    }
+}
+
+fn default_confidence() -> f32 {
+    1.0
+}
+
+/// Raw signature keyword (`impl`, `def`, `macro_rules`, ...) -> kind reported
+/// instead of the built-in mapping. Lets callers fix or extend classification
+/// for a language without changing [`signature_kind`].
+pub type KindOverrides = HashMap<String, String>;
+
+/// Keyword-level kind of a signature line, e.g. `pub(crate) async fn x()` -> `fn`.
+#[must_use]
+pub fn signature_kind(sig: &str) -> Option<&'static str> {
+    signature_keyword(sig).and_then(keyword_kind)
+}
+
+/// Like [`signature_kind`], but a keyword found in `overrides` wins.
+#[must_use]
+pub fn signature_kind_with<'a>(sig: &str, overrides: &'a KindOverrides) -> Option<&'a str> {
+    let keyword = signature_keyword(sig)?;
+    overrides
+        .get(keyword)
+        .map(String::as_str)
+        .or_else(|| keyword_kind(keyword))
+}
+
+/// Definition patterns: the built-in keyword table and modifier list, plus
+/// caller-registered keywords and modifiers for other languages (Kotlin
+/// `override fun`, Swift `func`, ...).
+#[derive(Debug, Clone, Default)]
+pub struct SignaturePatterns {
+    /// Keyword -> kind, checked before the built-in table
+    pub keywords: KindOverrides,
+    /// Words skipped before the keyword, in addition to the built-in modifiers
+    pub modifiers: Vec<String>,
+}
+
+impl SignaturePatterns {
+    #[must_use]
+    pub fn with_keyword(mut self, keyword: impl Into<String>, kind: impl Into<String>) -> Self {
+        self.keywords.insert(keyword.into(), kind.into());
+        self
+    }
+
+    #[must_use]
+    pub fn with_modifier(mut self, modifier: impl Into<String>) -> Self {
+        self.modifiers.push(modifier.into());
+        self
+    }
+
+    /// Keyword-level kind of `sig`, see [`signature_kind`].
+    #[must_use]
+    pub fn kind(&self, sig: &str) -> Option<&str> {
+        let (keyword, _) = split_signature(sig, &self.modifiers)?;
+        self.keywords
+            .get(keyword)
+            .map(String::as_str)
+            .or_else(|| keyword_kind(keyword))
+    }
+
+    /// Kind of the symbol `chunk` is about, see [`ContextChunk::symbol_kind`].
+    #[must_use]
+    pub fn symbol_kind(&self, chunk: &ContextChunk) -> Option<&str> {
+        if let Some(kind) = &chunk.scope_kind {
+            return scope_kind(kind);
+        }
+        chunk.symbol_signatures.iter().find_map(|sig| self.kind(sig))
+    }
+
+    /// Name of the defined symbol, e.g. `pub async fn load<T>(..)` -> `load`.
+    ///
+    /// `None` unless the keyword is a known definition keyword.
+    #[must_use]
+    pub fn name<'s>(&self, sig: &'s str) -> Option<&'s str> {
+        self.kind(sig)?;
+        let (_, rest) = split_signature(sig, &self.modifiers)?;
+        let rest = rest.trim_start();
+        let end = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        (end > 0).then(|| &rest[..end])
+    }
+}
+
+/// First word of a signature after visibility/async/... modifiers.
+fn signature_keyword(sig: &str) -> Option<&str> {
+    split_signature(sig, &[]).map(|(keyword, _)| keyword)
+}
+
+/// Split `sig` into its keyword and the text after it, skipping built-in and
+/// `extra_modifiers`.
+fn split_signature<'s>(sig: &'s str, extra_modifiers: &[String]) -> Option<(&'s str, &'s str)> {
+    const MODIFIERS: &[&str] = &[
+        "pub",
+        "async",
+        "unsafe",
+        "extern",
+        "export",
+        "default",
+        "static",
+        "public",
+        "private",
+        "protected",
+        "abstract",
+        "final",
+    ];
+
+    let mut rest = sig.trim_start();
+    loop {
+        if rest.starts_with("pub(") {
+            let close = rest.find(')')?;
+            rest = rest[close + 1..].trim_start();
+            continue;
+        }
+        let word_end = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        let word = &rest[..word_end];
+        if MODIFIERS.contains(&word) || extra_modifiers.iter().any(|m| m == word) {
+            rest = rest[word_end..].trim_start();
+            continue;
+        }
+        return (!word.is_empty()).then_some((word, &rest[word_end..]));
+    }
+}
+
+fn keyword_kind(word: &str) -> Option<&'static str> {
+    match word {
+        "fn" | "func" | "function" | "def" => Some("fn"),
+        "struct" => Some("struct"),
+        "enum" => Some("enum"),
+        "trait" => Some("trait"),
+        "impl" => Some("impl"),
+        "type" | "typedef" => Some("type"),
+        "mod" | "module" | "namespace" => Some("mod"),
+        "class" => Some("class"),
+        "interface" => Some("interface"),
+        "const" => Some("const"),
+        _ => None,
+    }
+}
+
+/// Map a scope-graph kind to the keyword-level kinds used above. Locals
+/// (`variable`, `parameter`, ...) are not definitions worth reporting.
+fn scope_kind(kind: &str) -> Option<&'static str> {
+    match kind {
+        "function" | "method" | "macro" => Some("fn"),
+        "struct" | "union" => Some("struct"),
+        "enum" => Some("enum"),
+        "trait" => Some("trait"),
+        "class" => Some("class"),
+        "interface" => Some("interface"),
+        "typedef" | "alias" | "type" => Some("type"),
+        "module" | "namespace" | "package" => Some("mod"),
+        "const" | "constant" | "static" => Some("const"),
+        _ => None,
+    }
+}
+
+/// Count how many chunks refer to each symbol kind.
+#[must_use]
+pub fn symbol_kind_summary(chunks: &[ContextChunk]) -> BTreeMap<&'static str, usize> {
+    let mut summary = BTreeMap::new();
+    for kind in chunks.iter().filter_map(ContextChunk::symbol_kind) {
+        *summary.entry(kind).or_insert(0) += 1;
+    }
+    summary
+}
+
+/// Like [`symbol_kind_summary`], classifying with `overrides`.
+#[must_use]
+pub fn symbol_kind_summary_with<'a>(
+    chunks: &[ContextChunk],
+    overrides: &'a KindOverrides,
+) -> BTreeMap<&'a str, usize> {
+    let mut summary = BTreeMap::new();
+    for kind in chunks.iter().filter_map(|c| c.symbol_kind_with(overrides)) {
+        *summary.entry(kind).or_insert(0) += 1;
+    }
+    summary
+}
+
+/// Render a kind summary as `3 fn, 2 struct` (most frequent first).
+#[must_use]
+pub fn format_kind_summary(summary: &BTreeMap<&str, usize>) -> String {
+    let mut entries = summary.iter().collect::<Vec<_>>();
+    entries.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    entries
+        .into_iter()
+        .map(|(kind, n)| format!("{n} {kind}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Hash of what a set of chunks shows the model, as 16 hex digits.
+///
+/// Built from the sorted `(path, start_line, end_line, content hash)` of each
+/// chunk, so it ignores order, scores and `ChunkId`s: two retrieval runs that
+/// picked the same code hash the same. Callers can key cached answers on
+/// `(question, context_hash)` and skip the LLM when neither changed.
+#[must_use]
+pub fn context_hash(chunks: &[ContextChunk]) -> String {
+    let mut entries = chunks
+        .iter()
+        .map(|c| {
+            (
+                &c.source.rel_path,
+                c.source.range.start_line,
+                c.source.range.end_line,
+                crate::fnv1a64(c.content.as_bytes()),
+            )
+        })
+        .collect::<Vec<_>>();
+    entries.sort();
+
+    let key = entries
+        .iter()
+        .map(|(path, start, end, hash)| format!("{}:{start}:{end}:{hash:016x}", path.display()))
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!("{:016x}", crate::fnv1a64(key.as_bytes()))
 }
 
 /// Builder for constructing context chunks from index chunks
@@ -205,6 +547,7 @@ impl ContextChunkBuilder {
 mod tests {
     use super::*;
     use crate::TextRange;
+    use proptest::prelude::*;
     use std::path::PathBuf;
 
     fn test_source() -> SourceLocation {
@@ -237,6 +580,36 @@ mod tests {
         assert_eq!(chunk.symbol_signatures.len(), 1);
     }
 
+    #[test]
+    fn test_context_hash_ignores_order_and_ids() {
+        let at = |path: &str, line: usize, content: &str| {
+            let source = SourceLocation {
+                rel_path: PathBuf::from(path),
+                range: TextRange::new(line, line + 2),
+                ..test_source()
+            };
+            ContextChunk::new(content, source, ContextType::CodeSnippet)
+        };
+        let a = at("src/a.rs", 1, "fn a() {}");
+        let b = at("src/b.rs", 5, "fn b() {}");
+
+        let hash = context_hash(&[a.clone(), b]);
+        assert_eq!(hash.len(), 16);
+        let mut rescored = at("src/b.rs", 5, "fn b() {}");
+        rescored.set_relevance(0.9);
+        assert_eq!(context_hash(&[rescored, a.clone()]), hash);
+
+        assert_ne!(
+            context_hash(&[a.clone(), at("src/b.rs", 5, "fn b() { 1 }")]),
+            hash
+        );
+        assert_ne!(
+            context_hash(&[a.clone(), at("src/b.rs", 6, "fn b() {}")]),
+            hash
+        );
+        assert_ne!(context_hash(&[a]), hash);
+    }
+
     #[test]
     fn test_format_for_prompt() {
         let chunk = ContextChunk::navigation_result(
@@ -265,6 +638,200 @@ mod tests {
         assert!(!chunk.content.contains("line3"));
     }
 
+    #[test]
+    fn test_truncate_to_lines_centres_on_hit() {
+        let body = (1..=10)
+            .map(|i| format!("    step{i}();"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let content = format!("fn run() {{\n{body}\n}}");
+        // Content is lines 10-21 of the file; step6() on line 16 matched
+        let mut source = test_source();
+        source.range = TextRange::new(10, 21);
+
+        let mut chunk = ContextChunk::new(content.clone(), source.clone(), ContextType::CodeSnippet);
+        chunk.hit_line = Some(16);
+        chunk.truncate_to_lines(3);
+        assert_eq!(
+            chunk.content,
+            "    // ... (5 lines omitted) ...\n    step5();\n    step6();\n    step7();\n    \
+             // ... (4 lines omitted) ..."
+        );
+        assert_eq!(chunk.source.range, TextRange::new(15, 17));
+
+        // Without a hit the window starts at the top
+        let mut chunk = ContextChunk::new(content.clone(), source.clone(), ContextType::CodeSnippet);
+        chunk.truncate_to_lines(4);
+        assert_eq!(
+            chunk.content,
+            "fn run() {\n    step1();\n    step2();\n    step3();\n    // ... (8 lines omitted) ..."
+        );
+        assert_eq!(chunk.source.range, TextRange::new(10, 13));
+
+        // Content that isn't exactly the range's lines keeps its head and range
+        let mut chunk = ContextChunk::new(content.clone(), test_source(), ContextType::CodeSnippet);
+        chunk.hit_line = Some(16);
+        chunk.truncate_to_lines(4);
+        assert!(chunk.content.starts_with("fn run() {\n    step1();"));
+        assert_eq!(chunk.source.range, test_source().range);
+
+        // Nothing to gain from eliding a single line
+        let mut chunk = ContextChunk::new(content, source, ContextType::CodeSnippet);
+        chunk.truncate_to_lines(11);
+        assert_eq!(chunk.content.lines().count(), 12);
+
+        let mut source = test_source();
+        source.rel_path = PathBuf::from("app.py");
+        source.range = TextRange::new(1, 5);
+        let mut chunk = ContextChunk::new("a\nb\nc\nd\ne", source, ContextType::CodeSnippet);
+        chunk.hit_line = Some(5);
+        chunk.truncate_to_lines(2);
+        assert_eq!(chunk.content, "# ... (3 lines omitted) ...\nd\ne");
+    }
+
+    /// One token per word, optionally wrapped in zero-width `[CLS]`/`[SEP]`
+    struct WordCounter {
+        special_tokens: bool,
+    }
+
+    impl TokenCounter for WordCounter {
+        fn count(&self, text: &str) -> usize {
+            self.encode_with_offsets(text).1.len()
+        }
+
+        fn encode_with_offsets(&self, text: &str) -> (Vec<u32>, Vec<(usize, usize)>) {
+            let mut offsets = text
+                .split_whitespace()
+                .map(|w| {
+                    let start = w.as_ptr() as usize - text.as_ptr() as usize;
+                    (start, start + w.len())
+                })
+                .collect::<Vec<_>>();
+            if self.special_tokens {
+                offsets.insert(0, (0, 0));
+                offsets.push((0, 0));
+            }
+            (vec![0; offsets.len()], offsets)
+        }
+    }
+
+    #[test]
+    fn test_truncation_ignores_zero_width_special_tokens() {
+        let content = "fn a() {}\nfn b() {}\nfn c() {}";
+        let truncated = |special_tokens: bool, max_tokens: usize| {
+            let counter = WordCounter { special_tokens };
+            let mut chunk = ContextChunk::new(content, test_source(), ContextType::CodeSnippet);
+            chunk.recount_tokens(&counter);
+            chunk.truncate_to_tokens_with(max_tokens, &counter);
+            chunk.content
+        };
+
+        for max_tokens in [1, 3, 6, 9] {
+            assert_eq!(truncated(false, max_tokens), truncated(true, max_tokens));
+        }
+        assert_eq!(truncated(true, 1), "fn");
+        assert_eq!(truncated(true, 7), "fn a() {}\nfn b() {}");
+        assert_eq!(truncated(true, 9), content);
+    }
+
+    #[test]
+    fn test_stable_id_depends_only_on_location() {
+        let a = ContextChunk::new("fn a() {}", test_source(), ContextType::CodeSnippet);
+        let b = ContextChunk::new("fn b() {}", test_source(), ContextType::RelatedSymbol);
+        assert_ne!(a.id, b.id);
+        assert_eq!(a.stable_id, b.stable_id);
+        assert_eq!(a.stable_id.len(), 8);
+
+        let mut other = test_source();
+        other.range = TextRange::new(10, 16);
+        let c = ContextChunk::new("fn a() {}", other, ContextType::CodeSnippet);
+        assert_ne!(a.stable_id, c.stable_id);
+    }
+
+    #[test]
+    fn test_symbol_kind_summary() {
+        let mk = |sig: &str| {
+            ContextChunkBuilder::new(test_source(), ContextType::NavigationResult)
+                .signature("crate::foo")
+                .signature(sig)
+                .build()
+        };
+        let chunks = vec![
+            mk("pub fn a()"),
+            mk("pub(crate) async fn b()"),
+            mk("pub struct C"),
+            mk("impl Display for C"),
+            mk("fn d()"),
+        ];
+
+        let summary = symbol_kind_summary(&chunks);
+        assert_eq!(summary.get("fn"), Some(&3));
+        assert_eq!(summary.get("struct"), Some(&1));
+        assert_eq!(format_kind_summary(&summary), "3 fn, 1 impl, 1 struct");
+    }
+
+    #[test]
+    fn test_kind_overrides_win_over_builtin_mapping() {
+        let mk = |sig: &str| {
+            ContextChunkBuilder::new(test_source(), ContextType::NavigationResult)
+                .signature(sig)
+                .build()
+        };
+        let chunks = vec![mk("impl Display for C"), mk("macro_rules! m"), mk("fn d()")];
+        let overrides = KindOverrides::from([
+            ("impl".to_owned(), "type".to_owned()),
+            ("macro_rules".to_owned(), "macro".to_owned()),
+        ]);
+
+        assert_eq!(signature_kind("macro_rules! m"), None);
+        let summary = symbol_kind_summary_with(&chunks, &overrides);
+        assert_eq!(format_kind_summary(&summary), "1 fn, 1 macro, 1 type");
+    }
+
+    #[test]
+    fn test_signature_patterns_extend_builtin_table() {
+        let builtin = SignaturePatterns::default();
+        assert_eq!(
+            builtin.name("pub(crate) async fn load<T>(x: T)"),
+            Some("load")
+        );
+        assert_eq!(builtin.name("class Widget:"), Some("Widget"));
+        assert_eq!(builtin.name("let x = 1;"), None);
+        assert_eq!(builtin.kind("override suspend fun load(id: Int)"), None);
+
+        let kotlin = SignaturePatterns::default()
+            .with_modifier("override")
+            .with_modifier("suspend")
+            .with_keyword("fun", "fn");
+        let sig = "override suspend fun load(id: Int)";
+        assert_eq!(kotlin.kind(sig), Some("fn"));
+        assert_eq!(kotlin.name(sig), Some("load"));
+        assert_eq!(kotlin.name("data class User(val id: Int)"), None);
+
+        let chunk = ContextChunkBuilder::new(test_source(), ContextType::NavigationResult)
+            .signature(sig)
+            .build();
+        assert_eq!(chunk.symbol_kind(), None);
+        assert_eq!(kotlin.symbol_kind(&chunk), Some("fn"));
+    }
+
+    #[test]
+    fn test_scope_kind_wins_over_signature_heuristic() {
+        let mut chunk = ContextChunkBuilder::new(test_source(), ContextType::NavigationResult)
+            .signature("fn helper() // legacy, see Config")
+            .build();
+        assert_eq!(chunk.symbol_kind(), Some("fn"));
+
+        // The scope graph says the chunk's definition is a struct.
+        chunk.scope_kind = Some("struct".to_owned());
+        assert_eq!(chunk.symbol_kind(), Some("struct"));
+
+        // A local binding is not a definition, whatever the text says.
+        chunk.scope_kind = Some("variable".to_owned());
+        assert_eq!(chunk.symbol_kind(), None);
+        assert!(symbol_kind_summary(&[chunk]).is_empty());
+    }
+
     #[test]
     fn test_builder() {
         let chunk = ContextChunkBuilder::new(test_source(), ContextType::CodeSnippet)
@@ -277,4 +844,26 @@ mod tests {
         assert_eq!(chunk.symbol_signatures, vec!["fn bar()".to_string()]);
         assert!((chunk.relevance_score - 0.85).abs() < 0.01);
     }
+
+    proptest! {
+        #[test]
+        fn prop_truncate_keeps_a_prefix_within_budget(
+            content in "(?s).{0,400}",
+            max_tokens in 0usize..64,
+        ) {
+            let mut chunk = ContextChunk::new(content.clone(), test_source(), ContextType::CodeSnippet);
+            let before = chunk.token_count;
+
+            // Must not panic on multi-byte text or a zero budget.
+            chunk.truncate_to_tokens(max_tokens);
+
+            prop_assert!(content.starts_with(&chunk.content));
+            prop_assert_eq!(chunk.token_count, crate::TokenBudget::estimate_tokens(&chunk.content));
+            if before > max_tokens {
+                prop_assert!(chunk.token_count <= max_tokens);
+            } else {
+                prop_assert_eq!(&chunk.content, &content);
+            }
+        }
+    }
 }
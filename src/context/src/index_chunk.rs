@@ -43,6 +43,10 @@ pub struct IndexChunk {
     pub modified_at: TimestampMs,
     /// Type of chunk
     pub chunk_type: IndexChunkType,
+    /// 1-based line that matched (e.g. a queried position) when it isn't
+    /// `source.range.start_line`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hit_line: Option<usize>,
 }
 
 impl IndexChunk {
@@ -62,9 +66,17 @@ impl IndexChunk {
             language: LanguageId::Unknown,
             modified_at: 0,
             chunk_type,
+            hit_line: None,
         }
     }
 
+    /// Set the line that matched, see [`Self::hit_line`]
+    #[must_use]
+    pub fn with_hit_line(mut self, line: usize) -> Self {
+        self.hit_line = Some(line);
+        self
+    }
+
     /// Create a symbol definition chunk
     #[must_use]
     pub fn symbol_definition(
@@ -5,21 +5,33 @@
 //! - Refine and optimize context for LLM consumption
 //! - Cache and manage token budgets
 
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
 
 pub mod cache;
 pub mod context_chunk;
 pub mod index_chunk;
+mod minhash;
+pub mod prompt;
 pub mod query;
 pub mod refill;
+pub mod token;
 
-pub use cache::ContextCache;
-pub use context_chunk::{ContextChunk, ContextType};
+pub use cache::{ContextCache, FileCacheKey};
+pub use context_chunk::{
+    context_hash, format_kind_summary, signature_kind, signature_kind_with, symbol_kind_summary,
+    symbol_kind_summary_with, ContextChunk, ContextType, KindOverrides, SignaturePatterns,
+};
 pub use index_chunk::{IndexChunk, IndexChunkType};
+pub use prompt::PromptTemplate;
 pub use query::ContextQuery;
-pub use refill::RefillPipeline;
+pub use refill::{code_likeness, MergeGap, RefillPipeline, SelectionOutcome, SelectionReason};
+#[cfg(feature = "hf-download")]
+pub use token::download_tokenizer;
+#[cfg(feature = "hf-tokenizer")]
+pub use token::HfTokenizer;
+pub use token::{tokenizer_repo, CharApproxCounter, TokenCounter};
 
 /// Unique identifier for chunks
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -51,6 +63,82 @@ impl SourceLocation {
     pub fn abs_path(&self) -> PathBuf {
         self.repo_root.join(&self.rel_path)
     }
+
+    /// Short handle derived from `rel_path:start_line:end_line`.
+    ///
+    /// Unlike `ChunkId`, this is the same across retrieval runs, so callers can
+    /// refer to "the same source" between turns. Uses FNV-1a to stay stable
+    /// across Rust versions.
+    #[must_use]
+    pub fn stable_id(&self) -> String {
+        let key = format!(
+            "{}:{}:{}",
+            self.rel_path.display(),
+            self.range.start_line,
+            self.range.end_line
+        );
+        format!("{:08x}", fnv1a64(key.as_bytes()) >> 32)
+    }
+
+    /// Same location with `rel_path` rewritten by [`normalize_rel_path`].
+    #[must_use]
+    pub fn normalized(mut self) -> Self {
+        self.rel_path = normalize_rel_path(&self.repo_root, &self.rel_path);
+        self
+    }
+}
+
+/// Repo-relative form of a hit path.
+///
+/// Absolute paths under `repo_root` (e.g. ones that escaped `strip_prefix`) are
+/// made relative, and `.` / `..` components are resolved lexically. Absolute
+/// paths outside the root are kept as-is, so `SourceLocation::abs_path` still
+/// points at the real file.
+#[must_use]
+pub fn normalize_rel_path(repo_root: &Path, path: &Path) -> PathBuf {
+    let path = lexical_clean(path);
+    if !path.is_absolute() {
+        return path;
+    }
+
+    let root = lexical_clean(repo_root);
+    if let Ok(rel) = path.strip_prefix(&root) {
+        return rel.to_path_buf();
+    }
+    // A relative root such as `.` only matches after resolving it on disk.
+    match (root.canonicalize(), path.canonicalize()) {
+        (Ok(root), Ok(abs)) => abs
+            .strip_prefix(&root)
+            .map(Path::to_path_buf)
+            .unwrap_or(path),
+        _ => path,
+    }
+}
+
+fn lexical_clean(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !out.pop() {
+                    out.push(component);
+                }
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// 64-bit FNV-1a; deterministic across runs and Rust versions.
+pub(crate) fn fnv1a64(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in bytes {
+        hash ^= u64::from(*b);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
 }
 
 /// Text range (line/column based)
@@ -166,6 +254,7 @@ pub type TimestampMs = u64;
 
 /// Token budget for context management
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct TokenBudget {
     pub max_context_tokens: usize,
 }
@@ -179,10 +268,17 @@ impl Default for TokenBudget {
 }
 
 impl TokenBudget {
-    /// Rough token estimation: ~4 chars per token
+    /// JSON Schema of the budget.
+    #[cfg(feature = "schema")]
+    #[must_use]
+    pub fn schema() -> schemars::schema::RootSchema {
+        schemars::schema_for!(Self)
+    }
+
+    /// Rough token estimation: ~4 chars per token (see [`CharApproxCounter`])
     #[must_use]
     pub fn estimate_tokens(text: &str) -> usize {
-        text.len().div_ceil(4)
+        CharApproxCounter.count(text)
     }
 }
 
@@ -213,6 +309,23 @@ mod tests {
         assert_eq!(sym2.full_name(), "foo");
     }
 
+    #[test]
+    fn test_normalize_rel_path() {
+        let root = Path::new("/repo");
+        assert_eq!(
+            normalize_rel_path(root, Path::new("/repo/src/lib.rs")),
+            PathBuf::from("src/lib.rs")
+        );
+        assert_eq!(
+            normalize_rel_path(root, Path::new("./src/../src/lib.rs")),
+            PathBuf::from("src/lib.rs")
+        );
+        assert_eq!(
+            normalize_rel_path(root, Path::new("/elsewhere/a.rs")),
+            PathBuf::from("/elsewhere/a.rs")
+        );
+    }
+
     #[test]
     fn test_token_estimation() {
         // 4 chars ~= 1 token
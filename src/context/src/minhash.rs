@@ -0,0 +1,76 @@
+//! MinHash signatures for near-duplicate detection.
+//!
+//! Content is split into identifier/number tokens, grouped into overlapping
+//! shingles of [`SHINGLE_TOKENS`] tokens, and summarized by the minimum of
+//! [`NUM_HASHES`] seeded hashes over those shingles. The fraction of equal
+//! minimums between two signatures estimates the Jaccard similarity of their
+//! shingle sets, so formatting and punctuation changes don't count.
+
+const NUM_HASHES: usize = 64;
+const SHINGLE_TOKENS: usize = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct MinHash([u64; NUM_HASHES]);
+
+impl MinHash {
+    /// Signature of `text`; `None` when it has no tokens.
+    pub(crate) fn of(text: &str) -> Option<Self> {
+        let tokens: Vec<&str> = text
+            .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .filter(|t| !t.is_empty())
+            .collect();
+        if tokens.is_empty() {
+            return None;
+        }
+
+        let mut mins = [u64::MAX; NUM_HASHES];
+        for shingle in tokens.windows(SHINGLE_TOKENS.min(tokens.len())) {
+            // The separator keeps ["ab", "c"] and ["a", "bc"] apart.
+            let base = crate::fnv1a64(shingle.join("\u{1f}").as_bytes());
+            for (i, min) in mins.iter_mut().enumerate() {
+                *min = (*min).min(mix(base, i as u64));
+            }
+        }
+        Some(Self(mins))
+    }
+
+    /// Estimated share of shingles the two texts have in common, in `[0, 1]`.
+    pub(crate) fn similarity(&self, other: &Self) -> f32 {
+        let same = self.0.iter().zip(&other.0).filter(|(a, b)| a == b).count();
+        same as f32 / NUM_HASHES as f32
+    }
+}
+
+/// The `seed`-th hash function: SplitMix64 over `base` offset by the seed.
+fn mix(base: u64, seed: u64) -> u64 {
+    let mut z = base.wrapping_add((seed + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn similarity_tracks_shared_shingles() {
+        let scaffold = |name: &str| {
+            format!(
+                "#[test]\nfn {name}() {{\n    let dir = tmp_dir();\n    let ctx = ToolContext::new(&dir);\n    \
+                 let reg = ToolRegistry::new();\n    let out = reg.run(&ctx, &call()).unwrap();\n    \
+                 assert!(out.ok);\n    cleanup(&dir);\n}}\n"
+            )
+        };
+        let a = MinHash::of(&scaffold("reads_file")).unwrap();
+        let b = MinHash::of(&scaffold("reads_files")).unwrap();
+        let reformatted = MinHash::of(&scaffold("reads_file").replace("\n    ", " ")).unwrap();
+        let other =
+            MinHash::of("pub struct Config { pub max_bytes: usize, pub dry_run: bool }").unwrap();
+
+        assert_eq!(a.similarity(&reformatted), 1.0);
+        assert!(a.similarity(&b) > 0.6, "{}", a.similarity(&b));
+        assert!(a.similarity(&other) < 0.2, "{}", a.similarity(&other));
+        assert!(MinHash::of(" {} ;").is_none());
+    }
+}
@@ -0,0 +1,202 @@
+//! PromptTemplate: how context chunks are framed in a prompt
+//!
+//! Different models prefer different framing (Markdown comments, XML tags,
+//! with or without line numbers). The default reproduces the original output.
+
+use serde::{Deserialize, Serialize};
+
+use crate::ContextChunk;
+
+/// Template used to render context chunks.
+///
+/// `chunk_header` / `chunk_footer` support these placeholders:
+/// - `{path}`: repo-relative path
+/// - `{start}` / `{end}`: 1-based line range
+/// - `{signatures}`: ` (sig1, sig2)`, or empty when there are none
+/// - `{also_in}`: ` [also in: a.rs, b.rs]`, or empty when the content is unique
+/// - `{near_duplicates}`: ` [2 near-duplicates omitted]`, or empty when none were collapsed
+/// - `{stable_id}`: location-derived chunk handle
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PromptTemplate {
+    /// Emitted once before the chunks
+    pub preamble: String,
+    /// Emitted before each chunk's content
+    pub chunk_header: String,
+    /// Emitted after each chunk's content
+    pub chunk_footer: String,
+    /// Emitted between chunks (and after the last one)
+    pub separator: String,
+    /// Emitted once after the chunks
+    pub postamble: String,
+    /// Prefix each content line with its source line number
+    pub line_numbers: bool,
+}
+
+impl Default for PromptTemplate {
+    fn default() -> Self {
+        Self {
+            preamble: "## Relevant Code Context\n\n".to_owned(),
+            chunk_header: "// {path}:{start}-{end}{signatures}{also_in}{near_duplicates}\n"
+                .to_owned(),
+            chunk_footer: String::new(),
+            separator: "\n".to_owned(),
+            postamble: "## End Context\n".to_owned(),
+            line_numbers: false,
+        }
+    }
+}
+
+impl PromptTemplate {
+    /// JSON Schema of the template.
+    #[cfg(feature = "schema")]
+    #[must_use]
+    pub fn schema() -> schemars::schema::RootSchema {
+        schemars::schema_for!(Self)
+    }
+
+    /// XML-tagged framing, one `<source>` element per chunk
+    #[must_use]
+    pub fn xml() -> Self {
+        Self {
+            preamble: "<context>\n".to_owned(),
+            chunk_header: "<source path=\"{path}\" lines=\"{start}-{end}\">\n".to_owned(),
+            chunk_footer: "</source>\n".to_owned(),
+            separator: String::new(),
+            postamble: "</context>\n".to_owned(),
+            line_numbers: false,
+        }
+    }
+
+    #[must_use]
+    pub fn with_line_numbers(mut self, line_numbers: bool) -> Self {
+        self.line_numbers = line_numbers;
+        self
+    }
+
+    /// Render all chunks; empty input renders to an empty string
+    #[must_use]
+    pub fn render(&self, chunks: &[ContextChunk]) -> String {
+        if chunks.is_empty() {
+            return String::new();
+        }
+
+        let mut output = self.preamble.clone();
+        for chunk in chunks {
+            output.push_str(&self.render_chunk(chunk));
+            output.push_str(&self.separator);
+        }
+        output.push_str(&self.postamble);
+        output
+    }
+
+    /// Render a single chunk (header, content, footer)
+    #[must_use]
+    pub fn render_chunk(&self, chunk: &ContextChunk) -> String {
+        let mut output = self.fill(&self.chunk_header, chunk);
+
+        if self.line_numbers {
+            let first = chunk.source.range.start_line;
+            for (i, line) in chunk.content.lines().enumerate() {
+                output.push_str(&format!("{:>5} | {line}\n", first + i));
+            }
+        } else {
+            output.push_str(&chunk.content);
+            output.push('\n');
+        }
+
+        output.push_str(&self.fill(&self.chunk_footer, chunk));
+        output
+    }
+
+    fn fill(&self, template: &str, chunk: &ContextChunk) -> String {
+        let signatures = if chunk.symbol_signatures.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", chunk.symbol_signatures.join(", "))
+        };
+
+        let also_in = if chunk.also_in.is_empty() {
+            String::new()
+        } else {
+            let paths = chunk
+                .also_in
+                .iter()
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>();
+            format!(" [also in: {}]", paths.join(", "))
+        };
+
+        let near_duplicates = match chunk.near_duplicates {
+            0 => String::new(),
+            1 => " [1 near-duplicate omitted]".to_owned(),
+            n => format!(" [{n} near-duplicates omitted]"),
+        };
+
+        template
+            .replace("{path}", &chunk.source.rel_path.display().to_string())
+            .replace("{start}", &chunk.source.range.start_line.to_string())
+            .replace("{end}", &chunk.source.range.end_line.to_string())
+            .replace("{signatures}", &signatures)
+            .replace("{also_in}", &also_in)
+            .replace("{near_duplicates}", &near_duplicates)
+            .replace("{stable_id}", &chunk.stable_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SourceLocation, TextRange};
+    use std::path::PathBuf;
+
+    fn chunk() -> ContextChunk {
+        ContextChunk::navigation_result(
+            "fn a() {}\nfn b() {}",
+            SourceLocation {
+                repo_root: PathBuf::from("/repo"),
+                rel_path: PathBuf::from("src/lib.rs"),
+                range: TextRange::new(10, 11),
+            },
+            "fn a()",
+        )
+    }
+
+    #[test]
+    fn test_default_keeps_comment_header() {
+        assert_eq!(
+            PromptTemplate::default().render_chunk(&chunk()),
+            "// src/lib.rs:10-11 (fn a())\nfn a() {}\nfn b() {}\n"
+        );
+    }
+
+    #[test]
+    fn test_default_notes_omitted_near_duplicates() {
+        let mut chunk = chunk();
+        chunk.near_duplicates = 2;
+        assert_eq!(
+            PromptTemplate::default().render_chunk(&chunk),
+            "// src/lib.rs:10-11 (fn a()) [2 near-duplicates omitted]\nfn a() {}\nfn b() {}\n"
+        );
+        chunk.near_duplicates = 1;
+        assert!(chunk
+            .format_for_prompt()
+            .starts_with("// src/lib.rs:10-11 (fn a()) [1 near-duplicate omitted]\n"));
+    }
+
+    #[test]
+    fn test_xml_with_line_numbers() {
+        let out = PromptTemplate::xml()
+            .with_line_numbers(true)
+            .render(&[chunk()]);
+        assert_eq!(
+            out,
+            "<context>\n<source path=\"src/lib.rs\" lines=\"10-11\">\n   10 | fn a() {}\n   11 | fn b() {}\n</source>\n</context>\n"
+        );
+    }
+
+    #[test]
+    fn test_empty_renders_nothing() {
+        assert!(PromptTemplate::default().render(&[]).is_empty());
+    }
+}
@@ -35,6 +35,9 @@ pub enum ContextQuery {
         base_symbol: String,
         relation: SymbolRelation,
     },
+
+    /// Symbol definition plus the definitions of its callees, `depth` hops deep
+    DefinitionWithDependencies { name: String, depth: usize },
 }
 
 /// Type of symbol relationship to query
@@ -106,6 +109,15 @@ impl ContextQuery {
         }
     }
 
+    /// Create a definition-with-dependencies query
+    #[must_use]
+    pub fn definition_with_dependencies(name: impl Into<String>, depth: usize) -> Self {
+        Self::DefinitionWithDependencies {
+            name: name.into(),
+            depth,
+        }
+    }
+
     /// Check if this query is for a specific symbol
     #[must_use]
     pub fn is_symbol_query(&self) -> bool {
@@ -155,6 +167,13 @@ impl ContextQuery {
             }
             // Related queries require graph traversal
             Self::Related { .. } => QueryComplexity::Medium,
+            Self::DefinitionWithDependencies { depth, .. } => {
+                if *depth <= 1 {
+                    QueryComplexity::Medium
+                } else {
+                    QueryComplexity::Slow
+                }
+            }
         }
     }
 }
@@ -30,17 +30,23 @@
 //! └──────────────┘
 //! ```
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use error::ResultExt;
+use intelligence::repo_scan::{is_test_path, is_test_source, TestFilePolicy};
+use serde::{Deserialize, Serialize};
 
 use crate::{
     cache::ContextCache,
     context_chunk::{ContextChunk, ContextType},
     index_chunk::IndexChunk,
+    minhash::MinHash,
+    normalize_rel_path,
+    prompt::PromptTemplate,
     query::{ContextQuery, SymbolRelation},
+    token::{CharApproxCounter, TokenCounter},
     ChunkId, LanguageId, SourceLocation, SymbolId, TextRange, TokenBudget,
 };
 
@@ -85,6 +91,30 @@ pub trait SymbolResolver: Send + Sync {
         location: &SourceLocation,
         context_lines: usize,
     ) -> error::Result<String>;
+
+    /// Signature, scope-graph kind and callees of the definition at location,
+    /// read in one pass; by default only the signature, via `get_signature`.
+    fn get_symbol_info(
+        &self,
+        repo_root: &Path,
+        location: &SourceLocation,
+    ) -> error::Result<SymbolInfo> {
+        Ok(SymbolInfo {
+            signature: self.get_signature(repo_root, location)?,
+            ..SymbolInfo::default()
+        })
+    }
+}
+
+/// What [`SymbolResolver::get_symbol_info`] knows about a definition.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolInfo {
+    /// Definition header, e.g. `pub fn parse(input: &str) -> Ast`
+    pub signature: Option<String>,
+    /// Scope-graph kind (`function`, `struct`, ...); `None` when unknown
+    pub kind: Option<String>,
+    /// Names of the functions called from the definition; empty when unknown
+    pub callees: Vec<String>,
 }
 
 /// RefillPipeline: The core context transformation engine
@@ -94,8 +124,110 @@ pub struct RefillPipeline {
     symbol_resolver: Arc<dyn SymbolResolver>,
     budget: TokenBudget,
     cache: ContextCache,
+    max_symbol_queries: usize,
+    template: PromptTemplate,
+    dedup_identical_content: bool,
+    near_duplicate_threshold: Option<f32>,
+    max_chunk_lines: Option<usize>,
+    max_chunks_per_file: Option<usize>,
+    max_candidates: Option<usize>,
+    merge_gap: Option<MergeGap>,
+    data_chunk_threshold: Option<f32>,
+    include_file_imports: bool,
+    pinned_paths: Vec<PathBuf>,
+    pinned_tokens: usize,
+    test_files: TestFilePolicy,
+    token_counter: Arc<dyn TokenCounter>,
+}
+
+/// How far apart two chunks of the same file may be and still be merged by
+/// [`RefillPipeline::with_merge_gap`]. Chunks that overlap or touch are
+/// always merged once merging is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeGap {
+    /// At most this many lines between the chunks
+    Lines(usize),
+    /// At most this many bytes (newlines included) between the chunks; suits
+    /// files whose line lengths are far from typical, e.g. minified code
+    Bytes(usize),
+}
+
+/// What `refine` did with a candidate chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectionOutcome {
+    /// Kept as is
+    Selected,
+    /// Kept, but cut to fit the remaining token budget
+    Truncated,
+    /// Another chunk for the same primary symbol came first
+    DuplicateSymbol,
+    /// Same content as a better-ranked chunk (listed in its `also_in`)
+    DuplicateContent,
+    /// Nearly the same content as a better-ranked chunk (counted in its
+    /// `near_duplicates`)
+    NearDuplicate,
+    /// Its file already had `max_chunks_per_file` better-ranked chunks
+    FileCapReached,
+    /// Scored below the `max_candidates` best hits, so never refined
+    CandidateCapReached,
+    /// Overlapped or sat within `merge_gap` of a better-ranked chunk of the
+    /// same file, which now spans both
+    MergedWithNeighbor,
+    /// Ranked below chunks that already used up the token budget
+    DroppedByBudget,
+    /// Test code under `TestFilePolicy::Exclude`, or other code under `Only`
+    TestFilePolicy,
+}
+
+/// Why a candidate chunk was (or wasn't) included by [`RefillPipeline::refine_explained`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SelectionReason {
+    pub source: SourceLocation,
+    pub outcome: SelectionOutcome,
+    /// Confidence-weighted relevance used for ranking (0 when never ranked)
+    pub rank_score: f32,
+    /// Lines and tokens of the candidate before any truncation
+    pub size_lines: usize,
+    pub token_count: usize,
 }
 
+impl std::fmt::Display for SelectionReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}-{} {:?} (score {:.2}, {} lines, {} tokens)",
+            self.source.rel_path.display(),
+            self.source.range.start_line,
+            self.source.range.end_line,
+            self.outcome,
+            self.rank_score,
+            self.size_lines,
+            self.token_count
+        )
+    }
+}
+
+/// Default cap on per-symbol lookups for a single task-driven query.
+///
+/// Each lookup walks the whole repository, so the cost of a task mentioning
+/// many identifiers would otherwise grow linearly with their count.
+pub const DEFAULT_MAX_SYMBOL_QUERIES: usize = 5;
+
+/// Cap on definitions collected by
+/// [`RefillPipeline::retrieve_definition_with_dependencies`].
+pub const MAX_DEPENDENCY_CHUNKS: usize = 16;
+
+/// Relevance multiplier applied per call hop away from the requested symbol.
+const DEPENDENCY_HOP_DECAY: f32 = 0.8;
+
+/// Confidence multiplier for chunks below the data-chunk threshold.
+const DATA_CHUNK_CONFIDENCE: f32 = 0.2;
+
+/// Identifier-like runs longer than this are treated as encoded data.
+const MAX_WORD_LEN: usize = 40;
+
 impl RefillPipeline {
     /// Create a new RefillPipeline
     pub fn new(
@@ -110,9 +242,143 @@ impl RefillPipeline {
             symbol_resolver,
             budget,
             cache: ContextCache::with_default_size(),
+            max_symbol_queries: DEFAULT_MAX_SYMBOL_QUERIES,
+            template: PromptTemplate::default(),
+            dedup_identical_content: false,
+            near_duplicate_threshold: None,
+            max_chunk_lines: None,
+            max_chunks_per_file: None,
+            max_candidates: None,
+            merge_gap: None,
+            data_chunk_threshold: None,
+            include_file_imports: false,
+            pinned_paths: Vec::new(),
+            pinned_tokens: 0,
+            test_files: TestFilePolicy::Include,
+            token_counter: Arc::new(CharApproxCounter),
         }
     }
 
+    /// Count and truncate chunk tokens with `counter` instead of the ~4 chars/token estimate.
+    #[must_use]
+    pub fn with_token_counter(mut self, counter: Arc<dyn TokenCounter>) -> Self {
+        self.token_counter = counter;
+        self
+    }
+
+    /// Collapse identical chunks from different locations during `refine` (default: off).
+    ///
+    /// Surrounding whitespace is ignored, except in languages where
+    /// indentation is syntax (Python), whose chunks must match byte for byte.
+    #[must_use]
+    pub fn with_content_dedup(mut self, enabled: bool) -> Self {
+        self.dedup_identical_content = enabled;
+        self
+    }
+
+    /// Also collapse chunks at least `threshold` similar to a better-ranked one
+    /// during `refine` (default: off). Similarity is the estimated share of
+    /// token shingles in common, `0.0..=1.0`; the kept chunk counts the rest
+    /// in `near_duplicates`.
+    #[must_use]
+    pub fn with_near_duplicate_threshold(mut self, threshold: f32) -> Self {
+        self.near_duplicate_threshold = Some(threshold.clamp(0.0, 1.0));
+        self
+    }
+
+    /// Cut chunks longer than `max_lines` to the lines around the match during
+    /// `refine` (default: off), so one long scope can't crowd out the rest of
+    /// the budget. Omission markers replace the lines cut.
+    #[must_use]
+    pub fn with_max_chunk_lines(mut self, max_lines: usize) -> Self {
+        self.max_chunk_lines = Some(max_lines);
+        self
+    }
+
+    /// Keep at most `max` chunks from any one file during `refine` (default:
+    /// no cap), so a file with many strong matches doesn't crowd out other
+    /// relevant files. The file's best-ranked chunks are kept.
+    #[must_use]
+    pub fn with_max_chunks_per_file(mut self, max: usize) -> Self {
+        self.max_chunks_per_file = Some(max);
+        self
+    }
+
+    /// Refine only the `max` best candidates by retrieval score (default: all).
+    /// Converting a candidate resolves its signature, so this keeps that work
+    /// proportional to what can make it into the context.
+    #[must_use]
+    pub fn with_max_candidates(mut self, max: usize) -> Self {
+        self.max_candidates = Some(max);
+        self
+    }
+
+    /// Merge chunks of the same file that overlap or are within `gap` of each
+    /// other during `refine` (default: off), so the prompt shows one snippet
+    /// instead of several overlapping or nearly adjacent ones.
+    /// `MergeGap::Lines(0)` merges only overlapping and touching chunks.
+    #[must_use]
+    pub fn with_merge_gap(mut self, gap: MergeGap) -> Self {
+        self.merge_gap = Some(gap);
+        self
+    }
+
+    /// Treat chunks whose [`code_likeness`] is below `threshold` as embedded
+    /// data (base64 blobs, long numeric tables) during `refine` (default: off).
+    /// Their confidence is cut so any real code ranks ahead of them; around
+    /// `0.3` separates the two well.
+    #[must_use]
+    pub fn with_data_chunk_threshold(mut self, threshold: f32) -> Self {
+        self.data_chunk_threshold = Some(threshold.clamp(0.0, 1.0));
+        self
+    }
+
+    /// Lead each selected file's chunks with its import/`use`/`#include` block
+    /// during `refine` (default: off, it costs tokens).
+    #[must_use]
+    pub fn with_file_imports(mut self, enabled: bool) -> Self {
+        self.include_file_imports = enabled;
+        self
+    }
+
+    /// Always lead the output of `refine` with these files (repo-relative or
+    /// absolute), in order, whatever the retrieval found. They are kept out of
+    /// scoring and share their own budget of `max_tokens`: the last file that
+    /// fits is truncated and later ones are dropped. The pinned budget comes
+    /// on top of `max_context_tokens`, so the context can grow to the sum of
+    /// both.
+    #[must_use]
+    pub fn with_pinned_paths(mut self, paths: Vec<PathBuf>, max_tokens: usize) -> Self {
+        self.pinned_paths = paths;
+        self.pinned_tokens = max_tokens;
+        self
+    }
+
+    /// Drop test code during `refine`, or keep only test code (default:
+    /// keep everything). A chunk is test code when its file is a test file
+    /// or its first line is a test marker (`#[cfg(test)]`, `def test_`, ...),
+    /// so implementation files with inline tests still count as
+    /// implementation. Pinned files are never dropped.
+    #[must_use]
+    pub fn with_test_files(mut self, policy: TestFilePolicy) -> Self {
+        self.test_files = policy;
+        self
+    }
+
+    /// Use a custom prompt framing for `build_context_string`.
+    #[must_use]
+    pub fn with_prompt_template(mut self, template: PromptTemplate) -> Self {
+        self.template = template;
+        self
+    }
+
+    /// Limit how many symbols a task-driven query resolves (earlier symbols win).
+    #[must_use]
+    pub fn with_max_symbol_queries(mut self, max: usize) -> Self {
+        self.max_symbol_queries = max;
+        self
+    }
+
     /// Get repository root
     #[must_use]
     pub fn repo_root(&self) -> &Path {
@@ -147,6 +413,11 @@ impl RefillPipeline {
                 base_symbol,
                 relation,
             } => self.retrieve_related(base_symbol, *relation, top_k),
+            ContextQuery::DefinitionWithDependencies { name, depth } => Ok(self
+                .dependency_chunks(name, *depth, top_k)?
+                .into_iter()
+                .map(|(_, chunk)| chunk)
+                .collect()),
         }?;
 
         // Cache results
@@ -167,60 +438,119 @@ impl RefillPipeline {
 
     /// Retrieve chunks for a symbol query (uses ScopeGraph)
     fn retrieve_symbol(&self, name: &str, top_k: usize) -> error::Result<Vec<IndexChunk>> {
-        let mut chunks = Vec::new();
-
         // 1. Get definition
+        let mut chunks = self.definition_chunks(name)?;
+
+        // 2. Get references (if we have room)
+        if chunks.len() < top_k {
+            let refs = self
+                .symbol_resolver
+                .find_references(&self.repo_root, name, top_k - chunks.len())
+                .unwrap_or_default();
+
+            for loc in refs {
+                let loc = loc.normalized();
+                let snippet = self
+                    .symbol_resolver
+                    .get_snippet(&self.repo_root, &loc, 3)
+                    .unwrap_or_default();
+
+                let _lang = detect_language(&loc.rel_path);
+                let chunk =
+                    IndexChunk::new(snippet, loc, crate::IndexChunkType::SymbolReference);
+                chunks.push(chunk);
+            }
+        }
+
+        Ok(chunks.into_iter().take(top_k).collect())
+    }
+
+    /// One definition chunk (signature + snippet) per definition of `name`
+    fn definition_chunks(&self, name: &str) -> error::Result<Vec<IndexChunk>> {
+        Ok(self
+            .definitions(name)?
+            .into_iter()
+            .map(|(chunk, _)| chunk)
+            .collect())
+    }
+
+    /// Like [`Self::definition_chunks`], each paired with what the resolver
+    /// knows about the definition
+    fn definitions(&self, name: &str) -> error::Result<Vec<(IndexChunk, SymbolInfo)>> {
         let defs = self
             .symbol_resolver
             .find_definition(&self.repo_root, name)
             .with_context(|| format!("find definition for {}", name))?;
 
+        let mut chunks = Vec::with_capacity(defs.len());
         for loc in defs {
-            let signature = self
+            let loc = loc.normalized();
+            let info = self
                 .symbol_resolver
-                .get_signature(&self.repo_root, &loc)
-                .ok()
-                .flatten();
+                .get_symbol_info(&self.repo_root, &loc)
+                .unwrap_or_default();
 
             let snippet = self
                 .symbol_resolver
                 .get_snippet(&self.repo_root, &loc, 5)
                 .unwrap_or_default();
 
-            let content = if let Some(sig) = signature {
+            let content = if let Some(sig) = &info.signature {
                 format!("{}\n{}", sig, snippet)
             } else {
                 snippet
             };
 
             let lang = detect_language(&loc.rel_path);
-            let mut chunk =
-                IndexChunk::symbol_definition(content, loc, SymbolId::new(name, ""));
+            let mut chunk = IndexChunk::symbol_definition(content, loc, SymbolId::new(name, ""));
             chunk.language = lang;
-            chunks.push(chunk);
+            chunks.push((chunk, info));
         }
+        Ok(chunks)
+    }
 
-        // 2. Get references (if we have room)
-        if chunks.len() < top_k {
-            let refs = self
-                .symbol_resolver
-                .find_references(&self.repo_root, name, top_k - chunks.len())
-                .unwrap_or_default();
-
-            for loc in refs {
-                let snippet = self
-                    .symbol_resolver
-                    .get_snippet(&self.repo_root, &loc, 3)
-                    .unwrap_or_default();
-
-                let _lang = detect_language(&loc.rel_path);
-                let chunk =
-                    IndexChunk::new(snippet, loc, crate::IndexChunkType::SymbolReference);
-                chunks.push(chunk);
+    /// Definitions of `name`, then of the functions it calls, breadth-first
+    /// up to `depth` call hops. Each chunk is paired with its hop count.
+    ///
+    /// Every symbol is expanded once, and the walk stops after `max_chunks`
+    /// chunks. Callees without a definition in the repo (std, external
+    /// crates) are skipped.
+    fn dependency_chunks(
+        &self,
+        name: &str,
+        depth: usize,
+        max_chunks: usize,
+    ) -> error::Result<Vec<(usize, IndexChunk)>> {
+        let mut out: Vec<(usize, IndexChunk)> = Vec::new();
+        let mut seen: HashSet<String> = HashSet::from([name.to_string()]);
+        let mut frontier = vec![name.to_string()];
+
+        for hop in 0..=depth {
+            let mut next = Vec::new();
+            for symbol in &frontier {
+                // Only the root's lookup failure is an error; a callee that
+                // fails to resolve is just not expanded.
+                let defs = match self.definitions(symbol) {
+                    Ok(defs) => defs,
+                    Err(e) if hop == 0 => return Err(e),
+                    Err(_) => continue,
+                };
+                for (chunk, info) in defs {
+                    if out.len() >= max_chunks {
+                        return Ok(out);
+                    }
+                    if hop < depth {
+                        next.extend(info.callees.into_iter().filter(|c| seen.insert(c.clone())));
+                    }
+                    out.push((hop, chunk));
+                }
+            }
+            if next.is_empty() {
+                break;
             }
+            frontier = next;
         }
-
-        Ok(chunks.into_iter().take(top_k).collect())
+        Ok(out)
     }
 
     /// Retrieve chunks for a position query
@@ -242,12 +572,13 @@ impl RefillPipeline {
 
         let source = SourceLocation {
             repo_root: self.repo_root.clone(),
-            rel_path: path.to_path_buf(),
+            rel_path: normalize_rel_path(&self.repo_root, path),
             range: TextRange::new(start + 1, end),
         };
 
         let lang = detect_language(path);
-        let chunk = IndexChunk::new(snippet, source, crate::IndexChunkType::CodeBlock);
+        let chunk = IndexChunk::new(snippet, source, crate::IndexChunkType::CodeBlock)
+            .with_hit_line(line + 1);
 
         // Also get file summary if it's a small file
         let mut chunks = vec![chunk];
@@ -260,7 +591,7 @@ impl RefillPipeline {
             );
             let source = SourceLocation {
                 repo_root: self.repo_root.clone(),
-                rel_path: path.to_path_buf(),
+                rel_path: normalize_rel_path(&self.repo_root, path),
                 range: TextRange::new(1, lines.len().min(20)),
             };
             let summary_chunk =
@@ -292,7 +623,7 @@ impl RefillPipeline {
         let lang = detect_language(path);
         let source = SourceLocation {
             repo_root: self.repo_root.clone(),
-            rel_path: path.to_path_buf(),
+            rel_path: normalize_rel_path(&self.repo_root, path),
             range: TextRange::new(1, lines.len()),
         };
 
@@ -315,11 +646,18 @@ impl RefillPipeline {
         let mut chunks = Vec::new();
         let mut seen_symbols: HashSet<String> = HashSet::new();
 
-        // 1. Retrieve mentioned symbols
+        // 1. Retrieve mentioned symbols (bounded: each lookup is a repo walk)
         for symbol in symbols {
             if seen_symbols.contains(symbol) {
                 continue;
             }
+            if seen_symbols.len() >= self.max_symbol_queries {
+                tracing::debug!(
+                    "Symbol query cap ({}) reached, skipping remaining symbols",
+                    self.max_symbol_queries
+                );
+                break;
+            }
             seen_symbols.insert(symbol.clone());
 
             match self.retrieve_symbol(symbol, 3) {
@@ -380,6 +718,13 @@ impl RefillPipeline {
                     })
                     .collect())
             }
+            SymbolRelation::Callees => Ok(self
+                .dependency_chunks(base_symbol, 1, top_k + base_locs.len())?
+                .into_iter()
+                .filter(|(hop, _)| *hop == 1)
+                .map(|(_, chunk)| chunk)
+                .take(top_k)
+                .collect()),
             _ => {
                 // Other relations require full ScopeGraph traversal
                 // For now, return empty
@@ -394,19 +739,104 @@ impl RefillPipeline {
 impl RefillPipeline {
     /// Refine IndexChunks into ContextChunks
     ///
-    /// Processing steps:
+    /// Processing steps (input is first ordered by rank, then location, so
+    /// the result doesn't depend on the order hits were discovered in):
     /// 1. Deduplicate by symbol (same symbol in multiple places)
-    /// 2. Sort by relevance score
-    /// 3. Truncate to token budget (keep highest relevance)
-    /// 4. Inject symbol signatures
+    /// 2. Sort by relevance score weighted by retrieval confidence
+    /// 3. Collapse byte-identical content across locations
+    /// 4. Merge overlapping or nearby chunks of a file, cap chunks per file,
+    ///    shorten chunks over `max_chunk_lines` and prepend each file's
+    ///    imports, when enabled
+    /// 5. Truncate to token budget (keep highest relevance)
+    /// 6. Inject symbol signatures
+    /// 7. Prepend pinned files within their own budget
     pub fn refine(&self, chunks: &[IndexChunk]) -> Vec<ContextChunk> {
+        self.refine_inner(chunks, None)
+    }
+
+    /// Like [`Self::refine`], also returning one [`SelectionReason`] per input chunk
+    /// (selected chunks first, in output order).
+    pub fn refine_explained(
+        &self,
+        chunks: &[IndexChunk],
+    ) -> (Vec<ContextChunk>, Vec<SelectionReason>) {
+        let mut reasons = Vec::with_capacity(chunks.len());
+        let refined = self.refine_inner(chunks, Some(&mut reasons));
+        reasons.sort_by_key(|r| match r.outcome {
+            SelectionOutcome::Selected | SelectionOutcome::Truncated => 0,
+            _ => 1,
+        });
+        (refined, reasons)
+    }
+
+    fn refine_inner(
+        &self,
+        chunks: &[IndexChunk],
+        mut explain: Option<&mut Vec<SelectionReason>>,
+    ) -> Vec<ContextChunk> {
+        // Every "keep the first" step below keeps the best-ranked chunk;
+        // location breaks ties, so the result ignores discovery order.
+        let pre_score =
+            |ic: &IndexChunk| calculate_relevance(ic) * retrieval_confidence(ic.chunk_type);
+        // Chunks dropped before conversion are counted like the budget counts
+        let counter = self.cache.cached_counter(self.token_counter.as_ref());
+        let mut ordered: Vec<&IndexChunk> = chunks.iter().collect();
+        ordered.sort_by(|a, b| {
+            pre_score(b)
+                .partial_cmp(&pre_score(a))
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| location_key(a).cmp(&location_key(b)))
+        });
+
+        if self.test_files != TestFilePolicy::Include {
+            ordered.retain(|chunk| {
+                if self.test_files.allows(is_test_chunk(chunk)) {
+                    return true;
+                }
+                if let Some(reasons) = explain.as_deref_mut() {
+                    reasons.push(SelectionReason {
+                        source: chunk.source.clone(),
+                        outcome: SelectionOutcome::TestFilePolicy,
+                        rank_score: 0.0,
+                        size_lines: chunk.content.lines().count(),
+                        token_count: counter.count(&chunk.content),
+                    });
+                }
+                false
+            });
+        }
+
+        // 0. Drop the weakest candidates before the expensive conversion
+        if let Some(max) = self.max_candidates.filter(|&max| ordered.len() > max) {
+            for chunk in ordered.drain(max..) {
+                if let Some(reasons) = explain.as_deref_mut() {
+                    reasons.push(SelectionReason {
+                        source: chunk.source.clone(),
+                        outcome: SelectionOutcome::CandidateCapReached,
+                        rank_score: pre_score(chunk),
+                        size_lines: chunk.content.lines().count(),
+                        token_count: counter.count(&chunk.content),
+                    });
+                }
+            }
+        }
+
         // 1. Deduplicate by primary symbol
         let mut seen_symbols: HashSet<SymbolId> = HashSet::new();
         let mut unique_chunks: Vec<&IndexChunk> = Vec::new();
 
-        for chunk in chunks {
+        for chunk in ordered {
             if let Some(symbol) = chunk.primary_symbol() {
                 if seen_symbols.contains(symbol) {
+                    if let Some(reasons) = explain.as_deref_mut() {
+                        reasons.push(SelectionReason {
+                            source: chunk.source.clone(),
+                            outcome: SelectionOutcome::DuplicateSymbol,
+                            rank_score: 0.0,
+                            size_lines: chunk.content.lines().count(),
+                            token_count: counter.count(&chunk.content),
+                        });
+                    }
                     continue;
                 }
                 seen_symbols.insert(symbol.clone());
@@ -420,17 +850,283 @@ impl RefillPipeline {
             .map(|ic| self.index_to_context(ic))
             .collect();
 
-        // 3. Sort by relevance (highest first)
+        // 3. Sort by confidence-weighted relevance (highest first), tighter chunks first on ties
         context_chunks.sort_by(|a, b| {
-            b.relevance_score
-                .partial_cmp(&a.relevance_score)
+            b.rank_score()
+                .partial_cmp(&a.rank_score())
                 .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.token_count.cmp(&b.token_count))
         });
 
-        // 4. Truncate to token budget
+        // 4. Collapse identical content found at other locations (keeps the best-ranked copy)
+        if self.dedup_identical_content {
+            context_chunks = dedup_identical_content(context_chunks, |dup| {
+                if let Some(reasons) = explain.as_deref_mut() {
+                    reasons.push(SelectionReason::of(dup, SelectionOutcome::DuplicateContent));
+                }
+            });
+        }
+        if let Some(threshold) = self.near_duplicate_threshold {
+            context_chunks = dedup_near_duplicates(context_chunks, threshold, |dup| {
+                if let Some(reasons) = explain.as_deref_mut() {
+                    reasons.push(SelectionReason::of(dup, SelectionOutcome::NearDuplicate));
+                }
+            });
+        }
+
+        if let Some(gap) = self.merge_gap {
+            context_chunks = self.merge_nearby_chunks(context_chunks, gap, |merged| {
+                if let Some(reasons) = explain.as_deref_mut() {
+                    reasons.push(SelectionReason::of(
+                        merged,
+                        SelectionOutcome::MergedWithNeighbor,
+                    ));
+                }
+            });
+        }
+
+        if let Some(max) = self.max_chunks_per_file {
+            let mut per_file: HashMap<PathBuf, usize> = HashMap::new();
+            context_chunks.retain(|chunk| {
+                let seen = per_file.entry(chunk.source.rel_path.clone()).or_default();
+                *seen += 1;
+                if *seen <= max {
+                    return true;
+                }
+                if let Some(reasons) = explain.as_deref_mut() {
+                    reasons.push(SelectionReason::of(chunk, SelectionOutcome::FileCapReached));
+                }
+                false
+            });
+        }
+
+        // Shorten long chunks only now, so dedup compared their full content
+        if let Some(max_lines) = self.max_chunk_lines {
+            let counter = self.cache.cached_counter(self.token_counter.as_ref());
+            for chunk in &mut context_chunks {
+                chunk.truncate_to_lines(max_lines);
+                chunk.recount_tokens(&counter);
+            }
+        }
+
+        // 5. Lead each file's best-ranked chunk with the file's import block
+        if self.include_file_imports {
+            context_chunks = self.prepend_file_imports(context_chunks);
+        }
+
+        // 6. Truncate to token budget
+        let Some(reasons) = explain else {
+            self.truncate_to_budget(&mut context_chunks);
+            return self.prepend_pinned(context_chunks);
+        };
+
+        let ranked = context_chunks
+            .iter()
+            .filter(|c| c.context_type != ContextType::Imports)
+            .map(|c| (c.id, SelectionReason::of(c, SelectionOutcome::Selected)))
+            .collect::<Vec<_>>();
         self.truncate_to_budget(&mut context_chunks);
+        let kept: HashMap<ChunkId, usize> = context_chunks
+            .iter()
+            .map(|c| (c.id, c.token_count))
+            .collect();
+        reasons.extend(ranked.into_iter().map(|(id, mut reason)| {
+            reason.outcome = match kept.get(&id) {
+                None => SelectionOutcome::DroppedByBudget,
+                Some(&tokens) if tokens < reason.token_count => SelectionOutcome::Truncated,
+                Some(_) => SelectionOutcome::Selected,
+            };
+            reason
+        }));
 
-        context_chunks
+        self.prepend_pinned(context_chunks)
+    }
+
+    /// Put the pinned files ahead of `chunks`, truncated to the pinned budget.
+    /// A selected chunk from a pinned file stays; it may show a later part.
+    fn prepend_pinned(&self, chunks: Vec<ContextChunk>) -> Vec<ContextChunk> {
+        if self.pinned_paths.is_empty() {
+            return chunks;
+        }
+
+        let counter = self.cache.cached_counter(self.token_counter.as_ref());
+        let mut remaining = self.pinned_tokens;
+        let mut out = Vec::with_capacity(self.pinned_paths.len() + chunks.len());
+        for path in &self.pinned_paths {
+            if remaining == 0 {
+                break;
+            }
+            let abs_path = self.repo_root.join(path);
+            let content = match self.file_provider.read_file(&abs_path) {
+                Ok(content) => content,
+                Err(err) => {
+                    tracing::debug!("pinned: cannot read {:?}: {err}", abs_path);
+                    continue;
+                }
+            };
+            let rel_path = abs_path
+                .strip_prefix(&self.repo_root)
+                .map_or_else(|_| path.clone(), Path::to_path_buf);
+            let source = SourceLocation {
+                repo_root: self.repo_root.clone(),
+                rel_path,
+                range: TextRange::new(1, content.lines().count().max(1)),
+            };
+            let mut chunk = ContextChunk::new(content, source, ContextType::Pinned);
+            chunk.recount_tokens(&counter);
+            chunk.truncate_to_tokens_with(remaining, &counter);
+            chunk.set_relevance(1.0);
+            remaining = remaining.saturating_sub(chunk.token_count);
+            out.push(chunk);
+        }
+        out.extend(chunks);
+        out
+    }
+
+    /// Merge each run of same-file chunks whose line ranges overlap or are
+    /// within `gap` into one chunk re-read from the file. The merged chunk
+    /// keeps the place and scores of the run's best-ranked chunk; `on_merged`
+    /// sees the others. File overviews are left alone, as are files that
+    /// can no longer be read.
+    fn merge_nearby_chunks(
+        &self,
+        chunks: Vec<ContextChunk>,
+        gap: MergeGap,
+        mut on_merged: impl FnMut(&ContextChunk),
+    ) -> Vec<ContextChunk> {
+        let mut by_file: HashMap<PathBuf, Vec<usize>> = HashMap::new();
+        for (i, chunk) in chunks.iter().enumerate() {
+            if chunk.context_type != ContextType::FileOverview {
+                by_file
+                    .entry(chunk.source.rel_path.clone())
+                    .or_default()
+                    .push(i);
+            }
+        }
+
+        let counter = self.cache.cached_counter(self.token_counter.as_ref());
+        let mut slots: Vec<Option<ContextChunk>> = chunks.into_iter().map(Some).collect();
+        let range_of = |slots: &[Option<ContextChunk>], i: usize| {
+            slots[i].as_ref().map_or((0, 0), |c| {
+                (c.source.range.start_line, c.source.range.end_line)
+            })
+        };
+
+        for (rel_path, mut indices) in by_file {
+            if indices.len() < 2 {
+                continue;
+            }
+            let abs_path = self.repo_root.join(&rel_path);
+            let content = match self.file_provider.read_file(&abs_path) {
+                Ok(content) => content,
+                Err(err) => {
+                    tracing::debug!("merge: cannot read {:?}: {err}", abs_path);
+                    continue;
+                }
+            };
+            let lines = content.lines().collect::<Vec<_>>();
+
+            indices.sort_by_key(|&i| range_of(&slots, i));
+            let mut runs: Vec<(Vec<usize>, usize, usize)> = Vec::new();
+            for i in indices {
+                let (start, end) = range_of(&slots, i);
+                match runs.last_mut() {
+                    Some((run, _, run_end)) if within_gap(&lines, *run_end, start, gap) => {
+                        run.push(i);
+                        *run_end = (*run_end).max(end);
+                    }
+                    _ => runs.push((vec![i], start, end)),
+                }
+            }
+
+            for (run, start, end) in runs.into_iter().filter(|(run, ..)| run.len() > 1) {
+                let Some(text) = lines.get(start.saturating_sub(1)..end.min(lines.len())) else {
+                    continue;
+                };
+                let text = text.join("\n");
+                // Lower index = better ranked.
+                let best = run.iter().copied().min().unwrap_or(run[0]);
+                let mut others = Vec::with_capacity(run.len() - 1);
+                for &i in run.iter().filter(|&&i| i != best) {
+                    if let Some(other) = slots[i].take() {
+                        on_merged(&other);
+                        others.push(other);
+                    }
+                }
+                let Some(kept) = slots[best].as_mut() else {
+                    continue;
+                };
+                for other in others {
+                    for sig in other.symbol_signatures {
+                        if !kept.symbol_signatures.contains(&sig) {
+                            kept.symbol_signatures.push(sig);
+                        }
+                    }
+                    for path in other.also_in {
+                        if !kept.also_in.contains(&path) {
+                            kept.also_in.push(path);
+                        }
+                    }
+                    kept.near_duplicates += other.near_duplicates;
+                }
+                kept.source.range = TextRange::new(start, end);
+                kept.stable_id = kept.source.stable_id();
+                kept.content = text;
+                kept.recount_tokens(&counter);
+            }
+        }
+
+        slots.into_iter().flatten().collect()
+    }
+
+    /// Insert an [`ContextType::Imports`] chunk ahead of the first chunk of
+    /// each file, unless that chunk already starts within the import block.
+    fn prepend_file_imports(&self, chunks: Vec<ContextChunk>) -> Vec<ContextChunk> {
+        let mut seen_files: HashSet<PathBuf> = HashSet::new();
+        let mut out = Vec::with_capacity(chunks.len());
+
+        for chunk in chunks {
+            if seen_files.insert(chunk.source.rel_path.clone()) {
+                if let Some(imports) = self.imports_chunk(&chunk) {
+                    out.push(imports);
+                }
+            }
+            out.push(chunk);
+        }
+        out
+    }
+
+    fn imports_chunk(&self, anchor: &ContextChunk) -> Option<ContextChunk> {
+        let abs_path = self.repo_root.join(&anchor.source.rel_path);
+        let content = match self.file_provider.read_file(&abs_path) {
+            Ok(content) => content,
+            Err(err) => {
+                tracing::debug!("imports: cannot read {:?}: {err}", abs_path);
+                return None;
+            }
+        };
+        let (start, end) = import_block(&content)?;
+        // 1-based ranges: the anchor already shows the block when it starts inside it.
+        if anchor.source.range.start_line <= end + 1 {
+            return None;
+        }
+
+        let block = content
+            .lines()
+            .skip(start)
+            .take(end - start + 1)
+            .collect::<Vec<_>>()
+            .join("\n");
+        let source = SourceLocation {
+            repo_root: self.repo_root.clone(),
+            rel_path: anchor.source.rel_path.clone(),
+            range: TextRange::new(start + 1, end + 1),
+        };
+        let mut chunk = ContextChunk::new(block, source, ContextType::Imports);
+        chunk.recount_tokens(&self.cache.cached_counter(self.token_counter.as_ref()));
+        chunk.set_relevance(anchor.relevance_score);
+        chunk.set_confidence(anchor.confidence);
+        Some(chunk)
     }
 
     /// Convert IndexChunk to ContextChunk
@@ -447,20 +1143,37 @@ impl RefillPipeline {
         let relevance = calculate_relevance(index);
 
         let mut chunk = ContextChunk::new(index.content.clone(), index.source.clone(), context_type);
+        chunk.recount_tokens(&self.cache.cached_counter(self.token_counter.as_ref()));
         chunk.set_relevance(relevance);
+        chunk.set_confidence(retrieval_confidence(index.chunk_type));
+        chunk.hit_line = Some(index.hit_line.unwrap_or(index.source.range.start_line));
+        if let Some(threshold) = self.data_chunk_threshold {
+            let likeness = code_likeness(&index.content);
+            if likeness < threshold {
+                tracing::debug!(
+                    "{}:{} looks like data (code likeness {likeness:.2}), deprioritized",
+                    index.source.rel_path.display(),
+                    index.source.range.start_line
+                );
+                chunk.set_confidence(chunk.confidence * DATA_CHUNK_CONFIDENCE);
+            }
+        }
 
         // Inject symbol signatures
         for symbol in &index.symbols {
             chunk.add_signature(symbol.full_name());
         }
 
-        // Try to get signature from symbol resolver for definitions
+        // Try to get signature (and the AST kind) from symbol resolver for definitions
         if index.chunk_type == crate::IndexChunkType::SymbolDefinition {
-            if let Ok(Some(sig)) = self
+            if let Ok(info) = self
                 .symbol_resolver
-                .get_signature(&self.repo_root, &index.source)
+                .get_symbol_info(&self.repo_root, &index.source)
             {
-                chunk.add_signature(sig);
+                if let Some(sig) = info.signature {
+                    chunk.add_signature(sig);
+                }
+                chunk.scope_kind = info.kind;
             }
         }
 
@@ -494,7 +1207,8 @@ impl RefillPipeline {
                 if remaining > 0 {
                     // Get mutable reference to last and truncate it
                     if let Some(last_mut) = chunks.last_mut() {
-                        last_mut.truncate_to_tokens(remaining);
+                        let counter = self.cache.cached_counter(self.token_counter.as_ref());
+                        last_mut.truncate_to_tokens_with(remaining, &counter);
                     }
                 } else {
                     // Remove the last chunk entirely
@@ -502,6 +1216,14 @@ impl RefillPipeline {
                 }
             }
         }
+
+        // An import block is only useful ahead of the chunk it was added for
+        if chunks
+            .last()
+            .is_some_and(|c| c.context_type == ContextType::Imports)
+        {
+            chunks.pop();
+        }
     }
 }
 
@@ -542,21 +1264,46 @@ impl RefillPipeline {
         Ok(new_chunks)
     }
 
-    /// Build context string for LLM prompt
-    pub fn build_context_string(&self, chunks: &[ContextChunk]) -> String {
-        if chunks.is_empty() {
-            return String::new();
+    /// Definition of `symbol_name` plus the definitions of what it calls,
+    /// following callees up to `depth` hops (`0` is the definition alone).
+    ///
+    /// Chunks come back definition first, then by hop, and fit the token
+    /// budget; nearer hops win when it runs out. The walk itself is capped at
+    /// [`MAX_DEPENDENCY_CHUNKS`] definitions.
+    pub fn retrieve_definition_with_dependencies(
+        &self,
+        symbol_name: &str,
+        depth: usize,
+    ) -> error::Result<Vec<ContextChunk>> {
+        let found = self.dependency_chunks(symbol_name, depth, MAX_DEPENDENCY_CHUNKS)?;
+        if found.is_empty() {
+            return Err(error::LunaError::not_found(format!(
+                "no definition found for {symbol_name}"
+            )));
         }
 
-        let mut output = String::from("## Relevant Code Context\n\n");
+        let mut chunks: Vec<ContextChunk> = found
+            .iter()
+            .map(|(hop, index)| {
+                let mut chunk = self.index_to_context(index);
+                chunk.set_relevance(chunk.relevance_score * DEPENDENCY_HOP_DECAY.powi(*hop as i32));
+                chunk
+            })
+            .collect();
 
-        for chunk in chunks {
-            output.push_str(&chunk.format_for_prompt());
-            output.push('\n');
-        }
+        // Stable, so chunks of the same hop keep their walk order
+        chunks.sort_by(|a, b| {
+            b.rank_score()
+                .partial_cmp(&a.rank_score())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        self.truncate_to_budget(&mut chunks);
+        Ok(chunks)
+    }
 
-        output.push_str("## End Context\n");
-        output
+    /// Build context string for LLM prompt
+    pub fn build_context_string(&self, chunks: &[ContextChunk]) -> String {
+        self.template.render(chunks)
     }
 }
 
@@ -568,6 +1315,260 @@ fn detect_language(path: &Path) -> LanguageId {
         .unwrap_or(LanguageId::Unknown)
 }
 
+/// Most lines an import block may span before the scan stops.
+const MAX_IMPORT_LINES: usize = 60;
+
+/// 0-based inclusive line span of the file's leading import block.
+///
+/// Scans from the top, skipping blank lines, comments, attributes and Python
+/// docstrings, and stops at the first other statement. Statements continued
+/// over several lines (`use a::{`, `import (`) are followed to their close.
+fn import_block(content: &str) -> Option<(usize, usize)> {
+    let mut span: Option<(usize, usize)> = None;
+    let mut depth = 0i32;
+    let mut docstring: Option<&str> = None;
+
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if span.is_some_and(|(start, _)| i - start >= MAX_IMPORT_LINES) {
+            break;
+        }
+        if depth > 0 {
+            depth += bracket_delta(line);
+            span = span.map(|(start, _)| (start, i));
+            continue;
+        }
+        if let Some(delim) = docstring {
+            if line.contains(delim) {
+                docstring = None;
+            }
+            continue;
+        }
+        if let Some(delim) = ["\"\"\"", "'''"].into_iter().find(|d| line.starts_with(d)) {
+            if line.matches(delim).count() == 1 {
+                docstring = Some(delim);
+            }
+            continue;
+        }
+        if is_import_line(line) {
+            depth = bracket_delta(line);
+            span = Some(span.map_or((i, i), |(start, _)| (start, i)));
+            continue;
+        }
+        let skippable = line.is_empty()
+            || line.starts_with("//")
+            || line.starts_with("/*")
+            || line.starts_with('*')
+            || line.starts_with('#')
+            || line.starts_with("package ");
+        if !skippable {
+            break;
+        }
+    }
+    span
+}
+
+fn is_import_line(line: &str) -> bool {
+    const PREFIXES: &[&str] = &[
+        "use ",
+        "pub use ",
+        "pub(crate) use ",
+        "pub(super) use ",
+        "extern crate ",
+        "import ",
+        "from ",
+        "#include",
+        "using ",
+    ];
+    PREFIXES.iter().any(|p| line.starts_with(p))
+}
+
+fn bracket_delta(line: &str) -> i32 {
+    line.chars()
+        .map(|c| match c {
+            '{' | '(' => 1,
+            '}' | ')' => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+impl SelectionReason {
+    fn of(chunk: &ContextChunk, outcome: SelectionOutcome) -> Self {
+        Self {
+            source: chunk.source.clone(),
+            outcome,
+            rank_score: chunk.rank_score(),
+            size_lines: chunk.content.lines().count(),
+            token_count: chunk.token_count,
+        }
+    }
+}
+
+/// Whether a chunk starting at `start_line` overlaps, touches or is within
+/// `gap` of one ending at `end_line` (1-based, inclusive) in a file of `lines`.
+fn within_gap(lines: &[&str], end_line: usize, start_line: usize, gap: MergeGap) -> bool {
+    if start_line <= end_line + 1 {
+        return true;
+    }
+    let between = start_line - end_line - 1;
+    match gap {
+        MergeGap::Lines(max) => between <= max,
+        MergeGap::Bytes(max) => {
+            let bytes: usize = lines
+                .iter()
+                .skip(end_line)
+                .take(between)
+                .map(|line| line.len() + 1)
+                .sum();
+            bytes <= max
+        }
+    }
+}
+
+/// Keep the first of each group of chunks with identical content, recording
+/// the other paths in `also_in`. Input order is preserved; `on_dup` sees each
+/// collapsed chunk.
+fn dedup_identical_content(
+    chunks: Vec<ContextChunk>,
+    mut on_dup: impl FnMut(&ContextChunk),
+) -> Vec<ContextChunk> {
+    let mut kept: Vec<ContextChunk> = Vec::with_capacity(chunks.len());
+    let mut by_hash: HashMap<u64, usize> = HashMap::new();
+
+    for chunk in chunks {
+        let hash = crate::fnv1a64(content_key(&chunk).as_bytes());
+        match by_hash.get(&hash) {
+            Some(&idx) if content_key(&kept[idx]) == content_key(&chunk) => {
+                on_dup(&chunk);
+                let first = &mut kept[idx];
+                let path = chunk.source.rel_path;
+                if path != first.source.rel_path && !first.also_in.contains(&path) {
+                    first.also_in.push(path);
+                }
+            }
+            _ => {
+                by_hash.insert(hash, kept.len());
+                kept.push(chunk);
+            }
+        }
+    }
+
+    kept
+}
+
+/// Content compared by `dedup_identical_content`: trimmed, except in Python,
+/// where blocks at different depths are different code.
+fn content_key(chunk: &ContextChunk) -> &str {
+    match detect_language(&chunk.source.rel_path) {
+        LanguageId::Python => &chunk.content,
+        _ => chunk.content.trim(),
+    }
+}
+
+/// Keep the first of each group of chunks whose content is at least
+/// `threshold` similar, counting the others in its `near_duplicates`. Input
+/// order is preserved; `on_dup` sees each collapsed chunk.
+fn dedup_near_duplicates(
+    chunks: Vec<ContextChunk>,
+    threshold: f32,
+    mut on_dup: impl FnMut(&ContextChunk),
+) -> Vec<ContextChunk> {
+    let mut kept: Vec<(ContextChunk, Option<MinHash>)> = Vec::with_capacity(chunks.len());
+
+    for chunk in chunks {
+        let signature = MinHash::of(&chunk.content);
+        let similar = signature.as_ref().and_then(|sig| {
+            kept.iter().position(|(_, other)| {
+                other
+                    .as_ref()
+                    .is_some_and(|other| other.similarity(sig) >= threshold)
+            })
+        });
+        match similar {
+            Some(idx) => {
+                on_dup(&chunk);
+                kept[idx].0.near_duplicates += 1;
+            }
+            None => kept.push((chunk, signature)),
+        }
+    }
+
+    kept.into_iter().map(|(chunk, _)| chunk).collect()
+}
+
+/// Whether a hit is test code: from a test file, or opening with a test marker.
+fn is_test_chunk(chunk: &IndexChunk) -> bool {
+    let path = &chunk.source.rel_path;
+    let first_line = chunk.content.lines().find(|l| !l.trim().is_empty());
+    is_test_path(path) || first_line.is_some_and(|l| is_test_source(path, l))
+}
+
+/// `(path, start, end)` of a hit, for a discovery-order independent sort.
+fn location_key(chunk: &IndexChunk) -> (&Path, usize, usize, usize, usize) {
+    let range = &chunk.source.range;
+    (
+        &chunk.source.rel_path,
+        range.start_line,
+        range.start_col,
+        range.end_line,
+        range.end_col,
+    )
+}
+
+/// Share of `text` (ignoring line breaks) that is whitespace or word-like
+/// runs, `0.0..=1.0`.
+///
+/// Code is mostly identifiers, keywords and spacing, so it scores high (~0.6+).
+/// Base64 blobs are one long run and numeric tables mostly digits and
+/// punctuation, so both score low. Empty text counts as code.
+#[must_use]
+pub fn code_likeness(text: &str) -> f32 {
+    let bytes = text.as_bytes();
+    let total = bytes.iter().filter(|&&b| b != b'\n' && b != b'\r').count();
+    if total == 0 {
+        return 1.0;
+    }
+
+    let is_word_byte = |b: u8| b.is_ascii_alphanumeric() || b == b'_' || !b.is_ascii();
+    let mut codeish = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        if is_word_byte(b) {
+            let start = i;
+            while i < bytes.len() && is_word_byte(bytes[i]) {
+                i += 1;
+            }
+            let word = &bytes[start..i];
+            let starts_like_ident = !word[0].is_ascii_digit();
+            if starts_like_ident && word.len() <= MAX_WORD_LEN {
+                codeish += word.len();
+            }
+            continue;
+        }
+        if b == b' ' || b == b'\t' {
+            codeish += 1;
+        }
+        i += 1;
+    }
+    codeish as f32 / total as f32
+}
+
+/// How precisely a chunk of this type is located.
+///
+/// Definitions/references come from scope resolution; code blocks and file
+/// summaries are fixed windows that may include unrelated lines.
+fn retrieval_confidence(chunk_type: crate::IndexChunkType) -> f32 {
+    match chunk_type {
+        crate::IndexChunkType::SymbolDefinition => 1.0,
+        crate::IndexChunkType::SymbolReference => 0.8,
+        crate::IndexChunkType::Documentation => 0.6,
+        crate::IndexChunkType::CodeBlock => 0.5,
+        crate::IndexChunkType::FileSummary => 0.3,
+    }
+}
+
 fn calculate_relevance(index: &IndexChunk) -> f32 {
     let mut score = 0.5; // Base score
 
@@ -590,7 +1591,6 @@ fn calculate_relevance(index: &IndexChunk) -> f32 {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::collections::HashMap;
     use std::sync::Mutex;
 
     // Mock FileProvider for testing
@@ -666,6 +1666,23 @@ mod tests {
         ) -> error::Result<String> {
             Ok("fn mock() -> i32 { 42 }".to_string())
         }
+
+        fn get_symbol_info(
+            &self,
+            _repo_root: &Path,
+            location: &SourceLocation,
+        ) -> error::Result<SymbolInfo> {
+            let callees: &[&str] = match location.rel_path.file_stem().and_then(|s| s.to_str()) {
+                Some("main") => &["parse", "run"],
+                Some("parse") => &["tokenize", "main"],
+                _ => &[],
+            };
+            Ok(SymbolInfo {
+                signature: Some("fn mock() -> i32".to_string()),
+                kind: Some("function".to_string()),
+                callees: callees.iter().map(|c| c.to_string()).collect(),
+            })
+        }
     }
 
     fn create_test_pipeline() -> RefillPipeline {
@@ -723,6 +1740,884 @@ mod tests {
         assert_eq!(refined.len(), 1); // Deduplicated
     }
 
+    #[test]
+    fn test_task_driven_caps_symbol_queries() {
+        let symbols: Vec<String> = (0..8).map(|i| format!("sym{i}")).collect();
+        let query = ContextQuery::TaskDriven {
+            keywords: Vec::new(),
+            paths: Vec::new(),
+            symbols: symbols.clone(),
+        };
+
+        let chunks = create_test_pipeline().retrieve(&query, 100).unwrap();
+        assert_eq!(chunks.len(), DEFAULT_MAX_SYMBOL_QUERIES);
+
+        let chunks = create_test_pipeline()
+            .with_max_symbol_queries(2)
+            .retrieve(&query, 100)
+            .unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[0].source.rel_path.ends_with("sym0.rs"));
+    }
+
+    #[test]
+    fn test_refine_prefers_confident_chunks() {
+        let pipeline = create_test_pipeline();
+        let source = SourceLocation {
+            repo_root: PathBuf::from("/repo"),
+            rel_path: PathBuf::from("src/lib.rs"),
+            range: TextRange::new(1, 5),
+        };
+
+        // File summaries score higher on relevance alone, but are a blind window.
+        let summary = IndexChunk::file_summary("// head", source.clone(), LanguageId::Rust);
+        let reference = IndexChunk::new("foo()", source, crate::IndexChunkType::SymbolReference);
+
+        let refined = pipeline.refine(&[summary, reference]);
+        assert_eq!(refined[0].context_type, ContextType::RelatedSymbol);
+        assert!(refined[0].confidence > refined[1].confidence);
+    }
+
+    #[test]
+    fn test_refine_collapses_identical_content_across_paths() {
+        let pipeline = create_test_pipeline();
+        let at = |path: &str| SourceLocation {
+            repo_root: PathBuf::from("/repo"),
+            rel_path: PathBuf::from(path),
+            range: TextRange::new(1, 3),
+        };
+
+        let chunks = vec![
+            IndexChunk::new(
+                "fn copied() {}",
+                at("a.rs"),
+                crate::IndexChunkType::CodeBlock,
+            ),
+            IndexChunk::new(
+                "fn copied() {}",
+                at("vendor/b.rs"),
+                crate::IndexChunkType::CodeBlock,
+            ),
+            IndexChunk::new(
+                "fn other() {}",
+                at("c.rs"),
+                crate::IndexChunkType::CodeBlock,
+            ),
+        ];
+
+        // Off unless asked for
+        assert_eq!(pipeline.refine(&chunks).len(), 3);
+
+        let refined = create_test_pipeline()
+            .with_content_dedup(true)
+            .refine(&chunks);
+        assert_eq!(refined.len(), 2);
+        let copied = refined
+            .iter()
+            .find(|c| c.content == "fn copied() {}")
+            .unwrap();
+        assert_eq!(copied.also_in, vec![PathBuf::from("vendor/b.rs")]);
+        assert!(copied
+            .format_for_prompt()
+            .contains("[also in: vendor/b.rs]"));
+
+        // Indentation is part of Python code: a nested copy stays
+        let python = vec![
+            IndexChunk::new(
+                "def run():\n    go()",
+                at("a.py"),
+                crate::IndexChunkType::CodeBlock,
+            ),
+            IndexChunk::new(
+                "    def run():\n    go()",
+                at("b.py"),
+                crate::IndexChunkType::CodeBlock,
+            ),
+        ];
+        let refined = create_test_pipeline()
+            .with_content_dedup(true)
+            .refine(&python);
+        assert_eq!(refined.len(), 2);
+    }
+
+    #[test]
+    fn test_refine_collapses_near_duplicates_only_when_enabled() {
+        let at = |path: &str| SourceLocation {
+            repo_root: PathBuf::from("/repo"),
+            rel_path: PathBuf::from(path),
+            range: TextRange::new(1, 8),
+        };
+        let scaffold = |name: &str| {
+            format!(
+                "fn {name}() {{\n    let dir = tmp_dir();\n    let ctx = ToolContext::new(&dir);\n    \
+                 let out = registry().run(&ctx, &call()).unwrap();\n    assert!(out.ok);\n    \
+                 cleanup(&dir);\n}}"
+            )
+        };
+        let chunks = vec![
+            IndexChunk::new(
+                scaffold("reads_file"),
+                at("tests/a.rs"),
+                crate::IndexChunkType::CodeBlock,
+            ),
+            IndexChunk::new(
+                scaffold("reads_files"),
+                at("tests/b.rs"),
+                crate::IndexChunkType::CodeBlock,
+            ),
+            IndexChunk::new(
+                "pub struct Config {\n    pub max_bytes: usize,\n}",
+                at("src/config.rs"),
+                crate::IndexChunkType::CodeBlock,
+            ),
+        ];
+
+        assert_eq!(create_test_pipeline().refine(&chunks).len(), 3);
+
+        let (refined, reasons) = create_test_pipeline()
+            .with_near_duplicate_threshold(0.6)
+            .refine_explained(&chunks);
+        assert_eq!(refined.len(), 2);
+        let kept = refined
+            .iter()
+            .find(|c| c.content.contains("reads_file"))
+            .unwrap();
+        assert_eq!(kept.near_duplicates, 1);
+        assert!(kept
+            .format_for_prompt()
+            .contains("[1 near-duplicate omitted]"));
+        assert_eq!(
+            reasons
+                .iter()
+                .filter(|r| r.outcome == SelectionOutcome::NearDuplicate)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_refine_is_independent_of_hit_order() {
+        let at = |path: &str, start: usize| SourceLocation {
+            repo_root: PathBuf::from("/repo"),
+            rel_path: PathBuf::from(path),
+            range: TextRange::new(start, start + 2),
+        };
+        let block = |content: &str, source: SourceLocation| {
+            IndexChunk::new(content, source, crate::IndexChunkType::CodeBlock)
+        };
+        let symbol = SymbolId::new("foo", "");
+        let chunks = vec![
+            IndexChunk::symbol_definition("fn foo() {}", at("b.rs", 1), symbol.clone()),
+            IndexChunk::symbol_definition("fn foo(x: u8) {}", at("a.rs", 9), symbol),
+            block("fn same() {}", at("d.rs", 4)),
+            block("fn same() {}", at("c.rs", 4)),
+            block("fn tie() {}", at("c.rs", 1)),
+        ];
+        let run = |chunks: &[IndexChunk]| {
+            create_test_pipeline()
+                .with_content_dedup(true)
+                .refine(chunks)
+                .into_iter()
+                .map(|c| (c.source.rel_path, c.source.range.start_line, c.content))
+                .collect::<Vec<_>>()
+        };
+
+        let first = run(&chunks);
+        assert_eq!(first, run(&chunks));
+        let mut reversed = chunks.clone();
+        reversed.reverse();
+        assert_eq!(first, run(&reversed));
+        // Among equally ranked copies the first by location survives, not by discovery
+        let paths: Vec<_> = first.iter().map(|(path, ..)| path.as_path()).collect();
+        assert!(paths.contains(&Path::new("a.rs")));
+        assert!(!paths.contains(&Path::new("b.rs")));
+        assert!(!paths.contains(&Path::new("d.rs")));
+    }
+
+    #[test]
+    fn test_refine_keeps_best_ranked_duplicate_over_earlier_location() {
+        let at = |path: &str| SourceLocation {
+            repo_root: PathBuf::from("/repo"),
+            rel_path: PathBuf::from(path),
+            range: TextRange::new(1, 2),
+        };
+        let symbol = SymbolId::new("foo", "");
+        let mut reference =
+            IndexChunk::new("foo();", at("a.rs"), crate::IndexChunkType::SymbolReference);
+        reference.symbols.push(symbol.clone());
+        let chunks = vec![
+            reference,
+            IndexChunk::symbol_definition("fn foo() {}", at("b.rs"), symbol),
+        ];
+
+        let refined = create_test_pipeline().refine(&chunks);
+        assert_eq!(refined.len(), 1);
+        assert_eq!(refined[0].source.rel_path, PathBuf::from("b.rs"));
+    }
+
+    #[test]
+    fn test_refine_caps_chunk_lines_only_when_enabled() {
+        let long = (1..=40)
+            .map(|i| format!("let x{i} = {i};"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let chunks = vec![IndexChunk::new(
+            long,
+            SourceLocation {
+                repo_root: PathBuf::from("/repo"),
+                rel_path: PathBuf::from("src/lib.rs"),
+                range: TextRange::new(1, 40),
+            },
+            crate::IndexChunkType::CodeBlock,
+        )];
+
+        let full = create_test_pipeline().refine(&chunks);
+        assert_eq!(full[0].content.lines().count(), 40);
+
+        let capped = create_test_pipeline()
+            .with_max_chunk_lines(6)
+            .refine(&chunks);
+        assert_eq!(capped[0].content.lines().count(), 7);
+        assert!(capped[0].content.starts_with("let x1 = 1;"));
+        assert!(capped[0].content.ends_with("// ... (34 lines omitted) ..."));
+        assert_eq!(capped[0].source.range, TextRange::new(1, 6));
+        assert!(capped[0].token_count < full[0].token_count);
+
+        // A hit inside the chunk is kept with the lines around it
+        let hit = vec![chunks[0].clone().with_hit_line(20)];
+        let capped = create_test_pipeline()
+            .with_max_chunk_lines(5)
+            .refine(&hit);
+        assert_eq!(
+            capped[0].content,
+            "// ... (17 lines omitted) ...\nlet x18 = 18;\nlet x19 = 19;\nlet x20 = 20;\n\
+             let x21 = 21;\nlet x22 = 22;\n// ... (18 lines omitted) ..."
+        );
+        assert_eq!(capped[0].source.range, TextRange::new(18, 22));
+    }
+
+    #[test]
+    fn test_refine_caps_chunks_per_file() {
+        let at = |path: &str, start: usize| SourceLocation {
+            repo_root: PathBuf::from("/repo"),
+            rel_path: PathBuf::from(path),
+            range: TextRange::new(start, start),
+        };
+        // Ten strong definitions in one file, one weaker reference elsewhere.
+        let mut chunks: Vec<IndexChunk> = (1..=10)
+            .map(|i| {
+                IndexChunk::symbol_definition(
+                    format!("fn hot{i:02}() {{}}"),
+                    at("src/hot.rs", i),
+                    SymbolId::new(format!("hot{i:02}"), ""),
+                )
+            })
+            .collect();
+        chunks.push(IndexChunk::new(
+            "hot1();",
+            at("src/cold.rs", 1),
+            crate::IndexChunkType::SymbolReference,
+        ));
+        let files = |refined: &[ContextChunk]| {
+            refined
+                .iter()
+                .map(|c| c.source.rel_path.clone())
+                .collect::<Vec<_>>()
+        };
+        // Room for the ten hot chunks (4 tokens each) only
+        let pipeline = || {
+            RefillPipeline::new(
+                PathBuf::from("/repo"),
+                Arc::new(MockFileProvider::new()),
+                Arc::new(MockSymbolResolver),
+                TokenBudget {
+                    max_context_tokens: 40,
+                },
+            )
+        };
+
+        let uncapped = pipeline().refine(&chunks);
+        assert!(!files(&uncapped).contains(&PathBuf::from("src/cold.rs")));
+
+        let (capped, reasons) = pipeline()
+            .with_max_chunks_per_file(3)
+            .refine_explained(&chunks);
+        let capped_files = files(&capped);
+        assert_eq!(
+            capped_files
+                .iter()
+                .filter(|p| p.as_path() == Path::new("src/hot.rs"))
+                .count(),
+            3
+        );
+        assert!(capped_files.contains(&PathBuf::from("src/cold.rs")));
+        assert_eq!(
+            reasons
+                .iter()
+                .filter(|r| r.outcome == SelectionOutcome::FileCapReached)
+                .count(),
+            7
+        );
+    }
+
+    #[test]
+    fn test_refine_only_converts_the_best_candidates() {
+        let at = |path: &str, start: usize| SourceLocation {
+            repo_root: PathBuf::from("/repo"),
+            rel_path: PathBuf::from(path),
+            range: TextRange::new(start, start),
+        };
+        // A weak reference listed first, then six definitions.
+        let mut chunks = vec![IndexChunk::new(
+            "f1();",
+            at("src/a.rs", 1),
+            crate::IndexChunkType::SymbolReference,
+        )];
+        chunks.extend((1..=6).map(|i| {
+            IndexChunk::symbol_definition(
+                format!("fn f{i}() {{}}"),
+                at("src/b.rs", i),
+                SymbolId::new(format!("f{i}"), ""),
+            )
+        }));
+        let pipeline = RefillPipeline::new(
+            PathBuf::from("/repo"),
+            Arc::new(MockFileProvider::new()),
+            Arc::new(MockSymbolResolver),
+            TokenBudget::default(),
+        )
+        .with_max_candidates(4);
+
+        let (refined, reasons) = pipeline.refine_explained(&chunks);
+        let mut lines = refined
+            .iter()
+            .map(|c| (c.source.rel_path.clone(), c.source.range.start_line))
+            .collect::<Vec<_>>();
+        lines.sort();
+        // Definitions win over the reference; ties keep location order.
+        assert_eq!(
+            lines,
+            (1..=4)
+                .map(|i| (PathBuf::from("src/b.rs"), i))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            reasons
+                .iter()
+                .filter(|r| r.outcome == SelectionOutcome::CandidateCapReached)
+                .count(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_refine_merges_overlapping_and_nearby_chunks() {
+        let files = Arc::new(MockFileProvider::new());
+        let body = (1..=12).map(|i| format!("line{i}")).collect::<Vec<_>>();
+        // Line 7 is long, as in minified code.
+        let mut content = body.clone();
+        content[6] = "x".repeat(200);
+        files.add_file(PathBuf::from("/repo/src/lib.rs"), content.join("\n"));
+        let pipeline = || {
+            RefillPipeline::new(
+                PathBuf::from("/repo"),
+                files.clone(),
+                Arc::new(MockSymbolResolver),
+                TokenBudget {
+                    max_context_tokens: 1000,
+                },
+            )
+        };
+        let block = |start: usize, end: usize| {
+            IndexChunk::new(
+                content[start - 1..end].join("\n"),
+                SourceLocation {
+                    repo_root: PathBuf::from("/repo"),
+                    rel_path: PathBuf::from("src/lib.rs"),
+                    range: TextRange::new(start, end),
+                },
+                crate::IndexChunkType::CodeBlock,
+            )
+        };
+        let ranges = |refined: &[ContextChunk]| {
+            let mut ranges = refined
+                .iter()
+                .map(|c| (c.source.range.start_line, c.source.range.end_line))
+                .collect::<Vec<_>>();
+            ranges.sort();
+            ranges
+        };
+
+        // 1-4 and 3-5 overlap; 8-9 is two lines further on, 12 two more.
+        let chunks = vec![block(1, 4), block(3, 5), block(8, 9), block(12, 12)];
+        assert_eq!(ranges(&pipeline().refine(&chunks)).len(), 4);
+
+        let (refined, reasons) = pipeline()
+            .with_merge_gap(MergeGap::Lines(0))
+            .refine_explained(&chunks);
+        assert_eq!(ranges(&refined), vec![(1, 5), (8, 9), (12, 12)]);
+        let merged = refined
+            .iter()
+            .find(|c| c.source.range.start_line == 1)
+            .unwrap();
+        assert_eq!(merged.content, body[..5].join("\n"));
+        assert_eq!(merged.stable_id, merged.source.stable_id());
+        assert_eq!(
+            reasons
+                .iter()
+                .filter(|r| r.outcome == SelectionOutcome::MergedWithNeighbor)
+                .count(),
+            1
+        );
+
+        let refined = pipeline()
+            .with_merge_gap(MergeGap::Lines(2))
+            .refine(&chunks);
+        assert_eq!(ranges(&refined), vec![(1, 12)]);
+
+        // Same line gaps, measured in bytes: the long line 7 keeps 5 and 8
+        // apart, the two short lines before 12 don't.
+        let refined = pipeline()
+            .with_merge_gap(MergeGap::Bytes(32))
+            .refine(&chunks);
+        assert_eq!(ranges(&refined), vec![(1, 5), (8, 12)]);
+    }
+
+    #[test]
+    fn test_code_likeness_separates_code_from_data() {
+        assert!(code_likeness("let total = items.iter().map(|i| i.len()).sum();") > 0.6);
+        assert!(code_likeness("def parse(self, text):\n    return text.split()") > 0.6);
+        let blob = format!(
+            "const LOGO: &str = \"{}\";",
+            "iVBORw0KGgoAAAANSUhEUgAAAAEAAAAB".repeat(20)
+        );
+        assert!(code_likeness(&blob) < 0.1);
+        let table = (0..64).map(|i| format!("0x{i:02x},")).collect::<String>();
+        assert!(code_likeness(&table) < 0.3);
+        assert_eq!(code_likeness(""), 1.0);
+    }
+
+    #[test]
+    fn test_refine_deprioritizes_data_chunks() {
+        let at = |path: &str| SourceLocation {
+            repo_root: PathBuf::from("/repo"),
+            rel_path: PathBuf::from(path),
+            range: TextRange::new(1, 1),
+        };
+        let chunks = vec![
+            IndexChunk::new(
+                format!("\"{}\"", "QUJDREVGR0hJSktMTU5PUFFSU1RVVldYWVo".repeat(4)),
+                at("src/a.rs"),
+                crate::IndexChunkType::CodeBlock,
+            ),
+            IndexChunk::new(
+                "fn decode(input: &str) -> Vec<u8> { base64::decode(input) }",
+                at("src/b.rs"),
+                crate::IndexChunkType::CodeBlock,
+            ),
+        ];
+        let pipeline = || {
+            RefillPipeline::new(
+                PathBuf::from("/repo"),
+                Arc::new(MockFileProvider::new()),
+                Arc::new(MockSymbolResolver),
+                TokenBudget::default(),
+            )
+        };
+
+        let refined = pipeline().refine(&chunks);
+        assert_eq!(refined[0].confidence, refined[1].confidence);
+
+        let refined = pipeline().with_data_chunk_threshold(0.3).refine(&chunks);
+        assert_eq!(refined.len(), 2);
+        assert_eq!(refined[0].source.rel_path, PathBuf::from("src/b.rs"));
+        assert!(refined[1].confidence < refined[0].confidence);
+    }
+
+    #[test]
+    fn test_refine_prepends_pinned_files_within_their_budget() {
+        let provider = MockFileProvider::new();
+        provider.add_file(
+            PathBuf::from("/repo/src/types.rs"),
+            "pub struct Id(u64);\n".to_string(),
+        );
+        provider.add_file(
+            PathBuf::from("/repo/src/schema.rs"),
+            "pub const TABLE: &str = \"users\";\n".repeat(20),
+        );
+        let chunks = vec![IndexChunk::new(
+            "fn handler() {}",
+            SourceLocation {
+                repo_root: PathBuf::from("/repo"),
+                rel_path: PathBuf::from("src/api.rs"),
+                range: TextRange::new(1, 1),
+            },
+            crate::IndexChunkType::CodeBlock,
+        )];
+        let pinned = vec![
+            PathBuf::from("src/types.rs"),
+            PathBuf::from("src/missing.rs"),
+            PathBuf::from("/repo/src/schema.rs"),
+        ];
+        let pipeline = RefillPipeline::new(
+            PathBuf::from("/repo"),
+            Arc::new(provider),
+            Arc::new(MockSymbolResolver),
+            TokenBudget {
+                max_context_tokens: 5,
+            },
+        )
+        .with_pinned_paths(pinned, 20);
+
+        let refined = pipeline.refine(&chunks);
+        let paths: Vec<_> = refined.iter().map(|c| c.source.rel_path.clone()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("src/types.rs"),
+                PathBuf::from("src/schema.rs"),
+                PathBuf::from("src/api.rs"),
+            ]
+        );
+        assert!(refined[..2]
+            .iter()
+            .all(|c| c.context_type == ContextType::Pinned));
+        let pinned_tokens: usize = refined[..2].iter().map(|c| c.token_count).sum();
+        assert!(pinned_tokens <= 20);
+        assert!(refined[1].content.len() < "pub const TABLE: &str = \"users\";\n".len() * 20);
+    }
+
+    #[test]
+    fn test_refine_applies_the_test_file_policy() {
+        let chunk = |path: &str, content: &str| {
+            IndexChunk::new(
+                content,
+                SourceLocation {
+                    repo_root: PathBuf::from("/repo"),
+                    rel_path: PathBuf::from(path),
+                    range: TextRange::new(1, 3),
+                },
+                crate::IndexChunkType::CodeBlock,
+            )
+        };
+        let chunks = vec![
+            chunk("src/parse.rs", "pub fn parse() {}"),
+            chunk("src/parse.rs", "#[cfg(test)]\nmod tests {}"),
+            chunk("tests/cli.rs", "fn runs() {}"),
+        ];
+        let refine = |policy| {
+            let pipeline = RefillPipeline::new(
+                PathBuf::from("/repo"),
+                Arc::new(MockFileProvider::new()),
+                Arc::new(MockSymbolResolver),
+                TokenBudget::default(),
+            )
+            .with_test_files(policy);
+            let (refined, reasons) = pipeline.refine_explained(&chunks);
+            let dropped = reasons
+                .iter()
+                .filter(|r| r.outcome == SelectionOutcome::TestFilePolicy)
+                .count();
+            (refined.len(), dropped)
+        };
+
+        assert_eq!(refine(TestFilePolicy::Include), (3, 0));
+        assert_eq!(refine(TestFilePolicy::Exclude), (1, 2));
+        assert_eq!(refine(TestFilePolicy::Only), (2, 1));
+    }
+
+    #[test]
+    fn test_import_block_detection() {
+        let rust = "//! Crate docs\n\nuse std::fmt;\nuse crate::{\n    a,\n    b,\n};\n\npub fn foo() {}\nuse late::Thing;\n";
+        assert_eq!(import_block(rust), Some((2, 6)));
+
+        let python = "\"\"\"Module docs\n\nmore\n\"\"\"\n# comment\nimport os\nfrom a import (\n    b,\n)\n\ndef f():\n    pass\n";
+        assert_eq!(import_block(python), Some((5, 8)));
+
+        let header = "#ifndef FOO_H\n#define FOO_H\n#include <stdio.h>\n\nint f(void);\n";
+        assert_eq!(import_block(header), Some((2, 2)));
+
+        assert_eq!(import_block("fn main() {}\nuse std::fmt;\n"), None);
+    }
+
+    #[test]
+    fn test_refine_prepends_file_imports() {
+        let files = Arc::new(MockFileProvider::new());
+        files.add_file(
+            PathBuf::from("/repo/src/lib.rs"),
+            "use std::fmt;\nuse std::sync::Arc;\n\npub fn head() {}\n\npub fn foo() -> Arc<i32> {\n    Arc::new(1)\n}\n"
+                .to_string(),
+        );
+        let pipeline = || {
+            RefillPipeline::new(
+                PathBuf::from("/repo"),
+                files.clone(),
+                Arc::new(MockSymbolResolver),
+                TokenBudget {
+                    max_context_tokens: 1000,
+                },
+            )
+        };
+        let at = |start: usize, end: usize| SourceLocation {
+            repo_root: PathBuf::from("/repo"),
+            rel_path: PathBuf::from("src/lib.rs"),
+            range: TextRange::new(start, end),
+        };
+        let chunks = vec![
+            IndexChunk::new(
+                "pub fn foo() -> Arc<i32> {\n    Arc::new(1)\n}",
+                at(6, 8),
+                crate::IndexChunkType::CodeBlock,
+            ),
+            IndexChunk::new(
+                "pub fn head() {}",
+                at(4, 4),
+                crate::IndexChunkType::CodeBlock,
+            ),
+        ];
+
+        // Off by default
+        assert_eq!(pipeline().refine(&chunks).len(), 2);
+
+        let (refined, reasons) = pipeline().with_file_imports(true).refine_explained(&chunks);
+        assert_eq!(refined.len(), 3);
+        assert_eq!(refined[0].context_type, ContextType::Imports);
+        assert_eq!(refined[0].content, "use std::fmt;\nuse std::sync::Arc;");
+        assert_eq!(refined[0].source.range.end_line, 2);
+        assert_eq!(refined[1].context_type, ContextType::CodeSnippet);
+        assert_eq!(refined[2].context_type, ContextType::CodeSnippet);
+        // Imports are not candidates, so they get no selection reason.
+        assert_eq!(reasons.len(), chunks.len());
+
+        // A chunk that already covers the top of the file needs no imports.
+        let whole = IndexChunk::new("use std::fmt;", at(1, 8), crate::IndexChunkType::CodeBlock);
+        let refined = pipeline().with_file_imports(true).refine(&[whole]);
+        assert_eq!(refined.len(), 1);
+    }
+
+    #[test]
+    fn test_refine_explained_reports_budget_and_duplicates() {
+        let pipeline = RefillPipeline::new(
+            PathBuf::from("/repo"),
+            Arc::new(MockFileProvider::new()),
+            Arc::new(MockSymbolResolver),
+            TokenBudget {
+                max_context_tokens: 12,
+            },
+        )
+        .with_content_dedup(true);
+        let at = |path: &str| SourceLocation {
+            repo_root: PathBuf::from("/repo"),
+            rel_path: PathBuf::from(path),
+            range: TextRange::new(1, 3),
+        };
+        let block = |content: &str, path: &str| {
+            IndexChunk::new(content, at(path), crate::IndexChunkType::CodeBlock)
+        };
+
+        // The first chunk (7 tokens) fits, its copy is collapsed into it, and
+        // neither of the next two fits in the 5 tokens left.
+        let chunks = vec![
+            block("fn first() { one(); two(); }", "a.rs"),
+            block("fn first() { one(); two(); }", "copy/a.rs"),
+            block("fn second() {\n    three();\n}", "b.rs"),
+            block("fn third() {\n    four(); five();\n}", "c.rs"),
+        ];
+
+        let (refined, reasons) = pipeline.refine_explained(&chunks);
+        assert_eq!(refined.len(), 1);
+        assert_eq!(refined[0].also_in, vec![PathBuf::from("copy/a.rs")]);
+        assert_eq!(reasons.len(), chunks.len());
+
+        let outcome = |path: &str| {
+            reasons
+                .iter()
+                .find(|r| r.source.rel_path == Path::new(path))
+                .map(|r| r.outcome)
+        };
+        assert_eq!(outcome("a.rs"), Some(SelectionOutcome::Selected));
+        assert_eq!(
+            outcome("copy/a.rs"),
+            Some(SelectionOutcome::DuplicateContent)
+        );
+        assert_eq!(outcome("b.rs"), Some(SelectionOutcome::DroppedByBudget));
+        assert_eq!(outcome("c.rs"), Some(SelectionOutcome::DroppedByBudget));
+        assert_eq!(reasons[0].source.rel_path, PathBuf::from("a.rs"));
+        assert!(reasons[0].to_string().starts_with("a.rs:1-3 Selected"));
+    }
+
+    /// One token per whitespace-separated word
+    struct WordCounter;
+
+    impl TokenCounter for WordCounter {
+        fn count(&self, text: &str) -> usize {
+            text.split_whitespace().count()
+        }
+
+        fn encode_with_offsets(&self, text: &str) -> (Vec<u32>, Vec<(usize, usize)>) {
+            let offsets = text
+                .split_whitespace()
+                .map(|w| {
+                    let start = w.as_ptr() as usize - text.as_ptr() as usize;
+                    (start, start + w.len())
+                })
+                .collect::<Vec<_>>();
+            (vec![0; offsets.len()], offsets)
+        }
+    }
+
+    #[test]
+    fn test_custom_token_counter_drives_budget() {
+        let pipeline = |counter: Arc<dyn TokenCounter>| {
+            RefillPipeline::new(
+                PathBuf::from("/repo"),
+                Arc::new(MockFileProvider::new()),
+                Arc::new(MockSymbolResolver),
+                TokenBudget {
+                    max_context_tokens: 2,
+                },
+            )
+            .with_token_counter(counter)
+        };
+        let chunk = |content: &str, path: &str| {
+            IndexChunk::new(
+                content,
+                SourceLocation {
+                    repo_root: PathBuf::from("/repo"),
+                    rel_path: PathBuf::from(path),
+                    range: TextRange::new(1, 1),
+                },
+                crate::IndexChunkType::CodeBlock,
+            )
+        };
+        let chunks = [
+            chunk("identifier_one", "a.rs"),
+            chunk("identifier_two", "b.rs"),
+        ];
+
+        // One word each: both fit.
+        let refined = pipeline(Arc::new(WordCounter)).refine(&chunks);
+        assert_eq!(refined.len(), 2);
+        assert!(refined.iter().all(|c| c.token_count == 1));
+
+        // ~4 chars per token: neither fits.
+        assert!(pipeline(Arc::new(CharApproxCounter))
+            .refine(&chunks)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_explained_drops_are_counted_with_the_pipeline_counter() {
+        let pipeline = RefillPipeline::new(
+            PathBuf::from("/repo"),
+            Arc::new(MockFileProvider::new()),
+            Arc::new(MockSymbolResolver),
+            TokenBudget {
+                max_context_tokens: 1000,
+            },
+        )
+        .with_token_counter(Arc::new(WordCounter))
+        .with_test_files(TestFilePolicy::Exclude)
+        .with_max_candidates(3);
+        let at = |path: &str| SourceLocation {
+            repo_root: PathBuf::from("/repo"),
+            rel_path: PathBuf::from(path),
+            range: TextRange::new(1, 1),
+        };
+        let block = |content: &str, path: &str| {
+            IndexChunk::new(content, at(path), crate::IndexChunkType::CodeBlock)
+        };
+        let symbol = SymbolId::new("foo", "");
+        let chunks = vec![
+            block("assert_eq!(foo(), 1);", "tests/foo.rs"),
+            IndexChunk::symbol_definition("fn foo() { one }", at("a.rs"), symbol.clone()),
+            IndexChunk::symbol_definition("fn foo() { one two }", at("b.rs"), symbol),
+            block("let kept = foo();", "c.rs"),
+            block("let capped = foo() + foo();", "d.rs"),
+        ];
+
+        let (_, reasons) = pipeline.refine_explained(&chunks);
+        let dropped = |outcome: SelectionOutcome| {
+            reasons
+                .iter()
+                .find(|r| r.outcome == outcome)
+                .map(|r| (r.source.rel_path.clone(), r.token_count))
+        };
+        // Word counts, not the ~4 chars/token estimate
+        assert_eq!(
+            dropped(SelectionOutcome::TestFilePolicy),
+            Some((PathBuf::from("tests/foo.rs"), 2))
+        );
+        assert_eq!(
+            dropped(SelectionOutcome::CandidateCapReached),
+            Some((PathBuf::from("d.rs"), 6))
+        );
+        assert_eq!(
+            dropped(SelectionOutcome::DuplicateSymbol),
+            Some((PathBuf::from("b.rs"), 6))
+        );
+    }
+
+    #[test]
+    fn test_retrieve_accepts_absolute_hit_path() {
+        let file_provider = Arc::new(MockFileProvider::new());
+        file_provider.add_file(
+            PathBuf::from("/repo/src/lib.rs"),
+            "fn a() {}\nfn b() {}".to_string(),
+        );
+        let pipeline = RefillPipeline::new(
+            PathBuf::from("/repo"),
+            file_provider,
+            Arc::new(MockSymbolResolver),
+            TokenBudget::default(),
+        );
+
+        // A path that escaped `strip_prefix` is still read, and reported repo-relative.
+        let chunks = pipeline
+            .retrieve(
+                &ContextQuery::File {
+                    path: PathBuf::from("/repo/src/lib.rs"),
+                },
+                5,
+            )
+            .unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].source.rel_path, PathBuf::from("src/lib.rs"));
+        assert_eq!(chunks[0].source.abs_path(), PathBuf::from("/repo/src/lib.rs"));
+    }
+
+    #[test]
+    fn test_definition_with_dependencies_walks_callees_by_hop() {
+        let pipeline = create_test_pipeline();
+        let files_of = |chunks: &[ContextChunk]| {
+            chunks
+                .iter()
+                .map(|c| c.source.rel_path.to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+
+        let root_only = pipeline
+            .retrieve_definition_with_dependencies("main", 0)
+            .unwrap();
+        assert_eq!(files_of(&root_only), ["src/main.rs"]);
+
+        // `main` is not revisited through `parse`'s callees.
+        let chunks = pipeline
+            .retrieve_definition_with_dependencies("main", 2)
+            .unwrap();
+        assert_eq!(
+            files_of(&chunks),
+            ["src/main.rs", "src/parse.rs", "src/run.rs", "src/tokenize.rs"]
+        );
+        assert!(chunks[0].relevance_score > chunks[1].relevance_score);
+        assert!(chunks[1].relevance_score > chunks[3].relevance_score);
+
+        let callees = pipeline
+            .retrieve(&ContextQuery::related("main", SymbolRelation::Callees), 10)
+            .unwrap();
+        assert_eq!(callees.len(), 2);
+    }
+
     #[test]
     fn test_truncate_to_budget() {
         let pipeline = create_test_pipeline();
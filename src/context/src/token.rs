@@ -0,0 +1,271 @@
+//! Token counting abstraction
+//!
+//! Budgeting and truncation only need counts and token boundaries, so callers
+//! without a tokenizer file can fall back to [`CharApproxCounter`].
+//! [`tokenizer_repo`] names a published tokenizer for well-known models, which
+//! `download_tokenizer` (feature `hf-download`) fetches and caches.
+
+/// Counts tokens and maps them back to byte offsets in the source text.
+pub trait TokenCounter: Send + Sync {
+    /// Number of tokens in `text`
+    fn count(&self, text: &str) -> usize;
+
+    /// Token ids plus their `(start, end)` byte offsets into `text`.
+    ///
+    /// Offsets always fall on `char` boundaries. Special tokens that cover no
+    /// text (`[CLS]`, `[SEP]`, ...) should be left out; consumers also tolerate
+    /// them, so boundaries don't depend on where a tokenizer puts them.
+    fn encode_with_offsets(&self, text: &str) -> (Vec<u32>, Vec<(usize, usize)>);
+}
+
+/// ~4 bytes per token approximation; ids are placeholders (always 0)
+///
+/// A token closes at the first char boundary at least 4 bytes past its start,
+/// so `count` always equals the number of offsets from `encode_with_offsets`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CharApproxCounter;
+
+const APPROX_BYTES_PER_TOKEN: usize = 4;
+
+impl TokenCounter for CharApproxCounter {
+    fn count(&self, text: &str) -> usize {
+        // Pure ASCII splits evenly; skip the char walk.
+        if text.is_ascii() {
+            return text.len().div_ceil(APPROX_BYTES_PER_TOKEN);
+        }
+
+        let mut tokens = 0;
+        let mut start = 0;
+        for (i, c) in text.char_indices() {
+            let end = i + c.len_utf8();
+            if end - start >= APPROX_BYTES_PER_TOKEN {
+                tokens += 1;
+                start = end;
+            }
+        }
+        tokens + usize::from(start < text.len())
+    }
+
+    fn encode_with_offsets(&self, text: &str) -> (Vec<u32>, Vec<(usize, usize)>) {
+        let mut offsets = Vec::with_capacity(self.count(text));
+        let mut start = 0;
+        for (i, c) in text.char_indices() {
+            let end = i + c.len_utf8();
+            if end - start >= APPROX_BYTES_PER_TOKEN {
+                offsets.push((start, end));
+                start = end;
+            }
+        }
+        if start < text.len() {
+            offsets.push((start, text.len()));
+        }
+        (vec![0; offsets.len()], offsets)
+    }
+}
+
+/// Adapter for a Hugging Face `tokenizers::Tokenizer`
+#[cfg(feature = "hf-tokenizer")]
+pub struct HfTokenizer(pub tokenizers::Tokenizer);
+
+/// Vocabularies smaller than this are demo/test tokenizers.
+#[cfg(feature = "hf-tokenizer")]
+const MIN_VOCAB_SIZE: usize = 1000;
+
+/// Sample encoded at load time to detect tokenizers that map code to `[UNK]`.
+#[cfg(feature = "hf-tokenizer")]
+const UNK_PROBE: &str = "pub fn parse_config(path: &Path) -> Result<Config, Error> { Ok(x) }";
+
+#[cfg(feature = "hf-tokenizer")]
+impl HfTokenizer {
+    /// Wrap a tokenizer, warning when it looks degenerate.
+    #[must_use]
+    pub fn new(tokenizer: tokenizers::Tokenizer) -> Self {
+        let vocab_size = tokenizer.get_vocab_size(true);
+        let probe = tokenizer
+            .encode(UNK_PROBE, false)
+            .map(|enc| enc.get_tokens().to_vec())
+            .unwrap_or_default();
+        if is_degenerate(vocab_size, &probe) {
+            tracing::warn!(
+                vocab_size,
+                "using a degenerate tokenizer; token budgets are unreliable (load a real tokenizer.json)"
+            );
+        }
+        Self(tokenizer)
+    }
+
+    /// Load a `tokenizer.json`
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> error::Result<Self> {
+        let path = path.as_ref();
+        tokenizers::Tokenizer::from_file(path)
+            .map(Self::new)
+            .map_err(|e| {
+                error::LunaError::invalid_input(format!(
+                    "failed to load tokenizer {}: {e}",
+                    path.display()
+                ))
+            })
+    }
+}
+
+#[cfg(feature = "hf-tokenizer")]
+impl TokenCounter for HfTokenizer {
+    fn count(&self, text: &str) -> usize {
+        self.0
+            .encode(text, false)
+            .map(|enc| text_tokens(&enc).count())
+            .unwrap_or_else(|_| CharApproxCounter.count(text))
+    }
+
+    fn encode_with_offsets(&self, text: &str) -> (Vec<u32>, Vec<(usize, usize)>) {
+        match self.0.encode(text, false) {
+            Ok(enc) => text_tokens(&enc).unzip(),
+            Err(_) => CharApproxCounter.encode_with_offsets(text),
+        }
+    }
+}
+
+/// Ids and offsets of the tokens that cover text, skipping the ones the
+/// tokenizer itself marks special and that span nothing (post-processor
+/// `[CLS]`/`[SEP]` and the like).
+#[cfg(feature = "hf-tokenizer")]
+fn text_tokens(enc: &tokenizers::Encoding) -> impl Iterator<Item = (u32, (usize, usize))> + '_ {
+    enc.get_ids()
+        .iter()
+        .zip(enc.get_offsets())
+        .zip(enc.get_special_tokens_mask())
+        .filter(|((_, (start, end)), special)| **special == 0 || start < end)
+        .map(|((id, offsets), _)| (*id, *offsets))
+}
+
+/// Hugging Face repos publishing a `tokenizer.json` for a model family, keyed
+/// by model-name prefix; more specific prefixes come first.
+const KNOWN_TOKENIZERS: &[(&str, &str)] = &[
+    ("gpt-4o", "Xenova/gpt-4o"),
+    ("gpt-4", "Xenova/gpt-4"),
+    ("gpt-3.5", "Xenova/gpt-3.5-turbo"),
+    ("claude", "Xenova/claude-tokenizer"),
+    ("qwen", "Qwen/Qwen2.5-7B-Instruct"),
+    ("deepseek", "deepseek-ai/DeepSeek-V3"),
+];
+
+/// Hugging Face repo whose tokenizer matches `model` (e.g. `gpt-4o-mini`, or
+/// `openai/gpt-4o-mini` as routers name it), `None` for unknown models.
+#[must_use]
+pub fn tokenizer_repo(model: &str) -> Option<&'static str> {
+    let name = model.rsplit_once('/').map_or(model, |(_, name)| name);
+    let name = name.to_ascii_lowercase();
+    KNOWN_TOKENIZERS
+        .iter()
+        .find(|(prefix, _)| name.starts_with(prefix))
+        .map(|&(_, repo)| repo)
+}
+
+/// Load the tokenizer of the Hugging Face repo `repo_id`, downloading it on
+/// first use and keeping a copy in `cache_dir` for later runs. Failing to
+/// write the copy only logs a warning.
+#[cfg(feature = "hf-download")]
+pub fn download_tokenizer(
+    repo_id: &str,
+    cache_dir: &std::path::Path,
+) -> error::Result<HfTokenizer> {
+    let cached = cache_dir.join(format!("{}.json", repo_id.replace('/', "--")));
+    if cached.is_file() {
+        return HfTokenizer::from_file(&cached);
+    }
+
+    let tokenizer = tokenizers::Tokenizer::from_pretrained(repo_id, None).map_err(|e| {
+        error::LunaError::invalid_input(format!("failed to download tokenizer {repo_id}: {e}"))
+    })?;
+    let saved = std::fs::create_dir_all(cache_dir)
+        .map_err(|e| e.to_string())
+        .and_then(|()| tokenizer.save(&cached, false).map_err(|e| e.to_string()));
+    if let Err(e) = saved {
+        tracing::warn!("cannot cache tokenizer at {}: {e}", cached.display());
+    }
+    Ok(HfTokenizer::new(tokenizer))
+}
+
+/// Tiny vocabulary, probe failed to encode, or at least half of its tokens are unknown.
+#[cfg(feature = "hf-tokenizer")]
+fn is_degenerate(vocab_size: usize, probe_tokens: &[String]) -> bool {
+    if vocab_size < MIN_VOCAB_SIZE || probe_tokens.is_empty() {
+        return true;
+    }
+    let unknown = probe_tokens
+        .iter()
+        .filter(|t| matches!(t.as_str(), "[UNK]" | "<unk>" | "<|unk|>"))
+        .count();
+    unknown * 2 >= probe_tokens.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_char_approx_offsets_cover_text_on_char_boundaries() {
+        let text = "let café = 1;";
+        let (ids, offsets) = CharApproxCounter.encode_with_offsets(text);
+        assert_eq!(ids.len(), offsets.len());
+        assert_eq!(offsets.first().map(|o| o.0), Some(0));
+        assert_eq!(offsets.last().map(|o| o.1), Some(text.len()));
+        for (start, end) in offsets {
+            assert!(text.is_char_boundary(start) && text.is_char_boundary(end));
+        }
+
+        let (_, ascii) = CharApproxCounter.encode_with_offsets("abcdefghij");
+        assert_eq!(ascii, vec![(0, 4), (4, 8), (8, 10)]);
+        assert_eq!(CharApproxCounter.count("abcdefghij"), 3);
+    }
+
+    #[test]
+    fn test_char_approx_count_matches_offsets_for_multibyte_text() {
+        // 3-byte chars: each token spans two of them (6 bytes), not 4 bytes
+        let text = "日本語のテキスト";
+        let (_, offsets) = CharApproxCounter.encode_with_offsets(text);
+        assert_eq!(offsets.len(), 4);
+        assert_eq!(CharApproxCounter.count(text), offsets.len());
+    }
+
+    proptest! {
+        #[test]
+        fn prop_char_approx_offsets_tile_text(text in "(?s).{0,200}") {
+            let (ids, offsets) = CharApproxCounter.encode_with_offsets(&text);
+            prop_assert_eq!(ids.len(), offsets.len());
+            prop_assert_eq!(CharApproxCounter.count(&text), offsets.len());
+
+            // Contiguous, non-empty, in bounds, on char boundaries, covering everything.
+            let mut pos = 0;
+            for (start, end) in offsets {
+                prop_assert_eq!(start, pos);
+                prop_assert!(start < end && end <= text.len());
+                prop_assert!(text.is_char_boundary(end));
+                pos = end;
+            }
+            prop_assert_eq!(pos, text.len());
+        }
+    }
+
+    #[test]
+    fn test_tokenizer_repo_matches_model_families() {
+        assert_eq!(tokenizer_repo("gpt-4o-mini"), Some("Xenova/gpt-4o"));
+        assert_eq!(tokenizer_repo("openai/gpt-4-turbo"), Some("Xenova/gpt-4"));
+        assert_eq!(
+            tokenizer_repo("Qwen/Qwen2.5-72B-Instruct"),
+            Some("Qwen/Qwen2.5-7B-Instruct")
+        );
+        assert_eq!(tokenizer_repo("my-local-model"), None);
+    }
+
+    #[cfg(feature = "hf-tokenizer")]
+    #[test]
+    fn test_degenerate_tokenizer_detection() {
+        let tokens = |t: &[&str]| t.iter().map(|s| (*s).to_owned()).collect::<Vec<_>>();
+        assert!(is_degenerate(30, &tokens(&["fn", "x"])));
+        assert!(is_degenerate(30_000, &tokens(&["[UNK]", "[UNK]", "("])));
+        assert!(is_degenerate(30_000, &[]));
+        assert!(!is_degenerate(30_000, &tokens(&["pub", "fn", "[UNK]"])));
+    }
+}
@@ -1,7 +1,9 @@
 //! Luna Core - Core type definitions
 
+pub mod location;
 pub mod symbol;
 pub mod text_range;
 
+pub use location::*;
 pub use symbol::*;
 pub use text_range::*;
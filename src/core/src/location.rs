@@ -0,0 +1,106 @@
+//! `path:line:column` location strings
+
+use std::fmt;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// A file location as compilers and models print it: `src/foo.rs:42:10`.
+///
+/// Line and column are 1-based.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Location {
+    pub path: PathBuf,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+/// Parse `path`, `path:line` or `path:line:column`.
+///
+/// Only numeric suffixes are split off, so `C:\src\foo.rs:3` keeps its drive
+/// letter; a trailing `:` (as in grep output) is ignored. Returns `None` for
+/// an empty path or a zero line/column.
+pub fn parse_location(s: &str) -> Option<Location> {
+    let s = s.trim();
+    let s = s.strip_suffix(':').unwrap_or(s);
+
+    let mut nums = Vec::with_capacity(2);
+    let mut rest = s;
+    while nums.len() < 2 {
+        let Some((head, tail)) = rest.rsplit_once(':') else {
+            break;
+        };
+        let Ok(n) = tail.parse::<usize>() else {
+            break;
+        };
+        if n == 0 {
+            return None;
+        }
+        nums.push(n);
+        rest = head;
+    }
+    if rest.is_empty() {
+        return None;
+    }
+
+    let (line, column) = match nums.as_slice() {
+        [] => (None, None),
+        [line] => (Some(*line), None),
+        [column, line, ..] => (Some(*line), Some(*column)),
+    };
+    Some(Location {
+        path: PathBuf::from(rest),
+        line,
+        column,
+    })
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{line}")?;
+            if let Some(column) = self.column {
+                write!(f, ":{column}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_round_trips() {
+        for s in ["src/foo.rs", "src/foo.rs:42", "src/foo.rs:42:10"] {
+            let loc = parse_location(s).unwrap();
+            assert_eq!(loc.to_string(), s);
+            assert_eq!(parse_location(&loc.to_string()), Some(loc));
+        }
+    }
+
+    #[test]
+    fn windows_drive_letter_is_kept() {
+        let loc = parse_location(r"C:\x.rs:3").unwrap();
+        assert_eq!(loc.path, PathBuf::from(r"C:\x.rs"));
+        assert_eq!(loc.line, Some(3));
+        assert_eq!(loc.column, None);
+    }
+
+    #[test]
+    fn trailing_colon_is_ignored() {
+        let loc = parse_location("src/foo.rs:7:").unwrap();
+        assert_eq!(loc.path, PathBuf::from("src/foo.rs"));
+        assert_eq!(loc.line, Some(7));
+        assert_eq!(loc.column, None);
+    }
+
+    #[test]
+    fn zero_line_or_column_is_rejected() {
+        assert_eq!(parse_location("src/foo.rs:0"), None);
+        assert_eq!(parse_location("src/foo.rs:3:0"), None);
+        assert_eq!(parse_location(":3"), None);
+    }
+}
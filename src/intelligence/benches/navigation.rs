@@ -0,0 +1,200 @@
+//! Benchmarks for repository scanning and tree-sitter navigation.
+//!
+//! Run with `cargo bench -p intelligence`. Fixtures are generated into a temp
+//! directory, so results don't depend on the checkout being benchmarked.
+
+use std::fs;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use intelligence::{
+    chunk_files,
+    repo_scan::{FsRepoFileProvider, RepoFileProvider, RepoScanOptions},
+    ChunkGranularity, ChunkOptions, Navigator, TreeSitterFile, TreeSitterNavigator,
+};
+
+/// Rust source with `fns` small functions; each calls its predecessor and a
+/// shared `helper` so reference search has hits in every file.
+fn rust_source(fns: usize, file_idx: usize) -> String {
+    let mut src = String::from("use std::collections::HashMap;\n\n");
+    for i in 0..fns {
+        src.push_str(&format!(
+            "/// Function {i} of file {file_idx}.\npub fn f{file_idx}_{i}(x: usize) -> usize {{\n    let mut map = HashMap::new();\n    map.insert(x, {i});\n    helper(x) + {prev}\n}}\n\n",
+            prev = if i == 0 {
+                "0".to_owned()
+            } else {
+                format!("f{file_idx}_{}(x)", i - 1)
+            }
+        ));
+    }
+    src
+}
+
+/// Synthetic repository on disk, removed on drop.
+struct FixtureRepo {
+    root: PathBuf,
+}
+
+impl FixtureRepo {
+    fn generate(files: usize, fns_per_file: usize) -> Self {
+        let root = std::env::temp_dir().join(format!(
+            "luna-bench-{}-{files}x{fns_per_file}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&root);
+        for i in 0..files {
+            let dir = root.join(format!("src/mod{}", i % 10));
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(
+                dir.join(format!("file{i}.rs")),
+                rust_source(fns_per_file, i),
+            )
+            .unwrap();
+        }
+        fs::write(
+            root.join("src/lib.rs"),
+            "pub fn helper(x: usize) -> usize {\n    x * 2\n}\n",
+        )
+        .unwrap();
+        Self { root }
+    }
+
+    fn path(&self) -> &Path {
+        &self.root
+    }
+}
+
+impl Drop for FixtureRepo {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+fn bench_scope_graph(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scope_graph");
+    for fns in [10, 100, 1000] {
+        let src = rust_source(fns, 0);
+        group.bench_with_input(BenchmarkId::from_parameter(fns), &src, |b, src| {
+            b.iter(|| {
+                TreeSitterFile::try_build(black_box(src.as_bytes()), "rust")
+                    .unwrap()
+                    .scope_graph()
+                    .unwrap()
+            });
+        });
+    }
+    group.finish();
+}
+
+fn bench_repo_scan(c: &mut Criterion) {
+    let repo = FixtureRepo::generate(200, 20);
+    let opt = RepoScanOptions::default();
+
+    let mut group = c.benchmark_group("repo_scan");
+    group.bench_function("list_files", |b| {
+        b.iter(|| {
+            FsRepoFileProvider
+                .list_files(repo.path(), &opt)
+                .unwrap()
+                .len()
+        });
+    });
+    group.bench_function("for_each_file_containing", |b| {
+        b.iter(|| {
+            let mut n = 0;
+            FsRepoFileProvider
+                .for_each_file_containing(repo.path(), &opt, "f7_3", &mut |_| {
+                    n += 1;
+                    ControlFlow::Continue(())
+                })
+                .unwrap();
+            n
+        });
+    });
+    group.finish();
+}
+
+fn bench_references(c: &mut Criterion) {
+    let repo = FixtureRepo::generate(100, 20);
+    let nav = TreeSitterNavigator::default();
+
+    let mut group = c.benchmark_group("find_references");
+    group.sample_size(20);
+    // Common name: every file has hits.
+    group.bench_function("common", |b| {
+        b.iter(|| {
+            nav.find_references(repo.path(), "helper", 10_000)
+                .unwrap()
+                .len()
+        });
+    });
+    // Rare name: the content pre-filter skips most files.
+    group.bench_function("rare", |b| {
+        b.iter(|| {
+            nav.find_references(repo.path(), "f7_3", 10_000)
+                .unwrap()
+                .len()
+        });
+    });
+    group.finish();
+}
+
+fn bench_chunk_files(c: &mut Criterion) {
+    let repo = FixtureRepo::generate(200, 20);
+    let files = FsRepoFileProvider
+        .list_files(repo.path(), &RepoScanOptions::default())
+        .unwrap();
+    let sequential = ChunkOptions::default().with_parallel_min_bytes(usize::MAX);
+    let parallel = ChunkOptions::default().with_parallel_min_bytes(0);
+
+    let mut group = c.benchmark_group("chunk_files");
+    group.sample_size(20);
+    group.bench_function("sequential", |b| {
+        b.iter(|| chunk_files(black_box(&files), &sequential).len());
+    });
+    group.bench_function("parallel", |b| {
+        b.iter(|| chunk_files(black_box(&files), &parallel).len());
+    });
+    group.finish();
+}
+
+/// One large file of big `impl` blocks, each split per method.
+fn bench_chunk_large_file(c: &mut Criterion) {
+    let mut src = String::new();
+    for i in 0..200 {
+        src.push_str(&format!("pub struct S{i};\n\nimpl S{i} {{\n"));
+        for j in 0..20 {
+            src.push_str(&format!(
+                "    pub fn m{j}(&self, x: usize) -> usize {{\n        x + {j}\n    }}\n\n"
+            ));
+        }
+        src.push_str("}\n\n");
+    }
+    let ts = TreeSitterFile::try_build(src.as_bytes(), "rust").unwrap();
+    let opt = ChunkOptions::default()
+        .with_granularity(ChunkGranularity::Members)
+        .with_max_lines(40);
+    let sequential = opt.with_parallel_min_bytes(usize::MAX);
+    let parallel = opt.with_parallel_min_bytes(0);
+
+    let mut group = c.benchmark_group("chunk_large_file");
+    group.sample_size(20);
+    group.bench_function("sequential", |b| {
+        b.iter(|| black_box(&ts).chunks(&sequential).unwrap().len());
+    });
+    group.bench_function("parallel", |b| {
+        b.iter(|| black_box(&ts).chunks(&parallel).unwrap().len());
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_scope_graph,
+    bench_repo_scan,
+    bench_references,
+    bench_chunk_files,
+    bench_chunk_large_file
+);
+criterion_main!(benches);
@@ -0,0 +1,395 @@
+//! Scope-aligned chunks for retrieval indexing.
+//!
+//! Chunk boundaries follow the declarations found by the scope graph, the same
+//! way [`crate::skeleton`] does. By default every top-level declaration is one
+//! chunk; with [`ChunkGranularity::Members`], containers (impl/class/trait
+//! bodies) longer than `max_lines` are split into one chunk per method, with
+//! runs of non-callable members (fields, constants) grouped together.
+//!
+//! [`chunk_files`] chunks many files at once, spreading them over the rayon
+//! pool once their total size makes that worthwhile. A single file past the
+//! same threshold splits its containers over the pool too.
+
+use std::ops::Range;
+use std::path::PathBuf;
+
+use rayon::prelude::*;
+use tree_sitter::{Node, Tree};
+
+use crate::{repo_scan::RepoFile, skeleton, ScopeGraph, TreeSitterFile};
+
+/// How far into containers chunking descends.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum ChunkGranularity {
+    /// One chunk per top-level declaration
+    #[default]
+    TopLevel,
+    /// Split large containers into per-member chunks
+    Members,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ChunkOptions {
+    pub granularity: ChunkGranularity,
+    /// Under [`ChunkGranularity::Members`], containers spanning more lines
+    /// than this are split
+    pub max_lines: usize,
+    /// [`chunk_files`] runs in parallel once the files add up to this many
+    /// bytes, and a single file this large splits its containers in
+    /// parallel; below it thread hand-off costs more than it saves
+    pub parallel_min_bytes: usize,
+}
+
+impl Default for ChunkOptions {
+    fn default() -> Self {
+        Self {
+            granularity: ChunkGranularity::TopLevel,
+            max_lines: 120,
+            parallel_min_bytes: 256 * 1024,
+        }
+    }
+}
+
+impl ChunkOptions {
+    /// JSON Schema of the chunking options.
+    #[cfg(feature = "schema")]
+    #[must_use]
+    pub fn schema() -> schemars::schema::RootSchema {
+        schemars::schema_for!(Self)
+    }
+
+    #[must_use]
+    pub fn with_granularity(mut self, granularity: ChunkGranularity) -> Self {
+        self.granularity = granularity;
+        self
+    }
+
+    #[must_use]
+    pub fn with_max_lines(mut self, max_lines: usize) -> Self {
+        self.max_lines = max_lines;
+        self
+    }
+
+    /// `usize::MAX` keeps chunking on the calling thread.
+    #[must_use]
+    pub fn with_parallel_min_bytes(mut self, bytes: usize) -> Self {
+        self.parallel_min_bytes = bytes;
+        self
+    }
+}
+
+/// A contiguous slice of a source file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeChunk {
+    pub byte_range: Range<usize>,
+    /// 0-based, inclusive
+    pub start_line: usize,
+    /// 0-based, inclusive
+    pub end_line: usize,
+    /// Header line of the enclosing container for member chunks, e.g. `impl Foo`
+    pub container: Option<String>,
+}
+
+impl CodeChunk {
+    fn spanning(first: Node<'_>, last: Node<'_>, container: Option<&str>) -> Self {
+        Self {
+            byte_range: first.start_byte()..last.end_byte(),
+            start_line: first.start_position().row,
+            end_line: last.end_position().row,
+            container: container.map(ToOwned::to_owned),
+        }
+    }
+}
+
+/// Chunks of one file, from [`chunk_files`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChunks {
+    pub rel_path: PathBuf,
+    pub chunks: Vec<CodeChunk>,
+}
+
+/// Chunk every file whose language is supported, in input order. Files that
+/// fail to parse are left out.
+///
+/// Each file is parsed and chunked independently, so once the input reaches
+/// [`ChunkOptions::parallel_min_bytes`] files are spread over the rayon pool.
+pub fn chunk_files(files: &[RepoFile], opt: &ChunkOptions) -> Vec<FileChunks> {
+    let chunk_one = |file: &RepoFile| {
+        let lang_id = crate::detect_lang_id(&file.rel_path)?;
+        let chunks = TreeSitterFile::try_build(file.content.as_bytes(), lang_id)
+            .ok()?
+            .chunks(opt)
+            .ok()?;
+        Some(FileChunks {
+            rel_path: file.rel_path.clone(),
+            chunks,
+        })
+    };
+
+    let total: usize = files.iter().map(|f| f.content.len()).sum();
+    if total < opt.parallel_min_bytes {
+        files.iter().filter_map(chunk_one).collect()
+    } else {
+        // `collect` on an indexed parallel iterator keeps input order.
+        files.par_iter().filter_map(chunk_one).collect()
+    }
+}
+
+/// Context for a 0-based `line` that falls outside every chunk (imports,
+/// module-level statements): the run of non-blank lines around it, at most
+/// 10 either way and never reaching into a chunk, plus the first line of the
+/// closest chunk above, if any. `None` when a chunk contains the line.
+///
+/// Returns `(start, end, header)`, all 0-based and inclusive.
+#[must_use]
+pub fn between_chunks(
+    lines: &[&str],
+    chunks: &[CodeChunk],
+    line: usize,
+) -> Option<(usize, usize, Option<usize>)> {
+    if line >= lines.len() || chunks.iter().any(|c| c.start_line <= line && line <= c.end_line) {
+        return None;
+    }
+    let prev = chunks
+        .iter()
+        .filter(|c| c.end_line < line)
+        .max_by_key(|c| c.end_line);
+    let lo = prev.map_or(0, |c| c.end_line + 1);
+    let hi = chunks
+        .iter()
+        .filter(|c| c.start_line > line)
+        .map(|c| c.start_line - 1)
+        .min()
+        .unwrap_or(lines.len() - 1);
+    let blank = |i: usize| lines[i].trim().is_empty();
+
+    let mut start = line;
+    while start > lo && line - start < 10 && !blank(start - 1) {
+        start -= 1;
+    }
+    let mut end = line;
+    while end < hi && end - line < 10 && !blank(end + 1) {
+        end += 1;
+    }
+    Some((start, end, prev.map(|c| c.start_line)))
+}
+
+pub(crate) fn collect(
+    src: &[u8],
+    tree: &Tree,
+    graph: &ScopeGraph,
+    opt: &ChunkOptions,
+) -> Vec<CodeChunk> {
+    let defs = skeleton::def_starts(graph);
+    let root = tree.root_node();
+
+    let mut cursor = root.walk();
+    let owners: Vec<usize> = root
+        .named_children(&mut cursor)
+        .enumerate()
+        .filter(|(_, node)| skeleton::owns_def(*node, &defs))
+        .map(|(i, _)| i)
+        .collect();
+
+    // Chunks of the top-level declarations at the given child indices (ascending).
+    let chunk_run = |indices: &[usize]| {
+        let mut out = Vec::new();
+        let root = tree.root_node();
+        let mut cursor = root.walk();
+        let mut children = root.named_children(&mut cursor).enumerate();
+        for &i in indices {
+            let Some((_, node)) = children.find(|(at, _)| *at == i) else {
+                break;
+            };
+            let before = out.len();
+            if let Some((header, body)) = splittable(src, node, opt) {
+                push_members(body, &defs, &header, &mut out);
+            }
+            if out.len() == before {
+                out.push(CodeChunk::spanning(node, node, None));
+            }
+        }
+        out
+    };
+
+    // Only splitting containers does work per declaration; below the
+    // threshold it isn't worth the thread hand-off.
+    if opt.granularity != ChunkGranularity::Members || src.len() < opt.parallel_min_bytes {
+        return chunk_run(&owners);
+    }
+    // Nodes aren't `Send`: each worker walks the tree to its own run of
+    // declarations, and the runs are put back in source order.
+    let run_len = owners.len().div_ceil(rayon::current_num_threads()).max(1);
+    let mut out: Vec<CodeChunk> = owners.par_chunks(run_len).flat_map_iter(chunk_run).collect();
+    out.sort_by_key(|c| c.byte_range.start);
+    out
+}
+
+/// Container header and body, when `node` should be chunked per member.
+fn splittable<'t>(src: &[u8], node: Node<'t>, opt: &ChunkOptions) -> Option<(String, Node<'t>)> {
+    if opt.granularity != ChunkGranularity::Members {
+        return None;
+    }
+    let lines = node.end_position().row - node.start_position().row + 1;
+    if lines <= opt.max_lines {
+        return None;
+    }
+
+    // Python-style decorators wrap the real definition.
+    let decl = node.child_by_field_name("definition").unwrap_or(node);
+    if skeleton::is_callable(decl.kind()) {
+        return None;
+    }
+    let body = decl.child_by_field_name("body")?;
+    let header = skeleton::node_text(src, decl.start_byte(), body.start_byte());
+    let header = header
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim_end()
+        .trim_end_matches('{')
+        .trim_end()
+        .to_owned();
+    Some((header, body))
+}
+
+fn push_members(body: Node<'_>, defs: &[usize], header: &str, out: &mut Vec<CodeChunk>) {
+    let mut group: Option<(Node<'_>, Node<'_>)> = None;
+
+    let mut cursor = body.walk();
+    for member in body.named_children(&mut cursor) {
+        if !skeleton::owns_def(member, defs) {
+            continue;
+        }
+        let kind = member
+            .child_by_field_name("definition")
+            .unwrap_or(member)
+            .kind();
+        if skeleton::is_callable(kind) {
+            if let Some((first, last)) = group.take() {
+                out.push(CodeChunk::spanning(first, last, Some(header)));
+            }
+            out.push(CodeChunk::spanning(member, member, Some(header)));
+        } else {
+            group = Some(match group {
+                Some((first, _)) => (first, member),
+                None => (member, member),
+            });
+        }
+    }
+    if let Some((first, last)) = group {
+        out.push(CodeChunk::spanning(first, last, Some(header)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TreeSitterFile;
+
+    const SRC: &str = r#"pub struct Foo {
+    pub a: i32,
+}
+
+impl Foo {
+    pub fn new(a: i32) -> Self {
+        Self { a }
+    }
+
+    fn get(&self) -> i32 {
+        self.a
+    }
+}
+"#;
+
+    fn chunk_texts(opt: ChunkOptions) -> Vec<(String, Option<String>)> {
+        let ts = TreeSitterFile::try_build(SRC.as_bytes(), "rust").unwrap();
+        ts.chunks(&opt)
+            .unwrap()
+            .into_iter()
+            .map(|c| (SRC[c.byte_range].to_owned(), c.container))
+            .collect()
+    }
+
+    #[test]
+    fn top_level_keeps_containers_whole() {
+        let chunks = chunk_texts(ChunkOptions::default().with_max_lines(3));
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[0].0.starts_with("pub struct Foo"));
+        assert!(chunks[1].0.starts_with("impl Foo {") && chunks[1].0.ends_with('}'));
+    }
+
+    #[test]
+    fn members_split_large_containers_per_method() {
+        let opt = ChunkOptions::default()
+            .with_granularity(ChunkGranularity::Members)
+            .with_max_lines(3);
+        let chunks = chunk_texts(opt);
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks[0].0.starts_with("pub struct Foo") && chunks[0].1.is_none());
+        assert_eq!(
+            chunks[1],
+            (
+                "pub fn new(a: i32) -> Self {\n        Self { a }\n    }".to_owned(),
+                Some("impl Foo".to_owned())
+            )
+        );
+        assert!(chunks[2].0.starts_with("fn get(&self)"));
+
+        // Small containers stay whole.
+        let chunks = chunk_texts(opt.with_max_lines(100));
+        assert_eq!(chunks.len(), 2);
+    }
+
+    #[test]
+    fn chunk_files_is_ordered_and_matches_sequential() {
+        let files = (0..8)
+            .map(|i| RepoFile {
+                rel_path: PathBuf::from(if i == 3 {
+                    "notes.txt".to_owned()
+                } else {
+                    format!("src/f{i}.rs")
+                }),
+                abs_path: PathBuf::from(format!("/repo/src/f{i}.rs")),
+                content: SRC.replace("Foo", &format!("Foo{i}")),
+                encoding: None,
+            })
+            .collect::<Vec<_>>();
+
+        let sequential = chunk_files(&files, &ChunkOptions::default());
+        let parallel = chunk_files(&files, &ChunkOptions::default().with_parallel_min_bytes(0));
+        assert_eq!(sequential, parallel);
+        // The unsupported file is skipped; the rest keep their order.
+        assert_eq!(
+            parallel
+                .iter()
+                .map(|f| f.rel_path.clone())
+                .collect::<Vec<_>>(),
+            [0, 1, 2, 4, 5, 6, 7]
+                .map(|i| PathBuf::from(format!("src/f{i}.rs")))
+                .to_vec()
+        );
+        assert!(parallel.iter().all(|f| f.chunks.len() == 2));
+    }
+
+    #[test]
+    fn large_file_splits_containers_in_parallel_in_source_order() {
+        let src: String = (0..40)
+            .map(|i| SRC.replace("Foo", &format!("Foo{i}")))
+            .collect();
+        let ts = TreeSitterFile::try_build(src.as_bytes(), "rust").unwrap();
+        let opt = ChunkOptions::default()
+            .with_granularity(ChunkGranularity::Members)
+            .with_max_lines(3);
+
+        let sequential = ts.chunks(&opt.with_parallel_min_bytes(usize::MAX)).unwrap();
+        let parallel = ts.chunks(&opt.with_parallel_min_bytes(0)).unwrap();
+        assert_eq!(sequential.len(), 40 * 3);
+        assert_eq!(sequential, parallel);
+        assert!(parallel
+            .windows(2)
+            .all(|w| w[0].byte_range.start < w[1].byte_range.start));
+    }
+}
@@ -2,6 +2,9 @@
 ///
 /// This type is intentionally minimal to keep `intelligence` pure and reusable.
 /// Future phases can add caching/incremental update via a `DocumentStore`.
+///
+/// Returns the byte index of each `\n`. On CRLF lines the `\r` before it is
+/// not part of the line's content; see [`line_content_end`].
 pub fn build_line_end_indices(content: &str) -> Vec<usize> {
     content
         .as_bytes()
@@ -10,3 +13,137 @@ pub fn build_line_end_indices(content: &str) -> Vec<usize> {
         .filter_map(|(i, &b)| (b == b'\n').then_some(i))
         .collect()
 }
+
+/// End of the content of the line terminated by the `\n` at `newline`,
+/// excluding a preceding `\r`.
+pub fn line_content_end(content: &str, newline: usize) -> usize {
+    if newline > 0 && content.as_bytes().get(newline - 1) == Some(&b'\r') {
+        newline - 1
+    } else {
+        newline
+    }
+}
+
+/// Line terminator of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    /// The terminator used by most lines of `content`; `Lf` on a tie or
+    /// when there are no line breaks.
+    #[must_use]
+    pub fn detect(content: &str) -> Self {
+        let crlf = content.matches("\r\n").count();
+        let lf = content.matches('\n').count() - crlf;
+        if crlf > lf {
+            Self::CrLf
+        } else {
+            Self::Lf
+        }
+    }
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::CrLf => "\r\n",
+        }
+    }
+}
+
+/// Editor (LSP) position: 0-based line and UTF-16 code-unit column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Utf16Position {
+    pub line: usize,
+    pub character: usize,
+}
+
+/// UTF-16 column of byte column `byte_col` in `line`.
+///
+/// A `byte_col` inside a char or past the end is clamped to the preceding char boundary.
+pub fn utf16_column(line: &str, byte_col: usize) -> usize {
+    let mut end = byte_col.min(line.len());
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    line[..end].chars().map(char::len_utf16).sum()
+}
+
+/// Byte column of UTF-16 column `utf16_col` in `line`.
+///
+/// A column inside a surrogate pair maps to the start of that char; past the end maps to the end.
+pub fn byte_column_from_utf16(line: &str, utf16_col: usize) -> usize {
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        units += c.len_utf16();
+        if units > utf16_col {
+            return i;
+        }
+    }
+    line.len()
+}
+
+/// Editor position of a byte-based [`Position`](core::text_range::Position) in `content`.
+pub fn utf16_position(content: &str, pos: &core::text_range::Position) -> Utf16Position {
+    let byte = pos.byte.min(content.len());
+    let line_start = content[..byte].rfind('\n').map_or(0, |i| i + 1);
+    Utf16Position {
+        line: pos.line,
+        character: utf16_column(&content[line_start..], byte - line_start),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_ending_detection_and_content_ends() {
+        assert_eq!(LineEnding::detect("a\r\nb\r\nc\n"), LineEnding::CrLf);
+        assert_eq!(LineEnding::detect("a\r\nb\nc\n"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("no breaks"), LineEnding::Lf);
+
+        let content = "ab\r\ncd\n";
+        let ends = build_line_end_indices(content);
+        assert_eq!(ends, [3, 6]);
+        assert_eq!(line_content_end(content, ends[0]), 2);
+        assert_eq!(line_content_end(content, ends[1]), 6);
+    }
+
+    #[test]
+    fn utf16_columns_count_surrogate_pairs_and_cjk() {
+        // '😀' is 4 bytes / 2 UTF-16 units, '名' is 3 bytes / 1 unit.
+        let line = "let s = \"😀\"; let 名前 = x;";
+        let x = line.find('x').unwrap();
+        assert_eq!(x, 29);
+        assert_eq!(utf16_column(line, x), 23);
+        assert_eq!(byte_column_from_utf16(line, 23), x);
+
+        let name = line.find('名').unwrap();
+        assert_eq!(utf16_column(line, name), 18);
+        assert_eq!(byte_column_from_utf16(line, 18), name);
+
+        // Inside the emoji / surrogate pair: clamp to its start.
+        assert_eq!(utf16_column(line, 10), 9);
+        assert_eq!(byte_column_from_utf16(line, 10), 9);
+        assert_eq!(byte_column_from_utf16(line, 1000), line.len());
+    }
+
+    #[test]
+    fn utf16_position_uses_the_containing_line() {
+        let content = "fn a() {}\nlet 😀 = b;\n";
+        let byte = content.find('b').unwrap();
+        let pos = core::text_range::Position::new(byte, 1, byte - 10);
+        assert_eq!(
+            utf16_position(content, &pos),
+            Utf16Position {
+                line: 1,
+                character: 9
+            }
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! Fuzzy file finder: locate files by a partial path, fzf-style.
+//!
+//! The query matches when its characters appear in order (case-insensitively)
+//! in the repo-relative path. Matches inside the file name, runs of
+//! consecutive characters and matches at word starts (`/`, `_`, `-`, `.`,
+//! camelCase humps) score higher. Files come from the same scan as search,
+//! so ignore rules and size caps apply, but contents are never read.
+
+use std::{
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    navigation::NavigationError,
+    repo_scan::{FsRepoFileProvider, RepoScanOptions},
+};
+
+/// Files whose relative path fuzzy-matches `query`, best first, with scores
+/// in `(0, 1]`. Ties go to the shorter path. An empty query matches nothing.
+pub fn find_files(
+    repo_root: &Path,
+    query: &str,
+    opt: &RepoScanOptions,
+) -> Result<Vec<(PathBuf, f32)>, NavigationError> {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut hits = Vec::new();
+    FsRepoFileProvider.for_each_path(repo_root, opt, &mut |rel_path| {
+        let path = rel_path.to_string_lossy().replace('\\', "/");
+        if let Some(score) = fuzzy_score(&query, &path) {
+            hits.push((rel_path.to_path_buf(), score));
+        }
+        ControlFlow::Continue(())
+    })?;
+
+    hits.sort_by(|(a_path, a), (b_path, b)| {
+        b.partial_cmp(a)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a_path.as_os_str().len().cmp(&b_path.as_os_str().len()))
+            .then_with(|| a_path.cmp(b_path))
+    });
+    Ok(hits)
+}
+
+/// Score of the lowercased `query` against a `/`-separated path; `None` if
+/// the query isn't a subsequence of the path.
+fn fuzzy_score(query: &[char], path: &str) -> Option<f32> {
+    let full = subsequence_score(query, path)?;
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let in_name = subsequence_score(query, name).unwrap_or(0.0);
+    // A match within the file name outweighs one spread over directories.
+    Some((full + 2.0 * in_name) / 3.0)
+}
+
+/// Greedy left-to-right subsequence match, normalized to `(0, 1]`.
+///
+/// Each matched char scores 1, plus 1 when it directly follows the previous
+/// match or 0.75 when it starts a word.
+fn subsequence_score(query: &[char], text: &str) -> Option<f32> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut score = 0.0;
+    let mut next = 0;
+    let mut prev: Option<usize> = None;
+
+    for &q in query {
+        let i = (next..chars.len()).find(|&i| chars[i].to_lowercase().eq(std::iter::once(q)))?;
+        score += 1.0;
+        if prev.is_some_and(|p| p + 1 == i) {
+            score += 1.0;
+        } else if is_word_start(&chars, i) {
+            score += 0.75;
+        }
+        prev = Some(i);
+        next = i + 1;
+    }
+    Some(score / (2.0 * query.len() as f32))
+}
+
+fn is_word_start(chars: &[char], i: usize) -> bool {
+    let Some(&before) = i.checked_sub(1).and_then(|p| chars.get(p)) else {
+        return true;
+    };
+    matches!(before, '/' | '_' | '-' | '.' | ' ')
+        || (before.is_lowercase() && chars[i].is_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unique_tmp_dir;
+    use std::fs;
+
+    fn score(query: &str, path: &str) -> Option<f32> {
+        let query: Vec<char> = query.chars().collect();
+        fuzzy_score(&query, path)
+    }
+
+    #[test]
+    fn scoring_prefers_file_names_and_consecutive_matches() {
+        assert!(score("xyz", "src/main.rs").is_none());
+        assert!(score("rmn", "src/main.rs").is_some());
+
+        // Same chars, but only one path has them in its file name.
+        assert!(score("main", "src/main.rs") > score("main", "main/src/lib.rs"));
+        // Consecutive beats scattered.
+        assert!(score("lib", "src/lib.rs") > score("lib", "src/l_i_b.rs"));
+        // Word starts beat mid-word matches.
+        assert!(score("rs", "repo_scan.rs") > score("rs", "parse.rs"));
+    }
+
+    #[test]
+    fn find_files_ranks_matches_and_respects_ignore_rules() {
+        let root = unique_tmp_dir("luna-find-files");
+        for dir in ["src/router", "target", ".hidden"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in [
+            "src/router.rs",
+            "src/router/mod.rs",
+            "src/render.rs",
+            "target/router.rs",
+            ".hidden/router.rs",
+        ] {
+            fs::write(root.join(file), "fn f() {}\n").unwrap();
+        }
+
+        let hits = find_files(&root, "router", &RepoScanOptions::default()).unwrap();
+        let paths: Vec<_> = hits.iter().map(|(p, _)| p.clone()).collect();
+        assert_eq!(
+            paths,
+            [
+                PathBuf::from("src/router.rs"),
+                PathBuf::from("src/router/mod.rs")
+            ]
+        );
+        assert!(hits[0].1 > hits[1].1 && hits[1].1 > 0.0);
+        assert!(find_files(&root, "  ", &RepoScanOptions::default())
+            .unwrap()
+            .is_empty());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -34,6 +34,27 @@ pub static ALL_LANGUAGES: &[&TSLanguageConfig] = &[
     &php::PHP,
 ];
 
+/// A language the build can parse, as listed by [`supported_languages`].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct LanguageInfo {
+    /// Primary language identifier, e.g. `Rust`; see [`TSLanguage::from_id`].
+    pub id: &'static str,
+    /// File extensions without the dot, e.g. `rs`.
+    pub extensions: &'static [&'static str],
+}
+
+/// Every language in [`ALL_LANGUAGES`]; files with other extensions are
+/// skipped by navigation.
+pub fn supported_languages() -> Vec<LanguageInfo> {
+    ALL_LANGUAGES
+        .iter()
+        .map(|lang| LanguageInfo {
+            id: lang.language_ids[0],
+            extensions: lang.file_extensions,
+        })
+        .collect()
+}
+
 /// A generic language wrapper type.
 ///
 /// The backing grammars/parser are supplied through the `Config` type.
@@ -127,6 +148,17 @@ mod tests {
 
     use tree_sitter::Query;
 
+    #[test]
+    fn supported_languages_lists_every_config() {
+        let langs = supported_languages();
+        assert_eq!(langs.len(), ALL_LANGUAGES.len());
+        let rust = langs.iter().find(|l| l.id == "Rust").unwrap();
+        assert_eq!(rust.extensions, &["rs"]);
+        assert!(langs
+            .iter()
+            .all(|l| matches!(TSLanguage::from_id(l.id), Language::Supported(_))));
+    }
+
     // ensure that the symbols in all queries files are supported symbols
     #[test]
     fn verify_all_symbol_kinds() {
@@ -1,19 +1,33 @@
+pub mod chunk;
 pub mod document;
+pub mod file_finder;
 pub mod language;
 pub mod namespace;
 pub mod navigation;
 pub mod repo_scan;
 pub mod scope_resolution;
+pub mod skeleton;
 pub mod snippet;
+pub mod stats;
+pub mod symbol_diff;
 
 pub use {
-    language::{Language, MemoizedQuery, TSLanguage, TSLanguageConfig, ALL_LANGUAGES},
+    chunk::{between_chunks, chunk_files, ChunkGranularity, ChunkOptions, CodeChunk, FileChunks},
+    document::Utf16Position,
+    file_finder::find_files,
+    language::{
+        supported_languages, Language, LanguageInfo, MemoizedQuery, TSLanguage, TSLanguageConfig,
+        ALL_LANGUAGES,
+    },
     namespace::*,
     navigation::{
-        NavigationError, Navigator, SearchResult, SnippetOptions, SymbolContext, SymbolLocation,
-        TreeSitterNavigator,
+        detect_lang_id, NavigationError, Navigator, Page, RelationKind, SearchResult,
+        SnippetOptions, SymbolContext, SymbolLocation, SymbolVisibility, TreeSitterNavigator,
     },
     scope_resolution::{NodeKind, ScopeGraph},
+    skeleton::file_skeleton,
+    stats::{repo_stats, RepoStats},
+    symbol_diff::{diff_symbols, SymbolChange, SymbolChangeKind},
 };
 
 use scope_resolution::ResolutionMethod;
@@ -107,8 +121,226 @@ impl<'a> TreeSitterFile<'a> {
             .collect::<Vec<_>>())
     }
 
+    /// Visibility of the declaration enclosing `range` (usually a definition's name).
+    ///
+    /// Walks up to the nearest declaration node and inspects its
+    /// `visibility_modifier` child, so attributes and multi-line headers don't
+    /// matter. Returns `None` for grammars without such a node unless the
+    /// language has an implicit default (Rust: private).
+    pub fn visibility_at(&self, range: core::text_range::TextRange) -> Option<SymbolVisibility> {
+        let root = self.tree.root_node();
+        let mut node = root.descendant_for_byte_range(range.start.byte, range.end.byte)?;
+
+        loop {
+            let mut cursor = node.walk();
+            let modifier = node
+                .children(&mut cursor)
+                .find(|c| c.kind() == "visibility_modifier");
+            if let Some(m) = modifier {
+                let text = m.utf8_text(self.src).ok()?;
+                return Some(SymbolVisibility::from_modifier(text));
+            }
+
+            let kind = node.kind();
+            if kind.ends_with("_item")
+                || kind.ends_with("_declaration")
+                || kind.ends_with("_definition")
+                || kind.ends_with("_signature")
+            {
+                break;
+            }
+            node = node.parent()?;
+        }
+
+        self.language
+            .language_ids
+            .iter()
+            .any(|id| id.eq_ignore_ascii_case("rust"))
+            .then_some(SymbolVisibility::Private)
+    }
+
+    /// Scope-graph kind (`function`, `struct`, ...) of the definition whose
+    /// name starts at `line`/`column` (0-based, column in bytes).
+    pub fn def_kind_at(&self, line: usize, column: usize) -> Option<&'static str> {
+        let graph = self.build_scope_graph().ok()?;
+        graph.graph.node_weights().find_map(|n| match n {
+            NodeKind::Def(d) if d.range.start.line == line && d.range.start.column == column => {
+                d.symbol_id.map(|id| id.name(self.language.namespaces))
+            }
+            _ => None,
+        })
+    }
+
+    /// Names of the functions called from the callable enclosing `range`
+    /// (usually a definition's name), in source order and deduplicated.
+    ///
+    /// Only the last path segment is kept (`self.parse` and `Parser::parse`
+    /// both yield `parse`); empty when `range` isn't inside a function.
+    pub fn callees_at(&self, range: core::text_range::TextRange) -> Vec<String> {
+        let root = self.tree.root_node();
+        let Some(mut node) = root.descendant_for_byte_range(range.start.byte, range.end.byte)
+        else {
+            return Vec::new();
+        };
+        while !skeleton::is_callable(node.kind()) {
+            match node.parent() {
+                Some(parent) => node = parent,
+                None => return Vec::new(),
+            }
+        }
+
+        let mut out: Vec<String> = Vec::new();
+        let mut stack = vec![node];
+        while let Some(n) = stack.pop() {
+            if matches!(
+                n.kind(),
+                "call_expression" | "call" | "method_invocation" | "method_call_expression"
+            ) {
+                let callee = ["function", "method", "name"]
+                    .iter()
+                    .find_map(|field| n.child_by_field_name(field))
+                    .and_then(|c| c.utf8_text(self.src).ok())
+                    .and_then(last_path_segment);
+                if let Some(name) = callee {
+                    if !out.contains(&name) {
+                        out.push(name);
+                    }
+                }
+            }
+            let mut cursor = n.walk();
+            let children: Vec<_> = n.named_children(&mut cursor).collect();
+            stack.extend(children.into_iter().rev());
+        }
+        out
+    }
+
+    /// Name at `range` qualified by up to `max_depth` enclosing named scopes
+    /// (modules, types, impls, classes), e.g. `parser::Parser::new`.
+    ///
+    /// Uses `::` for Rust and C++, `.` otherwise; generic arguments are dropped.
+    pub fn qualified_name_at(
+        &self,
+        range: core::text_range::TextRange,
+        max_depth: usize,
+    ) -> Option<String> {
+        let root = self.tree.root_node();
+        let mut node = root.descendant_for_byte_range(range.start.byte, range.end.byte)?;
+        let mut parts = vec![node.utf8_text(self.src).ok()?.to_owned()];
+
+        while parts.len() <= max_depth {
+            let Some(parent) = node.parent() else {
+                break;
+            };
+            node = parent;
+            let owner = match node.kind() {
+                "impl_item" => node.child_by_field_name("type"),
+                _ => node.child_by_field_name("name"),
+            };
+            // Skip the definition's own node (its `name` is `range` itself).
+            let Some(owner) = owner.filter(|o| o.start_byte() != range.start.byte) else {
+                continue;
+            };
+            if let Some(segment) = owner.utf8_text(self.src).ok().and_then(last_path_segment) {
+                parts.push(segment);
+            }
+        }
+
+        let separator = if self
+            .language
+            .language_ids
+            .iter()
+            .any(|id| id.eq_ignore_ascii_case("rust") || id.eq_ignore_ascii_case("c++"))
+        {
+            "::"
+        } else {
+            "."
+        };
+        parts.reverse();
+        Some(parts.join(separator))
+    }
+
+    /// Byte range of the whole declaration named at `range`: signature and
+    /// body, plus decorators and the comments/attributes directly above it.
+    ///
+    /// `None` when no enclosing node has `range` as its name.
+    pub fn definition_span_at(
+        &self,
+        range: core::text_range::TextRange,
+    ) -> Option<std::ops::Range<usize>> {
+        let root = self.tree.root_node();
+        let mut node = root.descendant_for_byte_range(range.start.byte, range.end.byte)?;
+        loop {
+            let names_range = ["name", "declarator"]
+                .iter()
+                .filter_map(|field| node.child_by_field_name(field))
+                .any(|n| n.start_byte() <= range.start.byte && range.end.byte <= n.end_byte());
+            if names_range && node.end_byte() > range.end.byte {
+                break;
+            }
+            node = node.parent()?;
+        }
+        // Python-style decorators wrap the real definition.
+        if let Some(parent) = node.parent().filter(|p| p.kind() == "decorated_definition") {
+            node = parent;
+        }
+
+        let mut start = node;
+        while let Some(prev) = start.prev_named_sibling() {
+            let leading = prev.kind().contains("comment") || prev.kind().contains("attribute");
+            if !leading || prev.end_position().row + 1 < start.start_position().row {
+                break;
+            }
+            start = prev;
+        }
+        Some(start.start_byte()..node.end_byte())
+    }
+
+    /// Byte ranges of comments and string literals, sorted and non-overlapping.
+    pub fn comment_and_string_ranges(&self) -> Vec<std::ops::Range<usize>> {
+        let mut out = Vec::new();
+        let mut cursor = self.tree.walk();
+        loop {
+            let node = cursor.node();
+            let kind = node.kind();
+            let is_match = node.is_named()
+                && (kind.contains("comment") || kind.contains("string") || kind == "char_literal");
+            if is_match {
+                out.push(node.byte_range());
+            } else if cursor.goto_first_child() {
+                continue;
+            }
+
+            // Next sibling, or the nearest ancestor's next sibling.
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() {
+                    return out;
+                }
+            }
+        }
+    }
+
     /// Produce a lexical scope-graph for this TreeSitterFile.
     pub fn scope_graph(self) -> Result<ScopeGraph, TreeSitterFileError> {
+        self.build_scope_graph()
+    }
+
+    /// Outline of the file: declaration headers with bodies elided.
+    ///
+    /// See [`crate::skeleton`] for details.
+    pub fn skeleton(&self) -> Result<String, TreeSitterFileError> {
+        let graph = self.build_scope_graph()?;
+        Ok(skeleton::render(self.src, self.tree.root_node(), &graph))
+    }
+
+    /// Split the file into declaration-aligned chunks for indexing.
+    ///
+    /// See [`crate::chunk`] for details.
+    pub fn chunks(&self, opt: &ChunkOptions) -> Result<Vec<CodeChunk>, TreeSitterFileError> {
+        let graph = self.build_scope_graph()?;
+        Ok(chunk::collect(self.src, &self.tree, &graph, opt))
+    }
+
+    fn build_scope_graph(&self) -> Result<ScopeGraph, TreeSitterFileError> {
         let query = self
             .language
             .scope_query
@@ -119,3 +351,37 @@ impl<'a> TreeSitterFile<'a> {
         Ok(ResolutionMethod::Generic.build_scope(query, root_node, self.src, self.language))
     }
 }
+
+/// Last identifier of a callee expression, ignoring generic arguments
+/// (`Vec::<u8>::with_capacity` -> `with_capacity`).
+fn last_path_segment(text: &str) -> Option<String> {
+    let mut depth = 0usize;
+    let stripped: String = text
+        .chars()
+        .filter(|&c| {
+            match c {
+                '<' => depth += 1,
+                '>' => depth = depth.saturating_sub(1),
+                _ => return depth == 0,
+            }
+            false
+        })
+        .collect();
+    stripped
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .rfind(|s| !s.is_empty())
+        .map(str::to_string)
+}
+
+/// Path under the system temp dir that no other test uses; not created.
+#[cfg(test)]
+pub(crate) fn unique_tmp_dir(prefix: &str) -> std::path::PathBuf {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("{prefix}-{nanos}-{n}"))
+}
@@ -1,11 +1,19 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+};
 
 use crate::{
+    chunk::{between_chunks, ChunkOptions},
     repo_scan::{FsRepoFileProvider, RepoFileProvider, RepoScanError, RepoScanOptions},
     TreeSitterFile, TreeSitterFileError,
 };
 
-use crate::{document::build_line_end_indices, snippet::SnippetBuilder};
+use crate::{
+    document::{build_line_end_indices, byte_column_from_utf16, utf16_position, Utf16Position},
+    snippet::SnippetBuilder,
+};
 
 use core::text_range::TextRange;
 
@@ -20,6 +28,20 @@ pub struct SnippetOptions {
 
     /// Whether to highlight the symbol range in the snippet.
     pub with_highlight: bool,
+
+    /// Max lines scanned when a signature spans several lines.
+    pub max_signature_lines: usize,
+
+    /// Truncate signatures longer than this many chars (with `…`, at a word boundary).
+    pub max_signature_len: Option<usize>,
+
+    /// Max enclosing scopes prefixed to `SymbolContext::qualified_name`.
+    pub max_qualifier_depth: usize,
+
+    /// For a location outside every top-level declaration of a file that
+    /// parses, show the statements around it, led by the header line of the
+    /// nearest declaration above, instead of the `context_lines` window.
+    pub between_scopes: bool,
 }
 
 impl Default for SnippetOptions {
@@ -28,6 +50,10 @@ impl Default for SnippetOptions {
             context_lines: 5,
             with_line_numbers: true,
             with_highlight: true,
+            max_signature_lines: 10,
+            max_signature_len: Some(160),
+            max_qualifier_depth: 4,
+            between_scopes: true,
         }
     }
 }
@@ -38,11 +64,87 @@ pub struct SymbolLocation {
     pub range: TextRange,
 }
 
+impl SymbolLocation {
+    /// Start/end as editor (UTF-16) positions; `content` is the file's text.
+    #[must_use]
+    pub fn utf16_range(&self, content: &str) -> (Utf16Position, Utf16Position) {
+        (
+            utf16_position(content, &self.range.start),
+            utf16_position(content, &self.range.end),
+        )
+    }
+}
+
+/// Visibility of a definition, as declared in source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolVisibility {
+    /// `pub` / `public`
+    Public,
+    /// `pub(crate)`, `pub(super)`, `pub(in path)` / `protected`
+    Restricted,
+    /// No modifier (Rust) or `private`
+    Private,
+}
+
+impl SymbolVisibility {
+    /// Map the text of a `visibility_modifier` node to a visibility.
+    pub fn from_modifier(text: &str) -> Self {
+        let t = text.trim();
+        if t == "pub" || t == "public" {
+            Self::Public
+        } else if t.starts_with("pub") || t == "protected" {
+            Self::Restricted
+        } else {
+            Self::Private
+        }
+    }
+
+    /// Line-prefix heuristic, only used when no syntax tree is available.
+    pub fn from_src_line(line: &str) -> Self {
+        let t = line.trim_start();
+        if t.starts_with("pub(") || t.starts_with("pub (") {
+            Self::Restricted
+        } else if t.starts_with("pub ") || t.starts_with("public ") {
+            Self::Public
+        } else {
+            Self::Private
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Public => "public",
+            Self::Restricted => "restricted",
+            Self::Private => "private",
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct SymbolContext {
     pub location: SymbolLocation,
     pub signature_line: Option<String>,
     pub snippet: String,
+    pub visibility: Option<SymbolVisibility>,
+    /// Scope-graph kind of the definition at the location (`function`,
+    /// `struct`, ...); `None` when no definition starts there or the file
+    /// doesn't parse.
+    pub kind: Option<&'static str>,
+    /// Functions called from the definition at the location, by last path
+    /// segment; empty for non-callables or unparsable files.
+    pub callees: Vec<String>,
+    /// Name qualified by its enclosing modules/types (`parser::Parser::new`);
+    /// the bare name when the file doesn't parse.
+    pub qualified_name: String,
+}
+
+/// How a file relates to the file passed to `TreeSitterNavigator::related_files`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RelationKind {
+    /// The other file uses a top-level symbol defined in the given file.
+    ReferencedBy,
+    /// The given file uses a top-level symbol defined in the other file.
+    References,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -51,6 +153,35 @@ pub struct SearchResult {
     pub references: Vec<SymbolLocation>,
 }
 
+/// One page of a result list, plus the size of the full list.
+#[derive(Debug, Clone, Default)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Index of `items[0]` in the full list
+    pub offset: usize,
+    /// Length of the full list
+    pub total: usize,
+}
+
+impl<T> Page<T> {
+    /// Take `limit` items starting at `offset` from an already ordered list.
+    #[must_use]
+    pub fn from_sorted(all: Vec<T>, offset: usize, limit: usize) -> Self {
+        let total = all.len();
+        let items = all.into_iter().skip(offset).take(limit).collect();
+        Self {
+            items,
+            offset,
+            total,
+        }
+    }
+
+    #[must_use]
+    pub fn has_more(&self) -> bool {
+        self.offset + self.items.len() < self.total
+    }
+}
+
 #[derive(Debug)]
 pub enum NavigationError {
     RepoScan(RepoScanError),
@@ -160,10 +291,46 @@ impl<P: RepoFileProvider> TreeSitterNavigator<P> {
         Self { provider, scan_opt }
     }
 
+    /// [`Navigator::get_symbol_context`] for a file already read and parsed.
+    fn symbol_context_in(
+        content: &str,
+        ts: Option<&TreeSitterFile<'_>>,
+        location: &SymbolLocation,
+        opt: &SnippetOptions,
+    ) -> SymbolContext {
+        let (signature_line, snippet) =
+            Self::extract_signature_and_snippet(content, &location.range, opt, ts);
+        let visibility = Self::visibility_of(&location.rel_path, content, location.range, ts);
+        let kind =
+            ts.and_then(|ts| ts.def_kind_at(location.range.start.line, location.range.start.column));
+        let callees = ts
+            .map(|ts| ts.callees_at(location.range))
+            .unwrap_or_default();
+        let qualified_name = ts
+            .and_then(|ts| ts.qualified_name_at(location.range, opt.max_qualifier_depth))
+            .or_else(|| {
+                content
+                    .get(location.range.start.byte..location.range.end.byte)
+                    .map(ToOwned::to_owned)
+            })
+            .unwrap_or_default();
+
+        SymbolContext {
+            location: location.clone(),
+            signature_line,
+            snippet,
+            visibility,
+            kind,
+            callees,
+            qualified_name,
+        }
+    }
+
     fn extract_signature_and_snippet(
         content: &str,
         range: &TextRange,
         opt: &SnippetOptions,
+        ts: Option<&TreeSitterFile<'_>>,
     ) -> (Option<String>, String) {
         let lines: Vec<&str> = content.lines().collect();
         if lines.is_empty() {
@@ -171,30 +338,59 @@ impl<P: RepoFileProvider> TreeSitterNavigator<P> {
         }
 
         let line = range.start.line.min(lines.len().saturating_sub(1));
-        let signature_line = Self::extract_definition_signature(&lines, line);
+        let signature_line = Self::extract_definition_signature(&lines, line, opt);
         let line_end_indices = build_line_end_indices(content);
-        let snippet = SnippetBuilder {
+        let builder = SnippetBuilder {
             context_lines: opt.context_lines,
             with_line_numbers: opt.with_line_numbers,
             with_highlight: opt.with_highlight,
             ..SnippetBuilder::default()
-        }
-        .build(content, &line_end_indices, *range)
-        .text;
+        };
+
+        let between = ts
+            .filter(|_| opt.between_scopes)
+            .and_then(|ts| ts.chunks(&ChunkOptions::default()).ok())
+            .and_then(|chunks| between_chunks(&lines, &chunks, line));
+        let Some((start, end, header)) = between else {
+            let snippet = builder.build(content, &line_end_indices, *range).text;
+            return (signature_line, snippet);
+        };
 
+        let block = builder
+            .build_lines(content, &line_end_indices, *range, start, end)
+            .text;
+        let snippet = match header {
+            Some(header) => {
+                let header = builder
+                    .build_lines(content, &line_end_indices, *range, header, header)
+                    .text;
+                format!("{header}\n...\n{block}")
+            }
+            None => block,
+        };
         (signature_line, snippet)
     }
 
-    fn extract_definition_signature(lines: &[&str], line_idx: usize) -> Option<String> {
+    fn extract_definition_signature(
+        lines: &[&str],
+        line_idx: usize,
+        opt: &SnippetOptions,
+    ) -> Option<String> {
         let line = lines.get(line_idx)?.trim();
         if line.is_empty() {
             return None;
         }
 
+        let compact = |sig: String| match opt.max_signature_len {
+            Some(max) => truncate_at_word(&sig, max),
+            None => sig,
+        };
+
         // If it's a function-like line, try to capture a multi-line signature up to `{` or `;`.
         if is_function_like_line(line) {
             let mut out = Vec::new();
-            for l in &lines[line_idx..] {
+            let scan_lines = opt.max_signature_lines.max(1);
+            for l in lines[line_idx..].iter().take(scan_lines) {
                 let t = l.trim_end();
                 if t.is_empty() {
                     break;
@@ -205,33 +401,35 @@ impl<P: RepoFileProvider> TreeSitterNavigator<P> {
                 }
             }
             if !out.is_empty() {
-                let joined = out.join("\n");
-                return Some(sanitize_function_signature(&joined));
+                let joined = collapse_whitespace(&out.join("\n"));
+                return Some(compact(sanitize_function_signature(&joined)));
             }
         }
 
         // Fallback: single-line definition header.
-        Some(sanitize_definition_header(line))
-    }
-
-    fn lang_id_for_path(path: &Path) -> Option<&'static str> {
-        match path.extension().and_then(|s| s.to_str()) {
-            Some("rs") => Some("rust"),
-            Some("go") => Some("go"),
-            Some("py") => Some("python"),
-            Some("js") => Some("javascript"),
-            Some("ts") => Some("typescript"),
-            Some("tsx") => Some("tsx"),
-            Some("java") => Some("java"),
-            Some("c") => Some("c"),
-            // NOTE: Language identifiers must match `language_ids` (case-insensitive).
-            // Our C++ config uses `"C++"`, not `"cpp"`.
-            Some("cpp") | Some("cc") | Some("cxx") | Some("hpp") | Some("h") => Some("C++"),
-            Some("rb") => Some("ruby"),
-            Some("php") => Some("php"),
-            Some("r") => Some("r"),
-            _ => None,
+        Some(compact(sanitize_definition_header(line)))
+    }
+
+    /// Prefer the syntax tree; fall back to the definition line when parsing fails.
+    fn visibility_of(
+        rel_path: &Path,
+        content: &str,
+        range: TextRange,
+        ts: Option<&TreeSitterFile<'_>>,
+    ) -> Option<SymbolVisibility> {
+        let lang_id = Self::lang_id_for_path(rel_path)?;
+        if let Some(ts) = ts {
+            return ts.visibility_at(range);
         }
+        if !lang_id.eq_ignore_ascii_case("rust") {
+            return None;
+        }
+        let line = content.lines().nth(range.start.line)?;
+        Some(SymbolVisibility::from_src_line(line))
+    }
+
+    pub(crate) fn lang_id_for_path(path: &Path) -> Option<&'static str> {
+        detect_lang_id(path)
     }
 
     fn find_identifier_occurrences(content: &str, name: &str, max: usize) -> Vec<TextRange> {
@@ -309,6 +507,27 @@ impl<P: RepoFileProvider> TreeSitterNavigator<P> {
     }
 }
 
+/// Tree-sitter language id for a path, based on its extension.
+pub fn detect_lang_id(path: &Path) -> Option<&'static str> {
+    match path.extension().and_then(|s| s.to_str()) {
+        Some("rs") => Some("rust"),
+        Some("go") => Some("go"),
+        Some("py") => Some("python"),
+        Some("js") => Some("javascript"),
+        Some("ts") => Some("typescript"),
+        Some("tsx") => Some("tsx"),
+        Some("java") => Some("java"),
+        Some("c") => Some("c"),
+        // NOTE: Language identifiers must match `language_ids` (case-insensitive).
+        // Our C++ config uses `"C++"`, not `"cpp"`.
+        Some("cpp") | Some("cc") | Some("cxx") | Some("hpp") | Some("h") => Some("C++"),
+        Some("rb") => Some("ruby"),
+        Some("php") => Some("php"),
+        Some("r") => Some("r"),
+        _ => None,
+    }
+}
+
 fn is_ident_continue(b: u8) -> bool {
     b == b'_'
         || b.is_ascii_lowercase()
@@ -359,6 +578,74 @@ fn sanitize_function_signature(sig: &str) -> String {
     s.to_owned()
 }
 
+/// Join a multi-line signature onto one line: `foo(\n    a: i32,\n)` -> `foo(a: i32)`.
+fn collapse_whitespace(sig: &str) -> String {
+    let joined = sig
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace("( ", "(")
+        .replace(" )", ")");
+    drop_trailing_commas(&joined)
+}
+
+/// Drop the trailing comma of parameter lists (`foo(a,)` -> `foo(a)`) and
+/// other lists of several elements, keeping the one that makes `(u8,)` a
+/// one-element tuple.
+fn drop_trailing_commas(s: &str) -> String {
+    // Per open bracket: the bracket, its top-level commas, and whether it
+    // follows a name (a parameter list, not a tuple).
+    let mut open: Vec<(char, usize, bool)> = Vec::new();
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '(' | '[' | '<' => {
+                let after_name = out
+                    .chars()
+                    .next_back()
+                    .is_some_and(|p| p.is_alphanumeric() || p == '_' || p == '>');
+                open.push((c, 0, after_name));
+            }
+            ',' => {
+                if let Some((_, commas, _)) = open.last_mut() {
+                    *commas += 1;
+                }
+            }
+            ')' | ']' => {
+                let strip = open
+                    .pop()
+                    .is_some_and(|(_, commas, after_name)| after_name || commas > 1);
+                if strip && out.ends_with(',') {
+                    out.pop();
+                }
+            }
+            // `->` is not a closing bracket
+            '>' if !out.ends_with('-') && open.last().is_some_and(|&(b, ..)| b == '<') => {
+                open.pop();
+            }
+            _ => {}
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Cut to at most `max` chars (including the trailing `…`), preferring a word boundary.
+fn truncate_at_word(s: &str, max: usize) -> String {
+    if s.chars().count() <= max {
+        return s.to_owned();
+    }
+
+    let keep = max.saturating_sub(1);
+    let end = s.char_indices().nth(keep).map_or(s.len(), |(i, _)| i);
+    let head = &s[..end];
+    let head = match head.rfind(' ') {
+        Some(i) if i > 0 => &head[..i],
+        _ => head,
+    };
+    format!("{}…", head.trim_end())
+}
+
 fn sanitize_definition_header(line: &str) -> String {
     // For non-function definitions, keep the header readable by trimming the trailing body start
     // `{` or terminal `;`.
@@ -513,14 +800,22 @@ impl<P: RepoFileProvider> Navigator for TreeSitterNavigator<P> {
             source: e,
         })?;
 
-        let (signature_line, snippet) =
-            Self::extract_signature_and_snippet(&content, &location.range, opt);
-
-        Ok(SymbolContext {
-            location: location.clone(),
-            signature_line,
-            snippet,
-        })
+        let ts = Self::lang_id_for_path(&location.rel_path)
+            .and_then(|lang_id| TreeSitterFile::try_build(content.as_bytes(), lang_id).ok());
+        // Callers outside this crate may only know line/column; derive the
+        // byte offsets the AST lookups use from them.
+        let line_starts = std::iter::once(0)
+            .chain(build_line_end_indices(&content).into_iter().map(|nl| nl + 1))
+            .collect::<Vec<_>>();
+        let byte_of = |line: usize, column: usize| {
+            line_starts
+                .get(line)
+                .map_or(content.len(), |start| (start + column).min(content.len()))
+        };
+        let mut location = location.clone();
+        location.range.start.byte = byte_of(location.range.start.line, location.range.start.column);
+        location.range.end.byte = byte_of(location.range.end.line, location.range.end.column);
+        Ok(Self::symbol_context_in(&content, ts.as_ref(), &location, opt))
     }
 
     fn find_references(
@@ -529,34 +824,19 @@ impl<P: RepoFileProvider> Navigator for TreeSitterNavigator<P> {
         name: &str,
         max: usize,
     ) -> Result<Vec<SymbolLocation>, NavigationError> {
-        let files = self.provider.list_files(repo_root, &self.scan_opt)?;
         let mut out = Vec::new();
+        if max == 0 {
+            return Ok(out);
+        }
 
-        for file in files {
+        self.find_references_streaming(repo_root, name, |loc| {
+            out.push(loc.clone());
             if out.len() >= max {
-                break;
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
             }
-            let remain = max.saturating_sub(out.len());
-
-            // Semantic-first: count only parsed reference nodes.
-            let semantic = Self::semantic_references_in_file(&file, name, remain);
-            if !semantic.is_empty() {
-                out.extend(semantic);
-                continue;
-            }
-
-            // Fallback: text-based occurrence scan.
-            let ranges = Self::find_identifier_occurrences(&file.content, name, remain);
-            for r in ranges {
-                out.push(SymbolLocation {
-                    rel_path: file.rel_path.clone(),
-                    range: r,
-                });
-                if out.len() >= max {
-                    break;
-                }
-            }
-        }
+        })?;
 
         Ok(out)
     }
@@ -614,6 +894,246 @@ impl<P: RepoFileProvider> Navigator for TreeSitterNavigator<P> {
 }
 
 impl<P: RepoFileProvider> TreeSitterNavigator<P> {
+    /// Like [`Navigator::find_references`], but reports each hit as soon as its
+    /// file has been scanned instead of after the whole walk.
+    ///
+    /// Return `ControlFlow::Break` from `on_hit` to stop the scan.
+    pub fn find_references_streaming(
+        &self,
+        repo_root: &Path,
+        name: &str,
+        mut on_hit: impl FnMut(&SymbolLocation) -> ControlFlow<()>,
+    ) -> Result<(), NavigationError> {
+        let code_only = self.scan_opt.code_only;
+
+        // Files that never mention `name` cannot reference it.
+        self.provider
+            .for_each_file_containing(repo_root, &self.scan_opt, name, &mut |file| {
+                // Semantic-first: count only parsed reference nodes.
+                let mut hits = Self::semantic_references_in_file(&file, name, usize::MAX);
+
+                // Fallback: text-based occurrence scan.
+                if hits.is_empty() {
+                    let mut ranges =
+                        Self::find_identifier_occurrences(&file.content, name, usize::MAX);
+                    if code_only {
+                        Self::retain_code_ranges(&file, &mut ranges);
+                    }
+                    hits = ranges
+                        .into_iter()
+                        .map(|range| SymbolLocation {
+                            rel_path: file.rel_path.clone(),
+                            range,
+                        })
+                        .collect();
+                }
+
+                for hit in &hits {
+                    on_hit(hit)?;
+                }
+                ControlFlow::Continue(())
+            })?;
+
+        Ok(())
+    }
+
+    /// Drop ranges that start inside a comment or string literal. Leaves
+    /// `ranges` untouched when the file can't be parsed.
+    fn retain_code_ranges(file: &crate::repo_scan::RepoFile, ranges: &mut Vec<TextRange>) {
+        let Some(lang_id) = Self::lang_id_for_path(&file.rel_path) else {
+            return;
+        };
+        let Ok(ts) = TreeSitterFile::try_build(file.content.as_bytes(), lang_id) else {
+            return;
+        };
+        let spans = ts.comment_and_string_ranges();
+        ranges.retain(|r| {
+            let b = r.start.byte;
+            let i = spans.partition_point(|s| s.start <= b);
+            !(i > 0 && spans[i - 1].contains(&b))
+        });
+    }
+
+    /// [`Navigator::goto_definition_at`] for editor positions, where `column`
+    /// counts UTF-16 code units (as in LSP) instead of bytes.
+    pub fn goto_definition_at_utf16(
+        &self,
+        repo_root: &Path,
+        rel_path: &Path,
+        line: usize,
+        column: usize,
+    ) -> Result<Vec<SymbolLocation>, NavigationError> {
+        let abs_path = repo_root.join(rel_path);
+        let content = std::fs::read_to_string(&abs_path).map_err(|e| NavigationError::Io {
+            path: abs_path.clone(),
+            source: e,
+        })?;
+        let line_text = content.lines().nth(line).unwrap_or_default();
+        let byte_column = byte_column_from_utf16(line_text, column);
+        self.goto_definition_at(repo_root, rel_path, line, byte_column)
+    }
+
+    /// One page of all references to `name`.
+    ///
+    /// Hits are ordered by path, then position, so a given `offset` keeps
+    /// returning the same hits while the tree is unchanged. The whole repo is
+    /// scanned to compute `total`.
+    pub fn find_references_page(
+        &self,
+        repo_root: &Path,
+        name: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Page<SymbolLocation>, NavigationError> {
+        let mut all = Vec::new();
+        self.find_references_streaming(repo_root, name, |loc| {
+            all.push(loc.clone());
+            ControlFlow::Continue(())
+        })?;
+        all.sort_by(|a, b| {
+            a.rel_path
+                .cmp(&b.rel_path)
+                .then(a.range.start.byte.cmp(&b.range.start.byte))
+        });
+        Ok(Page::from_sorted(all, offset, limit))
+    }
+
+    /// Full source of every definition of `name`, ordered by path, then
+    /// position.
+    ///
+    /// Each text covers the whole declaration (see
+    /// [`TreeSitterFile::definition_span_at`]); when the file doesn't parse it
+    /// falls back to the definition's line. `kind` keeps only definitions of
+    /// that scope-graph kind (`function`, `struct`, ...).
+    pub fn read_symbol(
+        &self,
+        repo_root: &Path,
+        name: &str,
+        kind: Option<&str>,
+        opt: &SnippetOptions,
+    ) -> Result<Vec<(SymbolContext, String)>, NavigationError> {
+        let mut defs = self.goto_definition(repo_root, name)?;
+        defs.sort_by(|a, b| {
+            a.rel_path
+                .cmp(&b.rel_path)
+                .then(a.range.start.byte.cmp(&b.range.start.byte))
+        });
+
+        // Read each defining file once, through the provider, and parse it once.
+        let mut contents: HashMap<PathBuf, String> = HashMap::new();
+        self.provider
+            .for_each_file_containing(repo_root, &self.scan_opt, name, &mut |file| {
+                if defs.iter().any(|loc| loc.rel_path == file.rel_path) {
+                    contents.insert(file.rel_path, file.content);
+                }
+                ControlFlow::Continue(())
+            })?;
+
+        let mut out = Vec::new();
+        for locs in defs.chunk_by(|a, b| a.rel_path == b.rel_path) {
+            let rel_path = &locs[0].rel_path;
+            let Some(content) = contents.get(rel_path) else {
+                continue;
+            };
+            let ts = Self::lang_id_for_path(rel_path)
+                .and_then(|lang_id| TreeSitterFile::try_build(content.as_bytes(), lang_id).ok());
+            for loc in locs {
+                let ctx = Self::symbol_context_in(content, ts.as_ref(), loc, opt);
+                if kind.is_some_and(|k| ctx.kind != Some(k)) {
+                    continue;
+                }
+                // The file may have changed since the search; skip stale locations.
+                let span = ts
+                    .as_ref()
+                    .and_then(|ts| ts.definition_span_at(loc.range))
+                    .or_else(|| {
+                        let start = content
+                            .get(..loc.range.start.byte)?
+                            .rfind('\n')
+                            .map_or(0, |i| i + 1);
+                        let end = content
+                            .get(loc.range.end.byte..)?
+                            .find('\n')
+                            .map_or(content.len(), |i| loc.range.end.byte + i);
+                        Some(start..end)
+                    });
+                if let Some(text) = span.and_then(|span| content.get(span)) {
+                    out.push((ctx, text.to_owned()));
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Files connected to `rel_path` through top-level symbols, in both directions.
+    ///
+    /// A name counts as a cross-file use when it occurs as an identifier in one
+    /// file, is a top-level definition in the other, and is not defined locally.
+    pub fn related_files(
+        &self,
+        repo_root: &Path,
+        rel_path: &Path,
+    ) -> Result<Vec<(PathBuf, RelationKind)>, NavigationError> {
+        let files = self.provider.list_files(repo_root, &self.scan_opt)?;
+        let Some(target) = files.iter().find(|f| f.rel_path == rel_path) else {
+            return Ok(Vec::new());
+        };
+        let Some((target_top, target_all)) = Self::def_names_in_file(target) else {
+            return Ok(Vec::new());
+        };
+
+        let uses = |content: &str, names: &HashSet<String>, local: &HashSet<String>| {
+            names.iter().any(|n| {
+                !local.contains(n) && !Self::find_identifier_occurrences(content, n, 1).is_empty()
+            })
+        };
+
+        let mut out = Vec::new();
+        for file in &files {
+            if file.rel_path == rel_path {
+                continue;
+            }
+            let Some((top, all)) = Self::def_names_in_file(file) else {
+                continue;
+            };
+            if uses(&file.content, &target_top, &all) {
+                out.push((file.rel_path.clone(), RelationKind::ReferencedBy));
+            }
+            if uses(&target.content, &top, &target_all) {
+                out.push((file.rel_path.clone(), RelationKind::References));
+            }
+        }
+
+        out.sort();
+        Ok(out)
+    }
+
+    /// (top-level definition names, all definition names) of a file.
+    fn def_names_in_file(
+        file: &crate::repo_scan::RepoFile,
+    ) -> Option<(HashSet<String>, HashSet<String>)> {
+        let lang_id = Self::lang_id_for_path(&file.rel_path)?;
+        let src = file.content.as_bytes();
+        let sg = TreeSitterFile::try_build(src, lang_id)
+            .ok()?
+            .scope_graph()
+            .ok()?;
+
+        let mut top = HashSet::new();
+        let mut all = HashSet::new();
+        for idx in sg.graph.node_indices() {
+            let Some(crate::NodeKind::Def(d)) = sg.get_node(idx) else {
+                continue;
+            };
+            let name = String::from_utf8_lossy(d.name(src)).into_owned();
+            if sg.is_top_level(idx) {
+                top.insert(name.clone());
+            }
+            all.insert(name);
+        }
+        Some((top, all))
+    }
+
     fn semantic_references_in_file(
         file: &crate::repo_scan::RepoFile,
         name: &str,
@@ -723,21 +1243,473 @@ mod tests {
         let _ = fs::remove_dir_all(&root);
     }
 
+    #[test]
+    fn get_symbol_context_between_scopes_shows_statements_and_header() {
+        let root = crate::unique_tmp_dir("nav_between_scopes");
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(
+            root.join("src/lib.rs"),
+            "pub fn a() -> u32 {\n    1\n}\n\nuse std::fmt;\nuse std::io;\n\npub fn b() {}\n",
+        )
+        .unwrap();
+
+        let nav = TreeSitterNavigator::default();
+        let loc = SymbolLocation {
+            rel_path: PathBuf::from("src/lib.rs"),
+            range: TextRange::new(
+                core::text_range::Position::new(47, 5, 4),
+                core::text_range::Position::new(54, 5, 11),
+            ),
+        };
+        let opt = SnippetOptions {
+            context_lines: 3,
+            with_highlight: false,
+            ..SnippetOptions::default()
+        };
+        let ctx = nav.get_symbol_context(&root, &loc, &opt).unwrap();
+        assert_eq!(
+            ctx.snippet,
+            "   1 pub fn a() -> u32 {\n...\n   5 use std::fmt;\n   6 use std::io;"
+        );
+
+        // Opting out keeps the plain window.
+        let window = SnippetOptions {
+            between_scopes: false,
+            ..opt
+        };
+        let ctx = nav.get_symbol_context(&root, &loc, &window).unwrap();
+        assert!(ctx.snippet.starts_with("   3 }") && ctx.snippet.contains("pub fn b()"));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn get_symbol_context_reads_visibility_from_syntax_tree() {
+        let root = unique_tmp_dir();
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("src")).unwrap();
+
+        fs::write(
+            root.join("src/lib.rs"),
+            "#[inline]\npub(crate) fn restricted() {}\n\n#[derive(Debug)]\npub struct Open;\n\nfn hidden() {}\n",
+        )
+        .unwrap();
+
+        let nav = TreeSitterNavigator::default();
+        let visibility_of = |name: &str| {
+            let loc = nav.goto_definition(&root, name).unwrap().remove(0);
+            nav.get_symbol_context(&root, &loc, &SnippetOptions::default())
+                .unwrap()
+                .visibility
+        };
+
+        assert_eq!(
+            visibility_of("restricted"),
+            Some(SymbolVisibility::Restricted)
+        );
+        assert_eq!(visibility_of("Open"), Some(SymbolVisibility::Public));
+        assert_eq!(visibility_of("hidden"), Some(SymbolVisibility::Private));
+
+        let kind_of = |name: &str| {
+            let loc = nav.goto_definition(&root, name).unwrap().remove(0);
+            nav.get_symbol_context(&root, &loc, &SnippetOptions::default())
+                .unwrap()
+                .kind
+        };
+        assert_eq!(kind_of("restricted"), Some("function"));
+        assert_eq!(kind_of("Open"), Some("struct"));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn get_symbol_context_lists_callees() {
+        let root = unique_tmp_dir();
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("src")).unwrap();
+
+        fs::write(
+            root.join("src/lib.rs"),
+            "fn run() {\n    let v = Vec::<u8>::new();\n    helper(v.len());\n    helper(0);\n}\n\nfn helper(_: usize) {}\n",
+        )
+        .unwrap();
+
+        let nav = TreeSitterNavigator::default();
+        let callees_of = |name: &str| {
+            let loc = nav.goto_definition(&root, name).unwrap().remove(0);
+            nav.get_symbol_context(&root, &loc, &SnippetOptions::default())
+                .unwrap()
+                .callees
+        };
+
+        assert_eq!(callees_of("run"), vec!["new", "helper", "len"]);
+        assert!(callees_of("helper").is_empty());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn read_symbol_returns_whole_definitions_sorted_by_path() {
+        let root = unique_tmp_dir();
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(
+            root.join("src/b.rs"),
+            "use std::fmt;\n\n/// Parses input.\n#[inline]\npub fn parse(s: &str) -> usize {\n    s.len()\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("src/a.rs"),
+            "pub struct parse {\n    n: usize,\n}\n",
+        )
+        .unwrap();
+
+        let nav = TreeSitterNavigator::default();
+        let opt = SnippetOptions::default();
+        let all = nav.read_symbol(&root, "parse", None, &opt).unwrap();
+        let texts: Vec<_> = all
+            .iter()
+            .map(|(ctx, text)| (ctx.location.rel_path.clone(), text.as_str()))
+            .collect();
+        assert_eq!(
+            texts,
+            [
+                (
+                    PathBuf::from("src/a.rs"),
+                    "pub struct parse {\n    n: usize,\n}"
+                ),
+                (
+                    PathBuf::from("src/b.rs"),
+                    "/// Parses input.\n#[inline]\npub fn parse(s: &str) -> usize {\n    s.len()\n}"
+                ),
+            ]
+        );
+
+        let functions = nav
+            .read_symbol(&root, "parse", Some("function"), &opt)
+            .unwrap();
+        assert_eq!(functions.len(), 1);
+        assert_eq!(functions[0].0.location.rel_path, PathBuf::from("src/b.rs"));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn get_symbol_context_qualifies_names_by_enclosing_scopes() {
+        let root = unique_tmp_dir();
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+
+        fs::write(
+            root.join("shapes.py"),
+            "class Parser:\n    def new(self):\n        pass\n\nclass Lexer:\n    class State:\n        def new(self):\n            pass\n",
+        )
+        .unwrap();
+
+        let nav = TreeSitterNavigator::default();
+        let qualified = |opt: &SnippetOptions| {
+            let mut names = nav
+                .goto_definition(&root, "new")
+                .unwrap()
+                .iter()
+                .map(|loc| {
+                    nav.get_symbol_context(&root, loc, opt)
+                        .unwrap()
+                        .qualified_name
+                })
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        assert_eq!(
+            qualified(&SnippetOptions::default()),
+            ["Lexer.State.new", "Parser.new"]
+        );
+        let shallow = SnippetOptions {
+            max_qualifier_depth: 1,
+            ..SnippetOptions::default()
+        };
+        assert_eq!(qualified(&shallow), ["Parser.new", "State.new"]);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn crlf_files_match_their_lf_equivalent() {
+        let root = unique_tmp_dir();
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("lf")).unwrap();
+        fs::create_dir_all(root.join("crlf")).unwrap();
+
+        let lf = "/// Docs.\npub struct Foo;\n\npub fn bar(\n    a: i32,\n) -> i32 {\n    a\n}\n";
+        let crlf = lf.replace('\n', "\r\n");
+        fs::write(root.join("lf/lib.rs"), lf).unwrap();
+        fs::write(root.join("crlf/lib.rs"), &crlf).unwrap();
+
+        let nav = TreeSitterNavigator::default();
+        let context_in = |dir: &str| {
+            let repo = root.join(dir);
+            let loc = nav.goto_definition(&repo, "bar").unwrap().remove(0);
+            nav.get_symbol_context(&repo, &loc, &SnippetOptions::default())
+                .unwrap()
+        };
+        let (a, b) = (context_in("lf"), context_in("crlf"));
+        assert_eq!(a.location.range.start.line, b.location.range.start.line);
+        assert_eq!(a.signature_line, b.signature_line);
+        assert_eq!(a.snippet, b.snippet);
+        assert!(!b.snippet.contains('\r'));
+
+        let chunk_lines = |src: &str| {
+            TreeSitterFile::try_build(src.as_bytes(), "rust")
+                .unwrap()
+                .chunks(&crate::ChunkOptions::default())
+                .unwrap()
+                .into_iter()
+                .map(|c| (c.start_line, c.end_line))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(chunk_lines(lf), chunk_lines(&crlf));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn symbol_visibility_from_src_line_handles_restricted() {
+        assert_eq!(
+            SymbolVisibility::from_src_line("    pub(super) fn x()"),
+            SymbolVisibility::Restricted
+        );
+        assert_eq!(
+            SymbolVisibility::from_src_line("pub fn x()"),
+            SymbolVisibility::Public
+        );
+        assert_eq!(
+            SymbolVisibility::from_src_line("fn x()"),
+            SymbolVisibility::Private
+        );
+    }
+
+    #[test]
+    fn find_references_streaming_stops_on_break() {
+        let root = unique_tmp_dir();
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("src")).unwrap();
+
+        fs::write(
+            root.join("src/lib.rs"),
+            "pub fn bar() {}\npub fn foo() { bar(); bar(); bar(); }\n",
+        )
+        .unwrap();
+
+        let nav = TreeSitterNavigator::default();
+        let mut seen = 0;
+        nav.find_references_streaming(&root, "bar", |_| {
+            seen += 1;
+            if seen == 2 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        })
+        .unwrap();
+        assert_eq!(seen, 2);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn goto_definition_at_utf16_handles_non_ascii_prefix() {
+        let root = unique_tmp_dir();
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("src")).unwrap();
+
+        let src = "fn target() {}\nfn main() { let _s = \"😀名\"; target(); }\n";
+        fs::write(root.join("src/lib.rs"), src).unwrap();
+
+        // Byte column 32, but 28 UTF-16 units: the emoji is 4 bytes / 2 units
+        // and '名' is 3 bytes / 1 unit.
+        let line = src.lines().nth(1).unwrap();
+        assert_eq!(line.find("target").unwrap(), 32);
+
+        let nav = TreeSitterNavigator::default();
+        let defs = nav
+            .goto_definition_at_utf16(&root, Path::new("src/lib.rs"), 1, 28)
+            .unwrap();
+        assert_eq!(defs.len(), 1);
+        assert_eq!(defs[0].range.start.line, 0);
+
+        let (start, end) = defs[0].utf16_range(src);
+        assert_eq!((start.line, start.character), (0, 3));
+        assert_eq!((end.line, end.character), (0, 9));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn find_references_page_is_stable_and_reports_total() {
+        let root = unique_tmp_dir();
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("src")).unwrap();
+
+        fs::write(
+            root.join("src/b.rs"),
+            "pub fn bar() {}\npub fn foo() { bar(); bar(); }\n",
+        )
+        .unwrap();
+        fs::write(root.join("src/a.rs"), "fn baz() { bar(); bar(); bar(); }\n").unwrap();
+
+        let nav = TreeSitterNavigator::default();
+        let mut pages = Vec::new();
+        let mut offset = 0;
+        loop {
+            let page = nav.find_references_page(&root, "bar", offset, 2).unwrap();
+            assert_eq!(page.total, 5);
+            offset += page.items.len();
+            let more = page.has_more();
+            pages.push(page);
+            if !more {
+                break;
+            }
+        }
+
+        assert_eq!(
+            pages.iter().map(|p| p.items.len()).collect::<Vec<_>>(),
+            vec![2, 2, 1]
+        );
+        let paths = pages
+            .iter()
+            .flat_map(|p| &p.items)
+            .map(|l| l.rel_path.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(paths[..3], vec![PathBuf::from("src/a.rs"); 3]);
+        assert_eq!(paths[3..], vec![PathBuf::from("src/b.rs"); 2]);
+        assert!(nav
+            .find_references_page(&root, "bar", 10, 2)
+            .unwrap()
+            .items
+            .is_empty());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn code_only_skips_mentions_in_comments_and_strings() {
+        let root = unique_tmp_dir();
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("src")).unwrap();
+
+        fs::write(
+            root.join("src/lib.rs"),
+            "// TODO: call needle_fn here
+const NAME: &str = \"needle_fn\";
+",
+        )
+        .unwrap();
+
+        let count = |code_only: bool| {
+            let opt = RepoScanOptions {
+                code_only,
+                ..RepoScanOptions::default()
+            };
+            let nav = TreeSitterNavigator::new(FsRepoFileProvider, opt);
+            let mut hits = 0;
+            nav.find_references_streaming(&root, "needle_fn", |_| {
+                hits += 1;
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+            hits
+        };
+        assert_eq!(count(false), 2);
+        assert_eq!(count(true), 0);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn related_files_reports_both_directions() {
+        let root = unique_tmp_dir();
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("src")).unwrap();
+
+        fs::write(root.join("src/util.rs"), "pub fn helper() -> i32 { 1 }\n").unwrap();
+        fs::write(
+            root.join("src/lib.rs"),
+            "pub struct Config;\n\npub fn run() -> i32 { helper() }\n",
+        )
+        .unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() { let _c = Config; }\n").unwrap();
+        fs::write(root.join("src/other.rs"), "fn unrelated() {}\n").unwrap();
+
+        let nav = TreeSitterNavigator::default();
+        let related = nav.related_files(&root, Path::new("src/lib.rs")).unwrap();
+        assert_eq!(
+            related,
+            vec![
+                (PathBuf::from("src/main.rs"), RelationKind::ReferencedBy),
+                (PathBuf::from("src/util.rs"), RelationKind::References),
+            ]
+        );
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
     #[test]
     fn extract_definition_signature_strips_trailing_brace_and_semicolon() {
         let lines = vec!["pub trait Navigator {", "    fn foo();", "}"];
-        let sig =
-            TreeSitterNavigator::<FsRepoFileProvider>::extract_definition_signature(&lines, 0)
-                .unwrap();
+        let sig = TreeSitterNavigator::<FsRepoFileProvider>::extract_definition_signature(
+            &lines,
+            0,
+            &SnippetOptions::default(),
+        )
+        .unwrap();
         assert_eq!(sig, "pub trait Navigator");
 
         let lines = vec!["pub struct Foo;"];
-        let sig =
-            TreeSitterNavigator::<FsRepoFileProvider>::extract_definition_signature(&lines, 0)
-                .unwrap();
+        let sig = TreeSitterNavigator::<FsRepoFileProvider>::extract_definition_signature(
+            &lines,
+            0,
+            &SnippetOptions::default(),
+        )
+        .unwrap();
         assert_eq!(sig, "pub struct Foo");
     }
 
+    #[test]
+    fn extract_definition_signature_joins_lines_and_truncates() {
+        let lines = vec![
+            "pub fn configure(",
+            "    first_option: usize,",
+            "    second_option: usize,",
+            ") -> Config {",
+        ];
+        let sig = |opt: SnippetOptions| {
+            TreeSitterNavigator::<FsRepoFileProvider>::extract_definition_signature(&lines, 0, &opt)
+                .unwrap()
+        };
+
+        assert_eq!(
+            sig(SnippetOptions::default()),
+            "pub fn configure(first_option: usize, second_option: usize) -> Config"
+        );
+        assert_eq!(
+            sig(SnippetOptions {
+                max_signature_len: Some(32),
+                ..SnippetOptions::default()
+            }),
+            "pub fn configure(first_option:…"
+        );
+        // Scan limit reached before the body brace.
+        assert_eq!(
+            sig(SnippetOptions {
+                max_signature_lines: 2,
+                ..SnippetOptions::default()
+            }),
+            "pub fn configure(first_option: usize,"
+        );
+    }
+
     #[test]
     fn goto_definition_finds_cpp_symbol_in_namespace_scope() {
         let root = unique_tmp_dir();
@@ -797,6 +1769,19 @@ public:
         let _ = fs::remove_dir_all(&root);
     }
 
+    #[test]
+    fn collapse_whitespace_keeps_one_element_tuples() {
+        assert_eq!(
+            collapse_whitespace("pub fn foo(\n    a: (u8,),\n    b: Vec<(u8,)>,\n) -> (u8,)"),
+            "pub fn foo(a: (u8,), b: Vec<(u8,)>) -> (u8,)"
+        );
+        assert_eq!(collapse_whitespace("fn f(\n    a: i32,\n)"), "fn f(a: i32)");
+        assert_eq!(
+            collapse_whitespace("fn g(\n    a: HashMap<u8, u8>,\n)"),
+            "fn g(a: HashMap<u8, u8>)"
+        );
+    }
+
     #[test]
     fn get_symbol_context_signature_does_not_include_body_brace() {
         let root = unique_tmp_dir();
@@ -1,20 +1,200 @@
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, HashSet, VecDeque};
 use std::{
     fs,
+    ops::ControlFlow,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
+/// What to do with symlinks found while walking a repository.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum SymlinkPolicy {
+    /// Ignore symlinks entirely.
+    #[default]
+    Skip,
+    /// Follow symlinks whose target is inside the repository.
+    FollowInRepo,
+    /// Follow all symlinks, including ones leaving the repository.
+    Follow,
+}
+
+/// Whether test files are scanned, e.g. to keep tests from crowding out the
+/// implementation, or to look only at how something is tested.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum TestFilePolicy {
+    /// Scan test files like any other.
+    #[default]
+    Include,
+    /// Skip files that [`is_test_path`] recognizes.
+    Exclude,
+    /// Scan only test files: [`is_test_path`] matches, plus files whose
+    /// content [`is_test_source`] recognizes (e.g. Rust `#[cfg(test)]`
+    /// modules) when the scan reads contents.
+    Only,
+}
+
+impl TestFilePolicy {
+    /// Whether a file classified as `is_test` passes.
+    #[must_use]
+    pub fn allows(self, is_test: bool) -> bool {
+        match self {
+            Self::Include => true,
+            Self::Exclude => !is_test,
+            Self::Only => is_test,
+        }
+    }
+}
+
+/// Whether `rel_path` looks like a test file: under a `tests/`, `test/`,
+/// `__tests__/` or `spec/` directory, or named like `*_test.rs`,
+/// `*_test.go`, `test_*.py`, `*.spec.ts`, `*.test.js`, `FooTest.java` or
+/// `*_spec.rb`.
+#[must_use]
+pub fn is_test_path(rel_path: &Path) -> bool {
+    let in_test_dir = rel_path.parent().is_some_and(|dir| {
+        dir.components().any(|c| {
+            matches!(
+                c.as_os_str().to_str(),
+                Some("tests" | "test" | "__tests__" | "spec")
+            )
+        })
+    });
+    if in_test_dir {
+        return true;
+    }
+    let Some(name) = rel_path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    let stem = name.split('.').next().unwrap_or(name);
+    stem.ends_with("_test")
+        || stem.ends_with("_spec")
+        || stem.starts_with("test_")
+        || stem.ends_with("Test")
+        || stem.ends_with("Tests")
+        || name == "conftest.py"
+        || name.contains(".test.")
+        || name.contains(".spec.")
+}
+
+/// Whether `content` (of the file at `rel_path`) is test code: the path
+/// matches [`is_test_path`], or the language's test markers start a line,
+/// e.g. `#[cfg(test)]` / `mod tests` / `#[test]` in Rust, `def test_` in
+/// Python, `@Test` in Java, `describe(` / `it(` in JavaScript and TypeScript.
+#[must_use]
+pub fn is_test_source(rel_path: &Path, content: &str) -> bool {
+    if is_test_path(rel_path) {
+        return true;
+    }
+    let lines = || content.lines().map(str::trim_start);
+    match rel_path.extension().and_then(|e| e.to_str()) {
+        Some("rs") => lines().any(|l| {
+            l.starts_with("#[cfg(test)]")
+                || l.starts_with("mod tests")
+                || l.starts_with("#[test]")
+                || l.starts_with("#[tokio::test")
+        }),
+        Some("py") => lines().any(|l| l.starts_with("def test_") || l.starts_with("class Test")),
+        Some("java") => lines().any(|l| l.starts_with("@Test")),
+        Some("js" | "ts" | "tsx") => lines()
+            .any(|l| l.starts_with("describe(") || l.starts_with("it(") || l.starts_with("test(")),
+        _ => false,
+    }
+}
+
+/// Which part of a repository a scan covers. Yielded paths stay relative to
+/// the repo root whatever the scope.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum SearchScope {
+    /// Walk the whole repository.
+    #[default]
+    WholeRepo,
+    /// Walk only this directory (relative to the repo root).
+    Subdir(PathBuf),
+    /// Visit only these files (relative to the repo root). They are still
+    /// subject to the extension, glob and size filters, but not to the
+    /// hidden / excluded directory rules.
+    Files(Vec<PathBuf>),
+}
+
+impl SearchScope {
+    /// First scope path that could leave the repository: an absolute path, or
+    /// one with a `..` or root component.
+    #[must_use]
+    pub fn escaping_path(&self) -> Option<&Path> {
+        let paths = match self {
+            Self::WholeRepo => return None,
+            Self::Subdir(sub) => std::slice::from_ref(sub),
+            Self::Files(files) => files.as_slice(),
+        };
+        paths
+            .iter()
+            .map(PathBuf::as_path)
+            .find(|p| !is_repo_relative(p))
+    }
+}
+
+/// Whether `path` is relative and stays below the directory it is joined to.
+#[must_use]
+pub fn is_repo_relative(path: &Path) -> bool {
+    path.components()
+        .all(|c| matches!(c, std::path::Component::Normal(_) | std::path::Component::CurDir))
+}
+
 /// Options controlling how repository files are discovered.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct RepoScanOptions {
     /// File extensions (without dot) to include, e.g. `"rs"`.
     pub include_extensions: &'static [&'static str],
 
     /// Directory names (not paths) to skip, e.g. `.git`, `target`.
-    pub exclude_dir_names: &'static [&'static str],
+    pub exclude_dir_names: Vec<String>,
+
+    /// Skip files and directories whose name starts with `.` (`.venv`,
+    /// `.mypy_cache`, `.next`, ...).
+    pub skip_hidden: bool,
+
+    /// Glob patterns (`*`, `?`, neither crossing `/`) for files to skip, e.g.
+    /// generated or minified code. Patterns without `/` match the file name,
+    /// others the repo-relative path.
+    pub exclude_file_globs: Vec<String>,
 
-    /// Skip files larger than this size.
+    /// Skip files larger than this size, unless `max_file_size_by_extension`
+    /// has an entry for the file's extension.
     pub max_file_size_bytes: usize,
+
+    /// Per-extension (without dot) overrides of `max_file_size_bytes`, e.g.
+    /// allow large `rs` files while skipping `json` early.
+    pub max_file_size_by_extension: BTreeMap<String, usize>,
+
+    /// Skip files smaller than this size (0 = keep all non-empty files).
+    /// Empty files are always skipped.
+    pub min_file_size_bytes: usize,
+
+    /// Stop after yielding this many files (`None` = no limit).
+    pub max_files: Option<usize>,
+
+    /// How symlinked files and directories are handled. Followed directories
+    /// are visited at most once, so link cycles can't loop the walk.
+    pub symlinks: SymlinkPolicy,
+
+    /// Ignore text matches inside comments and string literals.
+    /// Files whose language can't be parsed are matched in full.
+    pub code_only: bool,
+
+    /// Transcode non-UTF-8 files (Latin-1, GBK, Shift-JIS, ...) to UTF-8
+    /// instead of skipping them. Needs the `encoding` feature; without it
+    /// such files are still skipped.
+    pub auto_detect_encoding: bool,
+
+    /// Part of the repository to scan (default: all of it).
+    pub scope: SearchScope,
+
+    /// Whether test files are scanned (default: yes, like any other file).
+    pub test_files: TestFilePolicy,
 }
 
 impl Default for RepoScanOptions {
@@ -25,18 +205,121 @@ impl Default for RepoScanOptions {
                 "rs", "go", "py", "js", "ts", "tsx", "java", "c", "cpp", "cc", "cxx", "h", "hpp",
                 "rb", "php", "r", "proto",
             ],
-            exclude_dir_names: &[".git", "target"],
+            exclude_dir_names: vec![".git".to_owned(), "target".to_owned()],
+            skip_hidden: true,
+            exclude_file_globs: [
+                "*.lock",
+                "*.min.js",
+                "*.bundle.js",
+                "*.pb.go",
+                "*.pb.cc",
+                "*.pb.h",
+                "*_pb2.py",
+                "*_pb2_grpc.py",
+            ]
+            .map(str::to_owned)
+            .to_vec(),
             max_file_size_bytes: 500 * 10usize.pow(3),
+            max_file_size_by_extension: BTreeMap::new(),
+            min_file_size_bytes: 0,
+            max_files: None,
+            code_only: false,
+            symlinks: SymlinkPolicy::Skip,
+            auto_detect_encoding: false,
+            scope: SearchScope::WholeRepo,
+            test_files: TestFilePolicy::Include,
         }
     }
 }
 
+impl RepoScanOptions {
+    /// JSON Schema of the scan options.
+    #[cfg(feature = "schema")]
+    #[must_use]
+    pub fn schema() -> schemars::schema::RootSchema {
+        schemars::schema_for!(Self)
+    }
+
+    /// Also skip directories with this name.
+    #[must_use]
+    pub fn with_exclude_dir(mut self, name: impl Into<String>) -> Self {
+        let name = name.into();
+        if !self.exclude_dir_names.contains(&name) {
+            self.exclude_dir_names.push(name);
+        }
+        self
+    }
+
+    /// Also skip files matching this glob, see `exclude_file_globs`.
+    #[must_use]
+    pub fn with_exclude_file_glob(mut self, pattern: impl Into<String>) -> Self {
+        let pattern = pattern.into();
+        if !self.exclude_file_globs.contains(&pattern) {
+            self.exclude_file_globs.push(pattern);
+        }
+        self
+    }
+
+    /// Whether dotfiles and dot-directories are skipped (default: yes).
+    #[must_use]
+    pub fn with_skip_hidden(mut self, skip: bool) -> Self {
+        self.skip_hidden = skip;
+        self
+    }
+
+    /// Cap files with extension `ext` (without dot) at `max_bytes` instead of
+    /// `max_file_size_bytes`.
+    #[must_use]
+    pub fn with_max_file_size_for_extension(
+        mut self,
+        ext: impl Into<String>,
+        max_bytes: usize,
+    ) -> Self {
+        self.max_file_size_by_extension
+            .insert(ext.into(), max_bytes);
+        self
+    }
+
+    /// Size limit for `path`: its extension's override, or `max_file_size_bytes`.
+    #[must_use]
+    pub fn max_file_size_for(&self, path: &Path) -> usize {
+        path.extension()
+            .and_then(|s| s.to_str())
+            .and_then(|ext| self.max_file_size_by_extension.get(ext))
+            .copied()
+            .unwrap_or(self.max_file_size_bytes)
+    }
+
+    /// Whether non-UTF-8 files are transcoded rather than skipped (default: no).
+    #[must_use]
+    pub fn with_auto_detect_encoding(mut self, enabled: bool) -> Self {
+        self.auto_detect_encoding = enabled;
+        self
+    }
+
+    /// Restrict the scan to a subdirectory or a set of files.
+    #[must_use]
+    pub fn with_scope(mut self, scope: SearchScope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    /// Skip test files, or scan only them.
+    #[must_use]
+    pub fn with_test_files(mut self, policy: TestFilePolicy) -> Self {
+        self.test_files = policy;
+        self
+    }
+}
+
 /// A file collected from a repository scan.
 #[derive(Debug, Clone)]
 pub struct RepoFile {
     pub rel_path: PathBuf,
     pub abs_path: PathBuf,
     pub content: String,
+    /// Encoding `content` was transcoded from; `None` when the file is UTF-8.
+    pub encoding: Option<&'static str>,
 }
 
 #[derive(Debug)]
@@ -55,6 +338,10 @@ pub enum RepoScanError {
         path: PathBuf,
         source: std::path::StripPrefixError,
     },
+
+    ScopeOutsideRepo {
+        path: PathBuf,
+    },
 }
 
 impl std::fmt::Display for RepoScanError {
@@ -76,6 +363,9 @@ impl std::fmt::Display for RepoScanError {
                 repo_root.display(),
                 path.display()
             ),
+            Self::ScopeOutsideRepo { path } => {
+                write!(f, "search scope leaves the repository: {}", path.display())
+            }
         }
     }
 }
@@ -85,7 +375,7 @@ impl std::error::Error for RepoScanError {
         match self {
             Self::Io { source, .. } => Some(source),
             Self::StripPrefix { source, .. } => Some(source),
-            Self::RepoRootNotFound { .. } => None,
+            Self::RepoRootNotFound { .. } | Self::ScopeOutsideRepo { .. } => None,
         }
     }
 }
@@ -102,18 +392,214 @@ pub trait RepoFileProvider {
         repo_root: &Path,
         opt: &RepoScanOptions,
     ) -> Result<Vec<RepoFile>, RepoScanError>;
+
+    /// Visit files one by one; return `ControlFlow::Break` to stop early.
+    ///
+    /// The default implementation scans everything up front via `list_files`.
+    fn for_each_file(
+        &self,
+        repo_root: &Path,
+        opt: &RepoScanOptions,
+        on_file: &mut dyn FnMut(RepoFile) -> ControlFlow<()>,
+    ) -> Result<(), RepoScanError> {
+        for file in self.list_files(repo_root, opt)? {
+            if on_file(file).is_break() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Like `for_each_file`, but only visits files whose content contains `needle`.
+    ///
+    /// Providers can use the needle as a coarse pre-filter so non-matching
+    /// files are never materialized as `String`s.
+    fn for_each_file_containing(
+        &self,
+        repo_root: &Path,
+        opt: &RepoScanOptions,
+        needle: &str,
+        on_file: &mut dyn FnMut(RepoFile) -> ControlFlow<()>,
+    ) -> Result<(), RepoScanError> {
+        self.for_each_file(repo_root, opt, &mut |file| {
+            if file.content.contains(needle) {
+                on_file(file)
+            } else {
+                ControlFlow::Continue(())
+            }
+        })
+    }
+}
+
+/// File bytes, either memory-mapped (`mmap` feature) or read into a buffer.
+enum FileBytes {
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+    Owned(Vec<u8>),
+}
+
+impl std::ops::Deref for FileBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            #[cfg(feature = "mmap")]
+            Self::Mapped(m) => m,
+            Self::Owned(v) => v,
+        }
+    }
+}
+
+/// Files at least this large are memory-mapped (`mmap` feature); smaller ones
+/// are as cheap to read.
+#[cfg_attr(not(feature = "mmap"), allow(dead_code))]
+const MMAP_MIN_BYTES: u64 = 64 * 1024;
+
+/// Files modified this recently are read rather than mapped: they are likely
+/// still being written.
+#[cfg_attr(not(feature = "mmap"), allow(dead_code))]
+const MMAP_MIN_AGE: Duration = Duration::from_secs(2);
+
+/// Map large files when supported, falling back to `fs::read` (small or
+/// recently modified files, or platforms/filesystems where mapping fails).
+fn read_file_bytes(path: &Path, meta: &fs::Metadata) -> std::io::Result<FileBytes> {
+    #[cfg(feature = "mmap")]
+    if meta.len() >= MMAP_MIN_BYTES
+        && meta
+            .modified()
+            .ok()
+            .and_then(|t| t.elapsed().ok())
+            .is_some_and(|age| age >= MMAP_MIN_AGE)
+    {
+        let mapped = fs::File::open(path).and_then(|f| {
+            // SAFETY: `Mmap::map` is only sound while no one modifies the
+            // file, and nothing here can enforce that. Bytes written in place
+            // show up torn (the UTF-8 check rejects them, or they read as a
+            // stale version), but if another process truncates the file while
+            // it is mapped, touching the lost pages raises SIGBUS and kills
+            // the process. Skipping recently modified files narrows that
+            // window without closing it; build without `mmap` when other
+            // tools rewrite files in the scanned tree.
+            unsafe { memmap2::Mmap::map(&f) }
+        });
+        match mapped {
+            Ok(m) => return Ok(FileBytes::Mapped(m)),
+            Err(err) => tracing::debug!("mmap failed, reading instead: {path:?}, err={err}"),
+        }
+    }
+    #[cfg(not(feature = "mmap"))]
+    let _ = meta;
+
+    fs::read(path).map(FileBytes::Owned)
+}
+
+/// Guess the encoding of non-UTF-8 `bytes` and transcode them; `None` when
+/// the guess doesn't decode cleanly.
+#[cfg(feature = "encoding")]
+fn decode_non_utf8(bytes: &[u8]) -> Option<(String, &'static str)> {
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(bytes, true);
+    let encoding: &encoding_rs::Encoding = detector.guess(None, false);
+    let (text, used, had_errors) = encoding.decode(bytes);
+    (!had_errors).then(|| (text.into_owned(), used.name()))
+}
+
+#[cfg(not(feature = "encoding"))]
+fn decode_non_utf8(_bytes: &[u8]) -> Option<(String, &'static str)> {
+    None
+}
+
+fn contains_bytes(haystack: &[u8], needle: &[u8]) -> bool {
+    needle.is_empty() || haystack.windows(needle.len()).any(|w| w == needle)
 }
 
 /// File-system based repo scanner.
 #[derive(Debug, Default, Clone)]
 pub struct FsRepoFileProvider;
 
+/// How much of each file `FsRepoFileProvider::walk_dir` reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WalkMode<'a> {
+    /// Read every file.
+    Read,
+    /// Read every file, yielding only those whose bytes contain the needle.
+    Containing(&'a [u8]),
+    /// Don't read files; yielded `RepoFile`s have empty content.
+    PathsOnly,
+}
+
 impl FsRepoFileProvider {
+    /// Visit the repo-relative paths a scan would yield, without reading
+    /// file contents (size limits are checked against metadata only).
+    pub fn for_each_path(
+        &self,
+        repo_root: &Path,
+        opt: &RepoScanOptions,
+        on_path: &mut dyn FnMut(&Path) -> ControlFlow<()>,
+    ) -> Result<(), RepoScanError> {
+        if !repo_root.exists() {
+            return Err(RepoScanError::RepoRootNotFound {
+                repo_root: repo_root.to_path_buf(),
+            });
+        }
+
+        Self::walk_dir(repo_root, opt, WalkMode::PathsOnly, &mut |file| {
+            on_path(&file.rel_path)
+        })
+    }
+
+    /// Whether a scan under `opt` could yield `rel_path`, judged from the path
+    /// alone: scope, hidden and excluded directories, extension, globs and
+    /// `TestFilePolicy::Exclude`. Size, encoding and `TestFilePolicy::Only`
+    /// markers need the file itself and are left to the caller.
+    #[must_use]
+    pub fn admits_path(rel_path: &Path, opt: &RepoScanOptions) -> bool {
+        if !is_repo_relative(rel_path) || opt.scope.escaping_path().is_some() {
+            return false;
+        }
+        // Directories the walk descends through (listed files skip the dir rules)
+        let walked = match &opt.scope {
+            SearchScope::WholeRepo => Some(rel_path),
+            SearchScope::Subdir(sub) => match rel_path.strip_prefix(sub) {
+                Ok(below) => Some(below),
+                Err(_) => return false,
+            },
+            SearchScope::Files(files) => {
+                if !files.iter().any(|f| f == rel_path) {
+                    return false;
+                }
+                None
+            }
+        };
+        if let Some(walked) = walked {
+            let hidden = |name: &str| opt.skip_hidden && name.starts_with('.');
+            let file_name = walked.file_name().and_then(|s| s.to_str());
+            if file_name.is_none_or(hidden) {
+                return false;
+            }
+            let blocked_dir = walked
+                .parent()
+                .into_iter()
+                .flat_map(Path::components)
+                .any(|c| {
+                    let name = c.as_os_str().to_str();
+                    name.is_none_or(hidden) || Self::should_exclude_dir(name, opt)
+                });
+            if blocked_dir {
+                return false;
+            }
+        }
+
+        Self::should_include_file(rel_path, opt)
+            && !Self::should_exclude_file(rel_path, opt)
+            && !(opt.test_files == TestFilePolicy::Exclude && is_test_path(rel_path))
+    }
+
     fn should_exclude_dir(name: Option<&str>, opt: &RepoScanOptions) -> bool {
         let Some(name) = name else {
             return false;
         };
-        opt.exclude_dir_names.contains(&name)
+        opt.exclude_dir_names.iter().any(|d| d == name)
     }
 
     fn should_include_file(path: &Path, opt: &RepoScanOptions) -> bool {
@@ -123,119 +609,367 @@ impl FsRepoFileProvider {
         opt.include_extensions.contains(&ext)
     }
 
+    fn should_exclude_file(rel_path: &Path, opt: &RepoScanOptions) -> bool {
+        let rel = rel_path.to_string_lossy().replace('\\', "/");
+        let name = rel.rsplit('/').next().unwrap_or(&rel);
+        opt.exclude_file_globs.iter().any(|pat| {
+            if pat.contains('/') {
+                glob_match(pat, &rel)
+            } else {
+                glob_match(pat, name)
+            }
+        })
+    }
+
+    /// Metadata of a symlink's target, or `None` if the policy says to skip it
+    /// (or the link is broken).
+    fn follow_symlink(
+        path: &Path,
+        policy: SymlinkPolicy,
+        canonical_root: Option<&Path>,
+    ) -> Option<fs::Metadata> {
+        if policy == SymlinkPolicy::Skip {
+            return None;
+        }
+        let target = fs::canonicalize(path).ok()?;
+        if policy == SymlinkPolicy::FollowInRepo
+            && !canonical_root.is_some_and(|root| target.starts_with(root))
+        {
+            tracing::debug!("skip symlink leaving the repo: {path:?} -> {target:?}");
+            return None;
+        }
+        fs::metadata(&target).ok()
+    }
+
     fn walk_dir(
         repo_root: &Path,
-        dir: &Path,
         opt: &RepoScanOptions,
-        acc: &mut Vec<RepoFile>,
+        mode: WalkMode<'_>,
+        on_file: &mut dyn FnMut(RepoFile) -> ControlFlow<()>,
     ) -> Result<(), RepoScanError> {
-        let mut queue: VecDeque<PathBuf> = VecDeque::new();
-        queue.push_back(dir.to_path_buf());
-
-        while let Some(cur_dir) = queue.pop_front() {
-            let entries = match fs::read_dir(&cur_dir) {
-                Ok(v) => v,
-                Err(e) => {
-                    // - if the repo root itself is unreadable, fail fast
-                    // - otherwise, warn and continue
-                    let err = RepoScanError::Io {
-                        path: cur_dir.clone(),
-                        source: e,
-                    };
-                    if cur_dir == dir {
-                        return Err(err);
-                    }
-                    tracing::warn!("skip unreadable dir: {cur_dir:?}, err={err}");
-                    continue;
+        let mut yielded: usize = 0;
+        for entry in RepoWalker::new(repo_root, opt) {
+            let (path, meta) = entry?;
+            let Some(file) = Self::read_file(repo_root, path, &meta, opt, mode)? else {
+                continue;
+            };
+            if on_file(file).is_break() {
+                return Ok(());
+            }
+            yielded += 1;
+            if opt.max_files.is_some_and(|max| yielded >= max) {
+                tracing::debug!("stop scan: reached max_files={yielded}");
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+
+    /// Read a file [`RepoWalker`] yielded; `None` when its content is skipped.
+    fn read_file(
+        repo_root: &Path,
+        path: PathBuf,
+        meta: &fs::Metadata,
+        opt: &RepoScanOptions,
+        mode: WalkMode<'_>,
+    ) -> Result<Option<RepoFile>, RepoScanError> {
+        let rel_path = path
+            .strip_prefix(repo_root)
+            .map_err(|e| RepoScanError::StripPrefix {
+                repo_root: repo_root.to_path_buf(),
+                path: path.clone(),
+                source: e,
+            })?;
+        let test_path = is_test_path(rel_path);
+        let max_len = opt.max_file_size_for(&path);
+
+        let mut encoding = None;
+        let content = if mode == WalkMode::PathsOnly {
+            String::new()
+        } else {
+            let bytes = match read_file_bytes(&path, meta) {
+                Ok(b) => b,
+                Err(err) => {
+                    tracing::warn!("skip file (read failed): {path:?}, err={err}");
+                    return Ok(None);
                 }
             };
+            // Re-check on what was actually read: everything downstream
+            // (content, ranges, offsets) derives from these bytes only.
+            let len = bytes.len();
+            if len == 0 || len < opt.min_file_size_bytes || len > max_len {
+                return Ok(None);
+            }
+
+            // Coarse pre-filter on raw bytes; only matching files are copied out.
+            // Files that may be transcoded are checked after decoding instead.
+            if let WalkMode::Containing(needle) = mode {
+                if !contains_bytes(&bytes, needle)
+                    && (!opt.auto_detect_encoding || std::str::from_utf8(&bytes).is_ok())
+                {
+                    return Ok(None);
+                }
+            }
 
-            for entry in entries {
-                let entry = match entry {
-                    Ok(e) => e,
-                    Err(err) => {
-                        tracing::warn!("skip unreadable dir entry: {err}");
-                        continue;
+            match std::str::from_utf8(&bytes) {
+                Ok(s) => s.to_owned(),
+                Err(_) if opt.auto_detect_encoding => {
+                    let Some((text, name)) = decode_non_utf8(&bytes) else {
+                        tracing::warn!("skip file (non-utf8, unknown encoding): {path:?}");
+                        return Ok(None);
+                    };
+                    if let WalkMode::Containing(needle) = mode {
+                        if !contains_bytes(text.as_bytes(), needle) {
+                            return Ok(None);
+                        }
                     }
-                };
+                    tracing::debug!("transcoded {name} to utf-8: {path:?}");
+                    encoding = Some(name);
+                    text
+                }
+                Err(_) => {
+                    tracing::warn!("skip file (non-utf8): {path:?}");
+                    return Ok(None);
+                }
+            }
+        };
 
-                let path = entry.path();
+        if opt.test_files == TestFilePolicy::Only
+            && !test_path
+            && (mode == WalkMode::PathsOnly || !is_test_source(rel_path, &content))
+        {
+            return Ok(None);
+        }
 
-                let file_type = match entry.file_type() {
-                    Ok(t) => t,
-                    Err(err) => {
-                        tracing::warn!("skip entry with unknown type: {path:?}, err={err}");
-                        continue;
-                    }
-                };
+        Ok(Some(RepoFile {
+            rel_path: rel_path.to_path_buf(),
+            abs_path: path.clone(),
+            content,
+            encoding,
+        }))
+    }
+}
 
-                // Ignore symlinks.
-                if file_type.is_symlink() {
-                    continue;
-                }
+/// Iterator over the files a repository scan visits, as `(absolute path,
+/// metadata)`, in breadth-first order.
+///
+/// Applies the scope, directory, hidden-file and symlink rules of
+/// [`RepoScanOptions`], and the extension, glob, size and
+/// `TestFilePolicy::Exclude` filters, without reading any file. Checks that
+/// need the content (encoding, `TestFilePolicy::Only` markers) and
+/// `max_files` are left to the caller.
+///
+/// An unreadable scan root, or a scope path that is absolute or uses `..`,
+/// is yielded as an error; unreadable directories below it are logged and
+/// skipped.
+pub struct RepoWalker<'a> {
+    repo_root: PathBuf,
+    opt: &'a RepoScanOptions,
+    /// Files still to visit for `SearchScope::Files`
+    listed: std::slice::Iter<'a, PathBuf>,
+    /// Directory the walk started from; failing to read it is an error
+    start_dir: Option<PathBuf>,
+    queue: VecDeque<PathBuf>,
+    entries: Option<fs::ReadDir>,
+    /// Canonical root for the in-repo check, when links are followed
+    canonical_root: Option<PathBuf>,
+    /// Canonical dirs already queued, for cycle detection
+    visited: HashSet<PathBuf>,
+    /// Scope rejected by `new`, yielded before anything else
+    scope_error: Option<RepoScanError>,
+}
+
+impl<'a> RepoWalker<'a> {
+    #[must_use]
+    pub fn new(repo_root: &Path, opt: &'a RepoScanOptions) -> Self {
+        let scope_error = opt
+            .scope
+            .escaping_path()
+            .map(|path| RepoScanError::ScopeOutsideRepo {
+                path: path.to_path_buf(),
+            });
+        let (start_dir, listed) = match &opt.scope {
+            _ if scope_error.is_some() => (None, [].iter()),
+            SearchScope::WholeRepo => (Some(repo_root.to_path_buf()), [].iter()),
+            SearchScope::Subdir(sub) => (Some(repo_root.join(sub)), [].iter()),
+            SearchScope::Files(files) => (None, files.iter()),
+        };
+
+        // Only needed once links are followed.
+        let follow_links = opt.symlinks != SymlinkPolicy::Skip;
+        let canonical_root = follow_links
+            .then(|| fs::canonicalize(repo_root).ok())
+            .flatten();
+        let mut visited = HashSet::new();
+        if follow_links {
+            visited.extend(start_dir.as_ref().and_then(|d| fs::canonicalize(d).ok()));
+        }
 
-                if file_type.is_dir() {
-                    // Ignore excluded dirs.
-                    if !Self::should_exclude_dir(path.file_name().and_then(|s| s.to_str()), opt) {
-                        queue.push_back(path.clone());
+        Self {
+            repo_root: repo_root.to_path_buf(),
+            opt,
+            listed,
+            queue: start_dir.iter().cloned().collect(),
+            start_dir,
+            entries: None,
+            canonical_root,
+            visited,
+            scope_error,
+        }
+    }
+
+    /// Next regular file, before the file filters.
+    fn next_candidate(&mut self) -> Option<Result<PathBuf, RepoScanError>> {
+        if let Some(err) = self.scope_error.take() {
+            return Some(Err(err));
+        }
+        for rel in self.listed.by_ref() {
+            let path = self.repo_root.join(rel);
+            if path.is_file() {
+                return Some(Ok(path));
+            }
+            tracing::debug!("skip scoped path (not a file): {path:?}");
+        }
+
+        loop {
+            let Some(entries) = &mut self.entries else {
+                let dir = self.queue.pop_front()?;
+                match fs::read_dir(&dir) {
+                    Ok(v) => self.entries = Some(v),
+                    Err(e) => {
+                        // - if the scan root itself is unreadable, fail fast
+                        // - otherwise, warn and continue
+                        let err = RepoScanError::Io {
+                            path: dir.clone(),
+                            source: e,
+                        };
+                        if self.start_dir.as_ref() == Some(&dir) {
+                            return Some(Err(err));
+                        }
+                        tracing::warn!("skip unreadable dir: {dir:?}, err={err}");
                     }
+                }
+                continue;
+            };
+            let Some(entry) = entries.next() else {
+                self.entries = None;
+                continue;
+            };
+            let entry = match entry {
+                Ok(e) => e,
+                Err(err) => {
+                    tracing::warn!("skip unreadable dir entry: {err}");
                     continue;
                 }
+            };
+
+            if self.opt.skip_hidden && entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let path = entry.path();
 
-                // Ignore non-regular and non-included files.
-                if !file_type.is_file() || !Self::should_include_file(&path, opt) {
+            let file_type = match entry.file_type() {
+                Ok(t) => t,
+                Err(err) => {
+                    tracing::warn!("skip entry with unknown type: {path:?}, err={err}");
                     continue;
                 }
+            };
 
-                // Ignore files larger than max_file_size_bytes.
-                let meta = match fs::metadata(&path) {
-                    Ok(m) => m,
-                    Err(err) => {
-                        tracing::warn!("skip file (stat failed): {path:?}, err={err}");
-                        continue;
-                    }
-                };
+            let (is_dir, is_file) = if file_type.is_symlink() {
+                let policy = self.opt.symlinks;
+                match FsRepoFileProvider::follow_symlink(
+                    &path,
+                    policy,
+                    self.canonical_root.as_deref(),
+                ) {
+                    Some(meta) => (meta.is_dir(), meta.is_file()),
+                    None => continue,
+                }
+            } else {
+                (file_type.is_dir(), file_type.is_file())
+            };
 
-                if meta.len() as usize > opt.max_file_size_bytes {
+            if is_dir {
+                let name = path.file_name().and_then(|s| s.to_str());
+                // Ignore excluded dirs.
+                if FsRepoFileProvider::should_exclude_dir(name, self.opt) {
                     continue;
                 }
-
-                let bytes = match fs::read(&path) {
-                    Ok(b) => b,
-                    Err(err) => {
-                        tracing::warn!("skip file (read failed): {path:?}, err={err}");
-                        continue;
-                    }
-                };
-                if bytes.len() > opt.max_file_size_bytes {
+                // Ignore dirs already reached through another path (link cycles).
+                if self.opt.symlinks != SymlinkPolicy::Skip
+                    && !fs::canonicalize(&path).is_ok_and(|c| self.visited.insert(c))
+                {
                     continue;
                 }
+                self.queue.push_back(path);
+                continue;
+            }
 
-                let content = match std::str::from_utf8(&bytes) {
-                    Ok(s) => s.to_owned(),
-                    Err(_) => {
-                        tracing::warn!("skip file (non-utf8): {path:?}");
-                        continue;
-                    }
-                };
+            // Ignore non-regular files.
+            if is_file {
+                return Some(Ok(path));
+            }
+        }
+    }
 
-                let rel_path =
-                    path.strip_prefix(repo_root)
-                        .map_err(|e| RepoScanError::StripPrefix {
-                            repo_root: repo_root.to_path_buf(),
-                            path: path.clone(),
-                            source: e,
-                        })?;
+    /// Apply the file filters to `path`; `None` when it is skipped.
+    fn check_file(&self, path: PathBuf) -> Result<Option<(PathBuf, fs::Metadata)>, RepoScanError> {
+        let (repo_root, opt) = (self.repo_root.as_path(), self.opt);
+        if !FsRepoFileProvider::should_include_file(&path, opt) {
+            return Ok(None);
+        }
 
-                acc.push(RepoFile {
-                    rel_path: rel_path.to_path_buf(),
-                    abs_path: path.clone(),
-                    content,
-                });
+        let rel_path = path
+            .strip_prefix(repo_root)
+            .map_err(|e| RepoScanError::StripPrefix {
+                repo_root: repo_root.to_path_buf(),
+                path: path.clone(),
+                source: e,
+            })?;
+
+        if FsRepoFileProvider::should_exclude_file(rel_path, opt) {
+            return Ok(None);
+        }
+        // Content can only add test files (inline test modules), so `Only`
+        // is checked once the file is read.
+        if opt.test_files == TestFilePolicy::Exclude && is_test_path(rel_path) {
+            return Ok(None);
+        }
+
+        // Ignore empty files and files outside [min, max]_file_size_bytes.
+        let meta = match fs::metadata(&path) {
+            Ok(m) => m,
+            Err(err) => {
+                tracing::warn!("skip file (stat failed): {path:?}, err={err}");
+                return Ok(None);
             }
+        };
+
+        // Cheap pre-filter; the file may still change before it is read.
+        let len = meta.len() as usize;
+        if len == 0 || len < opt.min_file_size_bytes || len > opt.max_file_size_for(&path) {
+            return Ok(None);
         }
+        Ok(Some((path, meta)))
+    }
+}
 
-        Ok(())
+impl Iterator for RepoWalker<'_> {
+    type Item = Result<(PathBuf, fs::Metadata), RepoScanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let path = match self.next_candidate()? {
+                Ok(path) => path,
+                Err(err) => return Some(Err(err)),
+            };
+            match self.check_file(path) {
+                Ok(Some(file)) => return Some(Ok(file)),
+                Ok(None) => {}
+                Err(err) => return Some(Err(err)),
+            }
+        }
     }
 }
 
@@ -245,14 +979,568 @@ impl RepoFileProvider for FsRepoFileProvider {
         repo_root: &Path,
         opt: &RepoScanOptions,
     ) -> Result<Vec<RepoFile>, RepoScanError> {
+        let mut acc = Vec::new();
+        self.for_each_file(repo_root, opt, &mut |file| {
+            acc.push(file);
+            ControlFlow::Continue(())
+        })?;
+        Ok(acc)
+    }
+
+    fn for_each_file(
+        &self,
+        repo_root: &Path,
+        opt: &RepoScanOptions,
+        on_file: &mut dyn FnMut(RepoFile) -> ControlFlow<()>,
+    ) -> Result<(), RepoScanError> {
         if !repo_root.exists() {
             return Err(RepoScanError::RepoRootNotFound {
                 repo_root: repo_root.to_path_buf(),
             });
         }
 
-        let mut acc = Vec::new();
-        Self::walk_dir(repo_root, repo_root, opt, &mut acc)?;
-        Ok(acc)
+        Self::walk_dir(repo_root, opt, WalkMode::Read, on_file)
+    }
+
+    fn for_each_file_containing(
+        &self,
+        repo_root: &Path,
+        opt: &RepoScanOptions,
+        needle: &str,
+        on_file: &mut dyn FnMut(RepoFile) -> ControlFlow<()>,
+    ) -> Result<(), RepoScanError> {
+        if !repo_root.exists() {
+            return Err(RepoScanError::RepoRootNotFound {
+                repo_root: repo_root.to_path_buf(),
+            });
+        }
+
+        Self::walk_dir(
+            repo_root,
+            opt,
+            WalkMode::Containing(needle.as_bytes()),
+            on_file,
+        )
+    }
+}
+
+/// Minimal glob matcher: `*` matches any run of characters, `?` a single one;
+/// neither matches `/`, so `gen/*.rs` doesn't reach into `gen/sub/`.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let p = pattern.as_bytes();
+    let t = text.as_bytes();
+    let (mut pi, mut ti) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == t[ti] || (p[pi] == b'?' && t[ti] != b'/')) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == b'*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star.filter(|&(_, st)| t[st] != b'/') {
+            pi = sp + 1;
+            ti = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unique_tmp_dir;
+
+    #[test]
+    fn glob_match_handles_wildcards() {
+        assert!(glob_match("*.min.js", "app.min.js"));
+        assert!(!glob_match("*.min.js", "app.js"));
+        assert!(glob_match("*_pb2.py", "api_pb2.py"));
+        assert!(glob_match("gen/*.rs", "gen/a.rs"));
+        assert!(glob_match("?.rs", "a.rs"));
+        assert!(!glob_match("?.rs", "ab.rs"));
+        // Wildcards stay within one path segment
+        assert!(!glob_match("gen/*.rs", "gen/sub/a.rs"));
+        assert!(glob_match("gen/*/*.rs", "gen/sub/a.rs"));
+        assert!(!glob_match("*.rs", "src/a.rs"));
+        assert!(!glob_match("gen?a.rs", "gen/a.rs"));
+    }
+
+    #[test]
+    fn default_options_skip_generated_files() {
+        let opt = RepoScanOptions::default();
+        assert!(FsRepoFileProvider::should_exclude_file(
+            Path::new("proto/api.pb.go"),
+            &opt
+        ));
+        assert!(FsRepoFileProvider::should_exclude_file(
+            Path::new("web/vendor.min.js"),
+            &opt
+        ));
+        assert!(!FsRepoFileProvider::should_exclude_file(
+            Path::new("src/main.rs"),
+            &opt
+        ));
+
+        let opt = opt.with_exclude_file_glob("src/gen/*.rs");
+        assert!(FsRepoFileProvider::should_exclude_file(
+            Path::new("src/gen/api.rs"),
+            &opt
+        ));
+        assert!(!FsRepoFileProvider::should_exclude_file(
+            Path::new("src/gen/v2/api.rs"),
+            &opt
+        ));
+    }
+
+    #[test]
+    fn admits_path_follows_the_walk_rules() {
+        let admits =
+            |rel: &str, opt: &RepoScanOptions| FsRepoFileProvider::admits_path(Path::new(rel), opt);
+        let opt = RepoScanOptions::default();
+        assert!(admits("src/lib.rs", &opt));
+        assert!(!admits(".git/config", &opt));
+        assert!(!admits("target/debug/a.rs", &opt));
+        assert!(!admits(".hidden/a.rs", &opt));
+        assert!(!admits("notes.txt", &opt));
+        assert!(!admits("web/app.min.js", &opt));
+        assert!(!admits("../a.rs", &opt));
+
+        let sub = opt
+            .clone()
+            .with_scope(SearchScope::Subdir(PathBuf::from("src")));
+        assert!(admits("src/lib.rs", &sub));
+        assert!(!admits("benches/a.rs", &sub));
+
+        // Listed files skip the directory rules, but nothing else is in scope
+        let files = opt.with_scope(SearchScope::Files(vec![PathBuf::from(".ci/check.rs")]));
+        assert!(admits(".ci/check.rs", &files));
+        assert!(!admits("src/lib.rs", &files));
+    }
+
+    #[test]
+    fn for_each_file_containing_skips_non_matching_files() {
+        let root = unique_tmp_dir("luna-scan-test");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("a.rs"), "fn needle() {}\n").unwrap();
+        fs::write(root.join("b.rs"), "fn other() {}\n").unwrap();
+        fs::write(root.join("empty.rs"), "").unwrap();
+
+        let mut seen = Vec::new();
+        FsRepoFileProvider
+            .for_each_file_containing(&root, &RepoScanOptions::default(), "needle", &mut |f| {
+                seen.push(f.rel_path);
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+        assert_eq!(seen, vec![PathBuf::from("a.rs")]);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn files_above_the_mmap_threshold_read_like_small_ones() {
+        let root = unique_tmp_dir("luna-scan-mmap-test");
+        fs::create_dir_all(&root).unwrap();
+        let padding = "// padding\n".repeat(MMAP_MIN_BYTES as usize / 11 + 1);
+        let big = format!("{padding}fn needle() {{}}\n");
+        let path = root.join("big.rs");
+        fs::write(&path, &big).unwrap();
+        let aged = std::time::SystemTime::now() - MMAP_MIN_AGE * 10;
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(aged)
+            .unwrap();
+
+        let bytes = read_file_bytes(&path, &fs::metadata(&path).unwrap()).unwrap();
+        #[cfg(feature = "mmap")]
+        assert!(matches!(bytes, FileBytes::Mapped(_)));
+        assert_eq!(&*bytes, big.as_bytes());
+
+        // A file that was just written is read, not mapped
+        fs::write(&path, &big).unwrap();
+        let bytes = read_file_bytes(&path, &fs::metadata(&path).unwrap()).unwrap();
+        assert!(matches!(bytes, FileBytes::Owned(_)));
+        assert_eq!(&*bytes, big.as_bytes());
+
+        let mut seen = Vec::new();
+        FsRepoFileProvider
+            .for_each_file_containing(&root, &RepoScanOptions::default(), "needle", &mut |f| {
+                seen.push((f.rel_path, f.content.len()));
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+        assert_eq!(seen, vec![(PathBuf::from("big.rs"), big.len())]);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn non_utf8_files_are_skipped_unless_transcoding() {
+        let root = unique_tmp_dir("luna-scan-encoding-test");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("a.rs"), "// caf\u{e9}\nfn utf8() {}\n").unwrap();
+        // Latin-1: 0xE9 alone is not valid UTF-8.
+        fs::write(
+            root.join("b.rs"),
+            b"// caf\xe9 cr\xe8me br\xfbl\xe9e\nfn latin() {}\n",
+        )
+        .unwrap();
+
+        let scan = |opt: &RepoScanOptions, needle: &str| {
+            let mut seen = Vec::new();
+            FsRepoFileProvider
+                .for_each_file_containing(&root, opt, needle, &mut |f| {
+                    seen.push((f.rel_path, f.content, f.encoding));
+                    ControlFlow::Continue(())
+                })
+                .unwrap();
+            seen.sort();
+            seen
+        };
+
+        let plain = scan(&RepoScanOptions::default(), "fn");
+        assert_eq!(plain.len(), 1);
+        assert_eq!(plain[0].2, None);
+
+        let opt = RepoScanOptions::default().with_auto_detect_encoding(true);
+        let detected = scan(&opt, "fn");
+        if cfg!(feature = "encoding") {
+            assert_eq!(detected.len(), 2);
+            let (path, content, encoding) = &detected[1];
+            assert_eq!(path, Path::new("b.rs"));
+            assert!(content.contains("crème brûlée"), "{content}");
+            assert!(encoding.is_some());
+            // Non-ASCII needles match the transcoded text, not the raw bytes.
+            assert_eq!(scan(&opt, "brûlée").len(), 1);
+        } else {
+            assert_eq!(detected, plain);
+        }
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn min_file_size_skips_tiny_and_empty_files() {
+        let root = unique_tmp_dir("luna-scan-min-test");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("empty.rs"), "").unwrap();
+        fs::write(root.join("mod.rs"), "mod a;\n").unwrap();
+        fs::write(root.join("a.rs"), "pub fn a() -> i32 {\n    1\n}\n").unwrap();
+
+        let names = |opt: &RepoScanOptions| {
+            let mut names = FsRepoFileProvider
+                .list_files(&root, opt)
+                .unwrap()
+                .into_iter()
+                .map(|f| f.rel_path)
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        let opt = RepoScanOptions::default();
+        assert_eq!(
+            names(&opt),
+            vec![PathBuf::from("a.rs"), PathBuf::from("mod.rs")]
+        );
+
+        let opt = RepoScanOptions {
+            min_file_size_bytes: 16,
+            ..RepoScanOptions::default()
+        };
+        assert_eq!(names(&opt), vec![PathBuf::from("a.rs")]);
+
+        // A per-extension cap overrides the global one in both directions.
+        let opt = RepoScanOptions {
+            max_file_size_bytes: 8,
+            ..RepoScanOptions::default()
+        };
+        assert_eq!(names(&opt), vec![PathBuf::from("mod.rs")]);
+        let opt = opt.with_max_file_size_for_extension("rs", 64);
+        assert_eq!(
+            names(&opt),
+            vec![PathBuf::from("a.rs"), PathBuf::from("mod.rs")]
+        );
+        let opt = opt.with_max_file_size_for_extension("rs", 4);
+        assert!(names(&opt).is_empty());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn hidden_entries_are_skipped_unless_disabled() {
+        let root = unique_tmp_dir("luna-scan-hidden-test");
+        fs::create_dir_all(root.join(".venv/lib")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join("a.py"), "x = 1\n").unwrap();
+        fs::write(root.join(".venv/lib/site.py"), "y = 2\n").unwrap();
+        fs::write(root.join(".hidden.py"), "z = 3\n").unwrap();
+        fs::write(root.join(".git/hook.py"), "w = 4\n").unwrap();
+
+        let names = |opt: &RepoScanOptions| {
+            let mut names = FsRepoFileProvider
+                .list_files(&root, opt)
+                .unwrap()
+                .into_iter()
+                .map(|f| f.rel_path)
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        let opt = RepoScanOptions::default();
+        assert_eq!(names(&opt), vec![PathBuf::from("a.py")]);
+
+        // `.git` stays excluded by name.
+        let opt = opt.with_skip_hidden(false);
+        assert_eq!(
+            names(&opt),
+            vec![
+                PathBuf::from(".hidden.py"),
+                PathBuf::from(".venv/lib/site.py"),
+                PathBuf::from("a.py")
+            ]
+        );
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn scope_limits_the_walk_but_keeps_repo_relative_paths() {
+        let root = unique_tmp_dir("luna-scan-scope-test");
+        fs::create_dir_all(root.join("src/agent")).unwrap();
+        fs::create_dir_all(root.join(".github")).unwrap();
+        fs::write(root.join("src/lib.rs"), "fn lib() {}\n").unwrap();
+        fs::write(root.join("src/agent/plan.rs"), "fn plan() {}\n").unwrap();
+        fs::write(root.join("src/agent/act.rs"), "fn act() {}\n").unwrap();
+        fs::write(root.join(".github/check.py"), "x = 1\n").unwrap();
+        fs::write(root.join("notes.txt"), "fn text() {}\n").unwrap();
+
+        let names = |scope: SearchScope| {
+            let opt = RepoScanOptions::default().with_scope(scope);
+            let mut names = FsRepoFileProvider
+                .list_files(&root, &opt)
+                .unwrap()
+                .into_iter()
+                .map(|f| f.rel_path)
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        assert_eq!(names(SearchScope::WholeRepo).len(), 3);
+        assert_eq!(
+            names(SearchScope::Subdir(PathBuf::from("src/agent"))),
+            vec![
+                PathBuf::from("src/agent/act.rs"),
+                PathBuf::from("src/agent/plan.rs")
+            ]
+        );
+        // Listed files skip the hidden-dir rule but not the extension filter;
+        // missing ones are ignored.
+        assert_eq!(
+            names(SearchScope::Files(vec![
+                PathBuf::from("src/lib.rs"),
+                PathBuf::from(".github/check.py"),
+                PathBuf::from("notes.txt"),
+                PathBuf::from("src/gone.rs"),
+            ])),
+            vec![
+                PathBuf::from(".github/check.py"),
+                PathBuf::from("src/lib.rs")
+            ]
+        );
+
+        let mut seen = Vec::new();
+        let opt =
+            RepoScanOptions::default().with_scope(SearchScope::Subdir(PathBuf::from("src/agent")));
+        FsRepoFileProvider
+            .for_each_file_containing(&root, &opt, "plan", &mut |f| {
+                seen.push(f.rel_path);
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+        assert_eq!(seen, vec![PathBuf::from("src/agent/plan.rs")]);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn scope_paths_leaving_the_repo_are_rejected() {
+        let base = unique_tmp_dir("luna-scan-escape-test");
+        let root = base.join("repo");
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(base.join("other")).unwrap();
+        fs::write(root.join("a.rs"), "fn a() {}\n").unwrap();
+        fs::write(base.join("other/secret.rs"), "fn secret() {}\n").unwrap();
+        let abs_file = base.join("other/secret.rs");
+
+        for scope in [
+            SearchScope::Subdir(PathBuf::from("../other")),
+            SearchScope::Subdir(base.join("other")),
+            SearchScope::Files(vec![PathBuf::from("a.rs"), abs_file.clone()]),
+            SearchScope::Files(vec![PathBuf::from("src/../../other/secret.rs")]),
+        ] {
+            let opt = RepoScanOptions::default().with_scope(scope.clone());
+            let err = FsRepoFileProvider.list_files(&root, &opt).unwrap_err();
+            assert!(
+                matches!(err, RepoScanError::ScopeOutsideRepo { .. }),
+                "{scope:?}: {err}"
+            );
+        }
+
+        // `./` segments stay inside.
+        let opt =
+            RepoScanOptions::default().with_scope(SearchScope::Files(vec![PathBuf::from("./a.rs")]));
+        assert_eq!(FsRepoFileProvider.list_files(&root, &opt).unwrap().len(), 1);
+
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn test_files_are_recognized_by_path_and_content() {
+        for path in [
+            "tests/cli.rs",
+            "src/parser_test.rs",
+            "pkg/server_test.go",
+            "test_api.py",
+            "web/app.spec.ts",
+            "web/__tests__/app.js",
+            "src/test/java/FooTest.java",
+        ] {
+            assert!(is_test_path(Path::new(path)), "{path}");
+        }
+        for path in ["src/lib.rs", "src/latest.rs", "testdata/input.rs"] {
+            assert!(!is_test_path(Path::new(path)), "{path}");
+        }
+
+        let lib = Path::new("src/lib.rs");
+        assert!(is_test_source(lib, "fn a() {}\n#[cfg(test)]\nmod tests {}"));
+        assert!(!is_test_source(lib, "fn a() {}\n"));
+        assert!(is_test_source(Path::new("api.py"), "def test_get(): ..."));
+    }
+
+    #[test]
+    fn repo_walker_yields_filtered_files_with_metadata() {
+        let root = unique_tmp_dir("luna-scan-walker-test");
+        for dir in ["src", "target", ".cache"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        fs::write(root.join("src/a.rs"), "fn a() {}\n").unwrap();
+        fs::write(root.join("target/b.rs"), "fn b() {}\n").unwrap();
+        fs::write(root.join(".cache/c.rs"), "fn c() {}\n").unwrap();
+        fs::write(root.join("notes.txt"), "notes\n").unwrap();
+
+        let opt = RepoScanOptions::default();
+        let seen = RepoWalker::new(&root, &opt)
+            .map(|entry| {
+                let (path, meta) = entry.unwrap();
+                (path.strip_prefix(&root).unwrap().to_path_buf(), meta.len())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(seen, vec![(PathBuf::from("src/a.rs"), 10)]);
+
+        // A missing scan root is an error, not an empty walk.
+        let opt = opt.with_scope(SearchScope::Subdir(PathBuf::from("gone")));
+        let mut walker = RepoWalker::new(&root, &opt);
+        assert!(matches!(walker.next(), Some(Err(RepoScanError::Io { .. }))));
+        assert!(walker.next().is_none());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_file_policy_excludes_or_keeps_only_tests() {
+        let root = unique_tmp_dir("luna-scan-tests-test");
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("tests")).unwrap();
+        fs::write(root.join("src/lib.rs"), "fn lib() {}\n").unwrap();
+        fs::write(
+            root.join("src/parse.rs"),
+            "fn parse() {}\n#[cfg(test)]\nmod tests {}\n",
+        )
+        .unwrap();
+        fs::write(root.join("tests/cli.rs"), "#[test]\nfn cli() {}\n").unwrap();
+
+        let names = |policy: TestFilePolicy| {
+            let opt = RepoScanOptions::default().with_test_files(policy);
+            let mut names = FsRepoFileProvider
+                .list_files(&root, &opt)
+                .unwrap()
+                .into_iter()
+                .map(|f| f.rel_path)
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        assert_eq!(names(TestFilePolicy::Include).len(), 3);
+        // Inline test modules don't make an implementation file a test file...
+        assert_eq!(
+            names(TestFilePolicy::Exclude),
+            vec![PathBuf::from("src/lib.rs"), PathBuf::from("src/parse.rs")]
+        );
+        // ...but they are what `Only` is after.
+        assert_eq!(
+            names(TestFilePolicy::Only),
+            vec![PathBuf::from("src/parse.rs"), PathBuf::from("tests/cli.rs")]
+        );
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_policy_controls_links_and_stops_cycles() {
+        use std::os::unix::fs::symlink;
+
+        let base = unique_tmp_dir("luna-scan-link-test");
+        let root = base.join("repo");
+        let outside = base.join("outside");
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(root.join("src/a.rs"), "fn a() {}\n").unwrap();
+        fs::write(outside.join("b.rs"), "fn b() {}\n").unwrap();
+        symlink(&root, root.join("src/loop")).unwrap();
+        symlink(root.join("src/a.rs"), root.join("alias.rs")).unwrap();
+        symlink(&outside, root.join("ext")).unwrap();
+
+        let names = |symlinks: SymlinkPolicy| {
+            let opt = RepoScanOptions {
+                symlinks,
+                ..RepoScanOptions::default()
+            };
+            let mut names = FsRepoFileProvider
+                .list_files(&root, &opt)
+                .unwrap()
+                .into_iter()
+                .map(|f| f.rel_path)
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        assert_eq!(names(SymlinkPolicy::Skip), vec![PathBuf::from("src/a.rs")]);
+        assert_eq!(
+            names(SymlinkPolicy::FollowInRepo),
+            vec![PathBuf::from("alias.rs"), PathBuf::from("src/a.rs")]
+        );
+        assert_eq!(
+            names(SymlinkPolicy::Follow),
+            vec![
+                PathBuf::from("alias.rs"),
+                PathBuf::from("ext/b.rs"),
+                PathBuf::from("src/a.rs")
+            ]
+        );
+
+        let _ = fs::remove_dir_all(&base);
     }
 }
@@ -0,0 +1,183 @@
+//! File skeletons: declaration headers with bodies elided.
+//!
+//! The scope graph decides *what* is a declaration (any syntax node that owns a
+//! definition), the syntax tree decides *where* its body starts. Containers
+//! (impl/trait/mod/class/struct bodies) are expanded recursively; callables are
+//! collapsed to `{ ... }`.
+
+use std::path::Path;
+
+use tree_sitter::Node;
+
+use crate::{
+    navigation::NavigationError, repo_scan::FsRepoFileProvider, NodeKind, ScopeGraph,
+    TreeSitterFile, TreeSitterNavigator,
+};
+
+const INDENT: &str = "    ";
+
+/// Build the skeleton of a source file on disk.
+pub fn file_skeleton(path: &Path) -> Result<String, NavigationError> {
+    let content = std::fs::read_to_string(path).map_err(|e| NavigationError::Io {
+        path: path.to_path_buf(),
+        source: e,
+    })?;
+    let tree_sitter_err = |source| NavigationError::TreeSitter {
+        rel_path: path.to_path_buf(),
+        source,
+    };
+    let lang_id = TreeSitterNavigator::<FsRepoFileProvider>::lang_id_for_path(path)
+        .ok_or_else(|| tree_sitter_err(crate::TreeSitterFileError::UnsupportedLanguage))?;
+
+    TreeSitterFile::try_build(content.as_bytes(), lang_id)
+        .and_then(|ts| ts.skeleton())
+        .map_err(tree_sitter_err)
+}
+
+pub(crate) fn render(src: &[u8], root: Node<'_>, graph: &ScopeGraph) -> String {
+    let defs = def_starts(graph);
+
+    let mut lines = Vec::new();
+    render_children(src, root, &defs, 0, &mut lines);
+
+    let mut out = lines.join("\n");
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out
+}
+
+/// Start bytes of every definition in the scope graph.
+pub(crate) fn def_starts(graph: &ScopeGraph) -> Vec<usize> {
+    graph
+        .graph
+        .node_weights()
+        .filter_map(|n| match n {
+            NodeKind::Def(d) => Some(d.range.start.byte),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether `node` is (or contains) a declaration.
+pub(crate) fn owns_def(node: Node<'_>, defs: &[usize]) -> bool {
+    defs.iter()
+        .any(|&b| b >= node.start_byte() && b < node.end_byte())
+}
+
+fn render_children(
+    src: &[u8],
+    parent: Node<'_>,
+    defs: &[usize],
+    depth: usize,
+    out: &mut Vec<String>,
+) {
+    let mut cursor = parent.walk();
+    for child in parent.named_children(&mut cursor) {
+        render_node(src, child, defs, depth, out);
+    }
+}
+
+fn render_node(src: &[u8], node: Node<'_>, defs: &[usize], depth: usize, out: &mut Vec<String>) {
+    if !owns_def(node, defs) {
+        return;
+    }
+
+    // Python-style decorators wrap the real definition.
+    if let Some(inner) = node.child_by_field_name("definition") {
+        render_node(src, inner, defs, depth, out);
+        return;
+    }
+
+    let indent = INDENT.repeat(depth);
+    let Some(body) = node.child_by_field_name("body") else {
+        let text = node_text(src, node.start_byte(), node.end_byte());
+        let first = text.lines().next().unwrap_or_default().trim_end();
+        out.push(format!("{indent}{first}"));
+        return;
+    };
+
+    let header = node_text(src, node.start_byte(), body.start_byte());
+    let header = reindent(header.trim_end(), node.start_position().column, &indent);
+
+    if is_callable(node.kind()) {
+        out.push(format!("{header} {{ ... }}"));
+        return;
+    }
+
+    let mut nested = Vec::new();
+    render_children(src, body, defs, depth + 1, &mut nested);
+    if nested.is_empty() {
+        out.push(format!("{header} {{ ... }}"));
+    } else {
+        out.push(format!("{header} {{"));
+        out.extend(nested);
+        out.push(format!("{indent}}}"));
+    }
+}
+
+pub(crate) fn is_callable(kind: &str) -> bool {
+    ["function", "method", "constructor", "lambda", "closure"]
+        .iter()
+        .any(|k| kind.contains(k))
+}
+
+pub(crate) fn node_text(src: &[u8], start: usize, end: usize) -> String {
+    String::from_utf8_lossy(&src[start..end.min(src.len())]).into_owned()
+}
+
+/// Re-indent a (possibly multi-line) header so continuation lines keep their
+/// offset relative to the declaration's own column.
+fn reindent(header: &str, column: usize, indent: &str) -> String {
+    header
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            if i == 0 {
+                return format!("{indent}{line}");
+            }
+            let leading = line.len() - line.trim_start().len();
+            format!("{indent}{}", &line[leading.min(column)..])
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rust_skeleton_elides_bodies_and_keeps_nesting() {
+        let src = r#"use std::fmt;
+
+/// Doc
+#[derive(Debug)]
+pub struct Foo {
+    pub a: i32,
+}
+
+impl Foo {
+    pub fn new(a: i32) -> Self {
+        Self { a }
+    }
+
+    fn get(
+        &self,
+    ) -> i32 {
+        self.a
+    }
+}
+
+fn helper() -> i32 {
+    let x = 1;
+    x
+}
+"#;
+        let ts = TreeSitterFile::try_build(src.as_bytes(), "rust").unwrap();
+        assert_eq!(
+            ts.skeleton().unwrap(),
+            "pub struct Foo {\n    pub a: i32\n}\nimpl Foo {\n    pub fn new(a: i32) -> Self { ... }\n    fn get(\n        &self,\n    ) -> i32 { ... }\n}\nfn helper() -> i32 { ... }\n"
+        );
+    }
+}
@@ -1,5 +1,7 @@
 use core::text_range::TextRange;
 
+use crate::document::line_content_end;
+
 /// A snippet of text around a highlight range.
 #[derive(Debug, Clone)]
 pub struct Snippet {
@@ -35,7 +37,8 @@ impl Default for SnippetBuilder {
 impl SnippetBuilder {
     /// Expand a `TextRange` into a rendered snippet.
     ///
-    /// `line_end_indices` should contain the byte index of each `\n`.
+    /// `line_end_indices` should contain the byte index of each `\n`. CRLF
+    /// files render without their `\r`s; highlight offsets stay byte-based.
     pub fn build(&self, content: &str, line_end_indices: &[usize], range: TextRange) -> Snippet {
         let total_lines = line_end_indices.len().saturating_add(1);
         if total_lines == 0 {
@@ -49,18 +52,29 @@ impl SnippetBuilder {
         let focus_line = range.start.line.min(total_lines.saturating_sub(1));
         let start_line = focus_line.saturating_sub(self.context_lines);
         let end_line = (focus_line + self.context_lines).min(total_lines.saturating_sub(1));
+        self.build_lines(content, line_end_indices, range, start_line, end_line)
+    }
+
+    /// Render the 0-based lines `start_line..=end_line` (clamped to the file)
+    /// instead of a window around `range`, still highlighting `range`.
+    pub fn build_lines(
+        &self,
+        content: &str,
+        line_end_indices: &[usize],
+        range: TextRange,
+        start_line: usize,
+        end_line: usize,
+    ) -> Snippet {
+        let end_line = end_line.min(line_end_indices.len());
+        let start_line = start_line.min(end_line);
 
         let mut rendered = String::new();
         for line in start_line..=end_line {
             if line != start_line {
                 rendered.push('\n');
             }
-            let (line_start, line_end) = line_bounds(content.len(), line_end_indices, line);
-            let mut line_str = &content[line_start..line_end];
-            // strip trailing '\r' for Windows files
-            if let Some(stripped) = line_str.strip_suffix('\r') {
-                line_str = stripped;
-            }
+            let (line_start, line_end) = line_bounds(content, line_end_indices, line);
+            let line_str = &content[line_start..line_end];
 
             let mut body = if self.with_highlight {
                 highlight_line(
@@ -90,7 +104,9 @@ impl SnippetBuilder {
     }
 }
 
-fn line_bounds(content_len: usize, line_end_indices: &[usize], line: usize) -> (usize, usize) {
+/// Byte range of a line's content, without its terminator (`\n` or `\r\n`).
+fn line_bounds(content: &str, line_end_indices: &[usize], line: usize) -> (usize, usize) {
+    let content_len = content.len();
     let start = if line == 0 {
         0
     } else {
@@ -101,9 +117,13 @@ fn line_bounds(content_len: usize, line_end_indices: &[usize], line: usize) -> (
             .saturating_add(1)
     };
 
-    let end = line_end_indices.get(line).copied().unwrap_or(content_len);
+    let end = match line_end_indices.get(line) {
+        Some(&newline) if newline < content_len => line_content_end(content, newline),
+        _ => content_len,
+    };
 
-    (start.min(content_len), end.min(content_len))
+    let start = start.min(content_len);
+    (start, end.max(start))
 }
 
 fn highlight_line(
@@ -116,16 +136,45 @@ fn highlight_line(
 ) -> String {
     let hl_start = range.start.byte.max(line_start);
     let hl_end = range.end.byte.min(line_end);
-    if hl_start >= hl_end {
+    // The range may come from an older read of a file that has since changed,
+    // so it can point past the end or into the middle of a char.
+    if hl_start >= hl_end
+        || !content.is_char_boundary(hl_start)
+        || !content.is_char_boundary(hl_end)
+    {
         return line_str.to_owned();
     }
 
-    let rel_start = hl_start.saturating_sub(line_start);
-    let rel_end = hl_end.saturating_sub(line_start);
-
-    // Safety: tree-sitter ranges should align to UTF-8 boundaries.
-    let before = &content[line_start..line_start + rel_start];
-    let mid = &content[line_start + rel_start..line_start + rel_end];
-    let after = &content[line_start + rel_end..line_end];
+    let before = &content[line_start..hl_start];
+    let mid = &content[hl_start..hl_end];
+    let after = &content[hl_end..line_end];
     format!("{before}{}{mid}{}{after}", marker.0, marker.1)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::text_range::Position;
+
+    fn range(start: usize, end: usize) -> TextRange {
+        TextRange::new(Position::new(start, 0, start), Position::new(end, 0, end))
+    }
+
+    #[test]
+    fn stale_ranges_do_not_panic() {
+        let content = "let café = 1;";
+        let builder = SnippetBuilder {
+            with_line_numbers: false,
+            ..SnippetBuilder::default()
+        };
+
+        // Highlights on char boundaries as usual.
+        assert_eq!(
+            builder.build(content, &[], range(4, 9)).text,
+            "let §café§ = 1;"
+        );
+        // Ends inside `é` or past the end of the (since shrunk) file.
+        assert_eq!(builder.build(content, &[], range(4, 8)).text, content);
+        assert_eq!(builder.build(content, &[], range(40, 48)).text, content);
+    }
+}
@@ -0,0 +1,188 @@
+//! Repository statistics: files and lines per language, declarations per kind,
+//! and function sizes.
+//!
+//! Files come from the same scan as search (ignore rules and size caps
+//! included). Declarations are scope-graph definitions that are not locals of
+//! a function.
+
+use std::{collections::BTreeMap, ops::ControlFlow, path::Path};
+
+use serde::Serialize;
+use tree_sitter::Node;
+
+use crate::{
+    navigation::{detect_lang_id, NavigationError},
+    repo_scan::{FsRepoFileProvider, RepoFileProvider, RepoScanOptions},
+    skeleton, NodeKind, TreeSitterFile,
+};
+
+/// Language key for files without a supported grammar.
+const OTHER: &str = "other";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RepoStats {
+    pub files: usize,
+    pub total_lines: usize,
+    /// Keyed by language id (`other` for unsupported extensions)
+    pub files_by_language: BTreeMap<String, usize>,
+    pub lines_by_language: BTreeMap<String, usize>,
+    /// Keyed by symbol kind (`function`, `struct`, ...)
+    pub symbols_by_kind: BTreeMap<String, usize>,
+    /// Named functions and methods
+    pub functions: usize,
+    /// Sum of their line spans
+    pub function_lines: usize,
+    /// Files that matched a grammar but failed to parse
+    pub unparsed_files: usize,
+}
+
+impl RepoStats {
+    /// Mean function length in lines, `None` when there are no functions.
+    #[must_use]
+    pub fn avg_function_lines(&self) -> Option<f64> {
+        (self.functions > 0).then(|| self.function_lines as f64 / self.functions as f64)
+    }
+
+    /// One line per metric, for terminal output.
+    #[must_use]
+    pub fn render(&self) -> String {
+        let mut out = format!("files: {}\nlines: {}\n", self.files, self.total_lines);
+        for (lang, files) in &self.files_by_language {
+            let lines = self.lines_by_language.get(lang).copied().unwrap_or(0);
+            out.push_str(&format!("  {lang}: {files} files, {lines} lines\n"));
+        }
+        out.push_str("symbols:\n");
+        for (kind, count) in &self.symbols_by_kind {
+            out.push_str(&format!("  {kind}: {count}\n"));
+        }
+        out.push_str(&format!("functions: {}", self.functions));
+        if let Some(avg) = self.avg_function_lines() {
+            out.push_str(&format!(" (avg {avg:.1} lines)"));
+        }
+        out.push('\n');
+        if self.unparsed_files > 0 {
+            out.push_str(&format!("unparsed files: {}\n", self.unparsed_files));
+        }
+        out
+    }
+}
+
+/// Collect [`RepoStats`] for every file a search would scan.
+pub fn repo_stats(repo_root: &Path, opt: &RepoScanOptions) -> Result<RepoStats, NavigationError> {
+    let mut stats = RepoStats::default();
+
+    FsRepoFileProvider.for_each_file(repo_root, opt, &mut |file| {
+        let lang_id = detect_lang_id(&file.rel_path);
+        let lines = file.content.lines().count();
+        let lang = lang_id.unwrap_or(OTHER).to_owned();
+
+        stats.files += 1;
+        stats.total_lines += lines;
+        *stats.files_by_language.entry(lang.clone()).or_default() += 1;
+        *stats.lines_by_language.entry(lang).or_default() += lines;
+
+        if let Some(lang_id) = lang_id {
+            match TreeSitterFile::try_build(file.content.as_bytes(), lang_id) {
+                Ok(ts) => {
+                    if ts.add_symbol_stats(&mut stats).is_err() {
+                        stats.unparsed_files += 1;
+                    }
+                }
+                Err(err) => {
+                    tracing::debug!("stats: skip unparsable file {:?}: {err}", file.rel_path);
+                    stats.unparsed_files += 1;
+                }
+            }
+        }
+        ControlFlow::Continue(())
+    })?;
+
+    Ok(stats)
+}
+
+impl TreeSitterFile<'_> {
+    fn add_symbol_stats(&self, stats: &mut RepoStats) -> Result<(), crate::TreeSitterFileError> {
+        let graph = self.build_scope_graph()?;
+        let root = self.tree.root_node();
+
+        for node in graph.graph.node_weights() {
+            let NodeKind::Def(def) = node else {
+                continue;
+            };
+            if is_local(root, def.range.start.byte) {
+                continue;
+            }
+            let kind = def
+                .symbol_id
+                .map_or(OTHER, |id| id.name(self.language.namespaces));
+            *stats.symbols_by_kind.entry(kind.to_owned()).or_default() += 1;
+        }
+
+        let mut cursor = root.walk();
+        loop {
+            let node = cursor.node();
+            if skeleton::is_callable(node.kind()) && node.child_by_field_name("name").is_some() {
+                stats.functions += 1;
+                stats.function_lines += node.end_position().row - node.start_position().row + 1;
+            }
+            if cursor.goto_first_child() {
+                continue;
+            }
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Whether the definition starting at `byte` is a local (parameter, binding,
+/// nested item) of some function rather than the function's own name.
+fn is_local(root: Node<'_>, byte: usize) -> bool {
+    let mut node = root.descendant_for_byte_range(byte, byte);
+    while let Some(n) = node {
+        let is_own_name = n
+            .child_by_field_name("name")
+            .is_some_and(|name| name.start_byte() == byte);
+        if skeleton::is_callable(n.kind()) && !is_own_name {
+            return true;
+        }
+        node = n.parent();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unique_tmp_dir;
+    use std::fs;
+
+    #[test]
+    fn counts_files_lines_symbols_and_function_sizes() {
+        let root = unique_tmp_dir("luna_repo_stats");
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(
+            root.join("lib.rs"),
+            "pub struct Foo;\n\nfn one() -> i32 {\n    let x = 1;\n    x\n}\n\nfn two() {}\n",
+        )
+        .unwrap();
+        fs::write(root.join("notes.md"), "# notes\nhello\n").unwrap();
+        fs::write(root.join("target/skip.rs"), "fn skipped() {}\n").unwrap();
+
+        let stats = repo_stats(&root, &RepoScanOptions::default()).unwrap();
+        let _ = fs::remove_dir_all(&root);
+
+        assert_eq!(stats.files_by_language.get("rust"), Some(&1));
+        assert_eq!(stats.lines_by_language.get("rust"), Some(&8));
+        assert_eq!(stats.functions, 2);
+        assert_eq!(stats.function_lines, 5);
+        assert_eq!(stats.avg_function_lines(), Some(2.5));
+        assert_eq!(stats.symbols_by_kind.get("function"), Some(&2));
+        assert_eq!(stats.symbols_by_kind.get("struct"), Some(&1));
+        // `x` is local to `one`.
+        assert_eq!(stats.symbols_by_kind.get("variable"), None);
+        assert_eq!(stats.unparsed_files, 0);
+    }
+}
@@ -0,0 +1,194 @@
+//! Symbol-level diff of two versions of a file: which definitions were
+//! added, removed or changed, e.g. to summarize a PR for a changelog.
+//!
+//! Definitions are matched by qualified name (`parser::Parser::new`), so a
+//! rename shows up as a removal plus an addition. A definition is modified
+//! when the bytes of its whole declaration (comments and attributes directly
+//! above it included) differ. Locals and parameters are not reported.
+
+use std::collections::HashMap;
+
+use crate::{scope_resolution::NodeKind, TreeSitterFile, TreeSitterFileError};
+
+/// Enclosing scopes kept in the matched names.
+const MAX_QUALIFY_DEPTH: usize = 8;
+
+/// How a definition changed between the two versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SymbolChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// One changed definition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolChange {
+    /// Qualified name, e.g. `parser::Parser::new`.
+    pub name: String,
+    /// Scope-graph kind (`function`, `struct`, ...) in the newer version
+    /// when the symbol still exists.
+    pub kind: Option<&'static str>,
+    pub change: SymbolChangeKind,
+}
+
+/// Definitions that differ between `old_src` and `new_src`, sorted by name.
+/// Both are parsed as `lang_id` (see [`crate::detect_lang_id`]).
+///
+/// When a name is defined more than once (overloads, one method per trait
+/// impl), definitions are paired up in source order.
+pub fn diff_symbols(
+    old_src: &str,
+    new_src: &str,
+    lang_id: &str,
+) -> Result<Vec<SymbolChange>, TreeSitterFileError> {
+    let old = definitions(old_src, lang_id)?;
+    let mut new = definitions(new_src, lang_id)?;
+
+    let mut out = Vec::new();
+    for (name, olds) in old {
+        let news = new.remove(&name).unwrap_or_default();
+        for i in 0..olds.len().max(news.len()) {
+            let change = match (olds.get(i), news.get(i)) {
+                (Some(_), None) => SymbolChangeKind::Removed,
+                (None, Some(_)) => SymbolChangeKind::Added,
+                (Some(o), Some(n)) if o.body != n.body => SymbolChangeKind::Modified,
+                _ => continue,
+            };
+            out.push(SymbolChange {
+                name: name.clone(),
+                kind: news.get(i).or(olds.get(i)).and_then(|d| d.kind),
+                change,
+            });
+        }
+    }
+    out.extend(new.into_iter().flat_map(|(name, news)| {
+        news.into_iter().map(move |d| SymbolChange {
+            name: name.clone(),
+            kind: d.kind,
+            change: SymbolChangeKind::Added,
+        })
+    }));
+    out.sort_by(|a, b| (&a.name, a.change).cmp(&(&b.name, b.change)));
+    Ok(out)
+}
+
+struct Definition<'a> {
+    kind: Option<&'static str>,
+    body: &'a [u8],
+}
+
+/// Declarations in `src` by qualified name, each in source order.
+fn definitions<'a>(
+    src: &'a str,
+    lang_id: &str,
+) -> Result<HashMap<String, Vec<Definition<'a>>>, TreeSitterFileError> {
+    let file = TreeSitterFile::try_build(src.as_bytes(), lang_id)?;
+    let graph = file.build_scope_graph()?;
+
+    let mut defs: Vec<_> = graph
+        .graph
+        .node_weights()
+        .filter_map(|n| match n {
+            NodeKind::Def(d) => Some(d),
+            _ => None,
+        })
+        .collect();
+    defs.sort_by_key(|d| d.range.start.byte);
+
+    let mut out: HashMap<String, Vec<Definition<'a>>> = HashMap::new();
+    for def in defs {
+        // Only declarations have a span; locals and parameters don't.
+        let Some(span) = file.definition_span_at(def.range) else {
+            continue;
+        };
+        let Some(name) = file.qualified_name_at(def.range, MAX_QUALIFY_DEPTH) else {
+            continue;
+        };
+        out.entry(name).or_default().push(Definition {
+            kind: def.symbol_id.map(|id| id.name(file.language.namespaces)),
+            body: &src.as_bytes()[span],
+        });
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changes(old: &str, new: &str) -> Vec<(String, SymbolChangeKind)> {
+        diff_symbols(old, new, "rust")
+            .unwrap()
+            .into_iter()
+            .map(|c| (c.name, c.change))
+            .collect()
+    }
+
+    #[test]
+    fn reports_added_removed_renamed_and_modified_definitions() {
+        let old = "\
+struct Parser;
+
+impl Parser {
+    fn new() -> Self {
+        Parser
+    }
+
+    fn parse(&self) -> u32 {
+        1
+    }
+}
+
+fn unchanged() {}
+
+fn old_name() {}
+
+fn dropped() {}
+";
+        let new = "\
+struct Parser;
+
+impl Parser {
+    fn new() -> Self {
+        Parser
+    }
+
+    fn parse(&self) -> u32 {
+        let n = 2;
+        n
+    }
+}
+
+fn unchanged() {}
+
+fn new_name() {}
+
+/// Freshly added.
+fn added() {}
+";
+        use SymbolChangeKind::*;
+        assert_eq!(
+            changes(old, new),
+            vec![
+                ("Parser::parse".to_owned(), Modified),
+                ("added".to_owned(), Added),
+                ("dropped".to_owned(), Removed),
+                ("new_name".to_owned(), Added),
+                ("old_name".to_owned(), Removed),
+            ]
+        );
+
+        let diff = diff_symbols(old, new, "rust").unwrap();
+        assert_eq!(diff[0].kind, Some("function"));
+        assert!(changes(old, old).is_empty());
+    }
+
+    #[test]
+    fn doc_comment_edits_count_as_modifications() {
+        assert_eq!(
+            changes("/// Old.\nfn f() {}\n", "/// New.\nfn f() {}\n"),
+            vec![("f".to_owned(), SymbolChangeKind::Modified)]
+        );
+    }
+}
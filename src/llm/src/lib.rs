@@ -1,7 +1,9 @@
 //! LLM client interfaces
 
+mod limit;
 mod openai;
 
+pub use limit::{RequestLimiter, RequestLimits, RequestPermit};
 pub use openai::{OpenAIClient, OpenAIConfig};
 
 use error::{LunaError, Result};
@@ -58,11 +60,12 @@ impl LLMClient for StaticClient {
     }
 }
 
-/// A client that pops response from a queue
-
+/// A client that pops response from a queue, one per call, and records the
+/// prompts it was sent; lets tests drive a whole turn without a network.
 #[derive(Debug, Default)]
 pub struct MockClient {
     queue: std::sync::Mutex<std::collections::VecDeque<String>>,
+    prompts: std::sync::Mutex<Vec<String>>,
 }
 
 impl MockClient {
@@ -70,6 +73,7 @@ impl MockClient {
     pub fn new(response: Vec<String>) -> Self {
         Self {
             queue: std::sync::Mutex::new(response.into()),
+            prompts: std::sync::Mutex::default(),
         }
     }
 
@@ -77,10 +81,24 @@ impl MockClient {
         let mut q = self.queue.lock().expect("mock queue lock");
         q.push_back(response.into());
     }
+
+    /// Prompts received so far, oldest first.
+    pub fn prompts(&self) -> Vec<String> {
+        self.prompts.lock().expect("mock prompts lock").clone()
+    }
+
+    /// Scripted responses not yet returned.
+    pub fn remaining(&self) -> usize {
+        self.queue.lock().expect("mock queue lock").len()
+    }
 }
 
 impl LLMClient for MockClient {
-    fn complete(&self, _req: CompletionRequest) -> Result<CompletionResponse> {
+    fn complete(&self, req: CompletionRequest) -> Result<CompletionResponse> {
+        self.prompts
+            .lock()
+            .expect("mock prompts lock")
+            .push(req.prompt);
         let mut q = self.queue.lock().expect("mock queue lock");
         match q.pop_front() {
             Some(s) => Ok(CompletionResponse { content: s }),
@@ -0,0 +1,214 @@
+//! Client-side request limits (concurrency + requests-per-minute).
+//!
+//! Calls over the limit block until a slot frees up instead of erroring, so
+//! many sessions sharing one client apply backpressure rather than tripping
+//! provider rate limits.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Limits applied to outgoing LLM requests. `None` means unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RequestLimits {
+    /// Max requests in flight at once
+    pub max_in_flight: Option<usize>,
+    /// Max requests started per rolling minute
+    pub requests_per_minute: Option<u32>,
+}
+
+impl RequestLimits {
+    /// Read limits from environment variables
+    ///
+    /// Variables:
+    /// - `LUNA_LLM_MAX_IN_FLIGHT` (optional, unlimited by default)
+    /// - `LUNA_LLM_RPM` (optional, unlimited by default)
+    pub fn from_env() -> Self {
+        let parse = |key: &str| {
+            std::env::var(key)
+                .ok()
+                .and_then(|s| s.trim().parse::<u64>().ok())
+                .filter(|&n| n > 0)
+        };
+        Self {
+            max_in_flight: parse("LUNA_LLM_MAX_IN_FLIGHT").map(|n| n as usize),
+            requests_per_minute: parse("LUNA_LLM_RPM").map(|n| n.min(u64::from(u32::MAX)) as u32),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    in_flight: usize,
+    started: VecDeque<Instant>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    limits: RequestLimits,
+    window: Duration,
+    state: Mutex<State>,
+    freed: Condvar,
+}
+
+/// Blocking limiter shared by all clones.
+#[derive(Debug, Clone)]
+pub struct RequestLimiter {
+    shared: Arc<Shared>,
+}
+
+impl RequestLimiter {
+    #[must_use]
+    pub fn new(limits: RequestLimits) -> Self {
+        Self::with_window(limits, RATE_WINDOW)
+    }
+
+    /// Limiter whose requests-per-minute budget rolls over every `window`.
+    fn with_window(limits: RequestLimits, window: Duration) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                limits,
+                window,
+                state: Mutex::new(State::default()),
+                freed: Condvar::new(),
+            }),
+        }
+    }
+
+    #[must_use]
+    pub fn limits(&self) -> RequestLimits {
+        self.shared.limits
+    }
+
+    /// Wait for a free slot; the slot is released when the permit drops.
+    pub fn acquire(&self) -> RequestPermit {
+        let shared = &self.shared;
+        let mut state = shared.state.lock().expect("limiter lock");
+
+        loop {
+            let now = Instant::now();
+            while state
+                .started
+                .front()
+                .is_some_and(|t| now.duration_since(*t) >= shared.window)
+            {
+                state.started.pop_front();
+            }
+
+            let concurrency_ok = shared
+                .limits
+                .max_in_flight
+                .is_none_or(|max| state.in_flight < max);
+            let rate_wait = match shared.limits.requests_per_minute {
+                Some(rpm) if state.started.len() >= rpm as usize => state
+                    .started
+                    .front()
+                    .map(|t| shared.window.saturating_sub(now.duration_since(*t))),
+                _ => None,
+            };
+
+            match (concurrency_ok, rate_wait) {
+                (true, None) => break,
+                (_, Some(wait)) => {
+                    state = shared
+                        .freed
+                        .wait_timeout(state, wait)
+                        .expect("limiter lock")
+                        .0;
+                }
+                (false, None) => {
+                    state = shared.freed.wait(state).expect("limiter lock");
+                }
+            }
+        }
+
+        state.in_flight += 1;
+        if shared.limits.requests_per_minute.is_some() {
+            state.started.push_back(Instant::now());
+        }
+
+        RequestPermit {
+            shared: Arc::clone(shared),
+        }
+    }
+}
+
+/// Held for the duration of one request.
+#[derive(Debug)]
+pub struct RequestPermit {
+    shared: Arc<Shared>,
+}
+
+impl Drop for RequestPermit {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.shared.state.lock() {
+            state.in_flight = state.in_flight.saturating_sub(1);
+        }
+        self.shared.freed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_max_in_flight_blocks_instead_of_failing() {
+        let limiter = RequestLimiter::new(RequestLimits {
+            max_in_flight: Some(2),
+            requests_per_minute: None,
+        });
+        let current = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let handles = (0..6)
+            .map(|_| {
+                let limiter = limiter.clone();
+                let current = Arc::clone(&current);
+                let peak = Arc::clone(&peak);
+                std::thread::spawn(move || {
+                    let _permit = limiter.acquire();
+                    let now = current.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    std::thread::sleep(Duration::from_millis(20));
+                    current.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect::<Vec<_>>();
+        for h in handles {
+            h.join().unwrap();
+        }
+
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_requests_per_minute_blocks_until_window_rolls_over() {
+        let window = Duration::from_millis(100);
+        let limiter = RequestLimiter::with_window(
+            RequestLimits {
+                max_in_flight: None,
+                requests_per_minute: Some(2),
+            },
+            window,
+        );
+
+        let start = Instant::now();
+        drop(limiter.acquire());
+        drop(limiter.acquire());
+        assert!(start.elapsed() < window);
+
+        drop(limiter.acquire());
+        assert!(start.elapsed() >= window);
+    }
+
+    #[test]
+    fn test_unlimited_by_default() {
+        let limiter = RequestLimiter::new(RequestLimits::default());
+        let permits = (0..100).map(|_| limiter.acquire()).collect::<Vec<_>>();
+        assert_eq!(permits.len(), 100);
+    }
+}
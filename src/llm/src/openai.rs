@@ -7,7 +7,7 @@ use std::time::Duration;
 use error::{LunaError, Result};
 use serde::{Deserialize, Serialize};
 
-use crate::{CompletionRequest, CompletionResponse, LLMClient};
+use crate::{CompletionRequest, CompletionResponse, LLMClient, RequestLimiter, RequestLimits};
 
 /// OpenAI API request body
 #[derive(Debug, Clone, Serialize)]
@@ -15,7 +15,10 @@ struct ChatRequest {
     model: String,
     messages: Vec<Message>,
     temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
     max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -56,8 +59,12 @@ pub struct OpenAIConfig {
     pub timeout: Duration,
     /// Temperature (0.0 - 2.0)
     pub temperature: f32,
-    /// Max tokens per request
+    /// Max tokens per request; `None` leaves it to the provider
     pub max_tokens: Option<u32>,
+    /// Sequences that end the completion (e.g. `}` for JSON plans); empty sends none
+    pub stop: Vec<String>,
+    /// Concurrency / rate limits shared by all clones of the client
+    pub limits: RequestLimits,
 }
 
 impl Default for OpenAIConfig {
@@ -69,6 +76,8 @@ impl Default for OpenAIConfig {
             timeout: Duration::from_secs(60),
             temperature: 0.3,
             max_tokens: Some(4096),
+            stop: Vec::new(),
+            limits: RequestLimits::default(),
         }
     }
 }
@@ -81,6 +90,9 @@ impl OpenAIConfig {
     /// - `LUNA_LLM_BASE_URL` (optional, default: OpenAI)
     /// - `LUNA_LLM_MODEL` (optional, default: gpt-4o-mini)
     /// - `LUNA_LLM_TIMEOUT_SECS` (optional, default: 60)
+    /// - `LUNA_LLM_MAX_TOKENS` (optional, default: 4096; `0` sends no limit)
+    /// - `LUNA_LLM_STOP` (optional, newline-separated stop sequences)
+    /// - `LUNA_LLM_MAX_IN_FLIGHT` / `LUNA_LLM_RPM` (optional, see [`RequestLimits::from_env`])
     pub fn from_env() -> Option<Self> {
         let api_key = std::env::var("LUNA_LLM_API_KEY").ok()?;
         if api_key.is_empty() {
@@ -94,16 +106,35 @@ impl OpenAIConfig {
 
         let timeout_secs = std::env::var("LUNA_LLM_TIMEOUT_SECS")
             .ok()
-            .and_then(|s| s.parse().ok())
+            .and_then(|s| s.trim().parse().ok())
             .unwrap_or(60);
 
+        let max_tokens = match std::env::var("LUNA_LLM_MAX_TOKENS") {
+            Ok(v) => match v.trim().parse::<u32>() {
+                Ok(0) => None,
+                Ok(n) => Some(n),
+                Err(_) => Some(4096),
+            },
+            Err(_) => Some(4096),
+        };
+        let stop = std::env::var("LUNA_LLM_STOP")
+            .map(|v| {
+                v.lines()
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_owned)
+                    .collect()
+            })
+            .unwrap_or_default();
+
         Some(Self {
             base_url,
             api_key,
             model,
             timeout: Duration::from_secs(timeout_secs),
             temperature: 0.3,
-            max_tokens: Some(4096),
+            max_tokens,
+            stop,
+            limits: RequestLimits::from_env(),
         })
     }
 
@@ -133,6 +164,7 @@ impl OpenAIConfig {
 pub struct OpenAIClient {
     config: OpenAIConfig,
     client: reqwest::Client,
+    limiter: RequestLimiter,
 }
 
 impl OpenAIClient {
@@ -149,7 +181,12 @@ impl OpenAIClient {
             .build()
             .map_err(|e| LunaError::internal(format!("Failed to create HTTP client: {e}")))?;
 
-        Ok(Self { config, client })
+        let limiter = RequestLimiter::new(config.limits);
+        Ok(Self {
+            config,
+            client,
+            limiter,
+        })
     }
 
     /// Try to create client from environment variables
@@ -158,17 +195,8 @@ impl OpenAIClient {
         Self::new(config).ok()
     }
 
-    fn build_url(&self) -> String {
-        format!(
-            "{}/chat/completions",
-            self.config.base_url.trim_end_matches('/')
-        )
-    }
-}
-
-impl LLMClient for OpenAIClient {
-    fn complete(&self, req: CompletionRequest) -> Result<CompletionResponse> {
-        let request_body = ChatRequest {
+    fn chat_request(&self, prompt: String) -> ChatRequest {
+        ChatRequest {
             model: self.config.model.clone(),
             messages: vec![
                 Message {
@@ -177,18 +205,35 @@ impl LLMClient for OpenAIClient {
                 },
                 Message {
                     role: "user".to_owned(),
-                    content: req.prompt,
+                    content: prompt,
                 },
             ],
             temperature: self.config.temperature,
             max_tokens: self.config.max_tokens,
-        };
+            stop: self.config.stop.clone(),
+        }
+    }
+
+    fn build_url(&self) -> String {
+        format!(
+            "{}/chat/completions",
+            self.config.base_url.trim_end_matches('/')
+        )
+    }
+}
+
+impl LLMClient for OpenAIClient {
+    fn complete(&self, req: CompletionRequest) -> Result<CompletionResponse> {
+        let request_body = self.chat_request(req.prompt);
 
         let url = self.build_url();
         let api_key = self.config.api_key.clone();
         let client = self.client.clone();
+        let limiter = self.limiter.clone();
 
         let result = tokio::task::block_in_place(move || {
+            // Wait for a slot off the async workers; released once the response is read.
+            let _permit = limiter.acquire();
             tokio::runtime::Handle::current().block_on(async move {
                 let resp = client
                     .post(&url)
@@ -279,6 +324,31 @@ mod tests {
         assert_eq!(config.model, "Qwen/Qwen2.5-Coder-32B-Instruct");
     }
 
+    #[test]
+    fn test_request_sends_max_tokens_and_stop_only_when_set() {
+        let client = |config: OpenAIConfig| OpenAIClient::new(config).expect("client");
+        let body = |config| {
+            serde_json::to_value(client(config).chat_request("hi".to_owned())).expect("serialize")
+        };
+
+        let unset = body(OpenAIConfig {
+            api_key: "test-key".to_owned(),
+            max_tokens: None,
+            ..OpenAIConfig::default()
+        });
+        assert!(unset.get("max_tokens").is_none());
+        assert!(unset.get("stop").is_none());
+
+        let set = body(OpenAIConfig {
+            api_key: "test-key".to_owned(),
+            max_tokens: Some(256),
+            stop: vec!["}".to_owned()],
+            ..OpenAIConfig::default()
+        });
+        assert_eq!(set["max_tokens"], 256);
+        assert_eq!(set["stop"], serde_json::json!(["}"]));
+    }
+
     #[test]
     fn test_deepseek_config() {
         let config = OpenAIConfig {
@@ -0,0 +1,124 @@
+//! Post-processing for final answers: strips boilerplate models tend to add
+//! ("Based on the provided context, ...", a fence wrapped around the whole reply).
+
+use error::{LunaError, Result};
+use regex::Regex;
+
+/// Lead-ins removed by [`AnswerCleanup::default`]; matched case-insensitively
+/// at the start of the answer.
+pub const DEFAULT_PATTERNS: &[&str] = &[
+    r"^(?i)(based on|according to|from) (the )?(provided|given|above) (context|code|snippets?|information)[^,.:\n]*[,:]\s*",
+    r"^(?i)(sure|certainly|of course)[!.,]\s*",
+    r"^(?i)great question[!.]\s*",
+];
+
+/// Regex-based cleanup applied to a turn's final answer.
+#[derive(Debug, Clone)]
+pub struct AnswerCleanup {
+    patterns: Vec<Regex>,
+    /// Unwrap an answer that is one fenced block with no language (or
+    /// `markdown`/`text`); code fences with a language are kept.
+    unwrap_fence: bool,
+}
+
+impl Default for AnswerCleanup {
+    fn default() -> Self {
+        Self {
+            patterns: DEFAULT_PATTERNS
+                .iter()
+                .map(|p| Regex::new(p).expect("default answer pattern"))
+                .collect(),
+            unwrap_fence: true,
+        }
+    }
+}
+
+impl AnswerCleanup {
+    /// No patterns and no fence unwrapping; add rules with the builders.
+    pub fn empty() -> Self {
+        Self {
+            patterns: Vec::new(),
+            unwrap_fence: false,
+        }
+    }
+
+    /// Also remove every match of `pattern`.
+    pub fn with_pattern(mut self, pattern: &str) -> Result<Self> {
+        let re = Regex::new(pattern)
+            .map_err(|e| LunaError::invalid_input(format!("answer pattern {pattern:?}: {e}")))?;
+        self.patterns.push(re);
+        Ok(self)
+    }
+
+    pub fn with_unwrap_fence(mut self, unwrap_fence: bool) -> Self {
+        self.unwrap_fence = unwrap_fence;
+        self
+    }
+
+    /// Cleaned answer; falls back to `answer` if cleanup would leave nothing.
+    pub fn apply(&self, answer: &str) -> String {
+        let mut out = answer.trim().to_owned();
+        if self.unwrap_fence {
+            if let Some(inner) = unwrap_prose_fence(&out) {
+                out = inner.trim().to_owned();
+            }
+        }
+        for re in &self.patterns {
+            out = re.replace_all(&out, "").trim().to_owned();
+        }
+        if out.is_empty() {
+            return answer.to_owned();
+        }
+        // Lead-in removal can leave a lowercase first word mid-sentence.
+        let mut chars = out.chars();
+        match chars.next() {
+            Some(c) if c.is_lowercase() && answer.trim() != out => {
+                c.to_uppercase().chain(chars).collect()
+            }
+            _ => out,
+        }
+    }
+}
+
+/// Body of `text` when it is exactly one fence with no language, or a prose one.
+fn unwrap_prose_fence(text: &str) -> Option<&str> {
+    let rest = text.strip_prefix("```")?;
+    let (info, body) = rest.split_once('\n')?;
+    if !matches!(info.trim(), "" | "markdown" | "md" | "text") {
+        return None;
+    }
+    let body = body.trim_end().strip_suffix("```")?;
+    (!body.contains("```")).then_some(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_cleanup_strips_lead_ins_and_prose_fences_only() {
+        let c = AnswerCleanup::default();
+        assert_eq!(
+            c.apply("Based on the provided context, the parser lives in `src/parse.rs`."),
+            "The parser lives in `src/parse.rs`."
+        );
+        assert_eq!(c.apply("Sure! It returns None."), "It returns None.");
+        assert_eq!(
+            c.apply("```\nUse `cargo test`.\n```\n"),
+            "Use `cargo test`."
+        );
+
+        let code = "```rust\nfn main() {}\n```";
+        assert_eq!(c.apply(code), code);
+        assert_eq!(
+            c.apply("Based on the given code:"),
+            "Based on the given code:"
+        );
+
+        let custom = AnswerCleanup::empty()
+            .with_pattern(r"(?m)^Hope this helps!?$")
+            .expect("valid pattern");
+        assert_eq!(custom.apply("Answer.\nHope this helps!"), "Answer.");
+        assert!(AnswerCleanup::empty().with_pattern("(").is_err());
+    }
+}
@@ -0,0 +1,29 @@
+//! Cooperative cancellation of a running turn.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Flag a caller sets to stop a turn early, e.g. on Ctrl-C.
+///
+/// The turn checks it between plan steps, so a step that is already running
+/// (an LLM call, a command) finishes first; the output of the steps that ran
+/// is still returned, with [`crate::TerminationReason::Cancelled`].
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask the turn to stop; every clone of this token sees it.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
@@ -1,13 +1,21 @@
+use crate::answer::AnswerCleanup;
 use crate::planner;
 use crate::recorder::{NoopTrajectoryRecorder, TrajectoryRecorder};
 use crate::recorder_jsonl::JsonlTrajectoryRecorder;
+use crate::router::NavigationOptions;
 use crate::safety::{RuleBasedSafetyGuard, SafetyGuard};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use session::{InMemorySessionStore, JsonlSessionStore, SessionStore};
 use std::any::Any;
+use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use tools::ToolRegistry;
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct TokenBudget {
     /// Rough guard for user input size.
     pub max_input_chars: usize,
@@ -15,6 +23,35 @@ pub struct TokenBudget {
     pub max_io_bytes: usize,
     /// Maximum planned step count for a single turn.
     pub max_steps: usize,
+    /// Chunks kept from the initial context search for a turn.
+    pub initial_context_chunks: usize,
+    /// Chunks kept from each follow-up search for a symbol the model is missing.
+    pub refill_context_chunks: usize,
+    /// Best-scoring search hits refined into the initial context; the rest
+    /// are dropped before their files are parsed.
+    pub refine_candidates: usize,
+    /// Back-to-back repetitions of a step (or step pair) that count as a loop.
+    pub max_repeated_steps: usize,
+    /// Tokens for pinned files, on top of the context budget for retrieved chunks.
+    pub pinned_context_tokens: usize,
+    /// Collapse retrieved chunks at least this similar (0.0-1.0) into the
+    /// best-ranked one; `None` keeps them all.
+    pub near_duplicate_threshold: Option<f32>,
+    /// Collapse retrieved chunks with the same content at different paths
+    /// (copied or vendored code) into one, listing the other paths.
+    pub dedup_identical_content: bool,
+    /// Deprioritize retrieved chunks whose code-likeness (0.0-1.0) is below
+    /// this, e.g. embedded base64 or data tables; `None` keeps them as ranked.
+    pub data_chunk_threshold: Option<f32>,
+}
+
+#[cfg(feature = "schema")]
+impl TokenBudget {
+    /// JSON Schema of the budget.
+    #[must_use]
+    pub fn schema() -> schemars::schema::RootSchema {
+        schemars::schema_for!(Self)
+    }
 }
 
 impl Default for TokenBudget {
@@ -23,10 +60,49 @@ impl Default for TokenBudget {
             max_input_chars: 32_000,
             max_io_bytes: 64 * 1024,
             max_steps: 12,
+            initial_context_chunks: 10,
+            refill_context_chunks: 5,
+            refine_candidates: 50,
+            max_repeated_steps: 3,
+            pinned_context_tokens: 1000,
+            near_duplicate_threshold: None,
+            dedup_identical_content: false,
+            data_chunk_threshold: None,
         }
     }
 }
 
+/// How context is refreshed after a step edits a file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum PostEditContext {
+    /// Re-parse the edited file and take the declaration around the edit.
+    #[default]
+    ReparseEdited,
+    /// Search again with the edited file's name as the query.
+    ResearchFilename,
+    /// Keep the context collected before the edit.
+    None,
+}
+
+/// How strictly final answers must stick to the retrieved code.
+///
+/// Only the LLM planner (`LUNA_PLANNER=llm`) writes free-form answers, so
+/// only its prompt changes; the rule-based planner, including when it stands
+/// in for a failed LLM plan, reports tool output as is under either setting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum Grounding {
+    /// The model may fill gaps from general knowledge.
+    #[default]
+    Lenient,
+    /// Answer only from the provided context, cite `path:line`, and say
+    /// "not found in the provided code" otherwise.
+    Strict,
+}
+
 /// Runtime-wide denpendency injection
 pub struct RuntimeConfig {
     session_store: Arc<dyn SessionStore>,
@@ -35,6 +111,31 @@ pub struct RuntimeConfig {
     tools: Arc<ToolRegistry>,
     budget: TokenBudget,
     planner: Arc<dyn planner::TaskPlanner>,
+    /// Mutating/executing tools only report what they would do.
+    dry_run: bool,
+    /// Reject edits and command directories outside the repository root.
+    confine_to_repo: bool,
+    /// Backups `edit_file` keeps per file; 0 disables them.
+    edit_backups: usize,
+    /// Files always included in the context, within `pinned_context_tokens`.
+    pinned_paths: Vec<PathBuf>,
+    navigation: NavigationOptions,
+    post_edit_context: PostEditContext,
+    /// Language for final answers; `None` leaves it to the model.
+    answer_language: Option<String>,
+    grounding: Grounding,
+    /// Ask the model for `[n]` markers pointing at numbered context sources.
+    cite_sources: bool,
+    /// Run `answer_cleanup` over the final answer; the raw one is kept on the response.
+    clean_answer: bool,
+    answer_cleanup: AnswerCleanup,
+    /// Client for model calls outside planning, e.g. edit summaries.
+    llm_client: Arc<dyn llm::LLMClient>,
+    /// Summarize each edit's diff in one line with `llm_client`.
+    summarize_edits: bool,
+    /// Extra definition keywords and modifiers for labelling collected
+    /// symbols; its keywords replace the built-in keyword -> kind mapping.
+    signature_patterns: context::SignaturePatterns,
 }
 
 impl RuntimeConfig {
@@ -72,6 +173,79 @@ impl RuntimeConfig {
         self
     }
 
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn with_confine_to_repo(mut self, confine_to_repo: bool) -> Self {
+        self.confine_to_repo = confine_to_repo;
+        self
+    }
+
+    pub fn with_edit_backups(mut self, edit_backups: usize) -> Self {
+        self.edit_backups = edit_backups;
+        self
+    }
+
+    pub fn with_pinned_paths(mut self, pinned_paths: Vec<PathBuf>) -> Self {
+        self.pinned_paths = pinned_paths;
+        self
+    }
+
+    pub fn with_navigation(mut self, navigation: NavigationOptions) -> Self {
+        self.navigation = navigation;
+        self
+    }
+
+    pub fn with_post_edit_context(mut self, post_edit_context: PostEditContext) -> Self {
+        self.post_edit_context = post_edit_context;
+        self
+    }
+
+    pub fn with_answer_language(mut self, language: impl Into<String>) -> Self {
+        self.answer_language = Some(language.into());
+        self
+    }
+
+    /// Applies to the LLM planner only, see [`Grounding`].
+    pub fn with_grounding(mut self, grounding: Grounding) -> Self {
+        self.grounding = grounding;
+        self
+    }
+
+    pub fn with_cite_sources(mut self, cite_sources: bool) -> Self {
+        self.cite_sources = cite_sources;
+        self
+    }
+
+    pub fn with_clean_answer(mut self, clean_answer: bool) -> Self {
+        self.clean_answer = clean_answer;
+        self
+    }
+
+    pub fn with_llm_client(mut self, llm_client: Arc<dyn llm::LLMClient>) -> Self {
+        self.llm_client = llm_client;
+        self
+    }
+
+    pub fn with_summarize_edits(mut self, summarize_edits: bool) -> Self {
+        self.summarize_edits = summarize_edits;
+        self
+    }
+
+    /// Definition keywords and modifiers used for symbol kinds in events and citations.
+    pub fn with_signature_patterns(mut self, patterns: context::SignaturePatterns) -> Self {
+        self.signature_patterns = patterns;
+        self
+    }
+
+    /// Replaces the default cleanup rules; takes effect with [`Self::with_clean_answer`].
+    pub fn with_answer_cleanup(mut self, answer_cleanup: AnswerCleanup) -> Self {
+        self.answer_cleanup = answer_cleanup;
+        self
+    }
+
     pub fn session_store(&self) -> Arc<dyn SessionStore> {
         Arc::clone(&self.session_store)
     }
@@ -94,6 +268,116 @@ impl RuntimeConfig {
     pub fn planner(&self) -> Arc<dyn planner::TaskPlanner> {
         Arc::clone(&self.planner)
     }
+
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    pub fn confine_to_repo(&self) -> bool {
+        self.confine_to_repo
+    }
+
+    pub fn edit_backups(&self) -> usize {
+        self.edit_backups
+    }
+
+    pub fn pinned_paths(&self) -> Vec<PathBuf> {
+        self.pinned_paths.clone()
+    }
+
+    pub fn navigation(&self) -> NavigationOptions {
+        self.navigation.clone()
+    }
+
+    pub fn post_edit_context(&self) -> PostEditContext {
+        self.post_edit_context
+    }
+
+    pub fn answer_language(&self) -> Option<String> {
+        self.answer_language.clone()
+    }
+
+    pub fn grounding(&self) -> Grounding {
+        self.grounding
+    }
+
+    pub fn cite_sources(&self) -> bool {
+        self.cite_sources
+    }
+
+    pub fn signature_patterns(&self) -> context::SignaturePatterns {
+        self.signature_patterns.clone()
+    }
+
+    /// Client for edit summaries, `None` when they are disabled.
+    pub fn edit_summarizer(&self) -> Option<Arc<dyn llm::LLMClient>> {
+        self.summarize_edits.then(|| Arc::clone(&self.llm_client))
+    }
+
+    /// Cleanup for final answers, `None` when disabled.
+    pub fn answer_cleanup(&self) -> Option<&AnswerCleanup> {
+        self.clean_answer.then_some(&self.answer_cleanup)
+    }
+}
+
+/// Tool registry with time limits from the environment:
+/// `LUNA_TOOL_TIMEOUT_SECS=N` for every tool, and
+/// `LUNA_TOOL_TIMEOUTS=run_terminal=60,read_file=5` per tool.
+fn tools_from_env() -> ToolRegistry {
+    let secs = |v: &str| v.trim().parse::<u64>().ok().map(Duration::from_secs);
+    let mut tools = ToolRegistry::new();
+    if let Some(limit) = std::env::var("LUNA_TOOL_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| secs(&v))
+    {
+        tools = tools.with_default_timeout(limit);
+    }
+    if let Ok(v) = std::env::var("LUNA_TOOL_TIMEOUTS") {
+        for (name, limit) in v.split(',').filter_map(|entry| entry.split_once('=')) {
+            if let Some(limit) = secs(limit) {
+                tools = tools.with_timeout(name.trim(), limit);
+            }
+        }
+    }
+    tools
+}
+
+// Parsers for `LUNA_*` values, shared with `luna doctor` so it flags exactly
+// the values `RuntimeConfig::default` would ignore.
+
+/// `LUNA_PLANNER`: `Some(true)` for `llm`, `Some(false)` for `rule`, `None`
+/// for anything else (which runs the rule-based planner).
+pub(crate) fn parse_planner(v: &str) -> Option<bool> {
+    if v.eq_ignore_ascii_case("llm") {
+        Some(true)
+    } else if v.eq_ignore_ascii_case("rule") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Numeric settings; surrounding whitespace is ignored.
+pub(crate) fn parse_number<T: FromStr>(v: &str) -> Option<T> {
+    v.trim().parse().ok()
+}
+
+/// `snake_case` enum settings such as `LUNA_GROUNDING=strict`, taken verbatim.
+pub(crate) fn parse_variant<T: DeserializeOwned>(v: &str) -> Option<T> {
+    serde_json::from_value(serde_json::Value::String(v.to_owned())).ok()
+}
+
+/// `LUNA_KIND_OVERRIDES=impl=type,macro_rules=macro`; `None` when an entry
+/// lacks a keyword or a kind.
+pub(crate) fn parse_kind_overrides(v: &str) -> Option<context::KindOverrides> {
+    v.split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| {
+            let (keyword, kind) = entry.split_once('=')?;
+            let (keyword, kind) = (keyword.trim(), kind.trim());
+            (!keyword.is_empty() && !kind.is_empty()).then(|| (keyword.to_owned(), kind.to_owned()))
+        })
+        .collect()
 }
 
 impl Default for RuntimeConfig {
@@ -105,7 +389,7 @@ impl Default for RuntimeConfig {
             .map(|r| Arc::new(r) as Arc<dyn TrajectoryRecorder>)
             .unwrap_or_else(|| Arc::new(NoopTrajectoryRecorder));
         let safety: Arc<dyn SafetyGuard> = Arc::new(RuleBasedSafetyGuard::new(32));
-        let tools = Arc::new(ToolRegistry::new());
+        let tools = Arc::new(tools_from_env());
 
         // Try to create real LLM client from env
         let llm_client: Arc<dyn llm::LLMClient> =
@@ -117,12 +401,18 @@ impl Default for RuntimeConfig {
         let has_llm_client = llm_client.as_ref().type_id() != std::any::TypeId::of::<llm::DisabledClient>();
         let prefer_llm = std::env::var("LUNA_PLANNER")
             .ok()
-            .map(|v| v.eq_ignore_ascii_case("llm"))
+            .map(|v| parse_planner(&v) == Some(true))
             .unwrap_or(has_llm_client); // Auto-enable if LLM client is available
 
         let rule = Arc::new(planner::RuleBasedPlanner::new()) as Arc<dyn planner::TaskPlanner>;
-        let llm_planner = Arc::new(planner::LLMBasedPlanner::new(Arc::clone(&llm_client), 12))
-            as Arc<dyn planner::TaskPlanner>;
+        // LUNA_PLAN_REPAIR=0 fails malformed plans straight to the rule-based fallback
+        let plan_repair = std::env::var("LUNA_PLAN_REPAIR")
+            .map(|v| !matches!(v.trim(), "0" | "false" | "off"))
+            .unwrap_or(true);
+        let llm_planner = Arc::new(
+            planner::LLMBasedPlanner::new(Arc::clone(&llm_client), 12)
+                .with_plan_repair(plan_repair),
+        ) as Arc<dyn planner::TaskPlanner>;
         let planner: Arc<dyn planner::TaskPlanner> =
             Arc::new(planner::PlannerSelector::new(prefer_llm, rule, llm_planner));
 
@@ -131,8 +421,82 @@ impl Default for RuntimeConfig {
             trajectory,
             safety,
             tools,
-            budget: TokenBudget::default(),
+            budget: TokenBudget {
+                // LUNA_NEAR_DUPLICATE_THRESHOLD=0.9 collapses chunks that are 90% alike
+                near_duplicate_threshold: std::env::var("LUNA_NEAR_DUPLICATE_THRESHOLD")
+                    .ok()
+                    .and_then(|v| parse_number(&v)),
+                // LUNA_DEDUP_IDENTICAL=1 keeps one copy of code found at several paths
+                dedup_identical_content: std::env::var("LUNA_DEDUP_IDENTICAL")
+                    .map(|v| matches!(v.trim(), "1" | "true" | "on"))
+                    .unwrap_or(false),
+                // LUNA_DATA_CHUNK_THRESHOLD=0.3 ranks data-like chunks below code
+                data_chunk_threshold: std::env::var("LUNA_DATA_CHUNK_THRESHOLD")
+                    .ok()
+                    .and_then(|v| parse_number(&v)),
+                ..TokenBudget::default()
+            },
             planner,
+            dry_run: false,
+            confine_to_repo: false,
+            // LUNA_EDIT_BACKUPS=N keeps the last N originals of each edited file
+            edit_backups: std::env::var("LUNA_EDIT_BACKUPS")
+                .ok()
+                .and_then(|v| parse_number(&v))
+                .unwrap_or(0),
+            // LUNA_PINNED_PATHS=src/types.rs:schema.sql, separated like PATH
+            pinned_paths: std::env::var_os("LUNA_PINNED_PATHS")
+                .map(|v| {
+                    std::env::split_paths(&v)
+                        .filter(|p| !p.as_os_str().is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            navigation: NavigationOptions::default(),
+            // LUNA_POST_EDIT_CONTEXT=reparse_edited|research_filename|none
+            post_edit_context: std::env::var("LUNA_POST_EDIT_CONTEXT")
+                .ok()
+                .and_then(|v| parse_variant(&v))
+                .unwrap_or_default(),
+            answer_language: std::env::var("LUNA_ANSWER_LANGUAGE")
+                .ok()
+                .filter(|v| !v.trim().is_empty()),
+            // LUNA_GROUNDING=strict|lenient
+            grounding: std::env::var("LUNA_GROUNDING")
+                .ok()
+                .and_then(|v| parse_variant(&v))
+                .unwrap_or_default(),
+            // LUNA_CITE_SOURCES=1 asks for `[n]` source markers in answers
+            cite_sources: std::env::var("LUNA_CITE_SOURCES")
+                .map(|v| matches!(v.trim(), "1" | "true" | "on"))
+                .unwrap_or(false),
+            // LUNA_CLEAN_ANSWER=1 strips boilerplate from final answers
+            clean_answer: std::env::var("LUNA_CLEAN_ANSWER")
+                .map(|v| matches!(v.trim(), "1" | "true" | "on"))
+                .unwrap_or(false),
+            answer_cleanup: AnswerCleanup::default(),
+            llm_client,
+            // LUNA_SUMMARIZE_EDITS=1 adds a one-line summary of each edit to its step
+            summarize_edits: std::env::var("LUNA_SUMMARIZE_EDITS")
+                .map(|v| matches!(v.trim(), "1" | "true" | "on"))
+                .unwrap_or(false),
+            signature_patterns: context::SignaturePatterns {
+                // LUNA_KIND_OVERRIDES=impl=type,macro_rules=macro
+                keywords: std::env::var("LUNA_KIND_OVERRIDES")
+                    .ok()
+                    .and_then(|v| parse_kind_overrides(&v))
+                    .unwrap_or_default(),
+                // LUNA_SIGNATURE_MODIFIERS=override,suspend
+                modifiers: std::env::var("LUNA_SIGNATURE_MODIFIERS")
+                    .map(|v| {
+                        v.split(',')
+                            .map(str::trim)
+                            .filter(|m| !m.is_empty())
+                            .map(str::to_owned)
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            },
         }
     }
 }
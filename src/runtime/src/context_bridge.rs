@@ -7,11 +7,11 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use context::{
-    refill::{FileProvider, SymbolResolver},
+    refill::{FileProvider, SymbolInfo, SymbolResolver},
     SourceLocation, TextRange,
 };
 use intelligence::{
-    repo_scan::{FsRepoFileProvider, RepoFileProvider, RepoScanOptions},
+    repo_scan::{FsRepoFileProvider, RepoFileProvider, RepoScanOptions, TestFilePolicy},
     Navigator, SnippetOptions, SymbolLocation as IntelSymbolLocation,
 };
 use _core::text_range::{Position, TextRange as CoreTextRange};
@@ -106,15 +106,7 @@ impl<N: Navigator + Send + Sync> SymbolResolver for IntelligenceSymbolResolver<N
         repo_root: &Path,
         location: &SourceLocation,
     ) -> error::Result<Option<String>> {
-        let intel_loc = source_location_to_intel_location(location);
-        let opt = SnippetOptions::default();
-
-        let ctx = self
-            .navigator
-            .get_symbol_context(repo_root, &intel_loc, &opt)
-            .map_err(|e| error::LunaError::invalid_input(format!("navigation error: {e}")))?;
-
-        Ok(ctx.signature_line)
+        Ok(self.symbol_context(repo_root, location, 0)?.signature_line)
     }
 
     fn get_snippet(
@@ -123,18 +115,39 @@ impl<N: Navigator + Send + Sync> SymbolResolver for IntelligenceSymbolResolver<N
         location: &SourceLocation,
         context_lines: usize,
     ) -> error::Result<String> {
-        let intel_loc = source_location_to_intel_location(location);
+        Ok(self.symbol_context(repo_root, location, context_lines)?.snippet)
+    }
+
+    fn get_symbol_info(
+        &self,
+        repo_root: &Path,
+        location: &SourceLocation,
+    ) -> error::Result<SymbolInfo> {
+        let ctx = self.symbol_context(repo_root, location, 0)?;
+        Ok(SymbolInfo {
+            signature: ctx.signature_line,
+            kind: ctx.kind.map(ToOwned::to_owned),
+            callees: ctx.callees,
+        })
+    }
+}
+
+impl<N: Navigator> IntelligenceSymbolResolver<N> {
+    /// Navigator's view of the definition at `location`, with a snippet of
+    /// `context_lines` around it.
+    fn symbol_context(
+        &self,
+        repo_root: &Path,
+        location: &SourceLocation,
+        context_lines: usize,
+    ) -> error::Result<intelligence::SymbolContext> {
         let opt = SnippetOptions {
             context_lines,
             ..SnippetOptions::default()
         };
-
-        let ctx = self
-            .navigator
-            .get_symbol_context(repo_root, &intel_loc, &opt)
-            .map_err(|e| error::LunaError::invalid_input(format!("navigation error: {e}")))?;
-
-        Ok(ctx.snippet)
+        self.navigator
+            .get_symbol_context(repo_root, &source_location_to_intel_location(location), &opt)
+            .map_err(|e| error::LunaError::invalid_input(format!("navigation error: {e}")))
     }
 }
 
@@ -175,8 +188,13 @@ fn source_location_to_intel_location(loc: &SourceLocation) -> IntelSymbolLocatio
 }
 
 /// Factory function to create a fully configured RefillPipeline
+///
+/// `test_files` applies to both the symbol search and `refine`; `turn_budget`
+/// supplies the optional refine settings.
 pub fn create_refill_pipeline(
     repo_root: PathBuf,
+    test_files: TestFilePolicy,
+    turn_budget: &crate::config::TokenBudget,
 ) -> Option<context::RefillPipeline> {
     tracing::debug!("Creating RefillPipeline for: {}", repo_root.display());
 
@@ -193,8 +211,9 @@ pub fn create_refill_pipeline(
     tracing::debug!("Repo root validated, creating navigator...");
 
     // Create navigator
+    let scan_opt = RepoScanOptions::default().with_test_files(test_files);
     let navigator: Arc<intelligence::TreeSitterNavigator<FsRepoFileProvider>> =
-        Arc::new(intelligence::TreeSitterNavigator::default());
+        Arc::new(intelligence::TreeSitterNavigator::new(FsRepoFileProvider, scan_opt));
 
     // Create adapters
     let file_provider: Arc<dyn FileProvider> =
@@ -206,17 +225,154 @@ pub fn create_refill_pipeline(
         max_context_tokens: 4000,
     };
 
-    Some(context::RefillPipeline::new(
-        repo_root,
-        file_provider,
-        symbol_resolver,
-        budget,
+    let mut pipeline =
+        context::RefillPipeline::new(repo_root, file_provider, symbol_resolver, budget)
+            .with_test_files(test_files)
+            .with_content_dedup(turn_budget.dedup_identical_content);
+    if let Some(threshold) = turn_budget.near_duplicate_threshold {
+        pipeline = pipeline.with_near_duplicate_threshold(threshold);
+    }
+    if let Some(threshold) = turn_budget.data_chunk_threshold {
+        pipeline = pipeline.with_data_chunk_threshold(threshold);
+    }
+    Some(match model_token_counter() {
+        Some(counter) => pipeline.with_token_counter(counter),
+        None => pipeline,
+    })
+}
+
+/// Tokenizer for the configured model, loaded once per process; `None` keeps
+/// the pipeline's character approximation.
+#[cfg(feature = "tokenizer-download")]
+fn model_token_counter() -> Option<Arc<dyn context::TokenCounter>> {
+    static COUNTER: std::sync::OnceLock<Option<Arc<dyn context::TokenCounter>>> =
+        std::sync::OnceLock::new();
+    COUNTER.get_or_init(load_model_tokenizer).clone()
+}
+
+#[cfg(not(feature = "tokenizer-download"))]
+fn model_token_counter() -> Option<Arc<dyn context::TokenCounter>> {
+    None
+}
+
+#[cfg(feature = "tokenizer-download")]
+fn load_model_tokenizer() -> Option<Arc<dyn context::TokenCounter>> {
+    try_load_model_tokenizer().unwrap_or_else(|e| {
+        tracing::warn!("{e}; approximating token counts instead");
+        None
+    })
+}
+
+/// `LUNA_TOKENIZER` (a `tokenizer.json` path, a Hugging Face repo id, or
+/// `off`), else the known tokenizer for `LUNA_LLM_MODEL`. Downloads are
+/// cached under `<LUNA_HOME>/tokenizers`.
+///
+/// `Ok(None)` when no tokenizer is wanted or known for the model; an error
+/// when the configured one fails to load.
+#[cfg(feature = "tokenizer-download")]
+pub(crate) fn try_load_model_tokenizer() -> Result<Option<Arc<dyn context::TokenCounter>>, String> {
+    let setting = std::env::var("LUNA_TOKENIZER").unwrap_or_default();
+    let repo_id = match setting.trim() {
+        "off" => return Ok(None),
+        "" => {
+            let model = std::env::var("LUNA_LLM_MODEL")
+                .unwrap_or_else(|_| llm::OpenAIConfig::default().model);
+            let Some(repo_id) = context::tokenizer_repo(&model) else {
+                tracing::debug!("no known tokenizer for model {model}");
+                return Ok(None);
+            };
+            repo_id.to_owned()
+        }
+        path if Path::new(path).is_file() => {
+            return context::HfTokenizer::from_file(path)
+                .map(|tokenizer| Some(Arc::new(tokenizer) as Arc<dyn context::TokenCounter>))
+                .map_err(|e| e.to_string());
+        }
+        repo_id => repo_id.to_owned(),
+    };
+
+    let cache_dir = session::LunaHome::from_env()
+        .ok_or("cannot locate LUNA_HOME to cache tokenizers")?
+        .base_dir()
+        .join("tokenizers");
+    context::download_tokenizer(&repo_id, &cache_dir)
+        .map(|tokenizer| Some(Arc::new(tokenizer) as Arc<dyn context::TokenCounter>))
+        .map_err(|e| e.to_string())
+}
+
+/// Lines of a just-edited file around `line_1` (1-based), re-parsed from
+/// disk: the declaration containing the line, or a 21-line window when the
+/// file doesn't parse. A line between declarations (imports, module-level
+/// statements) gets the block of statements around it, led by the header
+/// line of the nearest declaration above, instead of a fixed window.
+pub fn edited_scope_chunk(
+    repo_root: &Path,
+    abs_path: &Path,
+    line_1: usize,
+) -> Option<context::ContextChunk> {
+    let content = std::fs::read_to_string(abs_path).ok()?;
+    let lines: Vec<&str> = content.lines().collect();
+    if lines.is_empty() {
+        return None;
+    }
+    let line = line_1.saturating_sub(1).min(lines.len() - 1);
+
+    let opt = intelligence::ChunkOptions {
+        granularity: intelligence::ChunkGranularity::Members,
+        ..Default::default()
+    };
+    let chunks = intelligence::detect_lang_id(abs_path)
+        .and_then(|lang_id| {
+            intelligence::TreeSitterFile::try_build(content.as_bytes(), lang_id).ok()
+        })
+        .and_then(|ts| ts.chunks(&opt).ok());
+    let last = lines.len() - 1;
+    let (start, end, header) = match chunks {
+        Some(chunks) => match chunks
+            .iter()
+            .find(|c| c.start_line <= line && line <= c.end_line)
+        {
+            Some(c) => (c.start_line, c.end_line.min(last), None),
+            None => intelligence::between_chunks(&lines, &chunks, line)?,
+        },
+        None => (line.saturating_sub(10), (line + 10).min(last), None),
+    };
+
+    let mut text = lines[start..=end].join("\n");
+    if let Some(header) = header {
+        let omitted = start - header - 1;
+        let comment = match abs_path.extension().and_then(|e| e.to_str()) {
+            Some("py" | "rb" | "r" | "R" | "sh") => "#",
+            _ => "//",
+        };
+        text = if omitted > 0 {
+            format!(
+                "{}\n{comment} ... ({omitted} lines omitted) ...\n{text}",
+                lines[header]
+            )
+        } else {
+            format!("{}\n{text}", lines[header])
+        };
+    }
+    let source = SourceLocation {
+        repo_root: repo_root.to_path_buf(),
+        rel_path: abs_path
+            .strip_prefix(repo_root)
+            .unwrap_or(abs_path)
+            .to_path_buf(),
+        range: TextRange::new(header.unwrap_or(start) + 1, end + 1),
+    };
+    Some(context::ContextChunk::new(
+        text,
+        source,
+        context::ContextType::CodeSnippet,
     ))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tmp_dir;
 
     #[test]
     fn test_location_conversion() {
@@ -246,4 +402,86 @@ mod tests {
         let back_to_intel = source_location_to_intel_location(&ctx_loc);
         assert_eq!(back_to_intel.range.start.line, 9); // Back to 0-based
     }
+
+    /// `Config` with `fn` in its comments, and `load` calling `make`
+    const CONFIG_RS: &str = "// fn load() builds this; see fn save() too\npub struct Config {\n    // fn-like: path()\n    pub path: String,\n}\n\npub fn load() -> Config {\n    make()\n}\n\nfn make() -> Config {\n    Config { path: String::new() }\n}\n";
+
+    #[test]
+    fn symbol_info_reads_signature_kind_and_callees_in_one_call() {
+        let dir = tmp_dir("bridge_symbol_info");
+        std::fs::write(dir.join("lib.rs"), CONFIG_RS).unwrap();
+        let navigator = Arc::new(intelligence::TreeSitterNavigator::new(
+            FsRepoFileProvider,
+            RepoScanOptions::default(),
+        ));
+        let resolver = IntelligenceSymbolResolver::new(navigator);
+        let info = |name: &str| {
+            let loc = resolver.find_definition(&dir, name).unwrap().remove(0);
+            resolver.get_symbol_info(&dir, &loc).unwrap()
+        };
+
+        let config = info("Config");
+        assert_eq!(config.signature.as_deref(), Some("pub struct Config"));
+        assert_eq!(config.kind.as_deref(), Some("struct"));
+        assert!(config.callees.is_empty());
+
+        let load = info("load");
+        assert_eq!(load.kind.as_deref(), Some("function"));
+        assert_eq!(load.callees, vec!["make".to_owned()]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn fn_in_a_comment_does_not_make_a_struct_a_function() {
+        let dir = tmp_dir("bridge_comment_fn");
+        std::fs::write(dir.join("lib.rs"), CONFIG_RS).unwrap();
+        let pipeline = create_refill_pipeline(
+            dir.clone(),
+            TestFilePolicy::Include,
+            &crate::config::TokenBudget::default(),
+        )
+        .unwrap();
+
+        let retrieved = pipeline
+            .retrieve(&context::ContextQuery::symbol("Config"), 1)
+            .unwrap();
+        let refined = pipeline.refine(&retrieved);
+        let chunk = &refined[0];
+        assert!(chunk.content.contains("// fn load()"), "{}", chunk.content);
+        assert_eq!(chunk.scope_kind.as_deref(), Some("struct"));
+        assert_eq!(chunk.symbol_kind(), Some("struct"));
+
+        // Without AST info a comment line is not taken for a signature either
+        let mut plain = chunk.clone();
+        plain.scope_kind = None;
+        plain.symbol_signatures = vec!["// fn load() builds this".to_owned()];
+        assert_eq!(plain.symbol_kind(), None);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn edited_line_between_declarations_gets_its_statements_and_header() {
+        let dir = tmp_dir("bridge_between_scopes");
+        let file = dir.join("lib.rs");
+        std::fs::write(
+            &file,
+            "pub fn a() -> u32 {\n    1\n}\n\nuse std::fmt;\nuse std::io;\n\npub fn b() {}\n",
+        )
+        .unwrap();
+
+        let chunk = edited_scope_chunk(&dir, &file, 6).unwrap();
+        assert_eq!(
+            chunk.content,
+            "pub fn a() -> u32 {\n// ... (3 lines omitted) ...\nuse std::fmt;\nuse std::io;"
+        );
+        assert_eq!(chunk.source.range.start_line, 1);
+        assert_eq!(chunk.source.range.end_line, 6);
+
+        let chunk = edited_scope_chunk(&dir, &file, 2).unwrap();
+        assert_eq!(chunk.content, "pub fn a() -> u32 {\n    1\n}");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
@@ -0,0 +1,225 @@
+//! Environment self-test behind `luna doctor`: each check reports pass/fail
+//! with a hint, instead of a setup problem surfacing mid-turn.
+
+use std::fmt;
+use std::path::Path;
+
+use crate::config::{
+    parse_kind_overrides, parse_number, parse_planner, parse_variant, Grounding, PostEditContext,
+};
+
+/// Outcome of one check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: &'static str,
+    pub passed: bool,
+    pub detail: String,
+    /// How to fix a failure.
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            passed: true,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            name,
+            passed: false,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = if self.passed { "ok  " } else { "FAIL" };
+        write!(f, "[{status}] {}: {}", self.name, self.detail)?;
+        if let Some(hint) = &self.hint {
+            write!(f, "\n       hint: {hint}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Run every check. `probe` sends one tiny completion to the configured
+/// endpoint and loads the tokenizer, downloading it if needed; it needs a
+/// multi-threaded tokio runtime.
+pub fn run_checks(repo_root: &Path, probe: bool) -> Vec<Check> {
+    vec![
+        llm_check(probe),
+        tokenizer_check(probe),
+        config_check(),
+        repo_writable_check(repo_root),
+    ]
+}
+
+fn llm_check(probe: bool) -> Check {
+    const NAME: &str = "llm";
+    let Some(config) = llm::OpenAIConfig::from_env() else {
+        return Check::fail(
+            NAME,
+            "LUNA_LLM_API_KEY is not set; only the rule-based planner is available",
+            "export LUNA_LLM_API_KEY (and LUNA_LLM_BASE_URL / LUNA_LLM_MODEL for non-OpenAI providers)",
+        );
+    };
+    let target = format!("{} at {}", config.model, config.base_url);
+    if !probe {
+        return Check::pass(NAME, format!("{target} (not probed)"));
+    }
+
+    let client = match llm::OpenAIClient::new(llm::OpenAIConfig {
+        max_tokens: Some(8),
+        ..config
+    }) {
+        Ok(client) => client,
+        Err(e) => return Check::fail(NAME, e.to_string(), "check the LUNA_LLM_* variables"),
+    };
+    let req = llm::CompletionRequest {
+        prompt: "Reply with OK.".to_owned(),
+    };
+    match llm::LLMClient::complete(&client, req) {
+        Ok(_) => Check::pass(NAME, format!("{target} answered")),
+        Err(e) => Check::fail(
+            NAME,
+            format!("{target}: {e}"),
+            "check the API key, LUNA_LLM_BASE_URL, LUNA_LLM_MODEL and network access",
+        ),
+    }
+}
+
+#[cfg(feature = "tokenizer-download")]
+fn tokenizer_check(probe: bool) -> Check {
+    const NAME: &str = "tokenizer";
+    if !probe {
+        return Check::pass(NAME, "not loaded");
+    }
+    match crate::context_bridge::try_load_model_tokenizer() {
+        Ok(Some(_)) => Check::pass(NAME, "loaded; context budgets use exact token counts"),
+        Ok(None) => Check::pass(NAME, "none for this model; token counts are approximated"),
+        Err(e) => Check::fail(
+            NAME,
+            e,
+            "set LUNA_TOKENIZER to a tokenizer.json path or Hugging Face repo id, or to `off`",
+        ),
+    }
+}
+
+#[cfg(not(feature = "tokenizer-download"))]
+fn tokenizer_check(_probe: bool) -> Check {
+    const NAME: &str = "tokenizer";
+    if std::env::var("LUNA_TOKENIZER").is_ok_and(|v| !v.trim().is_empty()) {
+        return Check::fail(
+            NAME,
+            "LUNA_TOKENIZER is set, but this build has no tokenizer support",
+            "rebuild with the `tokenizer-download` feature, or unset LUNA_TOKENIZER",
+        );
+    }
+    Check::pass(NAME, "not built in; token counts are approximated")
+}
+
+/// Environment settings that would otherwise fall back to defaults silently.
+/// Values are parsed by the same functions `RuntimeConfig::default` uses.
+fn config_check() -> Check {
+    const NAME: &str = "config";
+    let mut invalid = Vec::new();
+    let mut check = |var: &'static str, valid: fn(&str) -> bool| {
+        if let Ok(v) = std::env::var(var) {
+            if !valid(&v) {
+                invalid.push(format!("{var}={v:?}"));
+            }
+        }
+    };
+    check("LUNA_PLANNER", |v| parse_planner(v).is_some());
+    check("LUNA_POST_EDIT_CONTEXT", |v| {
+        parse_variant::<PostEditContext>(v).is_some()
+    });
+    check("LUNA_GROUNDING", |v| parse_variant::<Grounding>(v).is_some());
+    check("LUNA_EDIT_BACKUPS", |v| parse_number::<usize>(v).is_some());
+    check("LUNA_NEAR_DUPLICATE_THRESHOLD", |v| {
+        parse_number::<f32>(v).is_some()
+    });
+    check("LUNA_DATA_CHUNK_THRESHOLD", |v| parse_number::<f32>(v).is_some());
+    check("LUNA_KIND_OVERRIDES", |v| parse_kind_overrides(v).is_some());
+    check("LUNA_LLM_TIMEOUT_SECS", |v| parse_number::<u64>(v).is_some());
+    check("LUNA_LLM_MAX_TOKENS", |v| parse_number::<u32>(v).is_some());
+
+    if invalid.is_empty() {
+        Check::pass(NAME, "environment settings are valid")
+    } else {
+        Check::fail(
+            NAME,
+            format!("ignored invalid values: {}", invalid.join(", ")),
+            "see the environment variable table in the README for accepted values",
+        )
+    }
+}
+
+/// Edits and their backups are written next to the files they change.
+fn repo_writable_check(repo_root: &Path) -> Check {
+    const NAME: &str = "repo";
+    let probe = repo_root.join(format!(".luna-doctor-{}", std::process::id()));
+    match std::fs::write(&probe, b"") {
+        Ok(()) => {
+            let _ = std::fs::remove_file(&probe);
+            Check::pass(NAME, format!("{} is writable", repo_root.display()))
+        }
+        Err(e) => Check::fail(
+            NAME,
+            format!("cannot write to {}: {e}", repo_root.display()),
+            "run luna from a writable checkout, or use dry-run mode",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tmp_dir;
+
+    #[test]
+    fn repo_check_reports_unwritable_roots_with_a_hint() {
+        let dir = tmp_dir("doctor");
+
+        let ok = repo_writable_check(&dir);
+        assert!(ok.passed, "{ok}");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        let missing = repo_writable_check(&dir.join("missing"));
+        assert!(!missing.passed);
+        let shown = missing.to_string();
+        assert!(shown.starts_with("[FAIL] repo: cannot write to"), "{shown}");
+        assert!(shown.contains("\n       hint: "), "{shown}");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn config_values_are_judged_as_runtime_config_parses_them() {
+        // Numbers are trimmed, enum variants taken verbatim
+        assert_eq!(parse_number::<usize>(" 3\n"), Some(3));
+        assert_eq!(parse_number::<f32>("0.9"), Some(0.9));
+        assert_eq!(parse_number::<u32>("-1"), None);
+        assert_eq!(parse_variant::<Grounding>("strict"), Some(Grounding::Strict));
+        assert_eq!(parse_variant::<Grounding>("strict "), None);
+        assert_eq!(parse_planner("LLM"), Some(true));
+        assert_eq!(parse_planner("rule"), Some(false));
+        assert_eq!(parse_planner("auto"), None);
+        assert_eq!(
+            parse_kind_overrides(" impl=type, macro_rules = macro,"),
+            Some(context::KindOverrides::from([
+                ("impl".to_owned(), "type".to_owned()),
+                ("macro_rules".to_owned(), "macro".to_owned()),
+            ]))
+        );
+        assert_eq!(parse_kind_overrides("impl"), None);
+        assert_eq!(parse_kind_overrides("impl="), None);
+    }
+}
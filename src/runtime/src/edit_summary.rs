@@ -0,0 +1,76 @@
+//! One-line natural-language summaries of `edit_file` diffs, for commit
+//! messages and PR descriptions.
+
+/// Ask `client` for a one-line summary of `diff`.
+///
+/// `None` when the diff is trivial (see [`is_trivial_diff`]) or the model
+/// gives nothing usable; a failed call is logged, never fatal, since the edit
+/// itself already went through.
+pub fn summarize_edit(client: &dyn llm::LLMClient, diff: &str) -> Option<String> {
+    if is_trivial_diff(diff) {
+        return None;
+    }
+    let prompt = format!(
+        "Summarize the following code change in one line, written like a commit \
+         message subject (imperative mood, no trailing period). \
+         Reply with the summary only.\n\n{diff}"
+    );
+    let content = match client.complete(llm::CompletionRequest { prompt }) {
+        Ok(resp) => resp.content,
+        Err(e) => {
+            tracing::warn!("edit summary failed: {e}");
+            return None;
+        }
+    };
+    content
+        .lines()
+        .map(|l| l.trim().trim_matches(|c| c == '`' || c == '"').trim())
+        .find(|l| !l.is_empty())
+        .map(str::to_owned)
+}
+
+/// Whether `diff` (unified format) only changes whitespace or blank lines,
+/// or nothing at all.
+#[must_use]
+pub fn is_trivial_diff(diff: &str) -> bool {
+    let changed = |marker: char, header: &str| {
+        diff.lines()
+            .filter(|l| l.starts_with(marker) && !l.starts_with(header))
+            .flat_map(|l| l[1..].chars())
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+    };
+    changed('-', "---") == changed('+', "+++")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1,2 +1,2 @@\n";
+
+    #[test]
+    fn whitespace_and_blank_line_changes_are_trivial() {
+        assert!(is_trivial_diff(HEADER));
+        assert!(is_trivial_diff(&format!(
+            "{HEADER}-fn a() {{}}\n+fn a()  {{ }}\n+\n"
+        )));
+        assert!(!is_trivial_diff(&format!(
+            "{HEADER}-fn a() {{}}\n+fn b() {{}}\n"
+        )));
+    }
+
+    #[test]
+    fn summary_is_the_first_non_empty_line() {
+        let diff = format!("{HEADER}-let x = 1;\n+let x = 2;\n");
+        let client = llm::StaticClient::new("\n`Bump x to 2`\nextra\n");
+        assert_eq!(
+            summarize_edit(&client, &diff).as_deref(),
+            Some("Bump x to 2")
+        );
+
+        let client = llm::MockClient::new(Vec::new());
+        assert_eq!(summarize_edit(&client, &format!("{HEADER}-a\n+a \n")), None);
+        assert!(client.prompts().is_empty());
+    }
+}
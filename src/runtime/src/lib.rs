@@ -1,30 +1,55 @@
 //! Luna Runtime - Core runtime
 
+pub mod answer;
+pub mod cancel;
 pub mod command;
 pub mod config;
 pub mod context_bridge;
+pub mod doctor;
+pub mod edit_summary;
 pub mod intent;
+pub mod loop_guard;
 pub mod planner;
 pub mod recorder;
 pub mod recorder_jsonl;
 pub mod refill_trigger;
 pub mod render;
 pub mod request;
+pub mod resource;
 pub mod response;
 pub mod router;
 pub mod runtime;
 pub mod safety;
+#[cfg(feature = "schema")]
+pub mod schema;
 pub mod tpar;
 
+/// Fresh, empty directory under the system temp dir for a test.
+#[cfg(test)]
+pub(crate) fn tmp_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("luna_{name}_{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum RunMode {
     ChatTurn,
 }
 
+pub use intelligence::{
+    repo_scan::{SearchScope, TestFilePolicy},
+    supported_languages, LanguageInfo,
+};
+
 pub use {
-    config::RuntimeConfig,
+    answer::AnswerCleanup,
+    cancel::CancelToken,
+    config::{Grounding, PostEditContext, RuntimeConfig},
     recorder::{NoopTrajectoryRecorder, TrajectoryEvent, TrajectoryRecorder},
     request::{RequestMeta, RunRequest, SessionRef},
-    response::{RunResponse, RuntimeEvent},
+    resource::ResourceDescriptor,
+    response::{Citation, RunResponse, RuntimeEvent, TerminationReason},
+    router::NavigationOptions,
     runtime::LunaRuntime,
 };
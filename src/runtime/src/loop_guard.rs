@@ -0,0 +1,94 @@
+//! Loop detection for plans: a step, or a pair of steps ping-ponging, that
+//! repeats back to back is cut off instead of being run again.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::tpar::PlanStep;
+
+/// Longest cycle recognized (2 = `A B A B ...`).
+const MAX_PERIOD: usize = 2;
+
+/// Tracks steps in order and reports when the latest one completes a loop.
+#[derive(Debug, Clone)]
+pub struct LoopGuard {
+    max_repeats: usize,
+    history: Vec<u64>,
+}
+
+impl LoopGuard {
+    /// A loop is a cycle of one or two steps seen `max_repeats` times in a
+    /// row; below 2 nothing counts as a loop.
+    #[must_use]
+    pub fn new(max_repeats: usize) -> Self {
+        Self {
+            max_repeats,
+            history: Vec::new(),
+        }
+    }
+
+    /// Record `step`; `true` when it completes a loop.
+    pub fn observe(&mut self, step: &PlanStep) -> bool {
+        self.history.push(fingerprint(step));
+        (1..=MAX_PERIOD).any(|period| self.repeats(period))
+    }
+
+    fn repeats(&self, period: usize) -> bool {
+        let need = period * self.max_repeats;
+        if self.max_repeats < 2 || self.history.len() < need {
+            return false;
+        }
+        let tail = &self.history[self.history.len() - need..];
+        // A "pair" of identical steps is a period-1 loop, not a period-2 one.
+        (period == 1 || tail[0] != tail[1])
+            && tail.iter().enumerate().all(|(i, h)| *h == tail[i % period])
+    }
+}
+
+/// Drop `steps` from the one that completes a loop onwards; `true` if any were.
+pub fn truncate_loop(steps: &mut Vec<PlanStep>, max_repeats: usize) -> bool {
+    let mut guard = LoopGuard::new(max_repeats);
+    match steps.iter().position(|step| guard.observe(step)) {
+        Some(i) => {
+            steps.truncate(i);
+            true
+        }
+        None => false,
+    }
+}
+
+fn fingerprint(step: &PlanStep) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_string(step)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn echo(text: &str) -> PlanStep {
+        PlanStep::Echo {
+            text: text.to_owned(),
+        }
+    }
+
+    #[test]
+    fn cuts_repeated_steps_and_ping_pong() {
+        let mut steps = vec![echo("a"), echo("b"), echo("b"), echo("b"), echo("c")];
+        assert!(truncate_loop(&mut steps, 3));
+        assert_eq!(steps.len(), 3);
+
+        let mut steps = vec![echo("a"), echo("b"), echo("a"), echo("b"), echo("a")];
+        assert!(truncate_loop(&mut steps, 2));
+        assert_eq!(steps.len(), 3);
+
+        // Repeats below the limit, or interrupted, are fine.
+        let mut steps = vec![echo("a"), echo("a"), echo("b"), echo("a"), echo("a")];
+        assert!(!truncate_loop(&mut steps, 3));
+        assert_eq!(steps.len(), 5);
+        assert!(!truncate_loop(&mut steps, 1));
+    }
+}
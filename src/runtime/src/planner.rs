@@ -2,9 +2,10 @@
 
 use std::sync::Arc;
 
-use crate::config::TokenBudget;
-use crate::response::RuntimeEvent;
+use crate::config::{Grounding, TokenBudget};
+use crate::response::{Citation, RuntimeEvent};
 use crate::tpar::{CodeEntityKind, Plan, PlanStep, Task, TaskType};
+use tools::ToolArgs as _;
 
 /// Planner-only context.
 ///
@@ -16,6 +17,12 @@ pub struct PlannerContext {
     pub context_chunks: Vec<context::ContextChunk>,
     /// Repository root for path resolution
     pub repo_root: Option<std::path::PathBuf>,
+    /// Language for the final `echo` answer (e.g. "French"); `None` lets the model choose
+    pub answer_language: Option<String>,
+    /// Whether the final answer must come only from `context_chunks`
+    pub grounding: Grounding,
+    /// Number the context chunks and ask for `[n]` markers in the answer
+    pub cite_sources: bool,
 }
 
 impl std::fmt::Debug for PlannerContext {
@@ -24,6 +31,9 @@ impl std::fmt::Debug for PlannerContext {
             .field("budget", &self.budget)
             .field("context_chunks", &self.context_chunks.len())
             .field("repo_root", &self.repo_root)
+            .field("answer_language", &self.answer_language)
+            .field("grounding", &self.grounding)
+            .field("cite_sources", &self.cite_sources)
             .finish()
     }
 }
@@ -63,7 +73,12 @@ impl PlanValidator {
                 PlanStep::ToolCall { call } => {
                     let ok = matches!(
                         call.name.as_str(),
-                        "read_file" | "edit_file" | "run_terminal"
+                        "read_file"
+                            | "read_files"
+                            | "find_files"
+                            | "read_symbol"
+                            | "edit_file"
+                            | "run_terminal"
                     );
                     if !ok {
                         return Err(error::LunaError::invalid_input(format!(
@@ -148,24 +163,21 @@ impl RuleBasedPlanner {
                     .unwrap_or_default();
 
                 steps.push(PlanStep::ToolCall {
-                    call: tools::ToolCall {
-                        name: "read_file".to_owned(),
-                        args: serde_json::json!({ "path": path }),
-                    },
+                    call: tools::ReadFileArgs::new(&path).into_call(),
                 });
 
                 if let Some((line_1, new_line)) = Self::extract_edit_payload(task) {
                     steps.push(PlanStep::ToolCall {
-                        call: tools::ToolCall {
-                            name: "edit_file".to_owned(),
-                            args: serde_json::json!({ "path": path, "line_1": line_1, "new_line": new_line }),
-                        },
+                        call: tools::EditLineArgs {
+                            path: path.clone(),
+                            line_1,
+                            new_line,
+                            include_diff: false,
+                        }
+                        .into_call(),
                     });
                     steps.push(PlanStep::ToolCall {
-                        call: tools::ToolCall {
-                            name: "read_file".to_owned(),
-                            args: serde_json::json!({ "path": path }),
-                        },
+                        call: tools::ReadFileArgs::new(path).into_call(),
                     });
                 } else {
                     steps.push(PlanStep::ToolCall {
@@ -184,10 +196,7 @@ impl RuleBasedPlanner {
                     .map(|e| e.value.clone())
                     .unwrap_or_default();
                 steps.push(PlanStep::ToolCall {
-                    call: tools::ToolCall {
-                        name: "run_terminal".to_owned(),
-                        args: serde_json::json!({ "cmd": cmd }),
-                    },
+                    call: tools::RunTerminalArgs::new(cmd).into_call(),
                 });
             }
             TaskType::Chat => {
@@ -222,11 +231,12 @@ impl TaskPlanner for RuleBasedPlanner {
 
 /// LLM-based planner: request a JSON `Plan`.
 ///
-/// MVP: strict JSON parsing + validation + retry-once + fallback.
+/// MVP: strict JSON parsing + validation + at most one repair + fallback.
 #[derive(Clone)]
 pub struct LLMBasedPlanner {
     client: Arc<dyn llm::LLMClient>,
     validator: PlanValidator,
+    repair_malformed: bool,
 }
 
 impl LLMBasedPlanner {
@@ -235,14 +245,25 @@ impl LLMBasedPlanner {
         Self {
             client,
             validator: PlanValidator::new(max_steps),
+            repair_malformed: true,
         }
     }
 
+    /// Re-prompt once with the malformed output before failing (default: on).
+    #[must_use]
+    pub fn with_plan_repair(mut self, enabled: bool) -> Self {
+        self.repair_malformed = enabled;
+        self
+    }
+
     fn build_prompt(
         task: &Task,
         budget: &TokenBudget,
         repo_root: Option<&std::path::Path>,
         context_chunks: &[context::ContextChunk],
+        answer_language: Option<&str>,
+        grounding: Grounding,
+        cite_sources: bool,
     ) -> String {
         let example = r#"{
   "steps": [
@@ -259,7 +280,13 @@ impl LLMBasedPlanner {
             String::from("No relevant code context found.")
         } else {
             let mut ctx = String::from("\n");
+            let mut sources = Citation::from_chunks(context_chunks).into_iter();
             for chunk in context_chunks {
+                if cite_sources && chunk.context_type != context::ContextType::Imports {
+                    if let Some(source) = sources.next() {
+                        ctx.push_str(&format!("{source}\n"));
+                    }
+                }
                 ctx.push_str(&chunk.format_for_prompt());
                 ctx.push('\n');
             }
@@ -271,6 +298,29 @@ impl LLMBasedPlanner {
             .map(|p| format!("Project root: {}\n", p.display()))
             .unwrap_or_default();
 
+        let language_rule = answer_language
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(|l| {
+                format!("- Answer in {l}: write every 'echo' text in {l}, keep code and paths unchanged\n")
+            })
+            .unwrap_or_default();
+
+        let grounding_rule = match grounding {
+            Grounding::Lenient => "",
+            Grounding::Strict => {
+                "- Answer ONLY from the code context above or tool output: no APIs, files or behavior you have not seen\n\
+- Cite every claim in the 'echo' text as `path:line`\n\
+- If the code does not support an answer, echo exactly \"not found in the provided code\"\n"
+            }
+        };
+
+        let citation_rule = if cite_sources && !context_chunks.is_empty() {
+            "- Mark each claim in the 'echo' text with the `[n]` of the context source it comes from\n"
+        } else {
+            ""
+        };
+
         format!(
             "You are a planning engine for a code assistant.\n\
 {}\
@@ -279,25 +329,37 @@ Task type: {:?}\nUser input: {}\n\n\
 Create a plan using these step kinds:\n\
 - think: reasoning text (optional)\n\
 - intelligence: query for code navigation/explanation (ONLY if context above does not contain the answer)\n\
-- tool_call: invoke read_file, edit_file, or run_terminal\n\
+- tool_call: invoke read_file, read_files, find_files, read_symbol, edit_file, or run_terminal\n\
 - verify: run a command to verify changes\n\
 - echo: final response to user (MUST provide a helpful answer based on the context, NOT just echo the user input)\n\n\
 Available tools and their REQUIRED parameters:\n\
 1. read_file: {{\"path\": \"file/path.rs\"}}\n\
-2. edit_file: {{\"path\": \"file.rs\", \"line_1\": 10, \"new_line\": \"new content\"}} OR {{\"path\": \"file.rs\", \"start_line_1\": 10, \"end_line_1\": 15, \"replace_with\": \"new content\"}}\n\
-3. run_terminal: {{\"cmd\": \"command to execute\"}} - NOTE: use 'cmd' key, NOT 'command'\n\n\
+2. read_files: {{\"files\": [\"a.rs\", {{\"path\": \"b.rs\", \"start_line_1\": 10, \"end_line_1\": 40}}]}} - prefer this over several read_file calls\n\
+3. find_files: {{\"query\": \"partial/path\"}} - fuzzy-match file paths when only part of a name is known\n\
+4. read_symbol: {{\"name\": \"symbol_name\"}} (optional \"kind\": \"function\") - full source of every definition of a symbol\n\
+5. edit_file: {{\"path\": \"file.rs\", \"line_1\": 10, \"new_line\": \"new content\"}} OR {{\"path\": \"file.rs\", \"start_line_1\": 10, \"end_line_1\": 15, \"replace_with\": \"new content\"}}\n\
+6. run_terminal: {{\"cmd\": \"command to execute\"}} - NOTE: use 'cmd' key, NOT 'command'\n\n\
 CRITICAL RULES:\n\
 - The \"kind\" field MUST be exactly one of: \"think\", \"intelligence\", \"tool_call\", \"verify\", \"echo\" (NEVER use \"navigation\", \"search\", \"read\", \"edit\" or other values)\n\
 - run_terminal args MUST have {{\"cmd\": \"...\"}}, not {{\"command\": \"...\"}}\n\
 - All tool args must match the exact field names shown above\n\
 - Use file paths from the context when available\n\
 - If the context already shows the answer, use 'echo' to respond directly, NOT 'intelligence'\n\
-- Return ONLY valid JSON, no markdown, no backticks\n\n\
+- Return ONLY valid JSON, no markdown, no backticks\n\
+{}{}{}\n\
 Constraints:\n\
 - Maximum {} steps\n\
 - Return ONLY valid JSON\n\n\
 Example output:\n{}\n",
-            repo_section, context_section, task.task_type, task.raw_input, budget.max_steps, example
+            repo_section,
+            context_section,
+            task.task_type,
+            task.raw_input,
+            language_rule,
+            grounding_rule,
+            citation_rule,
+            budget.max_steps,
+            example
         )
     }
 
@@ -310,7 +372,7 @@ Example output:\n{}\n",
             "The previous output was invalid JSON or schema.\n\
 Error: {}\n\n\
 Previous output:\n{}\n\n\
-Please return ONLY valid JSON matching the Plan schema.\n\
+Return ONLY valid JSON matching the Plan schema: no prose, no markdown.\n\
 Max steps: {}\n\
 Required fields: steps[], estimated_tokens\n",
             error,
@@ -342,10 +404,47 @@ Required fields: steps[], estimated_tokens\n",
         trimmed.to_string()
     }
 
+    /// First balanced `{...}` in `s`, for JSON wrapped in prose.
+    fn extract_first_json_object(s: &str) -> Option<&str> {
+        let start = s.find('{')?;
+        let mut depth = 0usize;
+        let mut in_string = false;
+        let mut escaped = false;
+
+        for (i, c) in s[start..].char_indices() {
+            if in_string {
+                match c {
+                    _ if escaped => escaped = false,
+                    '\\' => escaped = true,
+                    '"' => in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match c {
+                '"' => in_string = true,
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(&s[start..=start + i]);
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
     fn try_parse_and_validate(&self, s: &str) -> error::Result<Plan> {
         let json_str = Self::extract_json(s);
 
         let v: serde_json::Value = serde_json::from_str(&json_str)
+            .or_else(|e| {
+                Self::extract_first_json_object(s)
+                    .and_then(|obj| serde_json::from_str(obj).ok())
+                    .ok_or(e)
+            })
             .map_err(|e| error::LunaError::invalid_input(format!("invalid json: {e}")))?;
         let plan: Plan = serde_json::from_value(v)
             .map_err(|e| error::LunaError::invalid_input(format!("invalid plan schema: {e}")))?;
@@ -365,7 +464,15 @@ impl TaskPlanner for LLMBasedPlanner {
         ctx: &PlannerContext,
         events: &mut dyn crate::response::EventSink,
     ) -> error::Result<Plan> {
-        let prompt = Self::build_prompt(task, &ctx.budget, ctx.repo_root.as_deref(), &ctx.context_chunks);
+        let prompt = Self::build_prompt(
+            task,
+            &ctx.budget,
+            ctx.repo_root.as_deref(),
+            &ctx.context_chunks,
+            ctx.answer_language.as_deref(),
+            ctx.grounding,
+            ctx.cite_sources,
+        );
 
         let ev = RuntimeEvent::TparPlanBuilt {
             plan: "planner=llm (deepseek) request".to_owned(),
@@ -380,8 +487,12 @@ impl TaskPlanner for LLMBasedPlanner {
         // 1st attempt
         match self.try_parse_and_validate(&out) {
             Ok(plan) => Ok(plan),
+            Err(first_err) if !self.repair_malformed => Err(first_err),
             Err(first_err) => {
-                // Retry once with a repair instruction.
+                // Repair once; a second failure goes to the caller's fallback.
+                events.emit(&RuntimeEvent::TparPlanBuilt {
+                    plan: format!("planner=llm repair: {first_err}"),
+                });
                 let repair_prompt = Self::build_repair_prompt(&first_err, &out, &ctx.budget);
                 let out2 = self
                     .client
@@ -480,9 +591,13 @@ mod tests {
                 max_input_chars: 2048,
                 max_io_bytes: 1024,
                 max_steps: 8,
+                ..TokenBudget::default()
             },
             context_chunks: Vec::new(),
             repo_root: None,
+            answer_language: None,
+            grounding: Grounding::Lenient,
+            cite_sources: false,
         };
 
         // Provide two responses: first fails, second also fails (triggering fallback)
@@ -504,6 +619,173 @@ mod tests {
         assert!(matches!(plan.steps[0], PlanStep::Intelligence { .. }));
     }
 
+    #[test]
+    fn llm_planner_repairs_at_most_once() {
+        let ctx = PlannerContext {
+            budget: TokenBudget::default(),
+            context_chunks: Vec::new(),
+            repo_root: None,
+            answer_language: None,
+            grounding: Grounding::Lenient,
+            cite_sources: false,
+        };
+        let task = mk_task(TaskType::Chat, "explain main");
+        let valid = r#"{"steps": [{"kind": "echo", "text": "hi"}], "estimated_tokens": 10}"#;
+
+        // Prose around the JSON is not worth a repair round-trip.
+        let client = Arc::new(llm::MockClient::new(vec![format!(
+            "Sure! Here is the plan:\n{valid}\nLet me know."
+        )]));
+        let plan = LLMBasedPlanner::new(client, 12)
+            .plan(&task, &ctx, &mut Vec::<RuntimeEvent>::new())
+            .unwrap();
+        assert_eq!(plan.steps.len(), 1);
+
+        let client = Arc::new(llm::MockClient::new(vec![
+            "not json".to_owned(),
+            valid.to_owned(),
+        ]));
+        let mut events = Vec::<RuntimeEvent>::new();
+        let plan = LLMBasedPlanner::new(client.clone(), 12)
+            .plan(&task, &ctx, &mut events)
+            .unwrap();
+        assert_eq!(plan.steps.len(), 1);
+        assert!(events.iter().any(|e| matches!(
+            e,
+            RuntimeEvent::TparPlanBuilt { plan } if plan.starts_with("planner=llm repair")
+        )));
+
+        // Disabled: the bad output fails without consuming the queued answer.
+        client.push("not json");
+        client.push(valid);
+        let err = LLMBasedPlanner::new(client.clone(), 12)
+            .with_plan_repair(false)
+            .plan(&task, &ctx, &mut Vec::<RuntimeEvent>::new());
+        assert!(err.is_err());
+        let plan = LLMBasedPlanner::new(client, 12)
+            .plan(&task, &ctx, &mut Vec::<RuntimeEvent>::new())
+            .unwrap();
+        assert_eq!(plan.steps.len(), 1);
+    }
+
+    #[test]
+    fn test_extract_first_json_object() {
+        let s = r#"Plan: {"a": "}{", "b": {"c": "\"}"}} trailing {"#;
+        assert_eq!(
+            LLMBasedPlanner::extract_first_json_object(s),
+            Some(r#"{"a": "}{", "b": {"c": "\"}"}}"#)
+        );
+        assert_eq!(
+            LLMBasedPlanner::extract_first_json_object("{ unclosed"),
+            None
+        );
+        assert_eq!(LLMBasedPlanner::extract_first_json_object("none"), None);
+    }
+
+    #[test]
+    fn test_prompt_requests_answer_language() {
+        let task = mk_task(TaskType::Explain, "what does main do");
+        let budget = TokenBudget::default();
+
+        let prompt = LLMBasedPlanner::build_prompt(
+            &task,
+            &budget,
+            None,
+            &[],
+            Some("French"),
+            Grounding::Lenient,
+            false,
+        );
+        assert!(prompt.contains("- Answer in French:"));
+
+        let prompt = LLMBasedPlanner::build_prompt(
+            &task,
+            &budget,
+            None,
+            &[],
+            Some("  "),
+            Grounding::Lenient,
+            false,
+        );
+        assert!(!prompt.contains("Answer in"));
+    }
+
+    #[test]
+    fn test_strict_grounding_prompt_requires_citations() {
+        let task = mk_task(TaskType::Explain, "what does main do");
+        let budget = TokenBudget::default();
+
+        let prompt = LLMBasedPlanner::build_prompt(
+            &task,
+            &budget,
+            None,
+            &[],
+            None,
+            Grounding::Strict,
+            false,
+        );
+        assert!(prompt.contains("Answer ONLY from the code context"));
+        assert!(prompt.contains("`path:line`"));
+        assert!(prompt.contains("\"not found in the provided code\""));
+
+        let prompt = LLMBasedPlanner::build_prompt(
+            &task,
+            &budget,
+            None,
+            &[],
+            None,
+            Grounding::Lenient,
+            false,
+        );
+        assert!(!prompt.contains("not found in the provided code"));
+    }
+
+    #[test]
+    fn test_cite_sources_numbers_context_chunks() {
+        let task = mk_task(TaskType::Explain, "what does bar do");
+        let budget = TokenBudget::default();
+        let source = |path: &str, start, end| context::SourceLocation {
+            repo_root: std::path::PathBuf::from("/repo"),
+            rel_path: std::path::PathBuf::from(path),
+            range: context::TextRange::new(start, end),
+        };
+        let imports = context::ContextChunk::new(
+            "use std::fs;",
+            source("src/foo.rs", 1, 1),
+            context::ContextType::Imports,
+        );
+        let bar = context::ContextChunk::navigation_result(
+            "fn bar() {}",
+            source("src/foo.rs", 42, 88),
+            "pub fn bar()",
+        );
+        let chunks = [imports, bar];
+
+        let prompt = LLMBasedPlanner::build_prompt(
+            &task,
+            &budget,
+            None,
+            &chunks,
+            None,
+            Grounding::Lenient,
+            true,
+        );
+        assert!(prompt.contains("[1] src/foo.rs:42-88 (fn bar)\n"));
+        assert!(!prompt.contains("[2]"));
+        assert!(prompt.contains("with the `[n]` of the context source"));
+
+        let prompt = LLMBasedPlanner::build_prompt(
+            &task,
+            &budget,
+            None,
+            &chunks,
+            None,
+            Grounding::Lenient,
+            false,
+        );
+        assert!(!prompt.contains("[1] src/foo.rs"));
+    }
+
     #[test]
     fn test_extract_json_from_markdown() {
         // Test with json language tag
@@ -543,9 +825,13 @@ mod tests {
                 max_input_chars: 2048,
                 max_io_bytes: 1024,
                 max_steps: 8,
+                ..TokenBudget::default()
             },
             context_chunks: Vec::new(),
             repo_root: None,
+            answer_language: None,
+            grounding: Grounding::Lenient,
+            cite_sources: false,
         };
 
         let task = mk_task(TaskType::Chat, "修复项目");
@@ -589,9 +875,13 @@ mod tests {
                     max_input_chars: 2048,
                     max_io_bytes: 1024,
                     max_steps: 8,
+                    ..TokenBudget::default()
                 },
                 context_chunks: Vec::new(),
                 repo_root: None,
+                answer_language: None,
+                grounding: Grounding::Lenient,
+                cite_sources: false,
             };
             let mut events = Vec::<RuntimeEvent>::new();
 
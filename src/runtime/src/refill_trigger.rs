@@ -58,6 +58,8 @@ pub struct RefillTrigger {
     max_refills: usize,
     /// Current refill count
     refill_count: usize,
+    /// Chunks retrieved per refilled symbol
+    max_chunks: usize,
 }
 
 impl RefillTrigger {
@@ -68,9 +70,17 @@ impl RefillTrigger {
             refilled_symbols: HashSet::new(),
             max_refills: 3,
             refill_count: 0,
+            max_chunks: crate::config::TokenBudget::default().refill_context_chunks,
         }
     }
 
+    /// Set how many chunks each refill retrieves (see
+    /// `TokenBudget::refill_context_chunks`)
+    pub fn with_max_chunks(mut self, max_chunks: usize) -> Self {
+        self.max_chunks = max_chunks;
+        self
+    }
+
     /// Check if we can still perform refills
     pub fn can_refill(&self) -> bool {
         self.refill_count < self.max_refills
@@ -177,7 +187,7 @@ impl RefillTrigger {
             name: symbol.to_string(),
         };
 
-        let index_chunks = self.pipeline.retrieve(&query, 5)?;
+        let index_chunks = self.pipeline.retrieve(&query, self.max_chunks)?;
         let context_chunks = self.pipeline.refine(&index_chunks);
 
         Ok(context_chunks)
@@ -334,6 +344,36 @@ mod tests {
         assert!(symbols.contains(&"UserService".to_string()));
     }
 
+    #[test]
+    fn max_chunks_limits_each_refill() {
+        let dir = crate::tmp_dir("refill_trigger_max_chunks");
+        std::fs::write(dir.join("lib.rs"), "pub fn target_fn() -> u32 {\n    1\n}\n").unwrap();
+        for i in 0..4 {
+            std::fs::write(
+                dir.join(format!("m{i}.rs")),
+                format!("pub fn caller_{i}() -> u32 {{\n    target_fn() + {i}\n}}\n"),
+            )
+            .unwrap();
+        }
+        let refill = |max_chunks: usize| {
+            // A fresh pipeline each time: its query cache ignores the limit.
+            let pipeline = crate::context_bridge::create_refill_pipeline(
+                dir.clone(),
+                intelligence::repo_scan::TestFilePolicy::Include,
+                &crate::config::TokenBudget::default(),
+            )
+            .unwrap();
+            let mut trigger = RefillTrigger::new(Arc::new(pipeline)).with_max_chunks(max_chunks);
+            match trigger.analyze_and_refill("I don't see target_fn in the context.") {
+                RefillResult::Success { new_chunks } => new_chunks.len(),
+                other => panic!("unexpected refill result: {other:?}"),
+            }
+        };
+
+        assert_eq!(refill(1), 1);
+        assert!(refill(4) > 1);
+    }
+
     #[test]
     fn test_no_false_positives() {
         let response = "I see the implementation clearly. It looks good to me.";
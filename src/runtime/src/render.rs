@@ -9,12 +9,23 @@ pub enum RenderStyle {
     Explain,
 }
 
+/// Other definitions listed after the primary one.
+pub const MAX_LISTED_CANDIDATES: usize = 5;
+
+/// Another definition matching the looked-up name.
+#[derive(Debug, Clone)]
+pub struct Candidate<'a> {
+    pub location: &'a SymbolLocation,
+    /// Shown instead of the bare location when set (`parser::Parser::new`).
+    pub qualified_name: Option<String>,
+}
+
 /// Render a successful symbol lookup result.
 pub fn render_symbol_navigation_success(
     name: &str,
     primary: &SymbolLocation,
     context: Result<SymbolContext, error::LunaError>,
-    other_candidates: &[SymbolLocation],
+    other_candidates: &[Candidate<'_>],
     references: &[SymbolLocation],
 ) -> String {
     render_symbol_result(
@@ -31,7 +42,7 @@ pub fn render_symbol_explain_success(
     name: &str,
     primary: &SymbolLocation,
     context: Result<SymbolContext, error::LunaError>,
-    other_candidates: &[SymbolLocation],
+    other_candidates: &[Candidate<'_>],
     references: &[SymbolLocation],
 ) -> String {
     render_symbol_result(
@@ -49,7 +60,7 @@ fn render_symbol_result(
     name: &str,
     primary: &SymbolLocation,
     context: Result<SymbolContext, error::LunaError>,
-    other_candidates: &[SymbolLocation],
+    other_candidates: &[Candidate<'_>],
     references: &[SymbolLocation],
 ) -> String {
     let line_1_based = primary.range.start.line + 1;
@@ -76,6 +87,9 @@ fn render_symbol_result(
                     if let Some(kind) = kind {
                         out.push_str(&format!("Type determined: {kind}\n"));
                     }
+                    if let Some(vis) = ctx.visibility {
+                        out.push_str(&format!("Visibility: {}\n", vis.as_str()));
+                    }
                     let doc = extract_doc_comment(&ctx.snippet, &sig);
                     if !doc.is_empty() {
                         out.push_str("Documentation comments (excerpt):\n");
@@ -104,12 +118,13 @@ fn render_symbol_result(
 
     if !other_candidates.is_empty() {
         out.push_str("\nOther candidate definitions:\n");
-        for loc in other_candidates.iter().take(5) {
-            out.push_str(&format!(
-                "- {}:{}\n",
-                loc.rel_path.display(),
-                loc.range.start.line + 1
-            ));
+        for candidate in other_candidates.iter().take(MAX_LISTED_CANDIDATES) {
+            let loc = candidate.location;
+            let position = format!("{}:{}", loc.rel_path.display(), loc.range.start.line + 1);
+            match &candidate.qualified_name {
+                Some(qualified) => out.push_str(&format!("- {qualified} ({position})\n")),
+                None => out.push_str(&format!("- {position}\n")),
+            }
         }
     }
 
@@ -355,7 +370,7 @@ pub fn render_symbol_navigation_missing_identifier() -> String {
 
 pub fn render_symbol_navigation_missing_repo_root(name: &str) -> String {
     let _ = name;
-    "⚠️ Unable to resolve repo_root: needs to be run in a git repository directory (cwd should trace back to `.git`).".to_owned()
+    "⚠️ Unable to resolve repo_root: needs to be run inside a repository (cwd should trace back to `.git`, `Cargo.toml` or `package.json`).".to_owned()
 }
 
 pub fn render_symbol_navigation_search_failed(name: &str, err: &error::LunaError) -> String {
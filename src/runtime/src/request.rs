@@ -1,4 +1,4 @@
-use crate::RunMode;
+use crate::{CancelToken, RunMode};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
@@ -21,6 +21,13 @@ pub struct RunRequest {
     pub input: String,
     pub cwd: Option<PathBuf>,
     pub meta: RequestMeta,
+    /// Chunks an external index already found (e.g. an editor's symbol DB).
+    /// They're merged ahead of Luna's own retrieval for the turn.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub seed_chunks: Vec<context::IndexChunk>,
+    /// Set to stop the turn between plan steps.
+    #[serde(skip)]
+    pub cancel: CancelToken,
 }
 
 impl RunRequest {
@@ -32,6 +39,8 @@ impl RunRequest {
             input: input.into(),
             cwd: None,
             meta: RequestMeta { trace: true },
+            seed_chunks: Vec::new(),
+            cancel: CancelToken::default(),
         }
     }
 
@@ -39,4 +48,14 @@ impl RunRequest {
         self.cwd = Some(cwd);
         self
     }
+
+    pub fn with_seed_chunks(mut self, seed_chunks: Vec<context::IndexChunk>) -> Self {
+        self.seed_chunks = seed_chunks;
+        self
+    }
+
+    pub fn with_cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
 }
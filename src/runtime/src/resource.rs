@@ -0,0 +1,205 @@
+//! MCP-style resources: repository source files, addressed by `file://` URI.
+//!
+//! Uses the same walk and ignore rules as navigation, so clients can browse
+//! and read files without going through the tool-execution path: a file the
+//! walk would skip (`.git/`, `target/`, hidden or excluded files) can't be
+//! read either.
+
+use std::path::{Path, PathBuf};
+
+use error::{LunaError, Result};
+use intelligence::repo_scan::{FsRepoFileProvider, RepoFileProvider, RepoScanOptions};
+use serde::{Deserialize, Serialize};
+
+const URI_SCHEME: &str = "file://";
+
+/// A readable file in the repository.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceDescriptor {
+    /// `file://` URI of the absolute path (RFC 8089, percent-encoded); pass it
+    /// to `read_resource`
+    pub uri: String,
+    pub rel_path: PathBuf,
+    /// Tree-sitter language id, e.g. `rust`
+    pub language: Option<String>,
+    pub size_bytes: u64,
+}
+
+/// Supported source files under `repo_root`, sorted by path.
+pub fn list_resources(repo_root: &Path, opt: &RepoScanOptions) -> Result<Vec<ResourceDescriptor>> {
+    let files = FsRepoFileProvider
+        .list_files(repo_root, opt)
+        .map_err(|e| LunaError::invalid_input(format!("list resources: {e}")))?;
+
+    let mut out = files
+        .into_iter()
+        .map(|f| ResourceDescriptor {
+            uri: file_uri(&f.abs_path),
+            language: intelligence::detect_lang_id(&f.rel_path).map(str::to_owned),
+            size_bytes: f.content.len() as u64,
+            rel_path: f.rel_path,
+        })
+        .collect::<Vec<_>>();
+    out.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+    Ok(out)
+}
+
+/// Contents of a resource returned by [`list_resources`].
+///
+/// Only files inside `repo_root` that a scan under `opt` would list, and
+/// within its size limit, can be read.
+pub fn read_resource(repo_root: &Path, uri: &str, opt: &RepoScanOptions) -> Result<String> {
+    let path = uri_path(uri)
+        .ok_or_else(|| LunaError::invalid_input(format!("unsupported resource uri: {uri}")))?;
+
+    let canonical = path
+        .canonicalize()
+        .map_err(|e| LunaError::io(Some(path.clone()), e))?;
+    let root = repo_root
+        .canonicalize()
+        .map_err(|e| LunaError::io(Some(repo_root.to_path_buf()), e))?;
+    let Ok(rel_path) = canonical.strip_prefix(&root) else {
+        return Err(LunaError::invalid_input(format!(
+            "resource outside repository: {uri}"
+        )));
+    };
+    if !FsRepoFileProvider::admits_path(rel_path, opt) {
+        return Err(LunaError::invalid_input(format!(
+            "resource excluded from the repository scan: {uri}"
+        )));
+    }
+
+    let len = std::fs::metadata(&canonical)
+        .map_err(|e| LunaError::io(Some(canonical.clone()), e))?
+        .len();
+    if len as usize > opt.max_file_size_for(&canonical) {
+        return Err(LunaError::invalid_input(format!(
+            "resource too large ({len} bytes): {uri}"
+        )));
+    }
+
+    std::fs::read_to_string(&canonical).map_err(|e| LunaError::io(Some(canonical), e))
+}
+
+/// Characters left as is in a URI path; everything else is percent-encoded.
+fn is_uri_path_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"-._~/:".contains(&b)
+}
+
+/// `file:///abs/path`, with Windows drives as `file:///C:/abs/path`.
+fn file_uri(abs_path: &Path) -> String {
+    let path = abs_path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::from(URI_SCHEME);
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+    for b in path.bytes() {
+        if is_uri_path_char(b) {
+            uri.push(char::from(b));
+        } else {
+            uri.push_str(&format!("%{b:02X}"));
+        }
+    }
+    uri
+}
+
+/// Path of a `file://` URI from [`file_uri`]; `None` for other schemes or
+/// broken escapes.
+fn uri_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix(URI_SCHEME)?.as_bytes();
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        if encoded[i] == b'%' {
+            let hex = std::str::from_utf8(encoded.get(i + 1..i + 3)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            bytes.push(encoded[i]);
+            i += 1;
+        }
+    }
+    let path = String::from_utf8(bytes).ok()?;
+    // `/C:/x` -> `C:/x`
+    let path = match path.as_bytes() {
+        [b'/', drive, b':', ..] if cfg!(windows) && drive.is_ascii_alphabetic() => &path[1..],
+        _ => path.as_str(),
+    };
+    Some(PathBuf::from(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tmp_dir;
+
+    #[test]
+    fn list_then_read_resources() {
+        let base = tmp_dir("resource");
+        let root = base.join("repo");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/lib.rs"), "pub fn a() {}\n").unwrap();
+        std::fs::write(root.join("notes.txt"), "not source\n").unwrap();
+        std::fs::write(base.join("secret.rs"), "fn s() {}\n").unwrap();
+
+        let opt = RepoScanOptions::default();
+        let resources = list_resources(&root, &opt).unwrap();
+        assert_eq!(resources.len(), 1);
+        assert_eq!(resources[0].rel_path, PathBuf::from("src/lib.rs"));
+        assert_eq!(resources[0].language.as_deref(), Some("rust"));
+        assert_eq!(resources[0].size_bytes, 14);
+
+        assert_eq!(
+            read_resource(&root, &resources[0].uri, &opt).unwrap(),
+            "pub fn a() {}\n"
+        );
+
+        let escape = file_uri(&root.join("../secret.rs"));
+        assert!(read_resource(&root, &escape, &opt).is_err());
+        assert!(read_resource(&root, "http://example.com/x.rs", &opt).is_err());
+
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn read_resource_refuses_files_the_scan_skips() {
+        let root = tmp_dir("resource_skipped");
+        for dir in [".git", "target/debug", "vendor", "src"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        std::fs::write(root.join(".git/config"), "[core]\n").unwrap();
+        std::fs::write(root.join(".env"), "TOKEN=x\n").unwrap();
+        std::fs::write(root.join("target/debug/build.rs"), "fn b() {}\n").unwrap();
+        std::fs::write(root.join("vendor/dep.rs"), "fn d() {}\n").unwrap();
+        std::fs::write(root.join("src/api.pb.go"), "package api\n").unwrap();
+        std::fs::write(root.join("src/lib.rs"), "pub fn a() {}\n").unwrap();
+
+        let opt = RepoScanOptions::default().with_exclude_dir("vendor");
+        let listed = list_resources(&root, &opt).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert!(read_resource(&root, &listed[0].uri, &opt).is_ok());
+
+        for rel in [
+            ".git/config",
+            ".env",
+            "target/debug/build.rs",
+            "vendor/dep.rs",
+            "src/api.pb.go",
+        ] {
+            let uri = file_uri(&root.join(rel));
+            let err = read_resource(&root, &uri, &opt).unwrap_err();
+            assert!(err.to_string().contains("excluded"), "{rel}: {err}");
+        }
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn resource_uris_are_percent_encoded() {
+        let path = Path::new("/repo/my dir/a#1.rs");
+        let uri = file_uri(path);
+        assert_eq!(uri, "file:///repo/my%20dir/a%231.rs");
+        assert_eq!(uri_path(&uri).as_deref(), Some(path));
+        assert_eq!(uri_path("file:///bad%2"), None);
+    }
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum RuntimeEvent {
@@ -15,6 +16,24 @@ pub enum RuntimeEvent {
     TparTaskClassified {
         task: String,
     },
+    /// TPAR: context collected for planning
+    TparContextCollected {
+        chunks: usize,
+        /// e.g. `3 fn, 2 struct`; empty when no kinds were recognized
+        kinds: String,
+    },
+    /// TPAR: numbered sources for the collected context, in prompt order;
+    /// only when citing sources is enabled
+    TparSourcesCollected {
+        sources: Vec<Citation>,
+    },
+    /// TPAR: context refreshed after a step edited files
+    TparContextRefreshed {
+        step_id: usize,
+        chunks: usize,
+        /// e.g. `1 fn`; empty when no kinds were recognized
+        kinds: String,
+    },
     /// TPAR: plan built for this trun
     TparPlanBuilt {
         plan: String,
@@ -33,6 +52,11 @@ pub enum RuntimeEvent {
     TparReviewed {
         ok: bool,
     },
+    /// Answer text, emitted as soon as a step produces it (ahead of the
+    /// formatted turn output)
+    AnswerDelta {
+        text: String,
+    },
 
     /// Runtime detected a symbol query in the user input.
     FoundIdentifier {
@@ -48,6 +72,70 @@ pub enum RuntimeEvent {
     },
 }
 
+/// One numbered source of a turn's answer, e.g. `[1] src/foo.rs:42-88 (fn bar)`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Citation {
+    /// 1-based; the `[n]` the model was asked to use
+    pub index: usize,
+    pub path: PathBuf,
+    pub start_line: usize,
+    pub end_line: usize,
+    /// `fn bar`, `struct Foo`, ...; `None` when the chunk is not a known definition
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+}
+
+impl Citation {
+    /// Number the chunks that can back an answer (all but import blocks), in order.
+    #[must_use]
+    pub fn from_chunks(chunks: &[context::ContextChunk]) -> Vec<Self> {
+        Self::from_chunks_with(chunks, &context::SignaturePatterns::default())
+    }
+
+    /// Like [`Self::from_chunks`], naming symbols with `patterns`.
+    #[must_use]
+    pub fn from_chunks_with(
+        chunks: &[context::ContextChunk],
+        patterns: &context::SignaturePatterns,
+    ) -> Vec<Self> {
+        chunks
+            .iter()
+            .filter(|c| c.context_type != context::ContextType::Imports)
+            .enumerate()
+            .map(|(i, chunk)| {
+                let name = chunk
+                    .symbol_signatures
+                    .iter()
+                    .find_map(|sig| patterns.name(sig));
+                Self {
+                    index: i + 1,
+                    path: chunk.source.rel_path.clone(),
+                    start_line: chunk.source.range.start_line,
+                    end_line: chunk.source.range.end_line,
+                    symbol: match (patterns.symbol_kind(chunk), name) {
+                        (Some(kind), Some(name)) => Some(format!("{kind} {name}")),
+                        _ => None,
+                    },
+                }
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for Citation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let path = self.path.display();
+        write!(f, "[{}] {path}:{}", self.index, self.start_line)?;
+        if self.end_line > self.start_line {
+            write!(f, "-{}", self.end_line)?;
+        }
+        if let Some(symbol) = &self.symbol {
+            write!(f, " ({symbol})")?;
+        }
+        Ok(())
+    }
+}
+
 /// A sink for runtime events
 pub trait EventSink {
     fn emit(&mut self, event: &RuntimeEvent);
@@ -67,10 +155,53 @@ impl EventSink for Vec<RuntimeEvent> {
     }
 }
 
+/// Why a turn ended, so callers can tell an answer from a step limit or a refusal.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "detail", rename_all = "snake_case")]
+pub enum TerminationReason {
+    /// Every planned step ran
+    #[default]
+    Answered,
+    /// The plan was cut to the step budget; the remaining steps did not run
+    MaxStepsReached,
+    /// The plan repeated a step (or a pair of steps) `max_repeated_steps`
+    /// times in a row; it was cut before the repetition
+    LoopDetected,
+    /// The repository was searched and nothing relevant came back
+    NoResults,
+    /// The turn stopped early (rejected input, failed step)
+    Stopped(String),
+    /// The caller cancelled the turn
+    Cancelled,
+    /// A safety rule refused one of the planned actions
+    PolicyBlocked(String),
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RunResponse {
     pub request_id: String,
     pub session_id: String,
     pub output: String,
+    /// Answer before cleanup; set only when cleanup changed it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_output: Option<String>,
+    #[serde(default)]
+    pub termination: TerminationReason,
     pub events: Vec<RuntimeEvent>,
 }
+
+impl RunResponse {
+    /// Numbered sources behind `output`; empty unless citing sources is
+    /// enabled and context was collected.
+    #[must_use]
+    pub fn sources(&self) -> &[Citation] {
+        self.events
+            .iter()
+            .rev()
+            .find_map(|ev| match ev {
+                RuntimeEvent::TparSourcesCollected { sources } => Some(sources.as_slice()),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+}
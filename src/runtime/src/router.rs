@@ -2,13 +2,48 @@ use std::path::{Path, PathBuf};
 
 use error::{Result, ResultExt as _};
 
-use intelligence::{Navigator, SnippetOptions, TreeSitterNavigator};
+use intelligence::{
+    repo_scan::{FsRepoFileProvider, RepoScanOptions},
+    Navigator, SnippetOptions, SymbolContext, SymbolLocation, TreeSitterNavigator,
+};
 
 use crate::{
     intent, render,
     response::{EventSink, RuntimeEvent},
 };
 
+/// Knobs for repository search during symbol navigation.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct NavigationOptions {
+    /// Which files are scanned (size cap, ignored dirs, file count cap).
+    pub scan: RepoScanOptions,
+    /// Max references listed per symbol.
+    pub max_references: usize,
+    /// Show definitions by qualified name (`parser::Parser::new`) instead of
+    /// the bare name, so same-named symbols can be told apart.
+    pub qualify_names: bool,
+}
+
+#[cfg(feature = "schema")]
+impl NavigationOptions {
+    /// JSON Schema of the navigation options.
+    #[must_use]
+    pub fn schema() -> schemars::schema::RootSchema {
+        schemars::schema_for!(Self)
+    }
+}
+
+impl Default for NavigationOptions {
+    fn default() -> Self {
+        Self {
+            scan: RepoScanOptions::default(),
+            max_references: 30,
+            qualify_names: false,
+        }
+    }
+}
+
 /// Runtime router decides which subsystem should handle the user input.
 ///
 #[derive(Debug, Default)]
@@ -17,6 +52,17 @@ pub struct RuntimeRouter {
 }
 
 impl RuntimeRouter {
+    pub fn new(opt: NavigationOptions) -> Self {
+        Self {
+            symbol_nav: SymbolNavigationRouter {
+                navigator: TreeSitterNavigator::new(FsRepoFileProvider, opt.scan),
+                snippet_opt: SnippetOptions::default(),
+                max_references: opt.max_references,
+                qualify_names: opt.qualify_names,
+            },
+        }
+    }
+
     pub fn maybe_handle(
         &self,
         user_input: &str,
@@ -35,10 +81,24 @@ impl RuntimeRouter {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 struct SymbolNavigationRouter {
-    navigator: TreeSitterNavigator<intelligence::repo_scan::FsRepoFileProvider>,
+    navigator: TreeSitterNavigator<FsRepoFileProvider>,
     snippet_opt: SnippetOptions,
+    max_references: usize,
+    qualify_names: bool,
+}
+
+impl Default for SymbolNavigationRouter {
+    fn default() -> Self {
+        let opt = NavigationOptions::default();
+        Self {
+            navigator: TreeSitterNavigator::new(FsRepoFileProvider, opt.scan),
+            snippet_opt: SnippetOptions::default(),
+            max_references: opt.max_references,
+            qualify_names: opt.qualify_names,
+        }
+    }
 }
 
 impl SymbolNavigationRouter {
@@ -134,11 +194,13 @@ impl SymbolNavigationRouter {
                     )
                 });
 
-            let other_candidates = definitions.get(1..).unwrap_or_default();
+            let display_name = self.display_name(name, &ctx);
+            let other_candidates =
+                self.candidates(&repo_root, definitions.get(1..).unwrap_or_default());
 
             let references = self
                 .navigator
-                .find_references(&repo_root, name, 30)
+                .find_references(&repo_root, name, self.max_references)
                 .unwrap_or_default();
 
             // Filter out definition locations themselves.
@@ -153,17 +215,17 @@ impl SymbolNavigationRouter {
 
             let section = match style {
                 render::RenderStyle::Navigation => render::render_symbol_navigation_success(
-                    name,
+                    &display_name,
                     primary,
                     ctx,
-                    other_candidates,
+                    &other_candidates,
                     &references,
                 ),
                 render::RenderStyle::Explain => render::render_symbol_explain_success(
-                    name,
+                    &display_name,
                     primary,
                     ctx,
-                    other_candidates,
+                    &other_candidates,
                     &references,
                 ),
             };
@@ -252,11 +314,13 @@ impl SymbolNavigationRouter {
             .ok()
             .and_then(|c| identifier_at_position(&c, line, col))
             .unwrap_or_else(|| "<position>".to_owned());
-        let other_candidates = definitions.get(1..).unwrap_or_default();
+        let display_name = self.display_name(&name, &ctx);
+        let other_candidates =
+            self.candidates(&repo_root, definitions.get(1..).unwrap_or_default());
 
         let mut references = self
             .navigator
-            .find_references(&repo_root, &name, 30)
+            .find_references(&repo_root, &name, self.max_references)
             .unwrap_or_default();
         references.retain(|r| {
             !definitions
@@ -268,17 +332,17 @@ impl SymbolNavigationRouter {
 
         let section = match style {
             render::RenderStyle::Navigation => render::render_symbol_navigation_success(
-                &name,
+                &display_name,
                 primary,
                 ctx,
-                other_candidates,
+                &other_candidates,
                 &references,
             ),
             render::RenderStyle::Explain => render::render_symbol_explain_success(
-                &name,
+                &display_name,
                 primary,
                 ctx,
-                other_candidates,
+                &other_candidates,
                 &references,
             ),
         };
@@ -286,6 +350,46 @@ impl SymbolNavigationRouter {
         let header = render::render_multi_header(&[name.as_str()]);
         Ok(format!("{header}{section}"))
     }
+
+    /// `name`, or the primary definition's qualified name under `qualify_names`.
+    fn display_name(&self, name: &str, ctx: &Result<SymbolContext>) -> String {
+        match ctx {
+            Ok(ctx) if self.qualify_names && !ctx.qualified_name.is_empty() => {
+                ctx.qualified_name.clone()
+            }
+            _ => name.to_owned(),
+        }
+    }
+
+    /// The listed alternative definitions, qualified under `qualify_names`.
+    fn candidates<'a>(
+        &self,
+        repo_root: &Path,
+        locations: &'a [SymbolLocation],
+    ) -> Vec<render::Candidate<'a>> {
+        let opt = SnippetOptions {
+            context_lines: 0,
+            ..self.snippet_opt.clone()
+        };
+        locations
+            .iter()
+            .take(render::MAX_LISTED_CANDIDATES)
+            .map(|location| {
+                let qualified_name = if self.qualify_names {
+                    self.navigator
+                        .get_symbol_context(repo_root, location, &opt)
+                        .ok()
+                        .map(|ctx| ctx.qualified_name)
+                } else {
+                    None
+                };
+                render::Candidate {
+                    location,
+                    qualified_name,
+                }
+            })
+            .collect()
+    }
 }
 
 fn identifier_at_position(content: &str, line: usize, column: usize) -> Option<String> {
@@ -322,15 +426,62 @@ fn is_ident_continue(b: u8) -> bool {
 }
 
 pub(crate) fn resolve_repo_root(cwd: Option<&Path>) -> Option<PathBuf> {
-    let mut cur = cwd?;
+    find_repo_root(cwd?)
+}
+
+/// Project manifests accepted as a repo root when no `.git` is found.
+const ROOT_MANIFESTS: &[&str] = &["Cargo.toml", "package.json"];
+
+/// Walk upward from `start` to the enclosing repository root.
+///
+/// The nearest ancestor containing `.git` wins; otherwise the nearest one with
+/// a project manifest (`Cargo.toml`, `package.json`).
+pub fn find_repo_root(start: &Path) -> Option<PathBuf> {
+    let mut cur = start;
     if cur.is_file() {
         cur = cur.parent()?;
     }
 
+    let mut manifest_dir = None;
     loop {
         if cur.join(".git").exists() {
             return Some(cur.to_path_buf());
         }
-        cur = cur.parent()?;
+        if manifest_dir.is_none() && ROOT_MANIFESTS.iter().any(|m| cur.join(m).is_file()) {
+            manifest_dir = Some(cur.to_path_buf());
+        }
+        match cur.parent() {
+            Some(parent) => cur = parent,
+            None => return manifest_dir,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unique_tmp_dir() -> PathBuf {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!("luna-root-test-{nanos}"))
+    }
+
+    #[test]
+    fn find_repo_root_prefers_git_over_nested_manifest() {
+        let root = unique_tmp_dir();
+        let nested = root.join("crates/a/src");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(root.join("crates/a/Cargo.toml"), "").unwrap();
+
+        // Without `.git` the nearest manifest is used.
+        assert_eq!(find_repo_root(&nested), Some(root.join("crates/a")));
+
+        std::fs::create_dir(root.join(".git")).unwrap();
+        assert_eq!(find_repo_root(&nested), Some(root.clone()));
+
+        let _ = std::fs::remove_dir_all(&root);
     }
 }
@@ -1,14 +1,16 @@
 use error::{LunaError, Result, ResultExt as _};
 use session::Role;
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use crate::{
+    cancel::CancelToken,
     command,
     config::RuntimeConfig,
     recorder::{TrajectoryEvent, TrajectoryStep},
     request::{RunRequest, SessionRef},
-    response::{EventSink, RunResponse, RuntimeEvent},
+    resource::ResourceDescriptor,
+    response::{EventSink, RunResponse, RuntimeEvent, TerminationReason},
 };
 
 pub struct LunaRuntime {
@@ -26,6 +28,33 @@ impl LunaRuntime {
         Self { config }
     }
 
+    pub fn config(&self) -> &RuntimeConfig {
+        &self.config
+    }
+
+    /// Source files under `repo_root`, using the configured scan options.
+    pub fn list_resources(&self, repo_root: &Path) -> Result<Vec<ResourceDescriptor>> {
+        crate::resource::list_resources(repo_root, &self.config.navigation().scan)
+    }
+
+    /// Contents of a resource URI from [`Self::list_resources`].
+    pub fn read_resource(&self, repo_root: &Path, uri: &str) -> Result<String> {
+        crate::resource::read_resource(repo_root, uri, &self.config.navigation().scan)
+    }
+
+    /// File, line and symbol counts for `repo_root`, using the configured scan options.
+    pub fn repo_stats(&self, repo_root: &Path) -> Result<intelligence::RepoStats> {
+        intelligence::repo_stats(repo_root, &self.config.navigation().scan)
+            .map_err(|e| LunaError::invalid_input(format!("repo stats: {e}")))
+    }
+
+    /// Files under `repo_root` whose path fuzzy-matches `query`, best first,
+    /// using the configured scan options.
+    pub fn find_files(&self, repo_root: &Path, query: &str) -> Result<Vec<(PathBuf, f32)>> {
+        intelligence::find_files(repo_root, query, &self.config.navigation().scan)
+            .map_err(|e| LunaError::invalid_input(format!("find files: {e}")))
+    }
+
     pub async fn run(&self, req: RunRequest) -> Result<RunResponse> {
         let trajectory = self.config.trajectory();
         trajectory.on_run_start(&req);
@@ -77,6 +106,24 @@ impl LunaRuntime {
         result
     }
 
+    /// Like [`Self::run`], passing answer text to `on_answer` as soon as
+    /// steps produce it; the full response is still returned at the end.
+    pub async fn run_with_answer_stream<F>(
+        &self,
+        req: RunRequest,
+        mut on_answer: F,
+    ) -> Result<RunResponse>
+    where
+        F: FnMut(&str) + Send,
+    {
+        self.run_with_event_hook(req, |event| {
+            if let RuntimeEvent::AnswerDelta { text } = event {
+                on_answer(text);
+            }
+        })
+        .await
+    }
+
     pub fn run_impl(
         &self,
         req: RunRequest,
@@ -99,6 +146,8 @@ impl LunaRuntime {
             session,
             input: user_input,
             cwd,
+            seed_chunks,
+            cancel,
             ..
         } = req;
 
@@ -144,6 +193,8 @@ impl LunaRuntime {
                         request_id,
                         session_id: current_session_id,
                         output: out,
+                        raw_output: None,
+                        termination: TerminationReason::Answered,
                         events: Vec::new(),
                     });
                 }
@@ -188,6 +239,8 @@ impl LunaRuntime {
                         request_id,
                         session_id: chosen,
                         output: out,
+                        raw_output: None,
+                        termination: TerminationReason::Answered,
                         events: Vec::new(),
                     });
                 }
@@ -235,13 +288,28 @@ impl LunaRuntime {
         events.emit(&RuntimeEvent::UserMessageAppended);
 
         // 3) produce assistant output
-        let output = self.produce_output(
-            &session_id,
-            &request_id,
-            &user_input,
-            cwd.as_deref(),
+        let (raw, termination) = self.produce_output(
+            TurnRequest {
+                session_id: &session_id,
+                request_id: &request_id,
+                user_input: &user_input,
+                cwd: cwd.as_deref(),
+                seed_chunks,
+                cancel,
+            },
             events,
         )?;
+        let (output, raw_output) = match self.config.answer_cleanup() {
+            Some(cleanup) => {
+                let cleaned = cleanup.apply(&raw);
+                if cleaned == raw {
+                    (raw, None)
+                } else {
+                    (cleaned, Some(raw))
+                }
+            }
+            None => (raw, None),
+        };
 
         // 4) append assistant message
         session.push_message(Role::Assistant, &output);
@@ -286,18 +354,25 @@ impl LunaRuntime {
             request_id,
             session_id,
             output,
+            raw_output,
+            termination,
             events: Vec::new(),
         })
     }
 
     fn produce_output(
         &self,
-        session_id: &str,
-        request_id: &str,
-        user_input: &str,
-        cwd: Option<&Path>,
+        turn: TurnRequest<'_>,
         events: &mut dyn EventSink,
-    ) -> Result<String> {
+    ) -> Result<(String, TerminationReason)> {
+        let TurnRequest {
+            session_id,
+            request_id,
+            user_input,
+            cwd,
+            seed_chunks,
+            cancel,
+        } = turn;
         crate::tpar::run_turn(
             user_input,
             crate::tpar::TurnContext {
@@ -310,12 +385,36 @@ impl LunaRuntime {
                 budget: self.config.budget(),
                 planner: self.config.planner(),
                 context_pipeline: None,
+                dry_run: self.config.dry_run(),
+                navigation: self.config.navigation(),
+                post_edit_context: self.config.post_edit_context(),
+                answer_language: self.config.answer_language(),
+                grounding: self.config.grounding(),
+                confine_to_repo: self.config.confine_to_repo(),
+                edit_backups: self.config.edit_backups(),
+                seed_chunks,
+                cancel,
+                pinned_paths: self.config.pinned_paths(),
+                edit_summarizer: self.config.edit_summarizer(),
+                cite_sources: self.config.cite_sources(),
+                signature_patterns: self.config.signature_patterns(),
             },
             events,
         )
     }
 }
 
+/// Per-turn inputs for [`LunaRuntime::produce_output`], split out of the
+/// already-destructured [`RunRequest`].
+struct TurnRequest<'a> {
+    session_id: &'a str,
+    request_id: &'a str,
+    user_input: &'a str,
+    cwd: Option<&'a Path>,
+    seed_chunks: Vec<context::IndexChunk>,
+    cancel: CancelToken,
+}
+
 fn now_ms() -> u64 {
     std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
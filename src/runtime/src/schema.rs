@@ -0,0 +1,48 @@
+//! JSON Schemas of the config and option structs, for front-ends that render
+//! config forms or validate payloads against the real types.
+
+use serde_json::{Map, Value};
+
+/// Schema of every config/option struct, keyed by type name.
+#[must_use]
+pub fn config_schemas() -> Value {
+    let schemas = [
+        ("TokenBudget", crate::config::TokenBudget::schema()),
+        ("NavigationOptions", crate::NavigationOptions::schema()),
+        (
+            "RepoScanOptions",
+            intelligence::repo_scan::RepoScanOptions::schema(),
+        ),
+        ("ChunkOptions", intelligence::ChunkOptions::schema()),
+        ("ContextTokenBudget", context::TokenBudget::schema()),
+        ("PromptTemplate", context::PromptTemplate::schema()),
+    ];
+
+    Value::Object(
+        schemas
+            .into_iter()
+            .map(|(name, schema)| {
+                (
+                    name.to_owned(),
+                    serde_json::to_value(schema).unwrap_or_default(),
+                )
+            })
+            .collect::<Map<_, _>>(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_schemas_describe_fields() {
+        let schemas = config_schemas();
+        let props = &schemas["NavigationOptions"]["properties"];
+        assert!(props.get("max_references").is_some());
+        assert!(props.get("scan").is_some());
+        assert!(schemas["PromptTemplate"]["properties"]
+            .get("line_numbers")
+            .is_some());
+    }
+}
@@ -8,12 +8,18 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 use error::{LunaError, ResultExt as _};
+use intelligence::repo_scan::TestFilePolicy;
+use tools::ToolArgs as _;
 
-use crate::config::TokenBudget;
+use crate::cancel::CancelToken;
+use crate::config::{Grounding, PostEditContext, TokenBudget};
 use crate::context_bridge::create_refill_pipeline;
+use crate::edit_summary::summarize_edit;
 use crate::planner::{PlannerContext, TaskPlanner};
 use crate::recorder::{TrajectoryRecorder, TrajectoryStep};
-use crate::response::{EventSink, RuntimeEvent};
+use crate::refill_trigger::{RefillResult, RefillTrigger};
+use crate::response::{Citation, EventSink, RuntimeEvent, TerminationReason};
+use crate::router::NavigationOptions;
 use crate::{intent, render, safety};
 
 #[derive(Clone)]
@@ -28,6 +34,33 @@ pub struct TurnContext {
     pub planner: Arc<dyn TaskPlanner>,
     /// RefillPipeline for dynamic context supplementation
     pub context_pipeline: Option<Arc<context::RefillPipeline>>,
+    /// Don't write files or spawn commands; report the intended action instead.
+    pub dry_run: bool,
+    /// Search knobs for intelligence steps.
+    pub navigation: NavigationOptions,
+    /// How context is refreshed after an edit step.
+    pub post_edit_context: PostEditContext,
+    /// Language the final answer should be written in.
+    pub answer_language: Option<String>,
+    /// Whether answers must come only from the retrieved code.
+    pub grounding: Grounding,
+    /// Ask the model to mark claims with `[n]` source numbers.
+    pub cite_sources: bool,
+    /// Keep edits and commands inside the repository root.
+    pub confine_to_repo: bool,
+    /// Backups kept per edited file; 0 disables them.
+    pub edit_backups: usize,
+    /// Precomputed hits from an external index, merged ahead of retrieval.
+    pub seed_chunks: Vec<context::IndexChunk>,
+    /// Checked between plan steps; once set the turn stops early.
+    pub cancel: CancelToken,
+    /// Files always put ahead of the retrieved context.
+    pub pinned_paths: Vec<PathBuf>,
+    /// Summarizes each edit's diff in one line; `None` skips that call.
+    pub edit_summarizer: Option<Arc<dyn llm::LLMClient>>,
+    /// Definition keywords and modifiers for the symbol kinds and names
+    /// reported in events.
+    pub signature_patterns: context::SignaturePatterns,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -117,10 +150,58 @@ impl PlanStep {
     }
 }
 
+/// What a step did, as recorded in its [`StepOutcome`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepKind {
+    Answer,
+    Thought,
+    Navigation,
+    Verify,
+    /// A read-only tool call.
+    Read,
+    /// A tool call that may change files.
+    Edit,
+    /// A tool call that runs a command.
+    Execute,
+}
+
+impl StepKind {
+    fn of_tool(side_effect: Option<tools::SideEffect>) -> Self {
+        match side_effect {
+            Some(tools::SideEffect::Mutates) => Self::Edit,
+            Some(tools::SideEffect::Executes) => Self::Execute,
+            Some(tools::SideEffect::ReadOnly) | None => Self::Read,
+        }
+    }
+}
+
+/// Result of one step. Decisions (rollback, review) use the typed fields;
+/// `output` is only for display.
 #[derive(Debug, Clone)]
 pub struct StepOutcome {
     pub ok: bool,
+    pub kind: StepKind,
     pub output: String,
+    /// Files the step actually changed on disk (never set in dry-run mode).
+    pub changed_files: Vec<PathBuf>,
+    /// Context refreshed for `changed_files`, per [`PostEditContext`].
+    pub context_delta: Vec<context::ContextChunk>,
+    /// One-line description of an edit, when edit summaries are on.
+    pub edit_summary: Option<String>,
+}
+
+impl StepOutcome {
+    fn ok(kind: StepKind, output: impl Into<String>) -> Self {
+        Self {
+            ok: true,
+            kind,
+            output: output.into(),
+            changed_files: Vec::new(),
+            context_delta: Vec::new(),
+            edit_summary: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -128,14 +209,15 @@ pub enum ReviewResult {
     Success,
     NeedsRevision { reason: String },
     NeedsRollback { reason: String },
+    Cancelled,
 }
 
-/// Run a single TPAR turn.
+/// Run a single TPAR turn, returning its output and why it ended.
 pub fn run_turn(
     user_input: &str,
     ctx: TurnContext,
     events: &mut dyn EventSink,
-) -> error::Result<String> {
+) -> error::Result<(String, TerminationReason)> {
     if user_input.chars().count() > ctx.budget.max_input_chars {
         let msg = format!(
             "❌ Input too long: exceeds max_input_chars={}. Please shorten or split your request.",
@@ -145,7 +227,10 @@ pub fn run_turn(
             task: "rejected".to_owned(),
         });
         events.emit(&RuntimeEvent::TparReviewed { ok: false });
-        return Ok(msg);
+        return Ok((
+            msg,
+            TerminationReason::Stopped("input exceeds max_input_chars".to_owned()),
+        ));
     }
 
     // Task
@@ -155,26 +240,96 @@ pub fn run_turn(
     });
 
     // Collect context chunks from task entities
-    let context_chunks = collect_context_from_task(&task, ctx.cwd.as_deref());
+    let collected = collect_context_from_task(
+        &task,
+        ctx.cwd.as_deref(),
+        &ctx.budget,
+        &ctx.pinned_paths,
+        ctx.navigation.scan.test_files,
+        &ctx.seed_chunks,
+    );
     tracing::info!(
         "Collected {} context chunks for task: {:?}",
-        context_chunks.len(),
+        collected.chunks.len(),
         task.raw_input
     );
+    events.emit(&RuntimeEvent::TparContextCollected {
+        chunks: collected.chunks.len(),
+        kinds: context::format_kind_summary(&context::symbol_kind_summary_with(
+            &collected.chunks,
+            &ctx.signature_patterns.keywords,
+        )),
+    });
+    if ctx.cite_sources {
+        let sources = Citation::from_chunks_with(&collected.chunks, &ctx.signature_patterns);
+        if !sources.is_empty() {
+            events.emit(&RuntimeEvent::TparSourcesCollected { sources });
+        }
+    }
+
+    // Questions about code with nothing found: say so rather than let the planner guess.
+    if collected.is_empty()
+        && matches!(task.task_type, TaskType::Query | TaskType::Explain)
+        && intent::extract_file_position(&task.raw_input).is_none()
+    {
+        events.emit(&RuntimeEvent::TparReviewed { ok: false });
+        let msg = format!(
+            "🔍 No relevant code found for: {}\nCheck the spelling, or name a file or symbol in the repository.",
+            task.raw_input
+        );
+        return Ok((msg, TerminationReason::NoResults));
+    }
+    let context_chunks = collected.chunks;
+    // Answers that name code the context lacks get one more search per symbol.
+    let refill_trigger = ctx
+        .context_pipeline
+        .clone()
+        .or(collected.pipeline)
+        .map(|pipeline| {
+            RefillTrigger::new(pipeline).with_max_chunks(ctx.budget.refill_context_chunks)
+        });
 
     // Plan with context
-    let plan = ctx.planner.plan(
+    let mut plan = ctx.planner.plan(
         &task,
         &PlannerContext {
             budget: ctx.budget.clone(),
             context_chunks,
             repo_root: ctx.cwd.clone(),
+            answer_language: ctx.answer_language.clone(),
+            grounding: ctx.grounding,
+            cite_sources: ctx.cite_sources,
         },
         events,
     )?;
+    let truncated = plan.steps.len() > ctx.budget.max_steps;
+    if truncated {
+        tracing::warn!(
+            "plan has {} steps, running the first {}",
+            plan.steps.len(),
+            ctx.budget.max_steps
+        );
+        plan.steps.truncate(ctx.budget.max_steps);
+    }
+    let looped = crate::loop_guard::truncate_loop(&mut plan.steps, ctx.budget.max_repeated_steps);
+    if looped {
+        tracing::warn!(
+            "plan repeats itself, running the first {} steps",
+            plan.steps.len()
+        );
+    }
     events.emit(&RuntimeEvent::TparPlanBuilt {
         plan: format!("steps={}", plan.steps.len()),
     });
+    // Planning can take a while with an LLM; don't start acting if the caller
+    // gave up meanwhile.
+    if ctx.cancel.is_cancelled() {
+        events.emit(&RuntimeEvent::TparReviewed { ok: false });
+        return Ok((
+            "⏹ Cancelled before any step ran".to_owned(),
+            TerminationReason::Cancelled,
+        ));
+    }
 
     // Act
     let mut exec = ActExecutor::new(
@@ -186,14 +341,35 @@ pub fn run_turn(
         ctx.tools,
         ctx.budget,
         ctx.context_pipeline.clone(),
-    );
+    )
+    .with_dry_run(ctx.dry_run)
+    .with_confine_to_repo(ctx.confine_to_repo)
+    .with_edit_backups(ctx.edit_backups)
+    .with_navigation(ctx.navigation.clone())
+    .with_post_edit_context(ctx.post_edit_context)
+    .with_edit_summarizer(ctx.edit_summarizer)
+    .with_kind_overrides(ctx.signature_patterns.keywords)
+    .with_refill_trigger(refill_trigger)
+    .with_cancel(ctx.cancel);
     let (out, review) = exec.execute(&plan, &task, events)?;
 
     // Review/Reflect
     let ok = matches!(review, ReviewResult::Success);
     events.emit(&RuntimeEvent::TparReviewed { ok });
 
-    Ok(out)
+    let termination = match review {
+        ReviewResult::Success if looped => TerminationReason::LoopDetected,
+        ReviewResult::Success if truncated => TerminationReason::MaxStepsReached,
+        ReviewResult::Success => TerminationReason::Answered,
+        ReviewResult::Cancelled => TerminationReason::Cancelled,
+        ReviewResult::NeedsRevision { reason } | ReviewResult::NeedsRollback { reason } => {
+            match exec.policy_block.take() {
+                Some(msg) => TerminationReason::PolicyBlocked(msg),
+                None => TerminationReason::Stopped(reason),
+            }
+        }
+    };
+    Ok((out, termination))
 }
 
 struct TaskAnalyzer;
@@ -287,8 +463,26 @@ struct ActExecutor {
     budget: TokenBudget,
     // For rollback: keep original content for any edited files.
     original_files: HashMap<PathBuf, String>,
+    // Files steps reported as changed; rollback is only needed when non-empty
+    changed_files: Vec<PathBuf>,
     // Optional RefillPipeline for dynamic context supplementation
     context_pipeline: Option<Arc<context::RefillPipeline>>,
+    dry_run: bool,
+    confine_to_repo: bool,
+    edit_backups: usize,
+    navigation: NavigationOptions,
+    post_edit_context: PostEditContext,
+    // Message of the safety decision that stopped the plan, if any
+    policy_block: Option<String>,
+    cancel: CancelToken,
+    edit_summarizer: Option<Arc<dyn llm::LLMClient>>,
+    kind_overrides: context::KindOverrides,
+    // Latest refreshed context per edited file, shown with the final output
+    refreshed_context: Vec<context::ContextChunk>,
+    // Follow-up retrieval for symbols an answer says it is missing
+    refill_trigger: Option<RefillTrigger>,
+    // Chunks found by `refill_trigger`, shown with the final output
+    followup_context: Vec<context::ContextChunk>,
 }
 
 impl ActExecutor {
@@ -311,10 +505,68 @@ impl ActExecutor {
             tools,
             budget,
             original_files: HashMap::new(),
+            changed_files: Vec::new(),
             context_pipeline,
+            dry_run: false,
+            confine_to_repo: false,
+            edit_backups: 0,
+            navigation: NavigationOptions::default(),
+            post_edit_context: PostEditContext::default(),
+            policy_block: None,
+            cancel: CancelToken::default(),
+            edit_summarizer: None,
+            kind_overrides: context::KindOverrides::new(),
+            refreshed_context: Vec::new(),
+            refill_trigger: None,
+            followup_context: Vec::new(),
         }
     }
 
+    fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    fn with_confine_to_repo(mut self, confine_to_repo: bool) -> Self {
+        self.confine_to_repo = confine_to_repo;
+        self
+    }
+
+    fn with_edit_backups(mut self, edit_backups: usize) -> Self {
+        self.edit_backups = edit_backups;
+        self
+    }
+
+    fn with_navigation(mut self, navigation: NavigationOptions) -> Self {
+        self.navigation = navigation;
+        self
+    }
+
+    fn with_post_edit_context(mut self, post_edit_context: PostEditContext) -> Self {
+        self.post_edit_context = post_edit_context;
+        self
+    }
+
+    fn with_cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    fn with_edit_summarizer(mut self, summarizer: Option<Arc<dyn llm::LLMClient>>) -> Self {
+        self.edit_summarizer = summarizer;
+        self
+    }
+
+    fn with_kind_overrides(mut self, kind_overrides: context::KindOverrides) -> Self {
+        self.kind_overrides = kind_overrides;
+        self
+    }
+
+    fn with_refill_trigger(mut self, trigger: Option<RefillTrigger>) -> Self {
+        self.refill_trigger = trigger;
+        self
+    }
+
     fn execute(
         &mut self,
         plan: &Plan,
@@ -322,15 +574,32 @@ impl ActExecutor {
         events: &mut dyn EventSink,
     ) -> error::Result<(String, ReviewResult)> {
         let repo_root = crate::router::resolve_repo_root(self.cwd.as_deref());
-        let tool_ctx = tools::ToolContext {
-            repo_root: repo_root.clone(),
-            cwd: self.cwd.clone(),
-            max_bytes: self.budget.max_io_bytes,
-        };
+        let tool_ctx = tools::ToolContext::new(repo_root.clone())
+            .with_cwd(self.cwd.clone())
+            .with_max_bytes(self.budget.max_io_bytes)
+            .with_dry_run(self.dry_run)
+            .with_confine_to_repo(self.confine_to_repo)
+            .with_max_backups(self.edit_backups)
+            .with_scan(self.navigation.scan.clone());
 
         let mut step_outputs: Vec<(usize, String, String)> = Vec::new(); // (step_id, step_label, output)
 
         for (i, step) in plan.steps.iter().enumerate() {
+            // Steps already run keep their effects; the caller gets their output.
+            if self.cancel.is_cancelled() {
+                tracing::info!("turn cancelled after {i} of {} steps", plan.steps.len());
+                let final_output = if step_outputs.is_empty() {
+                    "⏹ Cancelled before any step ran".to_owned()
+                } else {
+                    format_step_outputs(
+                        &format!("📋 Plan cancelled after {i} of {} steps", plan.steps.len()),
+                        step_outputs,
+                        "⏹ Cancelled\n",
+                    )
+                };
+                return Ok((final_output, ReviewResult::Cancelled));
+            }
+
             let step_id = i + 1;
             let step_label = step.label();
             events.emit(&RuntimeEvent::TparStepStarted {
@@ -339,19 +608,37 @@ impl ActExecutor {
             });
 
             let outcome = self.execute_step(step, task, &tool_ctx, repo_root.as_deref(), events);
-
-            let (ok, out_text, review) = match outcome {
-                Ok(v) => (v.ok, v.output, None),
+            let mut edit_summary = None;
+
+            let (ok, kind, out_text, review) = match outcome {
+                Ok(v) => {
+                    if !v.context_delta.is_empty() {
+                        events.emit(&RuntimeEvent::TparContextRefreshed {
+                            step_id,
+                            chunks: v.context_delta.len(),
+                            kinds: context::format_kind_summary(
+                                &context::symbol_kind_summary_with(
+                                    &v.context_delta,
+                                    &self.kind_overrides,
+                                ),
+                            ),
+                        });
+                        self.merge_refreshed_context(v.context_delta);
+                    }
+                    self.changed_files.extend(v.changed_files);
+                    edit_summary = v.edit_summary;
+                    (v.ok, Some(v.kind), v.output, None)
+                }
                 Err(err) => {
                     let reason = format!("Step {step_id} failed: {err}");
                     // If we already edited something, rollback.
-                    let needs_rollback = !self.original_files.is_empty();
+                    let needs_rollback = !self.changed_files.is_empty();
                     let review = if needs_rollback {
                         Some(ReviewResult::NeedsRollback { reason })
                     } else {
                         Some(ReviewResult::NeedsRevision { reason })
                     };
-                    (false, String::new(), review)
+                    (false, None, String::new(), review)
                 }
             };
 
@@ -367,7 +654,12 @@ impl ActExecutor {
                 }),
                 action: serde_json::to_value(step).unwrap_or(Value::Null),
                 reward: if ok { 0.2 } else { -0.5 },
-                outcome: serde_json::json!({ "ok": ok, "output_len": out_text.len() }),
+                outcome: serde_json::json!({
+                    "ok": ok,
+                    "kind": kind,
+                    "output_len": out_text.len(),
+                    "edit_summary": edit_summary,
+                }),
             });
 
             // Collect non-empty outputs with truncation
@@ -396,26 +688,29 @@ impl ActExecutor {
         }
 
         // Build formatted final output
-        let final_output = if step_outputs.is_empty() {
+        let mut final_output = if step_outputs.is_empty() {
             format!("✅ Completed: {}", task.raw_input)
         } else {
-            let mut out = String::new();
-            out.push_str(&format!("📋 Plan executed ({} steps)\n", plan.steps.len()));
-            out.push_str("═".repeat(40).as_str());
-            out.push('\n');
-            for (step_id, label, output) in step_outputs {
-                out.push_str(&format!("\n[Step {}] {}\n", step_id, label));
-                out.push_str("─".repeat(30).as_str());
-                out.push('\n');
-                out.push_str(&output);
-                out.push('\n');
-            }
-            out.push('\n');
-            out.push_str("═".repeat(40).as_str());
-            out.push('\n');
-            out.push_str("✅ Done\n");
-            out
+            format_step_outputs(
+                &format!("📋 Plan executed ({} steps)", plan.steps.len()),
+                step_outputs,
+                "✅ Done\n",
+            )
         };
+        if !self.refreshed_context.is_empty() {
+            final_output.push_str("\n🔄 Context after edits\n");
+            for chunk in &self.refreshed_context {
+                final_output.push_str(&chunk.format_for_prompt());
+                final_output.push('\n');
+            }
+        }
+        if !self.followup_context.is_empty() {
+            final_output.push_str("\n🔎 Context for symbols the answer was missing\n");
+            for chunk in &self.followup_context {
+                final_output.push_str(&chunk.format_for_prompt());
+                final_output.push('\n');
+            }
+        }
 
         Ok((final_output, ReviewResult::Success))
     }
@@ -429,36 +724,31 @@ impl ActExecutor {
         events: &mut dyn EventSink,
     ) -> error::Result<StepOutcome> {
         match step {
-            PlanStep::Echo { text } => Ok(StepOutcome {
-                ok: true,
-                output: text.clone(),
-            }),
-            PlanStep::Think { text } => Ok(StepOutcome {
-                ok: true,
-                output: format!("🤔 {}", text),
-            }),
+            PlanStep::Echo { text } => {
+                events.emit(&RuntimeEvent::AnswerDelta { text: text.clone() });
+                if let Some(trigger) = self.refill_trigger.as_mut() {
+                    if let RefillResult::Success { new_chunks } = trigger.analyze_and_refill(text) {
+                        self.followup_context.extend(new_chunks);
+                    }
+                }
+                Ok(StepOutcome::ok(StepKind::Answer, text.clone()))
+            }
+            PlanStep::Think { text } => {
+                Ok(StepOutcome::ok(StepKind::Thought, format!("🤔 {}", text)))
+            }
             PlanStep::Intelligence { style: _, query } => {
-                let router = crate::router::RuntimeRouter::default();
+                let router = crate::router::RuntimeRouter::new(self.navigation.clone());
                 let out = router
                     .maybe_handle(query, repo_root.or(self.cwd.as_deref()), events)?
                     .unwrap_or_else(|| format!("received: {query}"));
-                Ok(StepOutcome {
-                    ok: true,
-                    output: out,
-                })
+                Ok(StepOutcome::ok(StepKind::Navigation, out))
             }
             PlanStep::Verify { cmd } => {
-                let call = tools::ToolCall {
-                    name: "run_terminal".to_owned(),
-                    args: serde_json::json!({"cmd":cmd}),
-                };
+                let call = tools::RunTerminalArgs::new(cmd).into_call();
                 self.check_step_safety(task, &call)?;
                 let res = self.tools.run(tool_ctx, &call)?;
                 if res.ok {
-                    Ok(StepOutcome {
-                        ok: true,
-                        output: res.stdout,
-                    })
+                    Ok(StepOutcome::ok(StepKind::Verify, res.stdout))
                 } else {
                     Err(LunaError::invalid_input(res.stderr))
                 }
@@ -466,26 +756,56 @@ impl ActExecutor {
             PlanStep::ToolCall { call } => {
                 self.check_step_safety(task, call)?;
 
+                let kind = StepKind::of_tool(self.tools.side_effect(&call.name));
+                let target = call
+                    .args
+                    .get("path")
+                    .and_then(|v| v.as_str())
+                    .map(|path| tool_ctx.resolve_path(Path::new(path)))
+                    .filter(|_| kind == StepKind::Edit);
+
                 // For rollback: snapshot before editing.
-                if call.name == "edit_file" {
-                    if let Some(path) = call.args.get("path").and_then(|v| v.as_str()) {
-                        let abs = tool_ctx.resolve_path(Path::new(path));
-                        if let std::collections::hash_map::Entry::Vacant(e) =
-                            self.original_files.entry(abs.clone())
-                        {
-                            if let Ok(s) = std::fs::read_to_string(&abs) {
-                                e.insert(s);
-                            }
+                if let Some(abs) = &target {
+                    if let std::collections::hash_map::Entry::Vacant(e) =
+                        self.original_files.entry(abs.clone())
+                    {
+                        if let Ok(s) = std::fs::read_to_string(abs) {
+                            e.insert(s);
                         }
                     }
                 }
 
+                // The summary is written from the diff, so ask the tool for one.
+                let summarizer = self
+                    .edit_summarizer
+                    .as_ref()
+                    .filter(|_| kind == StepKind::Edit && !tool_ctx.dry_run);
+                let mut call = call.clone();
+                if summarizer.is_some() {
+                    if let Some(args) = call.args.as_object_mut() {
+                        args.insert("include_diff".to_owned(), Value::Bool(true));
+                    }
+                }
+                let call = &call;
+
                 let res = self.tools.run(tool_ctx, call)?;
                 if res.ok {
-                    Ok(StepOutcome {
-                        ok: true,
-                        output: res.stdout,
-                    })
+                    let edit_summary = match (summarizer, res.diff.as_deref()) {
+                        (Some(client), Some(diff)) => summarize_edit(client.as_ref(), diff),
+                        _ => None,
+                    };
+                    let output = match &edit_summary {
+                        Some(summary) => format!("{}\n📝 {summary}", res.stdout),
+                        None => res.stdout,
+                    };
+                    let mut outcome = StepOutcome::ok(kind, output);
+                    outcome.edit_summary = edit_summary;
+                    if !tool_ctx.dry_run {
+                        outcome.changed_files.extend(target);
+                    }
+                    outcome.context_delta =
+                        self.refresh_context(call, &outcome.changed_files, repo_root);
+                    Ok(outcome)
                 } else {
                     Err(error::LunaError::invalid_input(res.stderr))
                 }
@@ -493,22 +813,82 @@ impl ActExecutor {
         }
     }
 
-    fn check_step_safety(&self, task: &Task, call: &tools::ToolCall) -> error::Result<()> {
+    /// Replace what the turn knew about the edited files with `delta`, so the
+    /// final output shows each file as it reads after the latest edit.
+    fn merge_refreshed_context(&mut self, delta: Vec<context::ContextChunk>) {
+        self.refreshed_context.retain(|old| {
+            !delta
+                .iter()
+                .any(|new| new.source.rel_path == old.source.rel_path)
+        });
+        self.refreshed_context.extend(delta);
+    }
+
+    /// Context for files an edit step just changed, per `post_edit_context`.
+    fn refresh_context(
+        &self,
+        call: &tools::ToolCall,
+        changed_files: &[PathBuf],
+        repo_root: Option<&Path>,
+    ) -> Vec<context::ContextChunk> {
+        let Some(repo_root) = repo_root.filter(|_| !changed_files.is_empty()) else {
+            return Vec::new();
+        };
+        match self.post_edit_context {
+            PostEditContext::None => Vec::new(),
+            PostEditContext::ReparseEdited => {
+                let line_1 = ["line_1", "start_line_1"]
+                    .iter()
+                    .find_map(|key| call.args.get(key).and_then(Value::as_u64))
+                    .unwrap_or(1) as usize;
+                changed_files
+                    .iter()
+                    .filter_map(|abs| {
+                        crate::context_bridge::edited_scope_chunk(repo_root, abs, line_1)
+                    })
+                    .collect()
+            }
+            PostEditContext::ResearchFilename => {
+                let test_files = self.navigation.scan.test_files;
+                let Some(pipeline) = self.context_pipeline.clone().or_else(|| {
+                    create_refill_pipeline(repo_root.to_path_buf(), test_files, &self.budget)
+                        .map(Arc::new)
+                }) else {
+                    return Vec::new();
+                };
+                changed_files
+                    .iter()
+                    .filter_map(|abs| abs.file_stem()?.to_str())
+                    .flat_map(|stem| {
+                        let query = context::ContextQuery::Concept {
+                            description: stem.to_owned(),
+                        };
+                        pipeline
+                            .retrieve(&query, self.budget.refill_context_chunks)
+                            .map(|chunks| pipeline.refine(&chunks))
+                            .unwrap_or_default()
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    fn check_step_safety(&mut self, task: &Task, call: &tools::ToolCall) -> error::Result<()> {
         let ctx = safety::SafetyContext {
             session_id: self.session_id.clone(),
         };
-        let action = match call.name.as_str() {
-            "run_terminal" => safety::Action {
+        let action = match self.tools.side_effect(&call.name) {
+            Some(tools::SideEffect::Executes) => safety::Action {
                 kind: safety::ActionKind::Terminal,
                 payload: serde_json::json!({
                     "cmd": call.args.get("cmd").and_then(|v| v.as_str()).unwrap_or_default(),
                 }),
             },
-            "edit_file" => safety::Action {
+            Some(tools::SideEffect::Mutates) => safety::Action {
                 kind: safety::ActionKind::EditFile,
                 payload: call.args.clone(),
             },
-            _ => safety::Action {
+            Some(tools::SideEffect::ReadOnly) | None => safety::Action {
                 kind: safety::ActionKind::Command,
                 payload: serde_json::json!({ "tool": call.name, "task": task.task_type }),
             },
@@ -522,13 +902,18 @@ impl ActExecutor {
             safety::SafetyDecision::Warn { msg } => {
                 // Phase2 MVP: warn and stop (requires user confirmation / revised plan).
                 self.safety_guard.record(&ctx, &action);
+                self.policy_block = Some(msg.clone());
+                Err(error::LunaError::invalid_input(msg))
+            }
+            safety::SafetyDecision::Deny { msg } => {
+                self.policy_block = Some(msg.clone());
                 Err(error::LunaError::invalid_input(msg))
             }
-            safety::SafetyDecision::Deny { msg } => Err(error::LunaError::invalid_input(msg)),
         }
     }
 
     fn rollback(&mut self, tool_ctx: &tools::ToolContext) -> error::Result<()> {
+        self.changed_files.clear();
         for (abs, content) in self.original_files.drain() {
             let _ = tool_ctx; // keep signature stable.
             std::fs::write(&abs, content)
@@ -630,6 +1015,31 @@ fn parse_edit_line_to(input: &str) -> Option<(String, usize, String)> {
     Some((path.to_owned(), line_1, new_line.to_owned()))
 }
 
+/// Render `(step_id, label, output)` traces between a header and a footer line.
+fn format_step_outputs(
+    header: &str,
+    step_outputs: Vec<(usize, String, String)>,
+    footer: &str,
+) -> String {
+    let mut out = String::new();
+    out.push_str(header);
+    out.push('\n');
+    out.push_str("═".repeat(40).as_str());
+    out.push('\n');
+    for (step_id, label, output) in step_outputs {
+        out.push_str(&format!("\n[Step {}] {}\n", step_id, label));
+        out.push_str("─".repeat(30).as_str());
+        out.push('\n');
+        out.push_str(&output);
+        out.push('\n');
+    }
+    out.push('\n');
+    out.push_str("═".repeat(40).as_str());
+    out.push('\n');
+    out.push_str(footer);
+    out
+}
+
 // repo root resolution is shared with runtime router.
 fn now_micros() -> u64 {
     std::time::SystemTime::now()
@@ -642,10 +1052,30 @@ fn now_micros() -> u64 {
 ///
 /// Uses RefillPipeline for comprehensive context retrieval when available,
 /// falling back to simple file reading otherwise.
+/// Context collected for a task.
+struct TaskContext {
+    chunks: Vec<context::ContextChunk>,
+    /// The repository itself was searched, not just the fallback file reads
+    searched: bool,
+    /// Pipeline built for the search, reused for follow-up retrieval
+    pipeline: Option<Arc<context::RefillPipeline>>,
+}
+
+impl TaskContext {
+    /// The repository was searched and nothing relevant came back.
+    fn is_empty(&self) -> bool {
+        self.searched && self.chunks.is_empty()
+    }
+}
+
 fn collect_context_from_task(
     task: &Task,
     cwd: Option<&Path>,
-) -> Vec<context::ContextChunk> {
+    budget: &TokenBudget,
+    pinned_paths: &[PathBuf],
+    test_files: TestFilePolicy,
+    seed_chunks: &[context::IndexChunk],
+) -> TaskContext {
     use context::{ContextChunk, ContextQuery, ContextType, SourceLocation, TextRange};
     use std::path::PathBuf;
 
@@ -654,8 +1084,14 @@ fn collect_context_from_task(
     let repo_root = crate::router::resolve_repo_root(cwd).unwrap_or_else(|| cwd.unwrap_or(Path::new(".")).to_path_buf());
     tracing::debug!("Attempting to create RefillPipeline for: {}", repo_root.display());
 
-    if let Some(pipeline) = create_refill_pipeline(repo_root.clone()) {
+    let mut fallback_pipeline = None;
+    if let Some(pipeline) = create_refill_pipeline(repo_root.clone(), test_files, budget) {
         tracing::info!("RefillPipeline created successfully");
+        let pipeline = Arc::new(
+            pipeline
+                .with_max_candidates(budget.refine_candidates)
+                .with_pinned_paths(pinned_paths.to_vec(), budget.pinned_context_tokens),
+        );
         // Build query from task entities
         let mut symbols = Vec::new();
         let mut paths = Vec::new();
@@ -669,7 +1105,7 @@ fn collect_context_from_task(
         }
 
         // Use RefillPipeline if we have symbols or paths
-        if !symbols.is_empty() || !paths.is_empty() {
+        let retrieved = if !symbols.is_empty() || !paths.is_empty() {
             let query = ContextQuery::TaskDriven {
                 keywords: vec![task.raw_input.clone()],
                 paths,
@@ -677,21 +1113,37 @@ fn collect_context_from_task(
             };
             tracing::info!("Using RefillPipeline with query: {:?}", query);
 
-            match pipeline.retrieve(&query, 10) {
+            match pipeline.retrieve(&query, budget.initial_context_chunks) {
                 Ok(index_chunks) => {
                     tracing::info!("RefillPipeline retrieved {} chunks", index_chunks.len());
-                    let refined = pipeline.refine(&index_chunks);
-                    tracing::info!("RefillPipeline refined to {} chunks", refined.len());
-                    return refined;
+                    Some(index_chunks)
                 }
                 Err(e) => {
                     tracing::warn!("RefillPipeline retrieve failed: {}", e);
-                    // Fall through to simple fallback
+                    None
                 }
             }
         } else {
             tracing::warn!("No symbols or paths extracted from task");
+            None
+        };
+
+        // Seed hits and pinned files stand in for retrieval when the task gave nothing to search for.
+        if retrieved.is_some() || !seed_chunks.is_empty() || !pinned_paths.is_empty() {
+            let index_chunks = merge_seed_chunks(seed_chunks, retrieved.unwrap_or_default());
+            let (refined, reasons) = pipeline.refine_explained(&index_chunks);
+            tracing::info!("RefillPipeline refined to {} chunks", refined.len());
+            for reason in &reasons {
+                tracing::debug!("context selection: {reason}");
+            }
+            return TaskContext {
+                chunks: refined,
+                searched: true,
+                pipeline: Some(pipeline),
+            };
         }
+        // Fall through to simple fallback
+        fallback_pipeline = Some(pipeline);
     } else {
         tracing::warn!("Failed to create RefillPipeline for: {}", repo_root.display());
     }
@@ -730,7 +1182,10 @@ fn collect_context_from_task(
                     range: TextRange::new(1, line_count),
                 };
 
-                chunks.push(ContextChunk::new(summary, source, ContextType::FileOverview));
+                // Fixed head-of-file window, not scope-resolved.
+                let mut chunk = ContextChunk::new(summary, source, ContextType::FileOverview);
+                chunk.set_confidence(0.3);
+                chunks.push(chunk);
             }
         }
     }
@@ -758,11 +1213,31 @@ fn collect_context_from_task(
                 range: TextRange::new(1, 1),
             };
 
-            chunks.push(ContextChunk::new(context_text, source, ContextType::CodeSnippet));
+            let mut chunk = ContextChunk::new(context_text, source, ContextType::CodeSnippet);
+            chunk.set_confidence(0.1);
+            chunks.push(chunk);
         }
     }
 
-    chunks
+    TaskContext {
+        chunks,
+        searched: false,
+        pipeline: fallback_pipeline,
+    }
+}
+
+/// Seed chunks first, then retrieved chunks that don't cover the same source.
+fn merge_seed_chunks(
+    seed_chunks: &[context::IndexChunk],
+    retrieved: Vec<context::IndexChunk>,
+) -> Vec<context::IndexChunk> {
+    let mut merged = seed_chunks.to_vec();
+    for chunk in retrieved {
+        if !merged.iter().any(|seed| seed.source == chunk.source) {
+            merged.push(chunk);
+        }
+    }
+    merged
 }
 
 #[cfg(test)]
@@ -771,11 +1246,40 @@ mod tests {
     use crate::recorder::NoopTrajectoryRecorder;
     use crate::router::RuntimeRouter;
     use crate::safety::RuleBasedSafetyGuard;
-
-    fn tmp_dir(name: &str) -> PathBuf {
-        let dir = std::env::temp_dir().join(format!("luna_tpar_{name}_{}", uuid::Uuid::new_v4()));
-        std::fs::create_dir_all(&dir).unwrap();
-        dir
+    use crate::tmp_dir;
+
+    /// Rule-based turn in `cwd` with a small budget; tests override fields
+    /// with struct update syntax.
+    fn turn_ctx(cwd: Option<&Path>) -> TurnContext {
+        TurnContext {
+            session_id: "local:test".to_owned(),
+            request_id: "req:test".to_owned(),
+            cwd: cwd.map(Path::to_path_buf),
+            safety_guard: Arc::new(RuleBasedSafetyGuard::new(8)),
+            trajectory: Arc::new(NoopTrajectoryRecorder),
+            tools: Arc::new(tools::ToolRegistry::new()),
+            budget: TokenBudget {
+                max_input_chars: 2048,
+                max_io_bytes: 1024,
+                max_steps: 8,
+                ..TokenBudget::default()
+            },
+            planner: Arc::new(crate::planner::RuleBasedPlanner::new()),
+            context_pipeline: None,
+            dry_run: false,
+            navigation: NavigationOptions::default(),
+            post_edit_context: PostEditContext::default(),
+            answer_language: None,
+            grounding: Grounding::Lenient,
+            cite_sources: false,
+            confine_to_repo: false,
+            edit_backups: 0,
+            seed_chunks: Vec::new(),
+            cancel: CancelToken::default(),
+            pinned_paths: Vec::new(),
+            edit_summarizer: None,
+            signature_patterns: context::SignaturePatterns::default(),
+        }
     }
 
     #[test]
@@ -785,30 +1289,468 @@ mod tests {
         std::fs::write(&file, "hello\nworld\n").unwrap();
 
         let mut events = Vec::new();
-        let out = run_turn(
+        let (out, _) = run_turn(
+            &format!("修改 {} 第 2 行 为 WORLD", file.display()),
+            turn_ctx(Some(&dir)),
+            &mut events,
+        )
+        .unwrap();
+
+        assert!(out.contains("edited:"), "out={out}");
+        let updated = std::fs::read_to_string(&file).unwrap();
+        assert_eq!(updated, "hello\nWORLD\n");
+    }
+
+    #[test]
+    fn tpar_dry_run_edit_reports_diff_and_keeps_file() {
+        let dir = tmp_dir("dry_run");
+        let file = dir.join("a.txt");
+        std::fs::write(&file, "hello\nworld\n").unwrap();
+
+        let mut events = Vec::new();
+        let (out, _) = run_turn(
             &format!("修改 {} 第 2 行 为 WORLD", file.display()),
             TurnContext {
-                session_id: "local:test".to_owned(),
-                request_id: "req:test".to_owned(),
-                cwd: Some(dir.clone()),
-                safety_guard: Arc::new(RuleBasedSafetyGuard::new(8)),
-                trajectory: Arc::new(NoopTrajectoryRecorder),
-                tools: Arc::new(tools::ToolRegistry::new()),
+                dry_run: true,
+                ..turn_ctx(Some(&dir))
+            },
+            &mut events,
+        )
+        .unwrap();
+
+        assert!(out.contains("+WORLD"), "out={out}");
+        let unchanged = std::fs::read_to_string(&file).unwrap();
+        assert_eq!(unchanged, "hello\nworld\n");
+    }
+
+    #[test]
+    fn tpar_streams_echo_text_as_answer_deltas() {
+        let plan = r#"{"steps": [
+            {"kind": "think", "text": "look at main"},
+            {"kind": "echo", "text": "main starts the server"},
+            {"kind": "echo", "text": "and waits for shutdown"}
+        ], "estimated_tokens": 10}"#;
+        let client = Arc::new(llm::StaticClient::new(plan));
+
+        let mut events = Vec::new();
+        let (_, termination) = run_turn(
+            "what does main do",
+            TurnContext {
+                budget: TokenBudget::default(),
+                planner: Arc::new(crate::planner::LLMBasedPlanner::new(client, 8)),
+                ..turn_ctx(None)
+            },
+            &mut events,
+        )
+        .unwrap();
+
+        let deltas: Vec<&str> = events
+            .iter()
+            .filter_map(|e| match e {
+                RuntimeEvent::AnswerDelta { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(deltas, ["main starts the server", "and waits for shutdown"]);
+        assert_eq!(termination, TerminationReason::Answered);
+    }
+
+    #[test]
+    fn tpar_runs_only_the_step_budget() {
+        let plan = r#"{"steps": [
+            {"kind": "echo", "text": "one"},
+            {"kind": "echo", "text": "two"},
+            {"kind": "echo", "text": "three"}
+        ], "estimated_tokens": 10}"#;
+        let client = Arc::new(llm::StaticClient::new(plan));
+
+        let mut events = Vec::new();
+        let (out, termination) = run_turn(
+            "say something",
+            TurnContext {
                 budget: TokenBudget {
                     max_input_chars: 2048,
                     max_io_bytes: 1024,
-                    max_steps: 8,
+                    max_steps: 2,
+                    ..TokenBudget::default()
                 },
-                planner: Arc::new(crate::planner::RuleBasedPlanner::new()),
-                context_pipeline: None,
+                planner: Arc::new(crate::planner::LLMBasedPlanner::new(client, 8)),
+                ..turn_ctx(None)
             },
             &mut events,
         )
         .unwrap();
 
-        assert!(out.contains("edited:"), "out={out}");
-        let updated = std::fs::read_to_string(&file).unwrap();
-        assert_eq!(updated, "hello\nWORLD\n");
+        assert_eq!(termination, TerminationReason::MaxStepsReached);
+        assert!(out.contains("two") && !out.contains("three"), "out={out}");
+    }
+
+    #[test]
+    fn tpar_stops_a_plan_that_repeats_itself() {
+        let plan = r#"{"steps": [
+            {"kind": "echo", "text": "one"},
+            {"kind": "think", "text": "again"},
+            {"kind": "think", "text": "again"},
+            {"kind": "think", "text": "again"},
+            {"kind": "echo", "text": "two"}
+        ], "estimated_tokens": 10}"#;
+        let client = Arc::new(llm::StaticClient::new(plan));
+
+        let mut events = Vec::new();
+        let (out, termination) = run_turn(
+            "say something",
+            TurnContext {
+                planner: Arc::new(crate::planner::LLMBasedPlanner::new(client, 8)),
+                ..turn_ctx(None)
+            },
+            &mut events,
+        )
+        .unwrap();
+
+        assert_eq!(termination, TerminationReason::LoopDetected);
+        assert!(out.contains("one") && !out.contains("two"), "out={out}");
+    }
+
+    #[test]
+    fn tpar_returns_finished_steps_when_cancelled() {
+        let plan = r#"{"steps": [
+            {"kind": "echo", "text": "one"},
+            {"kind": "echo", "text": "two"},
+            {"kind": "echo", "text": "three"}
+        ], "estimated_tokens": 10}"#;
+        let client = Arc::new(llm::StaticClient::new(plan));
+
+        // Cancels the turn as soon as the first step completes, like Ctrl-C would.
+        struct CancelAfterFirstStep(CancelToken, Vec<RuntimeEvent>);
+        impl EventSink for CancelAfterFirstStep {
+            fn emit(&mut self, event: &RuntimeEvent) {
+                if matches!(event, RuntimeEvent::TparStepCompleted { step_id: 1, .. }) {
+                    self.0.cancel();
+                }
+                self.1.push(event.clone());
+            }
+        }
+
+        let cancel = CancelToken::new();
+        let mut events = CancelAfterFirstStep(cancel.clone(), Vec::new());
+        let (out, termination) = run_turn(
+            "say something",
+            TurnContext {
+                planner: Arc::new(crate::planner::LLMBasedPlanner::new(client, 8)),
+                cancel,
+                ..turn_ctx(None)
+            },
+            &mut events,
+        )
+        .unwrap();
+
+        assert_eq!(termination, TerminationReason::Cancelled);
+        assert!(out.contains("cancelled after 1 of 3 steps"), "out={out}");
+        assert!(out.contains("one") && !out.contains("two"), "out={out}");
+        assert!(events
+            .1
+            .iter()
+            .any(|e| matches!(e, RuntimeEvent::TparReviewed { ok: false })));
+    }
+
+    #[test]
+    fn tpar_reports_no_results_instead_of_planning() {
+        let dir = tmp_dir("no_results");
+        std::fs::create_dir_all(dir.join(".git")).unwrap();
+        std::fs::write(dir.join("lib.rs"), "pub fn present() {}\n").unwrap();
+        let client = Arc::new(llm::StaticClient::new(
+            r#"{"steps": [{"kind": "echo", "text": "made up"}], "estimated_tokens": 5}"#,
+        ));
+
+        let mut events = Vec::new();
+        let (out, termination) = run_turn(
+            "explain frobnicate_widget",
+            TurnContext {
+                budget: TokenBudget::default(),
+                planner: Arc::new(crate::planner::LLMBasedPlanner::new(client, 8)),
+                ..turn_ctx(Some(&dir))
+            },
+            &mut events,
+        )
+        .unwrap();
+
+        assert!(
+            out.contains("No relevant code found for: explain frobnicate_widget"),
+            "out={out}"
+        );
+        assert_eq!(termination, TerminationReason::NoResults);
+        assert!(!events
+            .iter()
+            .any(|e| matches!(e, RuntimeEvent::TparPlanBuilt { .. })));
+    }
+
+    #[test]
+    fn tpar_plans_from_seed_chunks_when_search_finds_nothing() {
+        let dir = tmp_dir("seed_chunks");
+        std::fs::create_dir_all(dir.join(".git")).unwrap();
+        std::fs::write(dir.join("lib.rs"), "pub fn present() {}\n").unwrap();
+        let client = Arc::new(llm::StaticClient::new(
+            r#"{"steps": [{"kind": "echo", "text": "seeded"}], "estimated_tokens": 5}"#,
+        ));
+        // What an editor's own index might hand over for the symbol.
+        let seed = context::IndexChunk::new(
+            "pub fn present() {}",
+            context::SourceLocation {
+                repo_root: dir.clone(),
+                rel_path: PathBuf::from("lib.rs"),
+                range: context::TextRange::new(1, 1),
+            },
+            context::IndexChunkType::SymbolDefinition,
+        );
+
+        let mut events = Vec::new();
+        let (out, termination) = run_turn(
+            "explain frobnicate_widget",
+            TurnContext {
+                budget: TokenBudget::default(),
+                planner: Arc::new(crate::planner::LLMBasedPlanner::new(client, 8)),
+                seed_chunks: vec![seed],
+                ..turn_ctx(Some(&dir))
+            },
+            &mut events,
+        )
+        .unwrap();
+
+        assert_ne!(termination, TerminationReason::NoResults, "out={out}");
+        assert!(events.iter().any(|e| matches!(
+            e,
+            RuntimeEvent::TparContextCollected { chunks, .. } if *chunks > 0
+        )));
+    }
+
+    #[test]
+    fn tpar_scripted_llm_drives_search_plan_edit_answer() {
+        let dir = tmp_dir("scripted_loop");
+        std::fs::create_dir_all(dir.join(".git")).unwrap();
+        let file = dir.join("lib.rs");
+        std::fs::write(&file, "pub fn greet() -> &'static str {\n    \"hi\"\n}\n").unwrap();
+        let plan = r#"{"steps": [
+            {"kind": "tool_call", "call": {"name": "read_symbol", "args": {"name": "greet"}}},
+            {"kind": "tool_call", "call": {"name": "edit_file",
+                "args": {"path": "lib.rs", "line_1": 2, "new_line": "    \"hello\""}}},
+            {"kind": "echo", "text": "greet now returns hello"}
+        ], "estimated_tokens": 20}"#;
+        let client = Arc::new(llm::MockClient::new(vec![plan.to_owned()]));
+
+        let mut events = Vec::new();
+        let (out, termination) = run_turn(
+            "change greet to return hello",
+            TurnContext {
+                budget: TokenBudget::default(),
+                planner: Arc::new(crate::planner::LLMBasedPlanner::new(client.clone(), 8)),
+                ..turn_ctx(Some(&dir))
+            },
+            &mut events,
+        )
+        .unwrap();
+
+        assert_eq!(termination, TerminationReason::Answered, "out={out}");
+        // One planning call, and the search found greet before it was made
+        let prompts = client.prompts();
+        assert_eq!(prompts.len(), 1);
+        let prompt = &prompts[0];
+        assert!(prompt.contains("pub fn greet()"), "prompt={prompt}");
+        assert_eq!(client.remaining(), 0);
+        // The read step saw the definition, the edit landed, the echo answered
+        assert!(out.contains("\"hi\""), "out={out}");
+        assert!(out.contains("greet now returns hello"), "out={out}");
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            "pub fn greet() -> &'static str {\n    \"hello\"\n}\n"
+        );
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, RuntimeEvent::TparContextRefreshed { step_id: 2, .. })));
+    }
+
+    #[test]
+    fn tpar_summarizes_edits_from_their_diff() {
+        let dir = tmp_dir("edit_summary");
+        std::fs::create_dir_all(dir.join(".git")).unwrap();
+        std::fs::write(dir.join("lib.rs"), "fn greet() {\n    \"hi\"\n}\n").unwrap();
+        let plan = r#"{"steps": [
+            {"kind": "tool_call", "call": {"name": "edit_file",
+                "args": {"path": "lib.rs", "line_1": 2, "new_line": "    \"hello\""}}}
+        ], "estimated_tokens": 10}"#;
+        let client = Arc::new(llm::StaticClient::new(plan));
+        let summarizer = Arc::new(llm::MockClient::new(Vec::new()));
+        summarizer.push("Make greet say hello");
+
+        let mut events = Vec::new();
+        let (out, termination) = run_turn(
+            "change greet to return hello",
+            TurnContext {
+                budget: TokenBudget::default(),
+                planner: Arc::new(crate::planner::LLMBasedPlanner::new(client, 8)),
+                edit_summarizer: Some(summarizer.clone()),
+                ..turn_ctx(Some(&dir))
+            },
+            &mut events,
+        )
+        .unwrap();
+
+        assert_eq!(termination, TerminationReason::Answered, "out={out}");
+        assert!(out.contains("📝 Make greet say hello"), "out={out}");
+        let prompts = summarizer.prompts();
+        assert_eq!(prompts.len(), 1);
+        assert!(prompts[0].contains("+    \"hello\""), "{prompts:?}");
+    }
+
+    #[test]
+    fn merge_seed_chunks_puts_seeds_first_and_drops_duplicate_sources() {
+        let chunk = |path: &str, content: &str| {
+            context::IndexChunk::new(
+                content,
+                context::SourceLocation {
+                    repo_root: PathBuf::from("/repo"),
+                    rel_path: PathBuf::from(path),
+                    range: context::TextRange::new(1, 3),
+                },
+                context::IndexChunkType::CodeBlock,
+            )
+        };
+
+        let merged = merge_seed_chunks(
+            &[chunk("a.rs", "seed a")],
+            vec![chunk("b.rs", "found b"), chunk("a.rs", "found a")],
+        );
+        let contents: Vec<_> = merged.iter().map(|c| c.content.as_str()).collect();
+        assert_eq!(contents, ["seed a", "found b"]);
+    }
+
+    #[test]
+    fn step_outcomes_report_kind_and_changed_files() {
+        let dir = tmp_dir("outcome");
+        std::fs::write(dir.join("a.txt"), "one\n").unwrap();
+        let step = PlanStep::ToolCall {
+            call: tools::ToolCall {
+                name: "edit_file".to_owned(),
+                args: serde_json::json!({"path": "a.txt", "line_1": 1, "new_line": "ONE"}),
+            },
+        };
+        let task = TaskAnalyzer::analyze("edit a.txt");
+        let mut events: Vec<RuntimeEvent> = Vec::new();
+
+        for dry_run in [true, false] {
+            let mut exec = ActExecutor::new(
+                "local:test",
+                "req:test",
+                Some(&dir),
+                Arc::new(RuleBasedSafetyGuard::new(8)),
+                Arc::new(NoopTrajectoryRecorder),
+                Arc::new(tools::ToolRegistry::new()),
+                TokenBudget::default(),
+                None,
+            );
+            let tool_ctx = tools::ToolContext::new(Some(dir.clone()))
+                .with_cwd(Some(dir.clone()))
+                .with_max_bytes(1024)
+                .with_dry_run(dry_run);
+            let outcome = exec
+                .execute_step(&step, &task, &tool_ctx, Some(&dir), &mut events)
+                .unwrap();
+            assert_eq!(outcome.kind, StepKind::Edit);
+            let changed = Vec::from_iter((!dry_run).then(|| dir.join("a.txt")));
+            assert_eq!(outcome.changed_files, changed, "dry_run={dry_run}");
+        }
+
+        let mut exec = ActExecutor::new(
+            "local:test",
+            "req:test",
+            Some(&dir),
+            Arc::new(RuleBasedSafetyGuard::new(8)),
+            Arc::new(NoopTrajectoryRecorder),
+            Arc::new(tools::ToolRegistry::new()),
+            TokenBudget::default(),
+            None,
+        );
+        let think = PlanStep::Think {
+            text: "EDIT COMPLETE".to_owned(),
+        };
+        let tool_ctx = tools::ToolContext::new(Some(dir.clone())).with_max_bytes(1024);
+        let outcome = exec
+            .execute_step(&think, &task, &tool_ctx, None, &mut events)
+            .unwrap();
+        // Output text never stands in for what happened.
+        assert_eq!(outcome.kind, StepKind::Thought);
+        assert!(outcome.changed_files.is_empty());
+    }
+
+    #[test]
+    fn edit_steps_refresh_context_per_post_edit_strategy() {
+        let dir = tmp_dir("post_edit");
+        let src = "pub fn first() -> i32 {\n    1\n}\n\npub fn second() -> i32 {\n    2\n}\n";
+        let step = PlanStep::ToolCall {
+            call: tools::ToolCall {
+                name: "edit_file".to_owned(),
+                args: serde_json::json!({"path": "lib.rs", "line_1": 6, "new_line": "    22"}),
+            },
+        };
+        let task = TaskAnalyzer::analyze("edit lib.rs");
+        let tool_ctx = tools::ToolContext::new(Some(dir.clone()))
+            .with_cwd(Some(dir.clone()))
+            .with_max_bytes(1024);
+
+        let mut events: Vec<RuntimeEvent> = Vec::new();
+        let mut refreshed = Vec::new();
+        for strategy in [PostEditContext::ReparseEdited, PostEditContext::None] {
+            std::fs::write(dir.join("lib.rs"), src).unwrap();
+            let mut exec = ActExecutor::new(
+                "local:test",
+                "req:test",
+                Some(&dir),
+                Arc::new(RuleBasedSafetyGuard::new(8)),
+                Arc::new(NoopTrajectoryRecorder),
+                Arc::new(tools::ToolRegistry::new()),
+                TokenBudget::default(),
+                None,
+            )
+            .with_post_edit_context(strategy);
+            let outcome = exec
+                .execute_step(&step, &task, &tool_ctx, Some(&dir), &mut events)
+                .unwrap();
+            refreshed.push(outcome.context_delta);
+        }
+
+        // Just the edited function, as it reads after the edit.
+        let [reparsed, none] = refreshed.try_into().unwrap();
+        assert_eq!(reparsed.len(), 1);
+        assert_eq!(reparsed[0].content, "pub fn second() -> i32 {\n    22\n}");
+        assert_eq!(reparsed[0].source.range, context::TextRange::new(5, 7));
+        assert!(none.is_empty());
+    }
+
+    #[test]
+    fn tpar_edit_output_shows_refreshed_context() {
+        let dir = tmp_dir("post_edit_turn");
+        std::fs::create_dir_all(dir.join(".git")).unwrap();
+        let file = dir.join("lib.rs");
+        std::fs::write(
+            &file,
+            "pub fn first() -> i32 {\n    1\n}\n\npub fn second() -> i32 {\n    2\n}\n",
+        )
+        .unwrap();
+
+        let mut events = Vec::new();
+        let (out, _) = run_turn(
+            &format!("修改 {} 第 6 行 为 22", file.display()),
+            TurnContext {
+                post_edit_context: PostEditContext::ReparseEdited,
+                ..turn_ctx(Some(&dir))
+            },
+            &mut events,
+        )
+        .unwrap();
+
+        // The edited function as it reads now, not the pre-edit window.
+        let (_, refreshed) = out.split_once("🔄 Context after edits").expect(&out);
+        assert!(refreshed.contains("pub fn second() -> i32 {\n22\n}"), "out={out}");
+        assert!(!refreshed.contains("pub fn first"), "out={out}");
     }
 
     #[test]
@@ -832,27 +1774,16 @@ mod tests {
     fn tpar_dangerous_terminal_is_denied() {
         let dir = tmp_dir("term");
         let mut events = Vec::new();
-        let out = run_turn(
+        let (out, termination) = run_turn(
             "运行 rm -rf /",
-            TurnContext {
-                session_id: "local:test".to_owned(),
-                request_id: "req:test".to_owned(),
-                cwd: Some(dir),
-                safety_guard: Arc::new(RuleBasedSafetyGuard::new(8)),
-                trajectory: Arc::new(NoopTrajectoryRecorder),
-                tools: Arc::new(tools::ToolRegistry::new()),
-                budget: TokenBudget {
-                    max_input_chars: 2048,
-                    max_io_bytes: 1024,
-                    max_steps: 8,
-                },
-                planner: Arc::new(crate::planner::RuleBasedPlanner::new()),
-                context_pipeline: None,
-            },
+            turn_ctx(Some(&dir)),
             &mut events,
         )
         .unwrap();
         assert!(out.contains("危险命令拦截"), "out={out}");
+        assert!(
+            matches!(termination, TerminationReason::PolicyBlocked(msg) if msg.contains("危险命令拦截"))
+        );
     }
 
     #[test]
@@ -7,7 +7,10 @@ use std::{
 use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 
-use crate::{now_ms, Message, Result, Session, SessionStore, SessionSummary, TimestampMs};
+use crate::{
+    now_ms, sort_summaries, Message, Result, Session, SessionStore, SessionSummary, TimestampMs,
+    SESSION_FORMAT_VERSION,
+};
 
 #[derive(Debug, Clone)]
 pub struct LunaHome {
@@ -49,6 +52,9 @@ enum SessionEvent {
         session_id: String,
         title: Option<String>,
         ts_ms: TimestampMs,
+        /// Format version the file was written with (absent = 0)
+        #[serde(default)]
+        version: u32,
     },
     MessageAppended {
         message: Message,
@@ -139,7 +145,13 @@ impl JsonlSessionStore {
                     session_id: _,
                     title,
                     ts_ms,
+                    version,
                 } => {
+                    if version > SESSION_FORMAT_VERSION {
+                        tracing::warn!(
+                            "session written by a newer format (v{version} > v{SESSION_FORMAT_VERSION}), reading best-effort: id={session_id}"
+                        );
+                    }
                     session = Some(Session {
                         id: session_id.to_owned(),
                         title,
@@ -188,6 +200,7 @@ impl SessionStore for JsonlSessionStore {
                 session_id: session_id.clone(),
                 title,
                 ts_ms: now,
+                version: SESSION_FORMAT_VERSION,
             },
         )?;
         self.persisted_counts.lock().insert(session_id, 0);
@@ -244,14 +257,50 @@ impl SessionStore for JsonlSessionStore {
             let Some(s) = self.replay_session_file(&id)? else {
                 continue;
             };
-            out.push(SessionSummary {
-                id: s.id,
-                title: s.title,
-                message_count: s.messages.len(),
-                updated_at: s.update_at,
-            });
+            out.push(s.summary());
         }
-        out.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+        sort_summaries(&mut out);
         Ok(out)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_reads_unversioned_session_file() {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let home = LunaHome {
+            base_dir: std::env::temp_dir().join(format!("luna-session-test-{nanos}")),
+        };
+        let store = JsonlSessionStore::new(home.clone());
+        store.ensure_dirs().unwrap();
+
+        // Written before `version` existed.
+        fs::write(
+            store.session_path("legacy"),
+            concat!(
+                r#"{"type":"session_created","session_id":"legacy","title":"old","ts_ms":1}"#,
+                "\n",
+                r#"{"type":"message_appended","message":{"id":"m1","role":"User","content":"hi","timestamp":2}}"#,
+                "\n",
+            ),
+        )
+        .unwrap();
+
+        let session = store.get("legacy").unwrap().unwrap();
+        assert_eq!(session.title.as_deref(), Some("old"));
+        assert_eq!(session.messages.len(), 1);
+        assert_eq!(session.update_at, 2);
+
+        let created = store.create(None).unwrap();
+        let first_line = fs::read_to_string(store.session_path(&created.id)).unwrap();
+        assert!(first_line.contains(&format!("\"version\":{SESSION_FORMAT_VERSION}")));
+
+        let _ = fs::remove_dir_all(home.base_dir());
+    }
+}
@@ -2,7 +2,7 @@
 
 use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 mod jsonl_store;
 
@@ -11,6 +11,12 @@ pub use jsonl_store::{JsonlSessionStore, LunaHome};
 pub type Result<T> = error::Result<T>;
 pub type TimestampMs = u64;
 
+/// Version of the on-disk session format written by this build.
+///
+/// Files without a version are version 0; every field added since has a
+/// serde default, so they load as is.
+pub const SESSION_FORMAT_VERSION: u32 = 1;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Session {
     pub id: String,
@@ -59,6 +65,52 @@ impl Session {
         self.update_at = now;
         self.messages.last().expect("just pushed")
     }
+
+    /// Lightweight card for listings.
+    #[must_use]
+    pub fn summary(&self) -> SessionSummary {
+        SessionSummary {
+            id: self.id.clone(),
+            title: self.title.clone(),
+            message_count: self.messages.len(),
+            updated_at: self.update_at,
+        }
+    }
+
+    /// What changed going from `self` to `newer`, keyed by message id.
+    #[must_use]
+    pub fn diff(&self, newer: &Session) -> SessionDiff {
+        let old_ids = self
+            .messages
+            .iter()
+            .map(|m| m.id.as_str())
+            .collect::<HashSet<_>>();
+        let new_ids = newer
+            .messages
+            .iter()
+            .map(|m| m.id.as_str())
+            .collect::<HashSet<_>>();
+
+        SessionDiff {
+            title: (self.title != newer.title).then(|| TitleChange {
+                from: self.title.clone(),
+                to: newer.title.clone(),
+            }),
+            added_messages: newer
+                .messages
+                .iter()
+                .filter(|m| !old_ids.contains(m.id.as_str()))
+                .map(|m| m.id.clone())
+                .collect(),
+            removed_messages: self
+                .messages
+                .iter()
+                .filter(|m| !new_ids.contains(m.id.as_str()))
+                .map(|m| m.id.clone())
+                .collect(),
+            updated_at: (self.update_at != newer.update_at).then_some(newer.update_at),
+        }
+    }
 }
 
 pub trait SessionStore: Send + Sync {
@@ -94,6 +146,36 @@ pub struct SessionSummary {
     pub updated_at: TimestampMs,
 }
 
+/// Changes between two snapshots of the same session.
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionDiff {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<TitleChange>,
+    /// Ids of messages only present in the newer snapshot, in order
+    pub added_messages: Vec<String>,
+    /// Ids of messages only present in the older snapshot, in order
+    pub removed_messages: Vec<String>,
+    /// New `update_at`, when it moved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<TimestampMs>,
+}
+
+impl SessionDiff {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.added_messages.is_empty()
+            && self.removed_messages.is_empty()
+            && self.updated_at.is_none()
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct TitleChange {
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
 #[derive(Debug, Default)]
 pub struct InMemorySessionStore {
     inner: Mutex<HashMap<String, Session>>,
@@ -130,20 +212,21 @@ impl SessionStore for InMemorySessionStore {
 
     fn list(&self) -> Result<Vec<SessionSummary>> {
         let guard = self.inner.lock();
-        let mut out = guard
-            .values()
-            .map(|s| SessionSummary {
-                id: s.id.clone(),
-                title: s.title.clone(),
-                message_count: s.messages.len(),
-                updated_at: s.update_at,
-            })
-            .collect::<Vec<_>>();
-        out.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+        let mut out = guard.values().map(Session::summary).collect::<Vec<_>>();
+        sort_summaries(&mut out);
         Ok(out)
     }
 }
 
+/// Most recently updated first; ties broken by id so listings are stable.
+pub(crate) fn sort_summaries(summaries: &mut [SessionSummary]) {
+    summaries.sort_by(|a, b| {
+        b.updated_at
+            .cmp(&a.updated_at)
+            .then_with(|| a.id.cmp(&b.id))
+    });
+}
+
 /// Generate a reasonably unique id
 pub fn gen_id(prefix: &str) -> String {
     format!("{prefix}:{}", uuid::Uuid::new_v4())
@@ -155,3 +238,62 @@ pub(crate) fn now_ms() -> TimestampMs {
         .unwrap_or_default()
         .as_micros() as TimestampMs
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_order_is_stable_when_timestamps_tie() {
+        let store = InMemorySessionStore::new();
+        for id in ["c", "a", "d", "b"] {
+            let mut session = Session::new(id, None);
+            session.update_at = 42;
+            store.save(session).unwrap();
+        }
+        let mut newest = Session::new("z", None);
+        newest.update_at = 43;
+        store.save(newest).unwrap();
+
+        let ids = |store: &InMemorySessionStore| {
+            store
+                .list()
+                .unwrap()
+                .into_iter()
+                .map(|s| s.id)
+                .collect::<Vec<_>>()
+        };
+        let first = ids(&store);
+        assert_eq!(first, ["z", "a", "b", "c", "d"]);
+        for _ in 0..10 {
+            assert_eq!(ids(&store), first);
+        }
+    }
+
+    #[test]
+    fn diff_reports_title_and_message_changes() {
+        let mut old = Session::new("s", None);
+        old.push_message(Role::User, "hi");
+        let dropped = old.push_message(Role::Assistant, "hello").id.clone();
+
+        let mut new = old.clone();
+        assert!(old.diff(&new).is_empty());
+
+        new.title = Some("greetings".to_owned());
+        new.messages.retain(|m| m.id != dropped);
+        let added = new.push_message(Role::User, "bye").id.clone();
+
+        let diff = old.diff(&new);
+        assert_eq!(
+            diff.title,
+            Some(TitleChange {
+                from: None,
+                to: Some("greetings".to_owned()),
+            })
+        );
+        assert_eq!(diff.added_messages, [added]);
+        assert_eq!(diff.removed_messages, [dropped]);
+        assert_eq!(new.summary().message_count, 2);
+        assert!(serde_json::to_value(&diff).is_ok());
+    }
+}
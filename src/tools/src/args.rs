@@ -0,0 +1,120 @@
+//! Typed arguments for the built-in tools.
+//!
+//! Each struct serializes to the `args` object its tool reads, so call sites
+//! build a [`ToolCall`] without assembling JSON by hand and a missing field is
+//! a compile error instead of a `missing args.*` failure at run time.
+
+use serde::Serialize;
+
+use crate::{InvalidUtf8, ToolCall};
+
+/// Arguments of one built-in tool.
+pub trait ToolArgs: Serialize + Sized {
+    /// Registry name of the tool these arguments are for.
+    const TOOL: &'static str;
+
+    fn into_call(self) -> ToolCall {
+        ToolCall {
+            name: Self::TOOL.to_owned(),
+            args: serde_json::to_value(self).expect("tool args serialize to JSON"),
+        }
+    }
+}
+
+/// `read_file`: the whole file.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReadFileArgs {
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invalid_utf8: Option<InvalidUtf8>,
+}
+
+impl ReadFileArgs {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            invalid_utf8: None,
+        }
+    }
+}
+
+impl ToolArgs for ReadFileArgs {
+    const TOOL: &'static str = "read_file";
+}
+
+/// `find_files`: paths matching a fuzzy query.
+#[derive(Debug, Clone, Serialize)]
+pub struct FindFilesArgs {
+    pub query: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+impl ToolArgs for FindFilesArgs {
+    const TOOL: &'static str = "find_files";
+}
+
+/// `read_symbol`: definitions of a symbol, optionally of one kind.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReadSymbolArgs {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+}
+
+impl ToolArgs for ReadSymbolArgs {
+    const TOOL: &'static str = "read_symbol";
+}
+
+/// `edit_file`: replace one line (1-based).
+#[derive(Debug, Clone, Serialize)]
+pub struct EditLineArgs {
+    pub path: String,
+    pub line_1: usize,
+    pub new_line: String,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub include_diff: bool,
+}
+
+impl ToolArgs for EditLineArgs {
+    const TOOL: &'static str = "edit_file";
+}
+
+/// `edit_file`: replace an inclusive, 1-based line range.
+#[derive(Debug, Clone, Serialize)]
+pub struct EditRangeArgs {
+    pub path: String,
+    pub start_line_1: usize,
+    pub end_line_1: usize,
+    pub replace_with: String,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub include_diff: bool,
+}
+
+impl ToolArgs for EditRangeArgs {
+    const TOOL: &'static str = "edit_file";
+}
+
+/// `run_terminal`: a shell command, by default in the context's cwd.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunTerminalArgs {
+    pub cmd: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub capture_combined: bool,
+}
+
+impl RunTerminalArgs {
+    pub fn new(cmd: impl Into<String>) -> Self {
+        Self {
+            cmd: cmd.into(),
+            cwd: None,
+            capture_combined: false,
+        }
+    }
+}
+
+impl ToolArgs for RunTerminalArgs {
+    const TOOL: &'static str = "run_terminal";
+}
@@ -2,9 +2,30 @@
 
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
 use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
 
 use error::ResultExt as _;
+use intelligence::document::LineEnding;
+use intelligence::repo_scan::{FsRepoFileProvider, RepoScanOptions};
+
+pub mod args;
+
+pub use intelligence::{supported_languages, LanguageInfo};
+
+pub use args::{
+    EditLineArgs, EditRangeArgs, FindFilesArgs, ReadFileArgs, ReadSymbolArgs, RunTerminalArgs,
+    ToolArgs,
+};
+
+/// Default [`ToolContext::max_bytes`].
+pub const DEFAULT_MAX_BYTES: usize = 64 * 1024;
+
+/// Default time limit for one tool call in [`ToolRegistry::run`].
+pub const DEFAULT_TOOL_TIMEOUT: Duration = Duration::from_secs(300);
 
 #[derive(Debug, Clone)]
 pub struct ToolContext {
@@ -14,9 +35,61 @@ pub struct ToolContext {
     pub cwd: Option<PathBuf>,
     /// Hard output limit for commands and file reads.
     pub max_bytes: usize,
+    /// Report what mutating/executing tools would do instead of doing it.
+    pub dry_run: bool,
+    /// Reject edit targets and command working directories outside `repo_root`.
+    pub confine_to_repo: bool,
+    /// Backups `edit_file` keeps per file (`{file}.{nanos}.bak`); 0 disables them.
+    pub max_backups: usize,
+    /// Which files `find_files` and `read_symbol` look at.
+    pub scan: RepoScanOptions,
 }
 
 impl ToolContext {
+    /// Context rooted at `repo_root`: no separate cwd, [`DEFAULT_MAX_BYTES`],
+    /// not dry-run, not confined and default scan options.
+    pub fn new(repo_root: Option<PathBuf>) -> Self {
+        Self {
+            repo_root,
+            cwd: None,
+            max_bytes: DEFAULT_MAX_BYTES,
+            dry_run: false,
+            confine_to_repo: false,
+            max_backups: 0,
+            scan: RepoScanOptions::default(),
+        }
+    }
+
+    pub fn with_cwd(mut self, cwd: Option<PathBuf>) -> Self {
+        self.cwd = cwd;
+        self
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn with_confine_to_repo(mut self, confine_to_repo: bool) -> Self {
+        self.confine_to_repo = confine_to_repo;
+        self
+    }
+
+    pub fn with_max_backups(mut self, max_backups: usize) -> Self {
+        self.max_backups = max_backups;
+        self
+    }
+
+    pub fn with_scan(mut self, scan: RepoScanOptions) -> Self {
+        self.scan = scan;
+        self
+    }
+
     #[must_use]
     pub fn resolve_path(&self, path: &Path) -> PathBuf {
         if path.is_absolute() {
@@ -30,6 +103,69 @@ impl ToolContext {
         }
         path.to_path_buf()
     }
+
+    /// [`Self::resolve_path`], then enforce `confine_to_repo`.
+    ///
+    /// The check runs on the canonical path, so `..` segments and symlinks
+    /// pointing out of the repository are rejected too.
+    pub fn resolve_confined(&self, path: &Path) -> error::Result<PathBuf> {
+        let abs = self.resolve_path(path);
+        if !self.confine_to_repo {
+            return Ok(abs);
+        }
+        let Some(repo_root) = &self.repo_root else {
+            return Err(error::LunaError::invalid_input(
+                "confine_to_repo is set but no repo root is known",
+            ));
+        };
+        let root = repo_root
+            .canonicalize()
+            .map_err(|e| error::LunaError::io(Some(repo_root.clone()), e))?;
+        if !canonicalize_existing_prefix(&abs).starts_with(&root) {
+            return Err(error::LunaError::invalid_input(format!(
+                "path escapes repo root {}: {}",
+                root.display(),
+                abs.display()
+            )));
+        }
+        Ok(abs)
+    }
+}
+
+/// `path` with a `:line[:column]` suffix split off, when the path as given
+/// doesn't exist but the shorter one does (models often pass `src/foo.rs:42`).
+fn split_location(ctx: &ToolContext, path: &str) -> (String, Option<usize>) {
+    if ctx.resolve_path(Path::new(path)).exists() {
+        return (path.to_owned(), None);
+    }
+    match _core::parse_location(path) {
+        Some(loc) if loc.line.is_some() && ctx.resolve_path(&loc.path).exists() => {
+            (loc.path.to_string_lossy().into_owned(), loc.line)
+        }
+        _ => (path.to_owned(), None),
+    }
+}
+
+/// Canonicalize the deepest existing ancestor of `path`, then apply the
+/// remaining (not yet existing) components lexically.
+fn canonicalize_existing_prefix(path: &Path) -> PathBuf {
+    for base in path.ancestors() {
+        let Ok(mut out) = base.canonicalize() else {
+            continue;
+        };
+        let rest = path.strip_prefix(base).unwrap_or(Path::new(""));
+        for comp in rest.components() {
+            match comp {
+                std::path::Component::ParentDir => {
+                    out.pop();
+                }
+                std::path::Component::Normal(c) => out.push(c),
+                _ => {}
+            }
+        }
+        return out;
+    }
+    path.to_path_buf()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,11 +174,38 @@ pub struct ToolCall {
     pub args: Value,
 }
 
+/// Which pipe a captured line came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolResult {
     pub ok: bool,
     pub stdout: String,
     pub stderr: String,
+    /// Output lines (with their `\n`) in arrival order; only filled by
+    /// `run_terminal` when `capture_combined` is set.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub combined: Vec<(Stream, String)>,
+    /// Unified diff of the change; only filled by `edit_file` when
+    /// `include_diff` is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
+    /// Why the call failed, when the registry can tell.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<ToolErrorCode>,
+}
+
+/// Machine-readable cause of a failed [`ToolResult`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolErrorCode {
+    /// The tool didn't finish within its time limit.
+    Timeout,
 }
 
 impl ToolResult {
@@ -52,6 +215,9 @@ impl ToolResult {
             ok: true,
             stdout: stdout.into(),
             stderr: String::new(),
+            combined: Vec::new(),
+            diff: None,
+            error_code: None,
         }
     }
 
@@ -61,20 +227,191 @@ impl ToolResult {
             ok: false,
             stdout: String::new(),
             stderr: stderr.into(),
+            combined: Vec::new(),
+            diff: None,
+            error_code: None,
+        }
+    }
+
+    #[must_use]
+    pub fn timed_out(name: &str, limit: Duration) -> Self {
+        Self {
+            error_code: Some(ToolErrorCode::Timeout),
+            ..Self::err(format!("{name} timed out after {}s", limit.as_secs_f64()))
         }
     }
 }
 
+/// What running a tool can do to the outside world.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SideEffect {
+    /// Only reads files or metadata.
+    ReadOnly,
+    /// Writes to the workspace.
+    Mutates,
+    /// Runs arbitrary code.
+    Executes,
+}
+
 pub trait Tool: Send + Sync {
     fn name(&self) -> &'static str;
     fn run(&self, ctx: &ToolContext, args: &Value) -> error::Result<ToolResult>;
+
+    /// Defaults to the most restrictive class; read-only tools must opt in.
+    fn side_effect(&self) -> SideEffect {
+        SideEffect::Executes
+    }
+}
+
+/// Read several files (optionally restricted to a 1-based inclusive line range) in one go.
+///
+/// Each entry gets its own result, so a missing file doesn't fail the batch.
+/// `ctx.max_bytes` caps the aggregate output: the file crossing the cap is
+/// truncated (at a char boundary) and later files fail with an error.
+pub fn read_files(
+    ctx: &ToolContext,
+    specs: &[(String, Option<(usize, usize)>)],
+    invalid_utf8: InvalidUtf8,
+) -> Vec<(String, error::Result<FileText>)> {
+    let mut remaining = ctx.max_bytes;
+    let mut out = Vec::with_capacity(specs.len());
+
+    for (path, range) in specs {
+        if remaining == 0 {
+            out.push((
+                path.clone(),
+                Err(error::LunaError::invalid_input(
+                    "read_files byte budget exhausted",
+                )),
+            ));
+            continue;
+        }
+
+        let res = read_file_range(ctx, path, *range, invalid_utf8).map(|mut file| {
+            truncate_at_char_boundary(&mut file.text, remaining);
+            remaining -= file.text.len();
+            file
+        });
+        out.push((path.clone(), res));
+    }
+
+    out
+}
+
+fn read_file_range(
+    ctx: &ToolContext,
+    path: &str,
+    range: Option<(usize, usize)>,
+    invalid_utf8: InvalidUtf8,
+) -> error::Result<FileText> {
+    let abs = ctx.resolve_path(Path::new(path));
+    let bytes = std::fs::read(&abs)
+        .map_err(|e| error::LunaError::io(Some(abs.clone()), e))
+        .with_context(|| format!("read file: {}", abs.display()))?;
+    let file = decode_utf8(&abs, bytes, invalid_utf8)?;
+
+    let Some((start_1, end_1)) = range else {
+        return Ok(file);
+    };
+    if start_1 == 0 || start_1 > end_1 {
+        return Err(error::LunaError::invalid_input(format!(
+            "invalid line range: {start_1}-{end_1}"
+        )));
+    }
+    let mut selected = file
+        .text
+        .lines()
+        .skip(start_1 - 1)
+        .take(end_1 - start_1 + 1)
+        .collect::<Vec<_>>()
+        .join("\n");
+    selected.push('\n');
+    Ok(FileText {
+        text: selected,
+        lossy: file.lossy,
+    })
+}
+
+/// What file reads do with bytes that aren't valid UTF-8.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InvalidUtf8 {
+    /// Replace them with U+FFFD and mark the text as lossy.
+    #[default]
+    Replace,
+    /// Fail the read rather than return altered text.
+    Error,
+}
+
+impl InvalidUtf8 {
+    /// The `invalid_utf8` tool argument (`"replace"` or `"error"`).
+    fn from_args(args: &Value) -> error::Result<Self> {
+        match args.get("invalid_utf8") {
+            None => Ok(Self::default()),
+            Some(v) => serde_json::from_value(v.clone()).map_err(|_| {
+                error::LunaError::invalid_input(format!(
+                    "invalid_utf8 must be \"replace\" or \"error\", got {v}"
+                ))
+            }),
+        }
+    }
+}
+
+/// Text of a file read by [`read_files`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileText {
+    pub text: String,
+    /// Invalid UTF-8 was replaced with U+FFFD: `text` is not byte-faithful
+    /// and must not be written back over the file.
+    pub lossy: bool,
+}
+
+fn decode_utf8(abs: &Path, bytes: Vec<u8>, invalid_utf8: InvalidUtf8) -> error::Result<FileText> {
+    match String::from_utf8(bytes) {
+        Ok(text) => Ok(FileText { text, lossy: false }),
+        Err(e) if invalid_utf8 == InvalidUtf8::Error => {
+            Err(error::LunaError::invalid_input(format!(
+                "not valid UTF-8 (first bad byte at {}): {}",
+                e.utf8_error().valid_up_to(),
+                abs.display()
+            )))
+        }
+        Err(e) => Ok(FileText {
+            text: String::from_utf8_lossy(e.as_bytes()).into_owned(),
+            lossy: true,
+        }),
+    }
+}
+
+fn lossy_warning(path: &str) -> String {
+    format!("warning: {path} is not valid UTF-8; invalid bytes were replaced with U+FFFD\n")
 }
 
-#[derive(Default)]
 pub struct ToolRegistry {
-    read_file: ReadFileTool,
-    edit_file: EditFileTool,
-    run_terminal: RunTerminalTool,
+    read_file: Arc<ReadFileTool>,
+    read_files: Arc<ReadFilesTool>,
+    find_files: Arc<FindFilesTool>,
+    read_symbol: Arc<ReadSymbolTool>,
+    edit_file: Arc<EditFileTool>,
+    run_terminal: Arc<RunTerminalTool>,
+    default_timeout: Duration,
+    timeouts: HashMap<String, Duration>,
+}
+
+impl Default for ToolRegistry {
+    fn default() -> Self {
+        Self {
+            read_file: Arc::default(),
+            read_files: Arc::default(),
+            find_files: Arc::default(),
+            read_symbol: Arc::default(),
+            edit_file: Arc::default(),
+            run_terminal: Arc::default(),
+            default_timeout: DEFAULT_TOOL_TIMEOUT,
+            timeouts: HashMap::new(),
+        }
+    }
 }
 
 impl ToolRegistry {
@@ -83,12 +420,84 @@ impl ToolRegistry {
         Self::default()
     }
 
+    /// Time limit for tools without their own (default [`DEFAULT_TOOL_TIMEOUT`]).
+    #[must_use]
+    pub fn with_default_timeout(mut self, timeout: Duration) -> Self {
+        self.default_timeout = timeout;
+        self
+    }
+
+    /// Time limit for the tool called `name`, overriding the default.
+    #[must_use]
+    pub fn with_timeout(mut self, name: impl Into<String>, timeout: Duration) -> Self {
+        self.timeouts.insert(name.into(), timeout);
+        self
+    }
+
+    /// Time limit [`Self::run`] applies to the tool called `name`.
+    #[must_use]
+    pub fn timeout(&self, name: &str) -> Duration {
+        self.timeouts
+            .get(name)
+            .copied()
+            .unwrap_or(self.default_timeout)
+    }
+
+    fn tool(&self, name: &str) -> Option<Arc<dyn Tool>> {
+        match name {
+            "read_file" => Some(self.read_file.clone()),
+            "read_files" => Some(self.read_files.clone()),
+            "find_files" => Some(self.find_files.clone()),
+            "read_symbol" => Some(self.read_symbol.clone()),
+            "edit_file" => Some(self.edit_file.clone()),
+            "run_terminal" => Some(self.run_terminal.clone()),
+            _ => None,
+        }
+    }
+
+    /// Side-effect class of a registered tool, `None` if unknown.
+    #[must_use]
+    pub fn side_effect(&self, name: &str) -> Option<SideEffect> {
+        self.tool(name).map(|tool| tool.side_effect())
+    }
+
+    /// Run `call` on a worker thread, waiting at most [`Self::timeout`].
+    ///
+    /// A tool that overruns yields a [`ToolResult::timed_out`] failure; its
+    /// thread is left to finish on its own and its result is discarded.
+    /// Processes it started are not killed.
+    ///
+    /// [`SideEffect::Mutates`] tools run to completion on the caller's thread:
+    /// an abandoned edit could still land after being reported as failed, and
+    /// then be applied again by a retry.
     pub fn run(&self, ctx: &ToolContext, call: &ToolCall) -> error::Result<ToolResult> {
-        match call.name.as_str() {
-            "read_file" => self.read_file.run(ctx, &call.args),
-            "edit_file" => self.edit_file.run(ctx, &call.args),
-            "run_terminal" => self.run_terminal.run(ctx, &call.args),
-            _ => Ok(ToolResult::err(format!("unknown tool: {}", call.name))),
+        let Some(tool) = self.tool(&call.name) else {
+            return Ok(ToolResult::err(format!("unknown tool: {}", call.name)));
+        };
+        if tool.side_effect() == SideEffect::Mutates {
+            return tool.run(ctx, &call.args);
+        }
+        let limit = self.timeout(&call.name);
+
+        let (tx, rx) = mpsc::channel();
+        let (ctx, args) = (ctx.clone(), call.args.clone());
+        std::thread::Builder::new()
+            .name(format!("tool-{}", call.name))
+            .spawn(move || {
+                // Nobody is listening any more once the call timed out.
+                let _ = tx.send(tool.run(&ctx, &args));
+            })
+            .map_err(|e| error::LunaError::io(None, e))
+            .context("spawn tool thread")?;
+
+        match rx.recv_timeout(limit) {
+            Ok(res) => res,
+            Err(mpsc::RecvTimeoutError::Timeout) => Ok(ToolResult::timed_out(&call.name, limit)),
+            // The sender was dropped without sending: the tool panicked.
+            Err(mpsc::RecvTimeoutError::Disconnected) => Err(error::LunaError::internal(format!(
+                "tool panicked: {}",
+                call.name
+            ))),
         }
     }
 }
@@ -101,22 +510,216 @@ impl Tool for ReadFileTool {
         "read_file"
     }
 
+    fn side_effect(&self) -> SideEffect {
+        SideEffect::ReadOnly
+    }
+
     fn run(&self, ctx: &ToolContext, args: &Value) -> error::Result<ToolResult> {
         let path = args
             .get("path")
             .and_then(|v| v.as_str())
             .ok_or_else(|| error::LunaError::invalid_input("read_file missing args.path"))?;
-        let abs = ctx.resolve_path(Path::new(path));
-        let bytes = std::fs::read(&abs)
-            .map_err(|e| error::LunaError::io(Some(abs.clone()), e))
-            .with_context(|| format!("read file: {}", abs.display()))?;
-        let limited = if bytes.len() > ctx.max_bytes {
-            &bytes[..ctx.max_bytes]
+        let (path, _) = split_location(ctx, path);
+        let invalid_utf8 = InvalidUtf8::from_args(args)?;
+        let mut file = read_file_range(ctx, &path, None, invalid_utf8)?;
+        truncate_at_char_boundary(&mut file.text, ctx.max_bytes);
+        let stderr = if file.lossy {
+            lossy_warning(&path)
         } else {
-            &bytes
+            String::new()
         };
-        let s = String::from_utf8_lossy(limited).to_string();
-        Ok(ToolResult::ok(s))
+        Ok(ToolResult {
+            stderr,
+            ..ToolResult::ok(file.text)
+        })
+    }
+}
+
+#[derive(Default)]
+struct ReadFilesTool;
+
+impl Tool for ReadFilesTool {
+    fn name(&self) -> &'static str {
+        "read_files"
+    }
+
+    fn side_effect(&self) -> SideEffect {
+        SideEffect::ReadOnly
+    }
+
+    fn run(&self, ctx: &ToolContext, args: &Value) -> error::Result<ToolResult> {
+        let files = args
+            .get("files")
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| error::LunaError::invalid_input("read_files missing args.files"))?;
+
+        let invalid_utf8 = InvalidUtf8::from_args(args)?;
+
+        // Accepted entries: "path" or { path, start_line_1?, end_line_1? }
+        let mut specs = Vec::with_capacity(files.len());
+        for f in files {
+            if let Some(path) = f.as_str() {
+                specs.push((split_location(ctx, path).0, None));
+                continue;
+            }
+            let path = f
+                .get("path")
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| error::LunaError::invalid_input("read_files entry missing path"))?;
+            let start = f.get("start_line_1").and_then(|v| v.as_u64());
+            let end = f.get("end_line_1").and_then(|v| v.as_u64());
+            let range = match (start, end) {
+                (Some(s), Some(e)) => Some((s as usize, e as usize)),
+                (Some(s), None) => Some((s as usize, usize::MAX)),
+                (None, Some(e)) => Some((1, e as usize)),
+                (None, None) => None,
+            };
+            specs.push((split_location(ctx, path).0, range));
+        }
+
+        let mut stdout = String::new();
+        let mut stderr = String::new();
+        let mut any_ok = false;
+        for ((path, range), (_, res)) in specs.iter().zip(read_files(ctx, &specs, invalid_utf8)) {
+            let header = match range {
+                Some((s, e)) if *e != usize::MAX => format!("=== {path}:{s}-{e} ==="),
+                Some((s, _)) => format!("=== {path}:{s}- ==="),
+                None => format!("=== {path} ==="),
+            };
+            match res {
+                Ok(FileText { text, lossy }) => {
+                    any_ok = true;
+                    stdout.push_str(&header);
+                    stdout.push('\n');
+                    stdout.push_str(&text);
+                    if !text.ends_with('\n') {
+                        stdout.push('\n');
+                    }
+                    if lossy {
+                        stderr.push_str(&lossy_warning(path));
+                    }
+                }
+                Err(e) => {
+                    stderr.push_str(&format!("{header}\n{e}\n"));
+                }
+            }
+        }
+
+        Ok(ToolResult {
+            ok: any_ok || specs.is_empty(),
+            stdout,
+            stderr,
+            combined: Vec::new(),
+            diff: None,
+            error_code: None,
+        })
+    }
+}
+
+/// Default number of paths listed by `find_files`.
+const DEFAULT_FIND_FILES_LIMIT: usize = 20;
+
+#[derive(Default)]
+struct FindFilesTool;
+
+impl Tool for FindFilesTool {
+    fn name(&self) -> &'static str {
+        "find_files"
+    }
+
+    fn side_effect(&self) -> SideEffect {
+        SideEffect::ReadOnly
+    }
+
+    fn run(&self, ctx: &ToolContext, args: &Value) -> error::Result<ToolResult> {
+        let query = args
+            .get("query")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| error::LunaError::invalid_input("find_files missing args.query"))?;
+        let limit = args
+            .get("limit")
+            .and_then(|v| v.as_u64())
+            .map_or(DEFAULT_FIND_FILES_LIMIT, |n| n as usize);
+        let root = ctx
+            .repo_root
+            .as_ref()
+            .or(ctx.cwd.as_ref())
+            .ok_or_else(|| error::LunaError::invalid_input("find_files needs a repo root"))?;
+
+        let hits = intelligence::find_files(root, query, &ctx.scan)
+            .map_err(|e| error::LunaError::invalid_input(format!("find files: {e}")))?;
+        if hits.is_empty() {
+            return Ok(ToolResult::ok(format!("no files match {query:?}\n")));
+        }
+
+        let mut stdout = String::new();
+        for (path, _) in hits.iter().take(limit) {
+            stdout.push_str(&path.display().to_string());
+            stdout.push('\n');
+        }
+        if hits.len() > limit {
+            stdout.push_str(&format!("... {} more\n", hits.len() - limit));
+        }
+        Ok(ToolResult::ok(stdout))
+    }
+}
+
+#[derive(Default)]
+struct ReadSymbolTool;
+
+impl Tool for ReadSymbolTool {
+    fn name(&self) -> &'static str {
+        "read_symbol"
+    }
+
+    fn side_effect(&self) -> SideEffect {
+        SideEffect::ReadOnly
+    }
+
+    fn run(&self, ctx: &ToolContext, args: &Value) -> error::Result<ToolResult> {
+        let name = args
+            .get("name")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| error::LunaError::invalid_input("read_symbol missing args.name"))?;
+        let kind = args.get("kind").and_then(|v| v.as_str());
+        let root = ctx
+            .repo_root
+            .as_ref()
+            .or(ctx.cwd.as_ref())
+            .ok_or_else(|| error::LunaError::invalid_input("read_symbol needs a repo root"))?;
+
+        let navigator = intelligence::TreeSitterNavigator::new(FsRepoFileProvider, ctx.scan.clone());
+        let defs = navigator
+            .read_symbol(root, name, kind, &intelligence::SnippetOptions::default())
+            .map_err(|e| error::LunaError::invalid_input(format!("read symbol: {e}")))?;
+        if defs.is_empty() {
+            return Ok(ToolResult::err(match kind {
+                Some(kind) => format!("no {kind} definition of {name:?} found\n"),
+                None => format!("no definition of {name:?} found\n"),
+            }));
+        }
+
+        let mut stdout = String::new();
+        for (sym, text) in &defs {
+            stdout.push_str(&format!(
+                "=== {}:{} {}{} ===\n{text}\n",
+                sym.location.rel_path.display(),
+                sym.location.range.start.line + 1,
+                sym.kind.map(|k| format!("{k} ")).unwrap_or_default(),
+                sym.qualified_name,
+            ));
+        }
+        let total = stdout.len();
+        truncate_at_char_boundary(&mut stdout, ctx.max_bytes);
+        let stderr = if stdout.len() < total {
+            format!("read_symbol output truncated to {} bytes\n", stdout.len())
+        } else {
+            String::new()
+        };
+        Ok(ToolResult {
+            stderr,
+            ..ToolResult::ok(stdout)
+        })
     }
 }
 
@@ -128,24 +731,44 @@ impl Tool for EditFileTool {
         "edit_file"
     }
 
+    fn side_effect(&self) -> SideEffect {
+        SideEffect::Mutates
+    }
+
     fn run(&self, ctx: &ToolContext, args: &Value) -> error::Result<ToolResult> {
         let path = args
             .get("path")
             .and_then(|v| v.as_str())
             .ok_or_else(|| error::LunaError::invalid_input("edit_file missing args.path"))?;
-        let abs = ctx.resolve_path(Path::new(path));
+        // `path:line` stands in for `line_1` when that is missing.
+        let (path, at_line) = split_location(ctx, path);
+        let path = path.as_str();
+        let abs = ctx.resolve_confined(Path::new(path))?;
 
-        let mut content = std::fs::read_to_string(&abs)
-            .map_err(|e| error::LunaError::io(Some(abs.clone()), e))
-            .with_context(|| format!("read file for edit: {}", abs.display()))?;
+        let mut content = match std::fs::read_to_string(&abs) {
+            Ok(content) => content,
+            // Editing lossily decoded text would corrupt the bytes we can't show.
+            Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+                return Ok(ToolResult::err(format!(
+                    "edit_file: {path} is not valid UTF-8; refusing to rewrite it"
+                )));
+            }
+            Err(e) => {
+                return Err(error::LunaError::io(Some(abs.clone()), e))
+                    .with_context(|| format!("read file for edit: {}", abs.display()));
+            }
+        };
         let had_trailing_newline = content.ends_with('\n');
+        let line_ending = LineEnding::detect(&content);
         let mut lines = content.lines().map(ToOwned::to_owned).collect::<Vec<_>>();
 
         // Supported shapes:
         // 1) { path, line_1, new_line }
         // 2) { path, start_line_1, end_line_1, replace_with }
-        if let (Some(line_1), Some(new_line)) = (
-            args.get("line_1").and_then(|v| v.as_u64()),
+        let (start, end, replacement) = if let (Some(line_1), Some(new_line)) = (
+            args.get("line_1")
+                .and_then(|v| v.as_u64())
+                .or(at_line.map(|l| l as u64)),
             args.get("new_line").and_then(|v| v.as_str()),
         ) {
             let idx = usize::try_from(line_1).ok().and_then(|v| v.checked_sub(1));
@@ -158,7 +781,7 @@ impl Tool for EditFileTool {
                     lines.len()
                 )));
             }
-            lines[i] = new_line.to_owned();
+            (i, i + 1, vec![new_line.to_owned()])
         } else if let (Some(start), Some(end), Some(replace_with)) = (
             args.get("start_line_1").and_then(|v| v.as_u64()),
             args.get("end_line_1").and_then(|v| v.as_u64()),
@@ -176,23 +799,193 @@ impl Tool for EditFileTool {
                 .lines()
                 .map(ToOwned::to_owned)
                 .collect::<Vec<_>>();
-            lines.splice(s0..=e0, repl_lines);
+            (s0, e0 + 1, repl_lines)
         } else {
             return Ok(ToolResult::err(
                 "edit_file missing args: provide (line_1,new_line) or (start_line_1,end_line_1,replace_with)",
             ));
+        };
+
+        let include_diff = args
+            .get("include_diff")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let diff = (ctx.dry_run || include_diff)
+            .then(|| render_line_diff(path, start, &lines[start..end], &replacement));
+
+        if ctx.dry_run {
+            return Ok(ToolResult::ok(diff.unwrap_or_default()));
+        }
+        if ctx.max_backups > 0 {
+            write_backup(&abs, content.as_bytes(), ctx.max_backups)?;
         }
+        lines.splice(start..end, replacement);
 
-        content = lines.join("\n");
-        // Preserve trailing newline if the original had it.
+        // Keep the file's line endings (and trailing newline, if any).
+        content = lines.join(line_ending.as_str());
         if had_trailing_newline {
-            content.push('\n');
+            content.push_str(line_ending.as_str());
         }
         std::fs::write(&abs, content)
             .map_err(|e| error::LunaError::io(Some(abs.clone()), e))
             .with_context(|| format!("write edited file: {}", abs.display()))?;
-        Ok(ToolResult::ok(format!("edited: {}", abs.display())))
+        Ok(ToolResult {
+            diff,
+            ..ToolResult::ok(format!("edited: {}", abs.display()))
+        })
+    }
+}
+
+const BACKUP_SUFFIX: &str = ".bak";
+
+/// Backups `edit_file` wrote for `path`, oldest first.
+///
+/// A backup sits next to the file as `{file_name}.{unix_nanos}.bak`, so
+/// every edit in a session stays recoverable.
+pub fn list_backups(path: &Path) -> error::Result<Vec<PathBuf>> {
+    let name = path.file_name().and_then(|n| n.to_str()).ok_or_else(|| {
+        error::LunaError::invalid_input(format!("not a file path: {}", path.display()))
+    })?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(error::LunaError::io(Some(dir.to_path_buf()), e)),
+    };
+
+    let mut out = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| error::LunaError::io(Some(dir.to_path_buf()), e))?;
+        let file_name = entry.file_name();
+        let Some(stamp) = file_name
+            .to_str()
+            .and_then(|n| n.strip_prefix(name))
+            .and_then(|n| n.strip_prefix('.'))
+            .and_then(|n| n.strip_suffix(BACKUP_SUFFIX))
+            .and_then(|n| n.parse::<u128>().ok())
+        else {
+            continue;
+        };
+        out.push((stamp, entry.path()));
     }
+    out.sort();
+    Ok(out.into_iter().map(|(_, p)| p).collect())
+}
+
+/// Overwrite `path` with the contents of `backup`, one of its
+/// [`list_backups`]. The backup itself is kept.
+pub fn restore_backup(path: &Path, backup: &Path) -> error::Result<()> {
+    if !list_backups(path)?.iter().any(|b| b == backup) {
+        return Err(error::LunaError::invalid_input(format!(
+            "{} is not a backup of {}",
+            backup.display(),
+            path.display()
+        )));
+    }
+    std::fs::copy(backup, path)
+        .map_err(|e| error::LunaError::io(Some(path.to_path_buf()), e))
+        .with_context(|| format!("restore backup: {}", backup.display()))?;
+    Ok(())
+}
+
+/// Save `original` as a new backup of `path`, then drop the oldest ones
+/// beyond `keep`.
+fn write_backup(path: &Path, original: &[u8], keep: usize) -> error::Result<()> {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut stamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    // Coarse clocks can repeat a timestamp; never overwrite an older backup.
+    let backup = loop {
+        let candidate = path.with_file_name(format!("{name}.{stamp}{BACKUP_SUFFIX}"));
+        if !candidate.exists() {
+            break candidate;
+        }
+        stamp += 1;
+    };
+    std::fs::write(&backup, original)
+        .map_err(|e| error::LunaError::io(Some(backup.clone()), e))
+        .with_context(|| format!("back up file for edit: {}", path.display()))?;
+
+    let backups = list_backups(path)?;
+    for old in &backups[..backups.len().saturating_sub(keep)] {
+        std::fs::remove_file(old).map_err(|e| error::LunaError::io(Some(old.clone()), e))?;
+    }
+    Ok(())
+}
+
+/// Apply several `edit_file` edits (same args shape) as one unit.
+///
+/// Every target is backed up before the first write. Edits run in order, each
+/// seeing the result of the previous ones on the same file; if any edit errors
+/// or reports failure, all targets are restored and an error is returned. A
+/// restore that fails doesn't stop the others; the error lists every file
+/// left unrestored.
+pub fn apply_edits_transactional(
+    ctx: &ToolContext,
+    edits: &[Value],
+) -> error::Result<Vec<ToolResult>> {
+    let mut backups: Vec<(PathBuf, Vec<u8>)> = Vec::new();
+    for args in edits {
+        let path = args
+            .get("path")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| error::LunaError::invalid_input("edit_file missing args.path"))?;
+        let abs = ctx.resolve_confined(Path::new(&split_location(ctx, path).0))?;
+        if ctx.dry_run || backups.iter().any(|(p, _)| *p == abs) {
+            continue;
+        }
+        let original = std::fs::read(&abs)
+            .map_err(|e| error::LunaError::io(Some(abs.clone()), e))
+            .with_context(|| format!("back up file for edit: {}", abs.display()))?;
+        backups.push((abs, original));
+    }
+
+    let mut results = Vec::with_capacity(edits.len());
+    for (i, args) in edits.iter().enumerate() {
+        let failure = match EditFileTool.run(ctx, args) {
+            Ok(res) if res.ok => {
+                results.push(res);
+                continue;
+            }
+            Ok(res) => res.stderr,
+            Err(err) => err.to_string(),
+        };
+
+        // Try every file even if one restore fails, and report all that did.
+        let unrestored: Vec<String> = backups
+            .iter()
+            .filter_map(|(abs, original)| {
+                std::fs::write(abs, original)
+                    .err()
+                    .map(|e| format!("{}: {e}", abs.display()))
+            })
+            .collect();
+        if !unrestored.is_empty() {
+            return Err(error::LunaError::invalid_input(format!(
+                "edit {} of {} failed: {failure}; could not restore {} of {} file(s): {}",
+                i + 1,
+                edits.len(),
+                unrestored.len(),
+                backups.len(),
+                unrestored.join("; ")
+            )));
+        }
+        return Err(error::LunaError::invalid_input(format!(
+            "edit {} of {} failed, restored {} file(s): {failure}",
+            i + 1,
+            edits.len(),
+            backups.len()
+        )));
+    }
+    Ok(results)
 }
 
 #[derive(Default)]
@@ -203,6 +996,10 @@ impl Tool for RunTerminalTool {
         "run_terminal"
     }
 
+    fn side_effect(&self) -> SideEffect {
+        SideEffect::Executes
+    }
+
     fn run(&self, ctx: &ToolContext, args: &Value) -> error::Result<ToolResult> {
         let cmd = args
             .get("cmd")
@@ -214,12 +1011,38 @@ impl Tool for RunTerminalTool {
             .map(PathBuf::from)
             .or_else(|| ctx.cwd.clone())
             .or_else(|| ctx.repo_root.clone());
+        let cwd = match cwd {
+            Some(dir) => Some(ctx.resolve_confined(&dir)?),
+            None if ctx.confine_to_repo => ctx.repo_root.clone(),
+            None => None,
+        };
+        let capture_combined = args
+            .get("capture_combined")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        if ctx.dry_run {
+            let dir = cwd
+                .as_ref()
+                .map(|d| d.display().to_string())
+                .unwrap_or_else(|| ".".to_owned());
+            return Ok(ToolResult::ok(format!(
+                "dry-run: would run `sh -lc {cmd}` in {dir}"
+            )));
+        }
 
         let mut command = std::process::Command::new("sh");
         command.arg("-lc").arg(cmd);
         if let Some(dir) = cwd {
             command.current_dir(dir);
         }
+
+        if capture_combined {
+            return output_combined(&mut command, ctx.max_bytes)
+                .map_err(error::LunaError::from)
+                .context("run terminal");
+        }
+
         let out = command
             .output()
             .map_err(error::LunaError::from)
@@ -238,8 +1061,745 @@ impl Tool for RunTerminalTool {
             ok: out.status.success(),
             stdout: String::from_utf8_lossy(&stdout).to_string(),
             stderr: String::from_utf8_lossy(&stderr).to_string(),
+            combined: Vec::new(),
+            diff: None,
+            error_code: None,
         })
     }
 }
 
+/// Run `command`, recording stdout/stderr lines in the order they are read.
+fn output_combined(
+    command: &mut std::process::Command,
+    max_bytes: usize,
+) -> std::io::Result<ToolResult> {
+    let mut child = command
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()?;
+
+    let mut result = record_combined(child.stdout.take(), child.stderr.take(), max_bytes);
+    result.ok = child.wait()?.success();
+    Ok(result)
+}
+
+/// Drain both pipes to their end into one record.
+///
+/// Each pipe is read line by line on its own thread into one channel, so
+/// the record follows arrival order rather than grouping by stream. Each
+/// stream (and the combined record) is capped at `max_bytes`.
+fn record_combined(
+    stdout: Option<impl Read + Send + 'static>,
+    stderr: Option<impl Read + Send + 'static>,
+    max_bytes: usize,
+) -> ToolResult {
+    let (tx, rx) = mpsc::channel();
+    let mut readers = Vec::with_capacity(2);
+    if let Some(pipe) = stdout {
+        readers.push(forward_lines(Stream::Stdout, pipe, tx.clone()));
+    }
+    if let Some(pipe) = stderr {
+        readers.push(forward_lines(Stream::Stderr, pipe, tx.clone()));
+    }
+    drop(tx);
+
+    let mut result = ToolResult::ok(String::new());
+    let mut combined_bytes = 0;
+    for (stream, line) in rx {
+        let buf = match stream {
+            Stream::Stdout => &mut result.stdout,
+            Stream::Stderr => &mut result.stderr,
+        };
+        push_capped(buf, &line, max_bytes);
+
+        if combined_bytes < max_bytes {
+            let mut line = line;
+            truncate_at_char_boundary(&mut line, max_bytes - combined_bytes);
+            combined_bytes += line.len();
+            result.combined.push((stream, line));
+        }
+    }
+    for reader in readers {
+        let _ = reader.join();
+    }
+    result
+}
+
+fn forward_lines(
+    stream: Stream,
+    pipe: impl Read + Send + 'static,
+    tx: mpsc::Sender<(Stream, String)>,
+) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        let mut reader = BufReader::new(pipe);
+        let mut buf = Vec::new();
+        while reader.read_until(b'\n', &mut buf).is_ok_and(|n| n > 0) {
+            if tx
+                .send((stream, String::from_utf8_lossy(&buf).into_owned()))
+                .is_err()
+            {
+                break;
+            }
+            buf.clear();
+        }
+    })
+}
+
+fn push_capped(buf: &mut String, s: &str, max_bytes: usize) {
+    let room = max_bytes.saturating_sub(buf.len());
+    let mut end = room.min(s.len());
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    buf.push_str(&s[..end]);
+}
+
+fn truncate_at_char_boundary(s: &mut String, max_bytes: usize) {
+    if s.len() <= max_bytes {
+        return;
+    }
+    let mut end = max_bytes;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    s.truncate(end);
+}
+
 // NOTE: `ToolContext::resolve_path` is the canonical helper.
+
+/// Unified-diff style hunk for a line-range replacement (`start` is 0-based).
+fn render_line_diff(path: &str, start: usize, old: &[String], new: &[String]) -> String {
+    let mut out = format!(
+        "--- a/{path}\n+++ b/{path}\n@@ -{},{} +{},{} @@\n",
+        start + 1,
+        old.len(),
+        start + 1,
+        new.len()
+    );
+    for l in old {
+        out.push_str(&format!("-{l}\n"));
+    }
+    for l in new {
+        out.push_str(&format!("+{l}\n"));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Path under the system temp dir that no other test uses; not created.
+    fn unique_tmp_dir(prefix: &str) -> PathBuf {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let n = NEXT.fetch_add(1, Ordering::Relaxed);
+        std::env::temp_dir().join(format!("{prefix}-{nanos}-{n}"))
+    }
+
+    #[test]
+    fn registry_reports_side_effects() {
+        let reg = ToolRegistry::new();
+        assert_eq!(reg.side_effect("read_file"), Some(SideEffect::ReadOnly));
+        assert_eq!(reg.side_effect("read_files"), Some(SideEffect::ReadOnly));
+        assert_eq!(reg.side_effect("find_files"), Some(SideEffect::ReadOnly));
+        assert_eq!(reg.side_effect("read_symbol"), Some(SideEffect::ReadOnly));
+        assert_eq!(reg.side_effect("edit_file"), Some(SideEffect::Mutates));
+        assert_eq!(reg.side_effect("run_terminal"), Some(SideEffect::Executes));
+        assert_eq!(reg.side_effect("nope"), None);
+    }
+
+    #[test]
+    fn dry_run_edit_returns_diff_without_writing() {
+        let dir = unique_tmp_dir("luna_tools_dry_run");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "one\ntwo\n").unwrap();
+
+        let ctx = ToolContext::new(Some(dir.clone()))
+            .with_max_bytes(1024)
+            .with_dry_run(true);
+        let reg = ToolRegistry::new();
+        let res = reg
+            .run(
+                &ctx,
+                &ToolCall {
+                    name: "edit_file".to_owned(),
+                    args: serde_json::json!({"path": "a.txt", "line_1": 2, "new_line": "TWO"}),
+                },
+            )
+            .unwrap();
+        assert!(res.ok);
+        assert!(
+            res.stdout.contains("@@ -2,1 +2,1 @@\n-two\n+TWO\n"),
+            "{}",
+            res.stdout
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.join("a.txt")).unwrap(),
+            "one\ntwo\n"
+        );
+
+        let res = reg
+            .run(
+                &ctx,
+                &ToolCall {
+                    name: "run_terminal".to_owned(),
+                    args: serde_json::json!({"cmd": "touch x"}),
+                },
+            )
+            .unwrap();
+        assert!(res.stdout.starts_with("dry-run: would run"));
+        assert!(!dir.join("x").exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn edit_keeps_crlf_line_endings() {
+        let dir = unique_tmp_dir("luna_tools_crlf");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "one\r\ntwo\r\nthree\r\n").unwrap();
+
+        let ctx = ToolContext::new(Some(dir.clone())).with_max_bytes(1024);
+        let res = ToolRegistry::new()
+            .run(
+                &ctx,
+                &ToolCall {
+                    name: "edit_file".to_owned(),
+                    args: serde_json::json!({
+                        "path": "a.txt",
+                        "start_line_1": 2,
+                        "end_line_1": 2,
+                        "replace_with": "2a\n2b",
+                    }),
+                },
+            )
+            .unwrap();
+        assert!(res.ok, "{}", res.stderr);
+        assert_eq!(
+            std::fs::read_to_string(dir.join("a.txt")).unwrap(),
+            "one\r\n2a\r\n2b\r\nthree\r\n"
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn read_files_isolates_errors_and_caps_total_bytes() {
+        let dir = unique_tmp_dir("luna_tools_read_files");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "one\ntwo\nthree\n").unwrap();
+        std::fs::write(dir.join("b.txt"), "0123456789").unwrap();
+
+        let ctx = ToolContext::new(Some(dir.clone())).with_max_bytes(12);
+        let out = read_files(
+            &ctx,
+            &[
+                ("a.txt".to_owned(), Some((2, 3))),
+                ("missing.txt".to_owned(), None),
+                ("b.txt".to_owned(), None),
+                ("a.txt".to_owned(), None),
+            ],
+            InvalidUtf8::Replace,
+        );
+
+        let text = |i: usize| out[i].1.as_ref().unwrap().text.as_str();
+        assert_eq!(text(0), "two\nthree\n");
+        assert!(out[1].1.is_err());
+        assert_eq!(text(2), "01");
+        assert!(out[3].1.is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn invalid_utf8_is_flagged_rejected_or_left_unedited() {
+        let dir = unique_tmp_dir("luna_tools_invalid_utf8");
+        std::fs::create_dir_all(&dir).unwrap();
+        let bytes = b"caf\xe9\n\xc3\xa9t\xc3\xa9\n";
+        std::fs::write(dir.join("latin1.txt"), bytes).unwrap();
+
+        let ctx = ToolContext::new(Some(dir.clone())).with_max_bytes(1024);
+        let spec = [("latin1.txt".to_owned(), None)];
+
+        let out = read_files(&ctx, &spec, InvalidUtf8::Replace);
+        let file = out[0].1.as_ref().unwrap();
+        assert!(file.lossy);
+        assert_eq!(file.text, "caf\u{fffd}\n\u{e9}t\u{e9}\n");
+        assert!(read_files(&ctx, &spec, InvalidUtf8::Error)[0].1.is_err());
+
+        let reg = ToolRegistry::new();
+        let read = |args| {
+            reg.run(
+                &ctx,
+                &ToolCall {
+                    name: "read_file".to_owned(),
+                    args,
+                },
+            )
+        };
+        let res = read(serde_json::json!({ "path": "latin1.txt" })).unwrap();
+        assert!(res.ok && res.stderr.contains("not valid UTF-8"));
+        assert!(
+            read(serde_json::json!({ "path": "latin1.txt", "invalid_utf8": "error" })).is_err()
+        );
+        assert!(read(serde_json::json!({ "path": "latin1.txt", "invalid_utf8": "drop" })).is_err());
+
+        // Truncation never splits a multi-byte char.
+        let small = ToolContext {
+            max_bytes: 5,
+            ..ctx.clone()
+        };
+        let out = read_files(&small, &spec, InvalidUtf8::Replace);
+        assert_eq!(out[0].1.as_ref().unwrap().text, "caf");
+
+        let res = reg
+            .run(
+                &ctx,
+                &ToolCall {
+                    name: "edit_file".to_owned(),
+                    args: serde_json::json!({ "path": "latin1.txt", "line_1": 1, "new_line": "x" }),
+                },
+            )
+            .unwrap();
+        assert!(!res.ok);
+        assert_eq!(std::fs::read(dir.join("latin1.txt")).unwrap(), bytes);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn run_terminal_captures_combined_output() {
+        let ctx = ToolContext::new(None).with_max_bytes(1024);
+        let res = ToolRegistry::new()
+            .run(
+                &ctx,
+                &ToolCall {
+                    name: "run_terminal".to_owned(),
+                    args: serde_json::json!({
+                        "cmd": "echo one; echo oops >&2; echo two",
+                        "capture_combined": true,
+                    }),
+                },
+            )
+            .unwrap();
+        assert!(res.ok);
+        assert_eq!(res.stdout, "one\ntwo\n");
+        assert_eq!(res.stderr, "oops\n");
+        // Across streams the order depends on scheduling; within one it is fixed
+        let stdout: Vec<_> = res
+            .combined
+            .iter()
+            .filter(|(stream, _)| *stream == Stream::Stdout)
+            .map(|(_, line)| line.as_str())
+            .collect();
+        assert_eq!(stdout, ["one\n", "two\n"]);
+        assert_eq!(res.combined.len(), 3);
+    }
+
+    /// Pipe end fed line by line by the test. Each `read` first reports that
+    /// the previous line was consumed, so the test can wait for it.
+    struct StepPipe {
+        lines: mpsc::Receiver<&'static str>,
+        wants_more: mpsc::Sender<()>,
+    }
+
+    impl Read for StepPipe {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let _ = self.wants_more.send(());
+            let Ok(line) = self.lines.recv() else {
+                return Ok(0);
+            };
+            buf[..line.len()].copy_from_slice(line.as_bytes());
+            Ok(line.len())
+        }
+    }
+
+    fn step_pipe() -> (StepPipe, mpsc::Sender<&'static str>, mpsc::Receiver<()>) {
+        let (line_tx, lines) = mpsc::channel();
+        let (wants_more, wants_more_rx) = mpsc::channel();
+        (StepPipe { lines, wants_more }, line_tx, wants_more_rx)
+    }
+
+    #[test]
+    fn combined_record_follows_arrival_order_across_streams() {
+        let (stdout, out_tx, out_ready) = step_pipe();
+        let (stderr, err_tx, err_ready) = step_pipe();
+        let recorder =
+            std::thread::spawn(move || record_combined(Some(stdout), Some(stderr), 1024));
+
+        // Feed the next line only once the previous one has been forwarded
+        out_ready.recv().unwrap();
+        err_ready.recv().unwrap();
+        out_tx.send("one\n").unwrap();
+        out_ready.recv().unwrap();
+        err_tx.send("oops\n").unwrap();
+        err_ready.recv().unwrap();
+        out_tx.send("two\n").unwrap();
+        out_ready.recv().unwrap();
+        drop((out_tx, err_tx));
+
+        let res = recorder.join().unwrap();
+        assert_eq!(res.stdout, "one\ntwo\n");
+        assert_eq!(res.stderr, "oops\n");
+        assert_eq!(
+            res.combined,
+            vec![
+                (Stream::Stdout, "one\n".to_owned()),
+                (Stream::Stderr, "oops\n".to_owned()),
+                (Stream::Stdout, "two\n".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn confine_to_repo_rejects_paths_outside_the_repo() {
+        let base = unique_tmp_dir("luna_tools_confine");
+        let repo = base.join("repo");
+        std::fs::create_dir_all(repo.join("src")).unwrap();
+        std::fs::write(repo.join("src/a.txt"), "one\n").unwrap();
+        std::fs::write(base.join("secret.txt"), "keep\n").unwrap();
+
+        let ctx = ToolContext::new(Some(repo.clone()))
+            .with_max_bytes(1024)
+            .with_confine_to_repo(true);
+        let reg = ToolRegistry::new();
+        let edit = |path: &str| {
+            reg.run(
+                &ctx,
+                &ToolCall {
+                    name: "edit_file".to_owned(),
+                    args: serde_json::json!({"path": path, "line_1": 1, "new_line": "changed"}),
+                },
+            )
+        };
+
+        assert!(edit("../secret.txt").is_err());
+        assert!(edit(base.join("secret.txt").to_str().unwrap()).is_err());
+        assert!(edit("src/../../secret.txt").is_err());
+        assert_eq!(
+            std::fs::read_to_string(base.join("secret.txt")).unwrap(),
+            "keep\n"
+        );
+        let res = edit("src/a.txt").unwrap();
+        assert!(res.ok);
+        assert!(res.diff.is_none());
+
+        let run = |cwd: &Path| {
+            reg.run(
+                &ctx,
+                &ToolCall {
+                    name: "run_terminal".to_owned(),
+                    args: serde_json::json!({"cmd": "pwd", "cwd": cwd}),
+                },
+            )
+        };
+        assert!(run(&base).is_err());
+        assert!(run(&repo.join("src")).unwrap().ok);
+
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn edit_file_returns_diff_when_requested() {
+        let dir = unique_tmp_dir("luna_tools_edit_diff");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "one\ntwo\nthree\n").unwrap();
+
+        let ctx = ToolContext::new(Some(dir.clone())).with_max_bytes(1024);
+        let res = ToolRegistry::new()
+            .run(
+                &ctx,
+                &ToolCall {
+                    name: "edit_file".to_owned(),
+                    args: serde_json::json!({
+                        "path": "a.txt",
+                        "start_line_1": 2,
+                        "end_line_1": 3,
+                        "replace_with": "TWO",
+                        "include_diff": true,
+                    }),
+                },
+            )
+            .unwrap();
+        assert!(res.ok);
+        assert_eq!(
+            res.diff.as_deref(),
+            Some("--- a/a.txt\n+++ b/a.txt\n@@ -2,2 +2,1 @@\n-two\n-three\n+TWO\n")
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.join("a.txt")).unwrap(),
+            "one\nTWO\n"
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn transactional_edits_restore_every_file_on_failure() {
+        let dir = unique_tmp_dir("luna_tools_edit_tx");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "a1\na2\n").unwrap();
+        std::fs::write(dir.join("b.txt"), "b1\n").unwrap();
+
+        let ctx = ToolContext::new(Some(dir.clone())).with_max_bytes(1024);
+        let edit = |path: &str, line_1: u64, new_line: &str| {
+            serde_json::json!({
+                "path": path,
+                "line_1": line_1,
+                "new_line": new_line,
+            })
+        };
+
+        let err = apply_edits_transactional(
+            &ctx,
+            &[
+                edit("a.txt", 1, "A1"),
+                edit("b.txt", 1, "B1"),
+                edit("b.txt", 5, "out of range"),
+            ],
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("edit 3 of 3 failed"), "{err}");
+        assert_eq!(
+            std::fs::read_to_string(dir.join("a.txt")).unwrap(),
+            "a1\na2\n"
+        );
+        assert_eq!(std::fs::read_to_string(dir.join("b.txt")).unwrap(), "b1\n");
+
+        let results = apply_edits_transactional(
+            &ctx,
+            &[
+                edit("a.txt", 1, "A1"),
+                edit("a.txt", 2, "A2"),
+                edit("b.txt", 1, "B1"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            std::fs::read_to_string(dir.join("a.txt")).unwrap(),
+            "A1\nA2\n"
+        );
+        assert_eq!(std::fs::read_to_string(dir.join("b.txt")).unwrap(), "B1\n");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn slow_tool_times_out_with_error_code() {
+        let reg = ToolRegistry::new()
+            .with_default_timeout(Duration::from_secs(60))
+            .with_timeout("run_terminal", Duration::from_millis(100));
+        assert_eq!(reg.timeout("read_file"), Duration::from_secs(60));
+
+        let ctx = ToolContext::new(Some(std::env::temp_dir()));
+        let started = std::time::Instant::now();
+        let res = reg
+            .run(&ctx, &RunTerminalArgs::new("sleep 5").into_call())
+            .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(!res.ok);
+        assert_eq!(res.error_code, Some(ToolErrorCode::Timeout));
+        assert!(res.stderr.starts_with("run_terminal timed out"));
+
+        let res = reg
+            .run(&ctx, &RunTerminalArgs::new("true").into_call())
+            .unwrap();
+        assert!(res.ok);
+        assert_eq!(res.error_code, None);
+    }
+
+    #[test]
+    fn edits_are_never_abandoned_on_timeout() {
+        let dir = unique_tmp_dir("luna_tools_edit_timeout");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "one\ntwo\n").unwrap();
+
+        let reg = ToolRegistry::new().with_timeout("edit_file", Duration::ZERO);
+        let ctx = ToolContext::new(Some(dir.clone()));
+        let edit = EditLineArgs {
+            path: "a.txt".to_owned(),
+            line_1: 2,
+            new_line: "TWO".to_owned(),
+            include_diff: false,
+        };
+        let res = reg.run(&ctx, &edit.into_call()).unwrap();
+        assert!(res.ok, "{}", res.stderr);
+        assert_eq!(
+            std::fs::read_to_string(dir.join("a.txt")).unwrap(),
+            "one\nTWO\n"
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn typed_args_build_calls_the_tools_accept() {
+        let dir = unique_tmp_dir("luna_tools_typed_args");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "one\ntwo\nthree\n").unwrap();
+
+        let call = RunTerminalArgs::new("true").into_call();
+        assert_eq!(call.name, "run_terminal");
+        assert_eq!(call.args, serde_json::json!({"cmd": "true"}));
+
+        let ctx = ToolContext::new(Some(dir.clone())).with_max_bytes(1024);
+        let reg = ToolRegistry::new();
+        let edit = EditRangeArgs {
+            path: "a.txt".to_owned(),
+            start_line_1: 2,
+            end_line_1: 3,
+            replace_with: "TWO".to_owned(),
+            include_diff: true,
+        };
+        let res = reg.run(&ctx, &edit.into_call()).unwrap();
+        assert!(res.ok, "{}", res.stderr);
+        assert!(res.diff.is_some());
+
+        let res = reg
+            .run(&ctx, &ReadFileArgs::new("a.txt").into_call())
+            .unwrap();
+        assert_eq!(res.stdout, "one\nTWO\n");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn edit_backups_are_kept_per_edit_and_pruned() {
+        let dir = unique_tmp_dir("luna_tools_backups");
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("a.txt");
+        std::fs::write(&file, "v0\n").unwrap();
+
+        let ctx = ToolContext::new(Some(dir.clone())).with_max_backups(2);
+        let reg = ToolRegistry::new();
+        for v in ["v1", "v2", "v3"] {
+            let edit = EditLineArgs {
+                path: "a.txt".to_owned(),
+                line_1: 1,
+                new_line: v.to_owned(),
+                include_diff: false,
+            };
+            assert!(reg.run(&ctx, &edit.into_call()).unwrap().ok);
+        }
+
+        // v0 was pruned; the two newest originals remain, oldest first.
+        let backups = list_backups(&file).unwrap();
+        let contents = backups
+            .iter()
+            .map(|b| std::fs::read_to_string(b).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(contents, ["v1\n", "v2\n"]);
+
+        restore_backup(&file, &backups[0]).unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "v1\n");
+        assert!(restore_backup(&file, &dir.join("other.txt")).is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn path_args_accept_a_line_suffix() {
+        let dir = unique_tmp_dir("luna_tools_location");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "one\ntwo\n").unwrap();
+
+        let ctx = ToolContext::new(Some(dir.clone()));
+        let reg = ToolRegistry::new();
+        let res = reg
+            .run(&ctx, &ReadFileArgs::new("a.txt:2:1").into_call())
+            .unwrap();
+        assert_eq!(res.stdout, "one\ntwo\n");
+
+        let call = ToolCall {
+            name: "edit_file".to_owned(),
+            args: serde_json::json!({"path": "a.txt:2", "new_line": "TWO"}),
+        };
+        assert!(reg.run(&ctx, &call).unwrap().ok);
+        assert_eq!(
+            std::fs::read_to_string(dir.join("a.txt")).unwrap(),
+            "one\nTWO\n"
+        );
+
+        let res = reg.run(&ctx, &ReadFileArgs::new("b.txt:2").into_call());
+        assert!(res.is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn read_symbol_prints_each_whole_definition() {
+        let dir = unique_tmp_dir("luna_tools_read_symbol");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("lib.rs"),
+            "use std::fmt;\n\n/// Parsed input.\npub struct parse {\n    n: usize,\n}\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("free.rs"), "pub fn parse() {}\n").unwrap();
+
+        let ctx = ToolContext::new(Some(dir.clone()));
+        let reg = ToolRegistry::new();
+        let call = |kind: Option<&str>| {
+            ReadSymbolArgs {
+                name: "parse".to_owned(),
+                kind: kind.map(ToOwned::to_owned),
+            }
+            .into_call()
+        };
+
+        let res = reg.run(&ctx, &call(None)).unwrap();
+        assert!(res.ok, "{}", res.stderr);
+        assert_eq!(
+            res.stdout,
+            "=== free.rs:1 function parse ===\npub fn parse() {}\n\
+             === lib.rs:4 struct parse ===\n/// Parsed input.\npub struct parse {\n    n: usize,\n}\n"
+        );
+
+        let res = reg.run(&ctx, &call(Some("struct"))).unwrap();
+        assert!(res.stdout.starts_with("=== lib.rs:4 struct parse ==="));
+        let res = reg.run(&ctx, &call(Some("enum"))).unwrap();
+        assert!(!res.ok);
+        assert_eq!(res.stderr, "no enum definition of \"parse\" found\n");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn find_files_uses_the_context_scan_options() {
+        let dir = unique_tmp_dir("luna_tools_find_files");
+        for path in ["src/parser.rs", "src/parse_args.rs", "vendor/parser.rs"] {
+            let file = dir.join(path);
+            std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+            std::fs::write(file, "fn main() {}\n").unwrap();
+        }
+        let reg = ToolRegistry::new();
+        let call = |limit: Option<usize>| {
+            FindFilesArgs {
+                query: "parser".to_owned(),
+                limit,
+            }
+            .into_call()
+        };
+
+        let ctx = ToolContext::new(Some(dir.clone()));
+        let res = reg.run(&ctx, &call(None)).unwrap();
+        assert!(res.ok, "{}", res.stderr);
+        assert!(res.stdout.contains("vendor/parser.rs"), "{}", res.stdout);
+
+        // The configured ignore dirs apply, and `limit` cuts the list
+        let ctx = ctx.with_scan(RepoScanOptions::default().with_exclude_dir("vendor"));
+        let res = reg.run(&ctx, &call(None)).unwrap();
+        assert!(!res.stdout.contains("vendor"), "{}", res.stdout);
+        assert!(res.stdout.starts_with("src/parser.rs\n"), "{}", res.stdout);
+        let res = reg.run(&ctx, &call(Some(1))).unwrap();
+        assert_eq!(res.stdout, "src/parser.rs\n... 1 more\n");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}